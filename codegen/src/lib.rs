@@ -0,0 +1,171 @@
+//! build.rs からパターンをコンパイルし、static な命令列を含む Rust のソースを生成する  
+//! 生成したソースを include! で取り込むと、実行時にはパターンをパース・コンパイルせずに Regex を組み立てられる  
+//! std の無い組み込み環境でも、regex! マクロ (OnceLock を使う) の代わりに使える
+//!
+//! ```
+//! use small_regex_codegen::Codegen;
+//!
+//! // build.rs の中で
+//! let source: String = Codegen::new().pattern("date", "(1|2)+-(1|2)+").generate().unwrap();
+//! assert!(source.contains("pub static DATE_INSTRUCTIONS"));
+//! assert!(source.contains("pub fn date() -> ::small_regex::Regex"));
+//! // std::fs::write(Path::new(&env::var("OUT_DIR").unwrap()).join("patterns.rs"), source).unwrap();
+//! // 使う側では include!(concat!(env!("OUT_DIR"), "/patterns.rs")); として取り込む
+//! ```
+
+use std::fmt::{self, Write};
+
+use small_regex::__private::{compile_pattern, Assertion, Instruction};
+
+/// 生成するパターンの一覧を保持し、Rust のソースを生成する型
+#[derive(Debug, Clone, Default)]
+pub struct Codegen {
+    patterns: Vec<(String, String)>, // (名前, パターン) の組
+}
+
+/// パターンのコンパイルに失敗した場合のエラーの型
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub name: String,              // 失敗したパターンの名前
+    pub error: small_regex::Error, // コンパイルのエラー
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pattern '{}': {}", self.name, self.error)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Codegen {
+    pub fn new() -> Codegen {
+        Codegen::default()
+    }
+
+    /// name という名前でパターンを追加する  
+    /// 生成するソースには、name の関数 (Regex を返す) と、name を大文字にした static の命令列・グループ名を定義する  
+    /// name が小文字・数字・`_` からなる識別子でない場合はパニックする
+    pub fn pattern(&mut self, name: &str, pattern: &str) -> &mut Codegen {
+        assert!(is_identifier(name), "invalid pattern name: {name:?}");
+        self.patterns.push((name.to_string(), pattern.to_string()));
+        self
+    }
+
+    /// 追加したパターンをすべてコンパイルし、Rust のソースを返す  
+    /// いずれかのパターンが不正な場合は、最初に失敗したパターンのエラーを返す
+    pub fn generate(&self) -> Result<String, Error> {
+        let mut source: String = String::from("// small-regex-codegen が生成したファイル。直接編集しないこと\n");
+        for (name, pattern) in self.patterns.iter() {
+            let (instructions, capture_names) =
+                compile_pattern(pattern).map_err(|error| Error { name: name.clone(), error })?;
+            let upper: String = name.to_uppercase();
+
+            source.push('\n');
+            let _ = writeln!(source, "pub static {upper}_INSTRUCTIONS: &[::small_regex::__private::Instruction] = &[");
+            for instruction in instructions.iter() {
+                let _ = writeln!(source, "    {},", instruction_source(instruction));
+            }
+            source.push_str("];\n");
+            let names: Vec<String> = capture_names
+                .iter()
+                .map(|name| match name {
+                    Some(name) => format!("::core::option::Option::Some({name:?})"),
+                    None => String::from("::core::option::Option::None"),
+                })
+                .collect();
+            let _ = writeln!(
+                source,
+                "pub static {upper}_CAPTURE_NAMES: &[::core::option::Option<&str>] = &[{}];",
+                names.join(", ")
+            );
+            let _ = writeln!(source, "\n/// パターン {pattern:?} の Regex を生成する (パターンのパースとコンパイルはしない)");
+            let _ = writeln!(source, "pub fn {name}() -> ::small_regex::Regex {{");
+            let _ = writeln!(
+                source,
+                "    ::small_regex::__private::from_static({pattern:?}, {upper}_INSTRUCTIONS, {upper}_CAPTURE_NAMES)"
+            );
+            source.push_str("}\n");
+        }
+        Ok(source)
+    }
+}
+
+/// name が小文字・数字・`_` からなり、数字で始まらない識別子かどうか
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// 命令を、生成したソースの中で同じ命令を表す式に変換する
+fn instruction_source(instruction: &Instruction) -> String {
+    const PREFIX: &str = "::small_regex::__private";
+    match instruction {
+        Instruction::Char(c) => format!("{PREFIX}::Instruction::Char({c:?})"),
+        Instruction::CharNoCase(c) => format!("{PREFIX}::Instruction::CharNoCase({c:?})"),
+        Instruction::AnyChar(dot_all) => format!("{PREFIX}::Instruction::AnyChar({dot_all})"),
+        Instruction::Property(property, negated) => {
+            let ranges: Vec<String> =
+                property.ranges().iter().map(|(start, end)| format!("({start:?}, {end:?})")).collect();
+            let constructor: &str = if property.is_case_insensitive() { "new_case_insensitive" } else { "new" };
+            format!(
+                "{PREFIX}::Instruction::Property({PREFIX}::Property::{constructor}({:?}, &[{}]), {negated})",
+                property.name,
+                ranges.join(", ")
+            )
+        }
+        Instruction::Match => format!("{PREFIX}::Instruction::Match"),
+        Instruction::Jump(counter) => format!("{PREFIX}::Instruction::Jump({counter})"),
+        Instruction::Split(counter1, counter2) => format!("{PREFIX}::Instruction::Split({counter1}, {counter2})"),
+        Instruction::Atomic(next) => format!("{PREFIX}::Instruction::Atomic({next})"),
+        Instruction::Commit => format!("{PREFIX}::Instruction::Commit"),
+        Instruction::Assert(assertion) => {
+            let assertion: &str = match assertion {
+                Assertion::StartText => "StartText",
+                Assertion::EndText => "EndText",
+                Assertion::EndTextNewline => "EndTextNewline",
+                Assertion::StartLine => "StartLine",
+                Assertion::EndLine => "EndLine",
+                Assertion::StartSearch => "StartSearch",
+            };
+            format!("{PREFIX}::Instruction::Assert({PREFIX}::Assertion::{assertion})")
+        }
+        Instruction::Save(slot) => format!("{PREFIX}::Instruction::Save({slot})"),
+        Instruction::Condition(group, else_counter) => {
+            format!("{PREFIX}::Instruction::Condition({group}, {else_counter})")
+        }
+        Instruction::MatchPattern(id) => format!("{PREFIX}::Instruction::MatchPattern({id})"),
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use super::{is_identifier, Codegen, Error};
+
+    #[test]
+    fn test_generate() {
+        let source: String = Codegen::new().pattern("ab", "a(?<x>b)").generate().unwrap();
+        assert!(source.contains("pub static AB_INSTRUCTIONS"));
+        assert!(source.contains("::small_regex::__private::Instruction::Char('b'),"));
+        assert!(source.contains("&[::core::option::Option::None, ::core::option::Option::Some(\"x\")];"));
+        assert!(source.contains("from_static(\"a(?<x>b)\", AB_INSTRUCTIONS, AB_CAPTURE_NAMES)"));
+    }
+
+    #[test]
+    fn test_error() {
+        let error: Error = Codegen::new().pattern("ok", "a").pattern("bad", "a(").generate().unwrap_err();
+        assert_eq!(error.name, "bad");
+        assert!(error.to_string().starts_with("pattern 'bad': "));
+    }
+
+    #[test]
+    fn test_is_identifier() {
+        assert!(is_identifier("date_2"));
+        assert!(!is_identifier("2date"));
+        assert!(!is_identifier("Date"));
+        assert!(!is_identifier(""));
+    }
+}
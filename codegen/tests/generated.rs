@@ -0,0 +1,35 @@
+use small_regex::Regex;
+use small_regex_codegen::Codegen;
+
+// build.rs で生成する代わりに、あらかじめ生成したソースを取り込む
+mod patterns {
+    include!("generated/patterns.rs");
+}
+
+/// generated/patterns.rs を生成する Codegen
+fn codegen() -> Codegen {
+    let mut codegen: Codegen = Codegen::new();
+    codegen
+        .pattern("key_value", "(?<key>(a|b)+)=(?<value>.*)$")
+        .pattern("greek", r"\p{Greek}+")
+        .pattern("atomic", "(?>a|ab)c");
+    codegen
+}
+
+#[test]
+fn test_up_to_date() {
+    // 命令列の生成方法が変わった場合は、generated/patterns.rs を生成し直す
+    assert_eq!(codegen().generate().unwrap(), include_str!("generated/patterns.rs"));
+}
+
+#[test]
+fn test_generated() {
+    let re: Regex = patterns::key_value();
+    assert_eq!(re.as_str(), "(?<key>(a|b)+)=(?<value>.*)$");
+    let caps = re.captures("x ab=cd").unwrap();
+    assert_eq!(&caps["key"], "ab");
+    assert_eq!(&caps["value"], "cd");
+    assert_eq!(patterns::greek().find("abαβ").unwrap().as_str(), "αβ");
+    assert!(!patterns::atomic().is_match("abc"));
+    assert!(patterns::atomic().is_match("ac"));
+}
@@ -0,0 +1,57 @@
+// small-regex-codegen が生成したファイル。直接編集しないこと
+
+pub static KEY_VALUE_INSTRUCTIONS: &[::small_regex::__private::Instruction] = &[
+    ::small_regex::__private::Instruction::Save(2),
+    ::small_regex::__private::Instruction::Save(4),
+    ::small_regex::__private::Instruction::Split(3, 5),
+    ::small_regex::__private::Instruction::Char('a'),
+    ::small_regex::__private::Instruction::Jump(6),
+    ::small_regex::__private::Instruction::Char('b'),
+    ::small_regex::__private::Instruction::Save(5),
+    ::small_regex::__private::Instruction::Split(1, 8),
+    ::small_regex::__private::Instruction::Save(3),
+    ::small_regex::__private::Instruction::Char('='),
+    ::small_regex::__private::Instruction::Save(6),
+    ::small_regex::__private::Instruction::Split(12, 14),
+    ::small_regex::__private::Instruction::AnyChar(false),
+    ::small_regex::__private::Instruction::Jump(11),
+    ::small_regex::__private::Instruction::Save(7),
+    ::small_regex::__private::Instruction::Assert(::small_regex::__private::Assertion::EndText),
+    ::small_regex::__private::Instruction::Match,
+];
+pub static KEY_VALUE_CAPTURE_NAMES: &[::core::option::Option<&str>] = &[::core::option::Option::None, ::core::option::Option::Some("key"), ::core::option::Option::None, ::core::option::Option::Some("value")];
+
+/// パターン "(?<key>(a|b)+)=(?<value>.*)$" の Regex を生成する (パターンのパースとコンパイルはしない)
+pub fn key_value() -> ::small_regex::Regex {
+    ::small_regex::__private::from_static("(?<key>(a|b)+)=(?<value>.*)$", KEY_VALUE_INSTRUCTIONS, KEY_VALUE_CAPTURE_NAMES)
+}
+
+pub static GREEK_INSTRUCTIONS: &[::small_regex::__private::Instruction] = &[
+    ::small_regex::__private::Instruction::Property(::small_regex::__private::Property::new("Greek", &[('Ͱ', 'ͳ'), ('͵', 'ͷ'), ('ͺ', 'ͽ'), ('Ϳ', 'Ϳ'), ('΄', '΄'), ('Ά', 'Ά'), ('Έ', 'Ί'), ('Ό', 'Ό'), ('Ύ', 'Ρ'), ('Σ', 'ϡ'), ('ϰ', 'Ͽ'), ('ᴦ', 'ᴪ'), ('ᵝ', 'ᵡ'), ('ᵦ', 'ᵪ'), ('ᶿ', 'ᶿ'), ('ἀ', 'ἕ'), ('Ἐ', 'Ἕ'), ('ἠ', 'ὅ'), ('Ὀ', 'Ὅ'), ('ὐ', 'ὗ'), ('Ὑ', 'Ὑ'), ('Ὓ', 'Ὓ'), ('Ὕ', 'Ὕ'), ('Ὗ', 'ώ'), ('ᾀ', 'ᾴ'), ('ᾶ', 'ῄ'), ('ῆ', 'ΐ'), ('ῖ', 'Ί'), ('῝', '`'), ('ῲ', 'ῴ'), ('ῶ', '῾'), ('Ω', 'Ω'), ('ꭥ', 'ꭥ'), ('𐅀', '𐆎'), ('𐆠', '𐆠'), ('𝈀', '𝉅')]), false),
+    ::small_regex::__private::Instruction::Split(0, 2),
+    ::small_regex::__private::Instruction::Match,
+];
+pub static GREEK_CAPTURE_NAMES: &[::core::option::Option<&str>] = &[::core::option::Option::None];
+
+/// パターン "\\p{Greek}+" の Regex を生成する (パターンのパースとコンパイルはしない)
+pub fn greek() -> ::small_regex::Regex {
+    ::small_regex::__private::from_static("\\p{Greek}+", GREEK_INSTRUCTIONS, GREEK_CAPTURE_NAMES)
+}
+
+pub static ATOMIC_INSTRUCTIONS: &[::small_regex::__private::Instruction] = &[
+    ::small_regex::__private::Instruction::Atomic(7),
+    ::small_regex::__private::Instruction::Split(2, 4),
+    ::small_regex::__private::Instruction::Char('a'),
+    ::small_regex::__private::Instruction::Jump(6),
+    ::small_regex::__private::Instruction::Char('a'),
+    ::small_regex::__private::Instruction::Char('b'),
+    ::small_regex::__private::Instruction::Commit,
+    ::small_regex::__private::Instruction::Char('c'),
+    ::small_regex::__private::Instruction::Match,
+];
+pub static ATOMIC_CAPTURE_NAMES: &[::core::option::Option<&str>] = &[::core::option::Option::None];
+
+/// パターン "(?>a|ab)c" の Regex を生成する (パターンのパースとコンパイルはしない)
+pub fn atomic() -> ::small_regex::Regex {
+    ::small_regex::__private::from_static("(?>a|ab)c", ATOMIC_INSTRUCTIONS, ATOMIC_CAPTURE_NAMES)
+}
@@ -0,0 +1,4 @@
+//! small-regex の C 向けの関数 (small_regex::ffi) を、共有ライブラリ・静的ライブラリとしてビルドするクレート  
+//! 宣言は include/small_regex.h にある
+
+pub use small_regex::ffi::*;
@@ -0,0 +1,36 @@
+/* small-regex の C 向けの宣言 (ffi クレートをビルドした libsmall_regex とリンクする) */
+
+#ifndef SMALL_REGEX_H
+#define SMALL_REGEX_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SMALL_REGEX_OK 0
+#define SMALL_REGEX_ERROR_NULL (-1)
+#define SMALL_REGEX_ERROR_UTF8 (-2)
+#define SMALL_REGEX_ERROR_PARSE (-3)
+#define SMALL_REGEX_ERROR_TOO_LARGE (-4)
+#define SMALL_REGEX_ERROR_BUDGET (-5)
+
+typedef struct small_regex small_regex;
+
+/* pattern をコンパイルし、*out に書き込む。small_regex_free で解放すること */
+int small_regex_compile(const char *pattern, small_regex **out);
+
+/* マッチすれば 1、しなければ 0、エラーの場合は負の値を返す */
+int small_regex_is_match(const small_regex *regex, const char *text);
+
+/* 最初のマッチの位置 (バイト単位) を *start, *end に書き込み 1 を返す。マッチしなければ 0 */
+int small_regex_find(const small_regex *regex, const char *text, size_t *start, size_t *end);
+
+void small_regex_free(small_regex *regex);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SMALL_REGEX_H */
@@ -0,0 +1,92 @@
+//! パターンをビルド時にパース・コンパイルする `regex!` マクロ  
+//! パターンが不正な場合はコンパイルエラーになる。コンパイルした命令列は static として埋め込む
+//!
+//! ```
+//! use small_regex_macros::regex;
+//!
+//! let re: &small_regex::Regex = regex!("ab*(de|fg)");
+//! assert!(re.is_match("abbbfg"));
+//! ```
+//!
+//! ```compile_fail
+//! use small_regex_macros::regex;
+//!
+//! let re = regex!("ab(c"); // unbalanced parenthesis
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, LitStr};
+
+use small_regex::__private::{compile_pattern, Assertion, Instruction};
+
+/// 文字列リテラルのパターンから `&'static small_regex::Regex` を生成する  
+/// 命令列はビルド時に生成し、Regex は最初に使う時に1度だけ組み立てる
+#[proc_macro]
+pub fn regex(input: TokenStream) -> TokenStream {
+    let literal: LitStr = parse_macro_input!(input as LitStr);
+    let pattern: String = literal.value();
+
+    let (instructions, capture_names) = match compile_pattern(&pattern) {
+        Ok(program) => program,
+        Err(e) => return syn::Error::new(literal.span(), e).to_compile_error().into(),
+    };
+
+    let instructions: Vec<TokenStream2> = instructions.iter().map(instruction_tokens).collect();
+    let capture_names: Vec<TokenStream2> = capture_names
+        .iter()
+        .map(|name| match name {
+            Some(name) => quote! { ::core::option::Option::Some(#name) },
+            None => quote! { ::core::option::Option::None },
+        })
+        .collect();
+
+    quote! {
+        {
+            use ::small_regex::__private::{Assertion, Instruction, Property};
+            static INSTRUCTIONS: &[Instruction] = &[#(#instructions),*];
+            static CAPTURE_NAMES: &[::core::option::Option<&str>] = &[#(#capture_names),*];
+            static REGEX: ::std::sync::OnceLock<::small_regex::Regex> = ::std::sync::OnceLock::new();
+            REGEX.get_or_init(|| ::small_regex::__private::from_static(#pattern, INSTRUCTIONS, CAPTURE_NAMES))
+        }
+    }
+    .into()
+}
+
+/// 命令を、展開先で同じ命令を生成する式に変換する
+fn instruction_tokens(instruction: &Instruction) -> TokenStream2 {
+    match instruction {
+        Instruction::Char(c) => quote! { Instruction::Char(#c) },
+        Instruction::CharNoCase(c) => quote! { Instruction::CharNoCase(#c) },
+        Instruction::AnyChar(dot_all) => quote! { Instruction::AnyChar(#dot_all) },
+        Instruction::Property(property, negated) => {
+            let name: &str = property.name;
+            let ranges = property.ranges().iter().map(|(start, end)| quote! { (#start, #end) });
+            if property.is_case_insensitive() {
+                quote! { Instruction::Property(Property::new_case_insensitive(#name, &[#(#ranges),*]), #negated) }
+            } else {
+                quote! { Instruction::Property(Property::new(#name, &[#(#ranges),*]), #negated) }
+            }
+        }
+        Instruction::Match => quote! { Instruction::Match },
+        Instruction::Jump(counter) => quote! { Instruction::Jump(#counter) },
+        Instruction::Split(counter1, counter2) => quote! { Instruction::Split(#counter1, #counter2) },
+        Instruction::Atomic(next) => quote! { Instruction::Atomic(#next) },
+        Instruction::Commit => quote! { Instruction::Commit },
+        Instruction::Assert(assertion) => {
+            let assertion: TokenStream2 = match assertion {
+                Assertion::StartText => quote! { Assertion::StartText },
+                Assertion::EndText => quote! { Assertion::EndText },
+                Assertion::EndTextNewline => quote! { Assertion::EndTextNewline },
+                Assertion::StartLine => quote! { Assertion::StartLine },
+                Assertion::EndLine => quote! { Assertion::EndLine },
+                Assertion::StartSearch => quote! { Assertion::StartSearch },
+            };
+            quote! { Instruction::Assert(#assertion) }
+        }
+        Instruction::Save(slot) => quote! { Instruction::Save(#slot) },
+        Instruction::Condition(group, else_counter) => quote! { Instruction::Condition(#group, #else_counter) },
+        Instruction::MatchPattern(id) => quote! { Instruction::MatchPattern(#id) },
+    }
+}
@@ -0,0 +1,26 @@
+use small_regex::Regex;
+use small_regex_macros::regex;
+
+#[test]
+fn test_regex() {
+    let re: &Regex = regex!("(?<word>a+)(b|c)?");
+    assert_eq!(re.as_str(), "(?<word>a+)(b|c)?");
+    let caps = re.captures("xaac").unwrap();
+    assert_eq!(&caps["word"], "aa");
+    assert_eq!(&caps[2], "c");
+}
+
+#[test]
+fn test_same_static() {
+    // 同じ呼び出し箇所では、同じ Regex を返す
+    let get = || regex!("a") as *const Regex;
+    assert_eq!(get(), get());
+}
+
+#[test]
+fn test_property() {
+    let re: &Regex = regex!("\\p{Greek}+");
+    assert_eq!(re.find("abαβ").unwrap().as_str(), "αβ");
+    let re: &Regex = regex!("(?i)\\p{Lu}+");
+    assert_eq!(re.find("1aB2").unwrap().as_str(), "aB");
+}
@@ -0,0 +1,142 @@
+#!/usr/bin/env python3
+"""src/unicode_tables.rs を生成するスクリプト
+
+一般カテゴリは標準ライブラリの unicodedata から、
+スクリプトは regex モジュールの \\p{Script=...} から範囲表を作る。
+(?i) で使う範囲表は、各プロパティに大文字・小文字で同一視する文字を加えたもので、
+元の範囲表と異なるプロパティのみ作る。
+
+    python3 scripts/unicode_tables.py > src/unicode_tables.rs
+"""
+import sys
+import unicodedata
+
+import regex
+
+SCRIPTS = [
+    "Arabic", "Armenian", "Bengali", "Common", "Cyrillic", "Devanagari",
+    "Georgian", "Greek", "Han", "Hangul", "Hebrew", "Hiragana", "Inherited",
+    "Katakana", "Latin", "Tamil", "Thai",
+]
+
+GROUPS = {
+    "C": ["Cc", "Cf", "Cn", "Co", "Cs"],
+    "L": ["Ll", "Lm", "Lo", "Lt", "Lu"],
+    "M": ["Mc", "Me", "Mn"],
+    "N": ["Nd", "Nl", "No"],
+    "P": ["Pc", "Pd", "Pe", "Pf", "Pi", "Po", "Ps"],
+    "S": ["Sc", "Sk", "Sm", "So"],
+    "Z": ["Zl", "Zp", "Zs"],
+}
+
+
+def code_points():
+    for cp in range(0x110000):
+        # サロゲートは Rust の char として表現できないため除外する
+        if 0xD800 <= cp <= 0xDFFF:
+            continue
+        yield cp
+
+
+def to_ranges(cps):
+    ranges = []
+    for cp in cps:
+        if ranges and ranges[-1][1] + 1 == cp:
+            ranges[-1][1] = cp
+        elif ranges and ranges[-1][1] == 0xD7FF and cp == 0xE000:
+            ranges[-1][1] = cp
+        else:
+            ranges.append([cp, cp])
+    return ranges
+
+
+def single(s):
+    return s if len(s) == 1 else None
+
+
+def simple_fold(c):
+    """src/unicode.rs の simple_fold と同じく、大文字にしてから小文字にした文字に畳み込む"""
+    if c == "\u0131":
+        return c
+    upper = single(c.upper()) or c
+    return single(upper.lower()) or single(c.lower()) or c
+
+
+def case_closure(cps, orbits):
+    """cps に、大文字・小文字で同一視する文字をすべて加える"""
+    closed = set(cps)
+    for cp in cps:
+        closed.update(orbits[simple_fold(chr(cp))])
+    return sorted(closed)
+
+
+def emit(name, ranges, out):
+    out.append(f"pub const {name}: &[(char, char)] = &[")
+    for lo, hi in ranges:
+        out.append(f"    ('\\u{{{lo:x}}}', '\\u{{{hi:x}}}'),")
+    out.append("];")
+    out.append("")
+
+
+def main():
+    categories = {}
+    for cp in code_points():
+        categories.setdefault(unicodedata.category(chr(cp)), []).append(cp)
+
+    out = [
+        "//! Unicode の一般カテゴリ・スクリプトの範囲表  ",
+        f"//! scripts/unicode_tables.py で生成している (Unicode {unicodedata.unidata_version})。手で編集しないこと",
+        "",
+    ]
+
+    orbits = {}
+    for cp in code_points():
+        orbits.setdefault(simple_fold(chr(cp)), []).append(cp)
+    properties = {}
+
+    names = []
+    for cat in sorted(set(categories) | {c for g in GROUPS.values() for c in g}):
+        emit(cat.upper(), to_ranges(categories.get(cat, [])), out)
+        names.append((cat, cat.upper()))
+        properties[cat] = categories.get(cat, [])
+    for group, members in sorted(GROUPS.items()):
+        cps = sorted(cp for m in members for cp in categories.get(m, []))
+        emit(group, to_ranges(cps), out)
+        names.append((group, group))
+        properties[group] = cps
+    out.append("/// 一般カテゴリ名と範囲表の対応")
+    out.append("pub const CATEGORIES: &[(&str, &[(char, char)])] = &[")
+    for name, ident in sorted(names):
+        out.append(f'    ("{name}", {ident}),')
+    out.append("];")
+    out.append("")
+
+    for script in SCRIPTS:
+        pattern = regex.compile(rf"\p{{Script={script}}}")
+        cps = [cp for cp in code_points() if pattern.match(chr(cp))]
+        emit(script.upper(), to_ranges(cps), out)
+        properties[script] = cps
+    out.append("/// スクリプト名と範囲表の対応")
+    out.append("pub const SCRIPTS: &[(&str, &[(char, char)])] = &[")
+    for script in SCRIPTS:
+        out.append(f'    ("{script}", {script.upper()}),')
+    out.append("];")
+    out.append("")
+
+    closed = []
+    for name, cps in sorted(properties.items()):
+        closure = case_closure(cps, orbits)
+        if closure != sorted(cps):
+            emit(f"{name.upper()}_NO_CASE", to_ranges(closure), out)
+            closed.append(name)
+    out.append("/// 大文字・小文字を区別しない場合 ((?i)) に、元の範囲表の代わりに使う範囲表の対応 (元の範囲表と異なるもののみ)")
+    out.append("pub const CASE_INSENSITIVE: &[(&str, &[(char, char)])] = &[")
+    for name in closed:
+        out.append(f'    ("{name}", {name.upper()}_NO_CASE),')
+    out.append("];")
+
+    sys.stdout.write("\n".join(out) + "\n")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,30 @@
+//! regex! マクロ (small-regex-macros) から使う型・関数  
+//! マクロの展開先とマクロの実装のためだけに公開しており、互換性は保証しない
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub use crate::compiler::Instruction;
+pub use crate::parser::Assertion;
+pub use crate::unicode::Property;
+
+use crate::builder::DEFAULT_NEST_LIMIT;
+use crate::compiler::{compile_program, Program};
+use crate::error::Error;
+use crate::evaluator::EvalOptions;
+use crate::parser::{parse_with_flags, Flags};
+use crate::regex::Regex;
+use crate::simplify::simplify;
+
+/// パターンをパースしてコンパイルし、命令列と各キャプチャグループの名前を返す
+pub fn compile_pattern(pattern: &str) -> Result<(Vec<Instruction>, Vec<Option<String>>), Error> {
+    let ast = simplify(parse_with_flags(pattern, Flags::default(), DEFAULT_NEST_LIMIT)?);
+    let program: Program = compile_program(&ast, usize::MAX)?;
+    Ok((program.instructions, program.capture_names))
+}
+
+/// compile_pattern で得た命令列から Regex を生成する (パターンはパースし直さない)
+pub fn from_static(pattern: &str, instructions: &[Instruction], capture_names: &[Option<&str>]) -> Regex {
+    let capture_names: Vec<Option<String>> = capture_names.iter().map(|name| name.map(String::from)).collect();
+    Regex::from_parts(pattern, instructions.to_vec(), capture_names, EvalOptions::default())
+}
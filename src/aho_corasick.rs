@@ -0,0 +1,222 @@
+//! リテラルの選択 (`error|warn|fatal` など) だけからなるパターンを、Aho-Corasick のオートマトンで探索するための型  
+//! 入力の各バイトを表の参照1回で読むため、命令列を評価するより速い  
+//! メタ文字を含まないリテラル1つだけのパターンは、表を作らずに部分文字列の探索 (memchr::memmem) で探す
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::input::is_raw_byte;
+use crate::parser::AST;
+
+/// パターンがリテラルの選択 (リテラル1つの場合を含む) だけからなる場合に、各リテラルを優先度の順に返す  
+/// キャプチャグループ、アンカー、`(?i)` の文字などを含む場合や、空のリテラルを含む場合は None を返す
+pub fn literals(ast: &AST) -> Option<Vec<String>> {
+    let mut literals: Vec<String> = Vec::new();
+    collect_literals(ast, &mut literals)?;
+    Some(literals)
+}
+
+fn collect_literals(ast: &AST, literals: &mut Vec<String>) -> Option<()> {
+    match ast {
+        AST::Or(left, right) => {
+            collect_literals(left, literals)?;
+            collect_literals(right, literals)
+        }
+        _ => {
+            let literal: String = literal(ast)?;
+            if literal.is_empty() {
+                return None;
+            }
+            literals.push(literal);
+            Some(())
+        }
+    }
+}
+
+/// AST が文字の連結だけからなる場合に、その文字列を返す  
+/// UTF-8 として不正なバイトを表す文字は、入力の中のバイト列が決まらないため、リテラルとしない
+fn literal(ast: &AST) -> Option<String> {
+    match ast {
+        AST::Char(c) if !is_raw_byte(*c) => Some(String::from(*c)),
+        AST::Seq(asts) => asts.iter().map(literal).collect(),
+        AST::Empty => Some(String::new()),
+        _ => None,
+    }
+}
+
+/// 状態が無いことを表す遷移先
+const NONE: u32 = u32::MAX;
+
+/// 複数のリテラルを同時に探索する Aho-Corasick のオートマトン  
+/// 失敗時の遷移をあらかじめ解決し、状態 * 256 + バイト の表で遷移する
+#[derive(Debug)]
+pub struct AhoCorasick {
+    lens: Vec<usize>,         // 各リテラルの長さ (バイト単位)
+    table: Vec<u32>,          // 状態 * 256 + バイト の位置に、遷移先の状態を記録する
+    outputs: Vec<Vec<usize>>, // 各状態で終わるリテラルの番号 (失敗時の遷移先で終わるものを含む)
+    max_len: usize,           // 最も長いリテラルの長さ
+    needle: Option<Vec<u8>>,  // リテラルが1つだけの場合のバイト列 (この場合は表を作らない)
+}
+
+impl AhoCorasick {
+    /// literals (優先度の高い順) を探索するオートマトンを生成する
+    pub fn new<S: AsRef<str>>(literals: &[S]) -> AhoCorasick {
+        if let [literal] = literals {
+            let needle: Vec<u8> = literal.as_ref().as_bytes().to_vec();
+            return AhoCorasick {
+                lens: vec![needle.len()],
+                table: Vec::new(),
+                outputs: Vec::new(),
+                max_len: needle.len(),
+                needle: Some(needle),
+            };
+        }
+        let mut table: Vec<u32> = vec![NONE; 256];
+        let mut outputs: Vec<Vec<usize>> = vec![Vec::new()];
+        for (i, literal) in literals.iter().enumerate() {
+            let mut state: usize = 0;
+            for byte in literal.as_ref().bytes() {
+                let next: u32 = table[state * 256 + byte as usize];
+                state = if next == NONE {
+                    let new: usize = outputs.len();
+                    table[state * 256 + byte as usize] = new as u32;
+                    table.extend_from_slice(&[NONE; 256]);
+                    outputs.push(Vec::new());
+                    new
+                } else {
+                    next as usize
+                };
+            }
+            outputs[state].push(i);
+        }
+
+        // 幅優先で、失敗時の遷移先 (最も長い真の接尾辞に対応する状態) を求め、表を埋める
+        let mut fail: Vec<usize> = vec![0; outputs.len()];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        for next in table[..256].iter_mut() {
+            match *next {
+                NONE => *next = 0,
+                state => queue.push_back(state as usize),
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            let inherited: Vec<usize> = outputs[fail[state]].clone();
+            outputs[state].extend(inherited);
+            for byte in 0..256 {
+                let fallback: u32 = table[fail[state] * 256 + byte];
+                match table[state * 256 + byte] {
+                    NONE => table[state * 256 + byte] = fallback,
+                    next => {
+                        fail[next as usize] = fallback as usize;
+                        queue.push_back(next as usize);
+                    }
+                }
+            }
+        }
+
+        let lens: Vec<usize> = literals.iter().map(|literal| literal.as_ref().len()).collect();
+        let max_len: usize = lens.iter().copied().max().unwrap_or(0);
+        AhoCorasick { lens, table, outputs, max_len, needle: None }
+    }
+
+    /// haystack の start 以降で、最も前から始まるリテラルの出現を (開始位置, 終了位置) で返す  
+    /// 同じ位置から始まるリテラルが複数ある場合は、優先度の高いものを返す (パターンの `|` と同じ leftmost-first)
+    pub fn find(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        if let Some(needle) = &self.needle {
+            let found: usize = start + memchr::memmem::find(haystack.get(start..)?, needle)?;
+            return Some((found, found + needle.len()));
+        }
+        let mut best: Option<(usize, usize)> = None; // これまでに見つけた (開始位置, リテラルの番号)
+        let mut state: usize = 0;
+        for (pos, byte) in haystack.iter().enumerate().skip(start) {
+            if let Some((best_start, _)) = best {
+                if pos >= best_start + self.max_len {
+                    // これ以降に見つかる出現は、best より後ろから始まる
+                    break;
+                }
+            }
+            state = self.table[state * 256 + *byte as usize] as usize;
+            for i in self.outputs[state].iter() {
+                let found: (usize, usize) = (pos + 1 - self.lens[*i], *i);
+                if best.is_none_or(|best| found < best) {
+                    best = Some(found);
+                }
+            }
+        }
+        best.map(|(start, i)| (start, start + self.lens[i]))
+    }
+
+    /// haystack の中に出現するリテラルの番号を調べ、matched の対応する位置を true にする
+    pub fn matches(&self, haystack: &[u8], matched: &mut [bool]) {
+        if let Some(needle) = &self.needle {
+            matched[0] |= memchr::memmem::find(haystack, needle).is_some();
+            return;
+        }
+        let mut state: usize = 0;
+        for byte in haystack.iter() {
+            state = self.table[state * 256 + *byte as usize] as usize;
+            for i in self.outputs[state].iter() {
+                matched[*i] = true;
+            }
+        }
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::{literals, AhoCorasick};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_literals() {
+        let expected: Vec<String> = vec!["error".into(), "warn".into(), "fatal".into()];
+        assert_eq!(literals(&parse("error|warn|fatal").unwrap()), Some(expected));
+        assert_eq!(literals(&parse("(error|warn)").unwrap()), None);
+        assert_eq!(literals(&parse("err.r|warn").unwrap()), None);
+        assert_eq!(literals(&parse("(?i)error|warn").unwrap()), None);
+        assert_eq!(literals(&parse("error").unwrap()), Some(vec!["error".into()]));
+    }
+
+    #[test]
+    fn test_find() {
+        let ac: AhoCorasick = AhoCorasick::new(&["abcd", "bc", "ab", "c"]);
+        // 最も前から始まる出現のうち、優先度の高いもの
+        assert_eq!(ac.find(b"xabce", 0), Some((1, 3)));
+        assert_eq!(ac.find(b"xabcd", 0), Some((1, 5)));
+        assert_eq!(ac.find(b"xabcd", 2), Some((2, 4)));
+        assert_eq!(ac.find(b"xxc", 0), Some((2, 3)));
+        assert_eq!(ac.find(b"xyz", 0), None);
+
+        let ac: AhoCorasick = AhoCorasick::new(&["α", "β"]);
+        assert_eq!(ac.find("γβα".as_bytes(), 0), Some((2, 4)));
+    }
+
+    #[test]
+    fn test_single_literal() {
+        // リテラルが1つの場合は、表を作らずに部分文字列として探す
+        let ac: AhoCorasick = AhoCorasick::new(&["a.b"]);
+        assert!(ac.table.is_empty());
+        assert_eq!(ac.find(b"axb a.b a.b", 0), Some((4, 7)));
+        assert_eq!(ac.find(b"axb a.b a.b", 5), Some((8, 11)));
+        assert_eq!(ac.find(b"a.b", 4), None);
+        let mut matched: [bool; 1] = [false];
+        ac.matches(b"xa.bx", &mut matched);
+        assert_eq!(matched, [true]);
+    }
+
+    #[test]
+    fn test_matches() {
+        let ac: AhoCorasick = AhoCorasick::new(&["he", "she", "his", "hers"]);
+        let mut matched: [bool; 4] = [false; 4];
+        ac.matches(b"ushers", &mut matched);
+        assert_eq!(matched, [true, true, false, true]);
+    }
+}
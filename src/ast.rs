@@ -0,0 +1,249 @@
+//! パターンを、文字列ではなくコードで組み立てるための公開の API  
+//! Ast は内部の AST を包み、`Ast::literal("abc").then(Ast::any().star())` のように組み合わせて作る  
+//! リテラルの文字はエスケープせずに渡せ、Regex を生成する際に正規化したパターンの文字列にしてからコンパイルする
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::builder::RegexBuilder;
+use crate::error::{Error, ParseError, ParseErrorKind};
+use crate::parser::{Assertion, AST};
+use crate::regex::Regex;
+use crate::unicode::{simple_fold, Property};
+use crate::visit::{walk_mut, VisitMut};
+
+/// コードで組み立てるパターン  
+/// キャプチャグループの番号は、組み立てたパターンの中の `(` の順 (左から) に 1 から付く
+///
+/// ```
+/// use small_regex::Ast;
+///
+/// let ast = Ast::literal("a.b").then(Ast::any().star()).or(Ast::literal("xyz").capture());
+/// assert_eq!(ast.to_string(), "a\\.b.*|(xyz)");
+/// let re = ast.build().unwrap();
+/// assert!(re.is_match("a.bcd"));
+/// assert!(!re.is_match("aXb"));
+/// assert_eq!(&re.captures("xyz").unwrap()[1], "xyz");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ast {
+    ast: AST,
+}
+
+impl Ast {
+    /// 空文字列にマッチするパターン
+    pub fn empty() -> Ast {
+        Ast { ast: AST::Empty }
+    }
+
+    /// text にそのままマッチするパターン (`.` や `*` などもエスケープせずに渡す)
+    pub fn literal(text: &str) -> Ast {
+        Ast::chars(text.chars().map(AST::Char).collect())
+    }
+
+    /// 大文字・小文字を区別せずに text にマッチするパターン
+    pub fn literal_no_case(text: &str) -> Ast {
+        Ast::chars(text.chars().map(|c| AST::CharNoCase(simple_fold(c))).collect())
+    }
+
+    /// 改行以外の任意の1文字にマッチするパターン (`.`)
+    pub fn any() -> Ast {
+        Ast { ast: AST::AnyChar(false) }
+    }
+
+    /// 改行を含む任意の1文字にマッチするパターン (`(?s).`)
+    pub fn any_with_newline() -> Ast {
+        Ast { ast: AST::AnyChar(true) }
+    }
+
+    /// Unicode のプロパティ (`L`, `Greek` など) に含まれる1文字にマッチするパターン (`\p{..}`)  
+    /// 未知のプロパティの場合は、ParseErrorKind::UnknownProperty のエラーを返す (位置は 0 とする)
+    pub fn property(name: &str) -> Result<Ast, Error> {
+        match Property::lookup(name) {
+            Some(property) => Ok(Ast { ast: AST::Property(property, false) }),
+            None => Err(ParseError::new(ParseErrorKind::UnknownProperty(name.to_string()), 0).into()),
+        }
+    }
+
+    /// 入力の先頭にマッチするパターン (`^`)
+    pub fn start_text() -> Ast {
+        Ast { ast: AST::Assert(Assertion::StartText) }
+    }
+
+    /// 入力の末尾にマッチするパターン (`$`)
+    pub fn end_text() -> Ast {
+        Ast { ast: AST::Assert(Assertion::EndText) }
+    }
+
+    /// 行の先頭にマッチするパターン (`(?m)^`)
+    pub fn start_line() -> Ast {
+        Ast { ast: AST::Assert(Assertion::StartLine) }
+    }
+
+    /// 行の末尾にマッチするパターン (`(?m)$`)
+    pub fn end_line() -> Ast {
+        Ast { ast: AST::Assert(Assertion::EndLine) }
+    }
+
+    /// self の後に next が続くパターン (連結)
+    pub fn then(self, next: Ast) -> Ast {
+        let mut asts: Vec<AST> = Vec::new();
+        for ast in [self.ast, next.ast] {
+            match ast {
+                AST::Seq(inner) => asts.extend(inner),
+                AST::Empty => {}
+                ast => asts.push(ast),
+            }
+        }
+        Ast::numbered(Ast::chars(asts).ast)
+    }
+
+    /// self または other にマッチするパターン (選択、self を優先する)
+    pub fn or(self, other: Ast) -> Ast {
+        Ast::numbered(AST::Or(Box::new(self.ast), Box::new(other.ast)))
+    }
+
+    /// self の 0 回以上の繰り返し (`*`)
+    pub fn star(self) -> Ast {
+        Ast { ast: AST::Star(Box::new(self.ast)) }
+    }
+
+    /// self の 1 回以上の繰り返し (`+`)
+    pub fn plus(self) -> Ast {
+        Ast { ast: AST::Plus(Box::new(self.ast)) }
+    }
+
+    /// self の 0 回または 1 回の繰り返し (`?`)
+    pub fn optional(self) -> Ast {
+        Ast { ast: AST::Question(Box::new(self.ast)) }
+    }
+
+    /// self にマッチした後はバックトラックしないパターン (アトミックグループ `(?>...)`)
+    pub fn atomic(self) -> Ast {
+        Ast { ast: AST::Atomic(Box::new(self.ast)) }
+    }
+
+    /// self をキャプチャグループにする
+    pub fn capture(self) -> Ast {
+        Ast::numbered(AST::Capture(0, None, Box::new(self.ast)))
+    }
+
+    /// self を名前付きのキャプチャグループにする  
+    /// 名前が不正な場合や重複する場合は、build でエラーを返す
+    pub fn named_capture(self, name: &str) -> Ast {
+        Ast::numbered(AST::Capture(0, Some(name.to_string()), Box::new(self.ast)))
+    }
+
+    /// パターンをコンパイルし、Regex を生成する  
+    /// フラグや上限を指定する場合は、builder が返す RegexBuilder を使う
+    pub fn build(&self) -> Result<Regex, Error> {
+        self.builder().build()
+    }
+
+    /// パターンの文字列から Regex を生成する RegexBuilder を作成する
+    pub fn builder(&self) -> RegexBuilder {
+        RegexBuilder::new(&self.to_string())
+    }
+
+    /// キャプチャグループに、左から順に 1 からの番号を付け直した Ast を作る
+    fn numbered(mut ast: AST) -> Ast {
+        Numbering { count: 0 }.visit_mut(&mut ast);
+        Ast { ast }
+    }
+
+    /// 文字などの列から、要素の数に応じた AST を持つ Ast を作る
+    fn chars(mut asts: Vec<AST>) -> Ast {
+        let ast: AST = match asts.len() {
+            0 => AST::Empty,
+            1 => asts.pop().unwrap(),
+            _ => AST::Seq(asts),
+        };
+        Ast { ast }
+    }
+}
+
+/// キャプチャグループに、パターンの中の `(` の順に番号を付ける
+struct Numbering {
+    count: usize,
+}
+
+impl VisitMut for Numbering {
+    fn visit_mut(&mut self, ast: &mut AST) {
+        if let AST::Capture(index, _, _) = ast {
+            self.count += 1;
+            *index = self.count;
+        }
+        walk_mut(self, ast);
+    }
+}
+
+/// 正規化したパターンの文字列として表示する (RegexBuilder::new にそのまま渡せる)
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pattern: String = self.ast.to_pattern_string();
+        f.write_str(&pattern)
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::Ast;
+    use crate::error::{Error, ParseError, ParseErrorKind};
+    use crate::parser::{parse, AST};
+    use crate::regex::Regex;
+    use crate::simplify::simplify;
+
+    #[test]
+    fn test_ast() {
+        let ast: Ast = Ast::start_text()
+            .then(Ast::literal("1+1").or(Ast::literal_no_case("ab")).capture())
+            .then(Ast::literal(" ").then(Ast::any_with_newline()).plus().optional())
+            .then(Ast::end_text());
+        assert_eq!(ast.to_string(), "^(1\\+1|(?i)ab)(?:(?: (?s).)+)?$");
+        let re: Regex = ast.build().unwrap();
+        assert!(re.is_match("1+1"));
+        assert!(re.is_match("aB \n x"));
+        assert!(!re.is_match("11"));
+        assert!(!re.is_match("ab x "));
+
+        let ast: Ast = Ast::literal("a").then(Ast::empty()).then(Ast::literal("b"));
+        assert_eq!(ast, Ast::literal("ab"));
+        assert_eq!(Ast::empty().to_string(), "");
+        assert_eq!(Ast::start_line().then(Ast::any()).then(Ast::end_line()).to_string(), "(?m)^.$");
+        assert_eq!(Ast::literal("ab").star().atomic().to_string(), "(?>(?:ab)*)");
+    }
+
+    #[test]
+    fn test_ast_captures() {
+        let ast: Ast = Ast::literal("x").named_capture("first").then(Ast::any().capture());
+        let re: Regex = ast.build().unwrap();
+        let caps = re.captures("xy").unwrap();
+        assert_eq!(&caps["first"], "x");
+        assert_eq!(&caps[2], "y");
+
+        // グループの番号は、組み立てた後のパターンの中の順に付く
+        let ast: Ast = Ast::any().capture().capture().or(Ast::literal("y").capture());
+        let expect: AST = simplify(parse("((.))|(y)").unwrap());
+        assert_eq!(ast.ast, expect);
+
+        let ast: Ast = Ast::literal("x").named_capture("1st");
+        assert!(ast.build().is_err());
+        assert!(Ast::literal("ab").builder().case_insensitive(true).build().unwrap().is_match("AB"));
+    }
+
+    #[test]
+    fn test_ast_property() {
+        #[cfg(feature = "unicode")]
+        assert!(Ast::property("Greek").unwrap().plus().build().unwrap().is_match("αβ"));
+        assert_eq!(
+            Ast::property("Unknown"),
+            Err(Error::Parse(ParseError::new(ParseErrorKind::UnknownProperty("Unknown".to_string()), 0)))
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! コンパイル済みの Regex を、独自のバイナリ形式で書き出し・読み込むための実装  
+//! serde に依存せず、ツールでコンパイルしたパターンを組み込み環境などで読み込むために使う
+//!
+//! 形式は先頭のマジックナンバー `SRGX` と形式のバージョンに続けて、探索の設定とパースのフラグ、パターン、グループの名前、命令列を並べる  
+//! 整数はすべて LEB128 (7ビットずつ下位から並べ、続きがあれば最上位ビットを立てる) で書き出す  
+//! Unicode プロパティは名前だけを書き出し、読み込む際に範囲表を引き直す
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::compiler::{is_valid_program, Instruction};
+use crate::error::{DecodeError, Error};
+use crate::evaluator::EvalOptions;
+use crate::parser::{Assertion, Flags};
+use crate::regex::Regex;
+use crate::unicode::Property;
+
+/// 先頭のマジックナンバー
+const MAGIC: &[u8; 4] = b"SRGX";
+
+/// 形式のバージョン (形式を変えた場合は上げる)  
+/// バージョン1はパースのフラグを書き出さない (フラグのビットはすべて0) ため、バージョン2として読み込める
+const VERSION: u64 = 2;
+
+impl Regex {
+    /// コンパイル済みの命令列、探索の設定、グループの名前をバイナリ形式で書き出す  
+    /// from_bytes で読み込むと、パターンをパースし直さずに同じ Regex を得られる
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let bytes: Vec<u8> = Regex::new("(?<x>a+)b").unwrap().to_bytes();
+    /// let re: Regex = Regex::from_bytes(&bytes).unwrap();
+    /// assert_eq!(&re.captures("xaab").unwrap()["x"], "aa");
+    /// assert!(Regex::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer: Writer = Writer { bytes: MAGIC.to_vec() };
+        writer.uint(VERSION);
+        let options: EvalOptions = self.options();
+        let flags: Flags = self.flags();
+        writer.uint(
+            options.anchored as u64
+                | (options.anchored_end as u64) << 1
+                | (options.longest as u64) << 2
+                | (flags.case_insensitive as u64) << 3
+                | (flags.multi_line as u64) << 4
+                | (flags.dot_all as u64) << 5
+                | (flags.extended as u64) << 6
+                | (flags.lenient_escapes as u64) << 7
+                | (flags.raw_bytes as u64) << 8,
+        );
+        writer.limit(options.backtrack_limit);
+        writer.limit(options.depth_limit);
+        writer.str(self.as_str());
+        writer.uint(self.names().len() as u64);
+        for name in self.names() {
+            match name {
+                Some(name) => {
+                    writer.uint(1);
+                    writer.str(name);
+                }
+                None => writer.uint(0),
+            }
+        }
+        writer.uint(self.instructions().len() as u64);
+        for inst in self.instructions() {
+            writer.instruction(inst);
+        }
+        writer.bytes
+    }
+
+    /// to_bytes で書き出したバイト列から Regex を生成する  
+    /// 形式やバージョンが異なる場合、途中で途切れている場合、命令列が範囲外を参照する場合などは Error::Decode を返す
+    pub fn from_bytes(bytes: &[u8]) -> Result<Regex, Error> {
+        Ok(decode(bytes)?)
+    }
+}
+
+/// バイト列から Regex を読み込む
+fn decode(bytes: &[u8]) -> Result<Regex, DecodeError> {
+    let rest: &[u8] = bytes.strip_prefix(MAGIC.as_slice()).ok_or(DecodeError::InvalidHeader)?;
+    let mut reader: Reader = Reader { bytes: rest };
+    let version: u64 = reader.uint()?;
+    if !(1..=VERSION).contains(&version) {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let flags: u64 = reader.uint()?;
+    if flags > 0x1ff {
+        return Err(DecodeError::InvalidData);
+    }
+    let options: EvalOptions = EvalOptions {
+        anchored: flags & 1 != 0,
+        anchored_end: flags & 2 != 0,
+        longest: flags & 4 != 0,
+        backtrack_limit: reader.limit()?,
+        depth_limit: reader.limit()?,
+    };
+    let pattern: String = reader.string()?;
+    let mut capture_names: Vec<Option<String>> = Vec::new();
+    for _ in 0..reader.uint()? {
+        let name: Option<String> = match reader.uint()? {
+            0 => None,
+            1 => Some(reader.string()?),
+            _ => return Err(DecodeError::InvalidData),
+        };
+        capture_names.push(name);
+    }
+    let mut instructions: Vec<Instruction> = Vec::new();
+    for _ in 0..reader.uint()? {
+        instructions.push(reader.instruction()?);
+    }
+    if !reader.bytes.is_empty() {
+        return Err(DecodeError::InvalidData);
+    }
+    if !is_valid_program(&instructions, capture_names.len()) {
+        return Err(DecodeError::InvalidProgram);
+    }
+    let flags: Flags = Flags {
+        case_insensitive: flags & 8 != 0,
+        multi_line: flags & 16 != 0,
+        dot_all: flags & 32 != 0,
+        extended: flags & 64 != 0,
+        lenient_escapes: flags & 128 != 0,
+        raw_bytes: flags & 256 != 0,
+    };
+    Ok(Regex::from_parts(&pattern, instructions, capture_names, options).with_flags(flags))
+}
+
+/// 命令の種類を表す番号
+const CHAR: u64 = 0;
+const CHAR_NO_CASE: u64 = 1;
+const ANY_CHAR: u64 = 2;
+const PROPERTY: u64 = 3;
+const MATCH: u64 = 4;
+const JUMP: u64 = 5;
+const SPLIT: u64 = 6;
+const ATOMIC: u64 = 7;
+const COMMIT: u64 = 8;
+const ASSERT: u64 = 9;
+const SAVE: u64 = 10;
+const CONDITION: u64 = 11;
+const MATCH_PATTERN: u64 = 12;
+
+/// アサーションの種類と番号の対応 (番号は配列の位置)
+const ASSERTIONS: [Assertion; 6] = [
+    Assertion::StartText,
+    Assertion::EndText,
+    Assertion::StartLine,
+    Assertion::EndLine,
+    Assertion::EndTextNewline,
+    Assertion::StartSearch,
+];
+
+/// バイト列を書き出す型
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn uint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.uint(value as u64);
+    }
+
+    /// None を 0、Some(n) を n + 1 として書き出す
+    fn limit(&mut self, limit: Option<usize>) {
+        self.uint(limit.map_or(0, |limit| limit as u64 + 1));
+    }
+
+    fn str(&mut self, s: &str) {
+        self.usize(s.len());
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+
+    fn instruction(&mut self, inst: &Instruction) {
+        match inst {
+            Instruction::Char(c) => {
+                self.uint(CHAR);
+                self.uint(*c as u64);
+            }
+            Instruction::CharNoCase(c) => {
+                self.uint(CHAR_NO_CASE);
+                self.uint(*c as u64);
+            }
+            Instruction::AnyChar(dot_all) => {
+                self.uint(ANY_CHAR);
+                self.uint(*dot_all as u64);
+            }
+            Instruction::Property(property, negated) => {
+                self.uint(PROPERTY);
+                self.str(&property.key());
+                self.uint(*negated as u64);
+            }
+            Instruction::Match => self.uint(MATCH),
+            Instruction::Jump(counter) => {
+                self.uint(JUMP);
+                self.usize(*counter);
+            }
+            Instruction::Split(counter1, counter2) => {
+                self.uint(SPLIT);
+                self.usize(*counter1);
+                self.usize(*counter2);
+            }
+            Instruction::Atomic(next) => {
+                self.uint(ATOMIC);
+                self.usize(*next);
+            }
+            Instruction::Commit => self.uint(COMMIT),
+            Instruction::Assert(assertion) => {
+                self.uint(ASSERT);
+                self.usize(ASSERTIONS.iter().position(|a| a == assertion).unwrap_or(0));
+            }
+            Instruction::Save(slot) => {
+                self.uint(SAVE);
+                self.usize(*slot);
+            }
+            Instruction::Condition(group, else_counter) => {
+                self.uint(CONDITION);
+                self.usize(*group);
+                self.usize(*else_counter);
+            }
+            Instruction::MatchPattern(id) => {
+                self.uint(MATCH_PATTERN);
+                self.usize(*id);
+            }
+        }
+    }
+}
+
+/// バイト列を先頭から読み込む型
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let (byte, rest) = self.bytes.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        self.bytes = rest;
+        Ok(*byte)
+    }
+
+    fn uint(&mut self) -> Result<u64, DecodeError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte: u8 = self.byte()?;
+            value |= u64::from(byte & 0x7f).checked_shl(shift).ok_or(DecodeError::InvalidData)?;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::InvalidData)
+    }
+
+    fn usize(&mut self) -> Result<usize, DecodeError> {
+        usize::try_from(self.uint()?).map_err(|_| DecodeError::InvalidData)
+    }
+
+    fn bool(&mut self) -> Result<bool, DecodeError> {
+        match self.uint()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::InvalidData),
+        }
+    }
+
+    fn char(&mut self) -> Result<char, DecodeError> {
+        let value: u32 = u32::try_from(self.uint()?).map_err(|_| DecodeError::InvalidData)?;
+        char::from_u32(value).ok_or(DecodeError::InvalidData)
+    }
+
+    fn limit(&mut self) -> Result<Option<usize>, DecodeError> {
+        match self.usize()? {
+            0 => Ok(None),
+            limit => Ok(Some(limit - 1)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let len: usize = self.usize()?;
+        if len > self.bytes.len() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let (s, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        core::str::from_utf8(s).map(String::from).map_err(|_| DecodeError::InvalidData)
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, DecodeError> {
+        let inst: Instruction = match self.uint()? {
+            CHAR => Instruction::Char(self.char()?),
+            CHAR_NO_CASE => Instruction::CharNoCase(self.char()?),
+            ANY_CHAR => Instruction::AnyChar(self.bool()?),
+            PROPERTY => {
+                let name: String = self.string()?;
+                let property: Property = Property::from_key(&name).ok_or(DecodeError::UnknownProperty(name))?;
+                Instruction::Property(property, self.bool()?)
+            }
+            MATCH => Instruction::Match,
+            JUMP => Instruction::Jump(self.usize()?),
+            SPLIT => Instruction::Split(self.usize()?, self.usize()?),
+            ATOMIC => Instruction::Atomic(self.usize()?),
+            COMMIT => Instruction::Commit,
+            ASSERT => Instruction::Assert(*ASSERTIONS.get(self.usize()?).ok_or(DecodeError::InvalidData)?),
+            SAVE => Instruction::Save(self.usize()?),
+            CONDITION => Instruction::Condition(self.usize()?, self.usize()?),
+            MATCH_PATTERN => Instruction::MatchPattern(self.usize()?),
+            _ => return Err(DecodeError::InvalidData),
+        };
+        Ok(inst)
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::builder::RegexBuilder;
+    use crate::error::{DecodeError, Error};
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_round_trip() {
+        let re: Regex = RegexBuilder::new("(?i)(?<x>a+)b$").anchored(true).backtrack_limit(300).build().unwrap();
+        let re: Regex = Regex::from_bytes(&re.to_bytes()).unwrap();
+        assert_eq!(re.as_str(), "(?i)(?<x>a+)b$");
+        assert_eq!(&re.captures("aAB").unwrap()["x"], "aA");
+        assert!(!re.is_match("xaab"));
+        assert_eq!(re.options().backtrack_limit, Some(300));
+        assert_eq!(re.options().depth_limit, None);
+
+        let re: Regex = Regex::new("(?>a|ab)(1)?(?(1)c|d)").unwrap();
+        let decoded: Regex = Regex::from_bytes(&re.to_bytes()).unwrap();
+        assert_eq!(decoded.instructions(), re.instructions());
+
+        // パースのフラグも読み込む
+        let re: Regex = RegexBuilder::new("a.b").case_insensitive(true).dot_matches_new_line(true).build().unwrap();
+        let decoded: Regex = Regex::from_bytes(&re.to_bytes()).unwrap();
+        assert_eq!(decoded.flags(), re.flags());
+        assert_eq!(decoded.debug_ast(), re.debug_ast());
+
+        // バージョン1の形式も読み込める
+        let mut bytes: Vec<u8> = Regex::new("ab").unwrap().to_bytes();
+        bytes[4] = 1;
+        assert!(Regex::from_bytes(&bytes).unwrap().is_match("xab"));
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_property() {
+        let bytes: Vec<u8> = Regex::new("\\p{Greek}+").unwrap().to_bytes();
+        let re: Regex = Regex::from_bytes(&bytes).unwrap();
+        assert_eq!(re.find("abαβ").unwrap().as_str(), "αβ");
+
+        let bytes: Vec<u8> = Regex::new("(?i)\\p{Lu}+").unwrap().to_bytes();
+        let re: Regex = Regex::from_bytes(&bytes).unwrap();
+        assert_eq!(re.find("1aB2").unwrap().as_str(), "aB");
+    }
+
+    #[test]
+    fn test_invalid() {
+        let bytes: Vec<u8> = Regex::new("a(b)").unwrap().to_bytes();
+        let error = |e: DecodeError| Err(Error::Decode(e));
+        assert_eq!(Regex::from_bytes(b"XRGX\x01").map(|_| ()), error(DecodeError::InvalidHeader));
+        assert_eq!(Regex::from_bytes(b"SRGX\x03").map(|_| ()), error(DecodeError::UnsupportedVersion(3)));
+        for len in 4..bytes.len() {
+            assert_eq!(Regex::from_bytes(&bytes[..len]).map(|_| ()), error(DecodeError::UnexpectedEnd), "{}", len);
+        }
+        let mut trailing: Vec<u8> = bytes.clone();
+        trailing.push(0);
+        assert_eq!(Regex::from_bytes(&trailing).map(|_| ()), error(DecodeError::InvalidData));
+
+        // 最後の命令 (Match) を Jump(100) に書き換えると、範囲外に飛ぶ命令列になる
+        let mut jump: Vec<u8> = bytes.clone();
+        jump.pop();
+        jump.extend_from_slice(&[5, 100]);
+        assert_eq!(Regex::from_bytes(&jump).map(|_| ()), error(DecodeError::InvalidProgram));
+    }
+}
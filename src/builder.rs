@@ -0,0 +1,418 @@
+//! フラグや上限を指定して Regex を生成するための型
+
+use alloc::string::{String, ToString};
+
+use crate::aho_corasick::literals;
+use crate::bytes;
+use crate::compiler::{compile_program, Program};
+use crate::complexity::{analyze, ComplexityReport};
+use crate::error::Error;
+use crate::evaluator::EvalOptions;
+use crate::parser::{parse_with_flags, Flags};
+use crate::regex::Regex;
+use crate::simplify::simplify;
+
+/// フラグや上限を指定して Regex を生成する型  
+/// 各メソッドで設定を変更し、最後に build で Regex を生成する
+///
+/// ```
+/// use small_regex::RegexBuilder;
+///
+/// let re = RegexBuilder::new("^ab.$").case_insensitive(true).multi_line(true).build().unwrap();
+/// assert!(re.is_match("x\nABc\ny"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RegexBuilder {
+    pattern: String,
+    flags: Flags,
+    options: EvalOptions,
+    size_limit: usize,        // 命令列の長さの上限
+    nest_limit: usize,        // AST のネストの深さの上限
+    allow_invalid_utf8: bool, // bytes::Regex で、不正な UTF-8 を含む入力を探索するかどうか
+    dfa: bool,                // 命令列を DFA に変換するかどうか
+    lazy_dfa: bool,           // is_match で lazy DFA を使うかどうか
+    dfa_state_limit: usize,   // DFA の状態の数の上限
+}
+
+/// DFA の状態の数の上限の初期値
+const DEFAULT_DFA_STATE_LIMIT: usize = 10_000;
+
+/// 命令列の長さの上限の初期値
+const DEFAULT_SIZE_LIMIT: usize = 1 << 20;
+
+/// AST のネストの深さの上限の初期値
+pub(crate) const DEFAULT_NEST_LIMIT: usize = 250;
+
+impl RegexBuilder {
+    /// pattern から Regex を生成する RegexBuilder を作成する  
+    /// 初期状態では、すべてのフラグが無効で、命令列の長さとネストの深さには既定の上限がある
+    pub fn new(pattern: &str) -> RegexBuilder {
+        RegexBuilder {
+            pattern: pattern.to_string(),
+            flags: Flags::default(),
+            options: EvalOptions::default(),
+            size_limit: DEFAULT_SIZE_LIMIT,
+            nest_limit: DEFAULT_NEST_LIMIT,
+            allow_invalid_utf8: true,
+            dfa: false,
+            lazy_dfa: false,
+            dfa_state_limit: DEFAULT_DFA_STATE_LIMIT,
+        }
+    }
+
+    /// 大文字・小文字を区別しないかどうか (`(?i)` と同じ)  
+    /// 'σ', 'Σ', 'ς' のように同一視する文字が3つ以上ある場合もすべて同一視し、`\p{Lu}` などのプロパティにも適用する
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.flags.case_insensitive = yes;
+        self
+    }
+
+    /// `^`, `$` を各行の先頭・末尾にマッチさせるかどうか (`(?m)` と同じ)
+    pub fn multi_line(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.flags.multi_line = yes;
+        self
+    }
+
+    /// `.` を改行にもマッチさせるかどうか (`(?s)` と同じ)
+    pub fn dot_matches_new_line(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.flags.dot_all = yes;
+        self
+    }
+
+    /// パターンの中の空白と `#` からのコメントを無視するかどうか (`(?x)` と同じ)
+    pub fn ignore_whitespace(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.flags.extended = yes;
+        self
+    }
+
+    /// 未知のエスケープ文字 (`\d` など) をエラーにするかどうか (初期値は true)  
+    /// false の場合は、`\d` を `d` のように、エスケープ文字そのものにマッチさせる
+    pub fn strict_escapes(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.flags.lenient_escapes = !yes;
+        self
+    }
+
+    /// 探索を開始した位置でのみマッチを試みるかどうか  
+    /// find_iter などでは、直前のマッチの終了位置でのみマッチを試みる
+    pub fn anchored(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.options.anchored = yes;
+        self
+    }
+
+    /// 同じ開始位置のマッチのうち、最も長いものを選ぶかどうか (POSIX の leftmost-longest)  
+    /// 無効の場合は、`|` の左の分岐や貪欲な繰り返しを優先して最初に見つかったマッチを選ぶ (leftmost-first)  
+    /// 有効にするとすべての分岐を試すため、探索は遅くなる
+    pub fn leftmost_longest(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.options.longest = yes;
+        self
+    }
+
+    /// コンパイルした命令列の長さの上限 (初期値は 2^20)  
+    /// 上限を超えた場合、build は Error::LimitExceeded を返す
+    pub fn size_limit(&mut self, limit: usize) -> &mut RegexBuilder {
+        self.size_limit = limit;
+        self
+    }
+
+    /// パターンの AST のネストの深さの上限 (初期値は 250)  
+    /// グループと量指定子はそれぞれ AST を1段深くし、`|` の分岐は分岐の数の log2 だけ深くする  
+    /// 上限を超えた場合、build は Error::LimitExceeded を返す
+    pub fn nest_limit(&mut self, limit: usize) -> &mut RegexBuilder {
+        self.nest_limit = limit;
+        self
+    }
+
+    /// 1回の探索で実行する命令の数の上限  
+    /// 上限に達した場合、その探索はマッチしなかったものとして扱う (try_is_match などの try_ で始まるメソッドは Error::BudgetExceeded を返す)
+    pub fn backtrack_limit(&mut self, limit: usize) -> &mut RegexBuilder {
+        self.options.backtrack_limit = Some(limit);
+        self
+    }
+
+    /// バックトラックで評価する場合に、戻るために覚えておく分岐の数の上限  
+    /// 上限に達した場合、その探索はマッチしなかったものとして扱う (try_ で始まるメソッドは Error::BudgetExceeded を返す)
+    pub fn depth_limit(&mut self, limit: usize) -> &mut RegexBuilder {
+        self.options.depth_limit = Some(limit);
+        self
+    }
+
+    /// build の際に命令列を DFA に変換し、is_match, is_match_at で使うかどうか  
+    /// DFA は入力の各文字を表の参照1回で読むため、マッチするかどうかだけを繰り返し判定する場合に速い  
+    /// 行のアンカーやアトミックグループなど DFA で表せない命令を含む場合や、状態の数が上限を超える場合は変換しない
+    pub fn dfa(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.dfa = yes;
+        self
+    }
+
+    /// is_match, is_match_at で、探索しながら必要な状態だけを生成する DFA (lazy DFA) を使うかどうか  
+    /// すべての状態を生成すると数が膨大になるパターンでも、実際の入力で辿る状態だけを生成するため、DFA に近い速さで判定できる  
+    /// dfa と両方を指定した場合は、DFA に変換できなかったときに lazy DFA を使う
+    pub fn lazy_dfa(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.lazy_dfa = yes;
+        self
+    }
+
+    /// DFA の状態の数の上限 (初期値は 10000)  
+    /// 上限を超える場合は DFA に変換せず、lazy DFA か通常の評価器で判定する  
+    /// lazy DFA では、探索ごとにキャッシュする状態の数の上限になり、上限に達するとキャッシュを空にして作り直す
+    pub fn dfa_state_limit(&mut self, limit: usize) -> &mut RegexBuilder {
+        self.dfa_state_limit = limit;
+        self
+    }
+
+    /// bytes::Regex で、不正な UTF-8 を含む入力を探索するかどうか (初期値は true)  
+    /// 探索する場合、不正なバイトは1バイトずつ、`.` やパターンの `\x80`..`\xFF` にマッチする文字として読む  
+    /// 探索しない場合、そのような入力には何もマッチしない
+    pub fn allow_invalid_utf8(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.allow_invalid_utf8 = yes;
+        self
+    }
+
+    /// build と同様にコンパイルし、バイト列を対象にマッチする bytes::Regex を生成する  
+    /// パターンの括弧の無い `\x80`..`\xFF` は、U+0080..U+00FF の文字ではなく、その値の不正なバイトにマッチする
+    pub fn build_bytes(&self) -> Result<bytes::Regex, Error> {
+        let mut builder: RegexBuilder = self.clone();
+        builder.flags.raw_bytes = true;
+        Ok(bytes::Regex::from_regex(builder.build()?, self.allow_invalid_utf8))
+    }
+
+    /// パターンをパースし、命令列にコンパイルして Regex を生成する  
+    /// パターンが不正な場合や、ネストの深さ・命令列の長さが上限を超えた場合はエラーを返す
+    pub fn build(&self) -> Result<Regex, Error> {
+        let ast = simplify(parse_with_flags(&self.pattern, self.flags, self.nest_limit)?);
+        let program: Program = compile_program(&ast, self.size_limit)?;
+        let mut regex: Regex =
+            Regex::from_parts(&self.pattern, program.instructions, program.capture_names, self.options).with_flags(self.flags);
+        if let Some(literals) = literals(&ast) {
+            regex = regex.with_literals(&literals);
+        }
+        regex = regex.with_inner_literal(&ast);
+        regex = regex.with_shift_or(&ast);
+        if self.dfa {
+            regex = regex.with_dfa(&ast, self.dfa_state_limit);
+        }
+        if self.lazy_dfa {
+            regex = regex.with_lazy_dfa(self.dfa_state_limit);
+        }
+        Ok(regex)
+    }
+
+    /// 設定したフラグでパターンをパースし、バックトラックで時間のかかる形をしていないかを調べる (Regex は生成しない)  
+    /// 利用者が入力したパターンを受け付ける前に、危険なものを拒否するために使う  
+    /// バックトラックの評価器は実行した位置を記録して同じ位置の再実行を避けるが、命令列と入力が大きい場合は記録しないため、  
+    /// Risk::Exponential のパターンは入力によって探索が終わらなくなることがある
+    ///
+    /// ```
+    /// use small_regex::{RegexBuilder, Risk};
+    ///
+    /// assert_eq!(RegexBuilder::new("(a+)+$").analyze().unwrap().risk, Risk::Exponential);
+    /// assert_eq!(RegexBuilder::new(" *x *").analyze().unwrap().risk, Risk::Low);
+    /// ```
+    pub fn analyze(&self) -> Result<ComplexityReport, Error> {
+        let ast = simplify(parse_with_flags(&self.pattern, self.flags, self.nest_limit)?);
+        Ok(analyze(&ast))
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use super::RegexBuilder;
+    use crate::error::{Error, LimitKind, ParseError, ParseErrorKind};
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_flags() {
+        let re: Regex = RegexBuilder::new("ab").case_insensitive(true).build().unwrap();
+        assert!(re.is_match("xAB"));
+
+        let re: Regex = RegexBuilder::new("^b$").multi_line(true).build().unwrap();
+        assert_eq!(re.find_iter("a\nb\nb").map(|m| m.start()).collect::<Vec<usize>>(), vec![2, 4]);
+
+        let re: Regex = RegexBuilder::new("a.b").dot_matches_new_line(true).build().unwrap();
+        assert!(re.is_match("a\nb"));
+        assert!(!Regex::new("a.b").unwrap().is_match("a\nb"));
+
+        let re: Regex = RegexBuilder::new("a b # comment").ignore_whitespace(true).build().unwrap();
+        assert!(re.is_match("ab"));
+
+        // パターンの中のフラグが優先する
+        let re: Regex = RegexBuilder::new("a(?-i)b").case_insensitive(true).build().unwrap();
+        assert!(re.is_match("Ab"));
+        assert!(!re.is_match("AB"));
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        // 大文字・小文字で同一視する文字は、2つより多くてもすべてマッチする
+        let cases: &[(&str, &[&str])] = &[("σ", &["σ", "Σ", "ς"]), ("ς", &["σ", "Σ"]), ("s", &["S", "ſ"]), ("k", &["K", "\u{212a}"])];
+        for (pattern, texts) in cases {
+            for builder in [RegexBuilder::new(pattern), RegexBuilder::new(pattern).dfa(true).clone()] {
+                let re: Regex = builder.clone().case_insensitive(true).build().unwrap();
+                for text in texts.iter() {
+                    assert!(re.is_full_match(text), "{} / {}", pattern, text);
+                    assert_eq!(re.find(text).map(|m| m.range()), Some(0..text.len()), "{} / {}", pattern, text);
+                }
+            }
+        }
+        assert!(!RegexBuilder::new("i").case_insensitive(true).build().unwrap().is_match("ı"));
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_case_insensitive_property() {
+        let re: Regex = Regex::new("(?i)\\p{Lu}+").unwrap();
+        assert_eq!(re.find("1aBς2").map(|m| m.as_str()), Some("aBς"));
+        let re: Regex = Regex::new("(?i)\\P{Lu}").unwrap();
+        assert!(!re.is_match("aB"));
+        assert!(re.is_match("1"));
+        // (?i) の外のプロパティは、大文字・小文字を区別する
+        let re: Regex = Regex::new("(?i)a(?-i)\\p{Ll}").unwrap();
+        assert!(re.is_match("Ab"));
+        assert!(!re.is_match("AB"));
+        let re: Regex = RegexBuilder::new("\\p{Ll}").case_insensitive(true).dfa(true).build().unwrap();
+        assert!(re.is_match("Q"));
+        assert_eq!(re.to_pattern_string().unwrap(), "(?i)\\p{Ll}");
+    }
+
+    #[test]
+    fn test_strict_escapes() {
+        let error: Error = ParseError::new(ParseErrorKind::InvalidEscape('d'), 1).into();
+        assert_eq!(RegexBuilder::new("a\\d").build().map(|_| ()), Err(error.clone()));
+        assert_eq!(RegexBuilder::new("a\\d").strict_escapes(true).build().map(|_| ()), Err(error));
+
+        let re: Regex = RegexBuilder::new("a\\d\\.").strict_escapes(false).build().unwrap();
+        assert!(re.is_match("ad."));
+        assert!(!re.is_match("a1."));
+        assert!(!re.is_match("adx"));
+    }
+
+    #[test]
+    fn test_anchored() {
+        let re: Regex = RegexBuilder::new("a").anchored(true).build().unwrap();
+        assert!(re.is_match("ab"));
+        assert!(!re.is_match("ba"));
+        // 直前のマッチの終了位置でのみマッチを試みる
+        assert_eq!(re.find_iter("aaba").count(), 2);
+    }
+
+    #[test]
+    fn test_leftmost_longest() {
+        let re: Regex = RegexBuilder::new("a|ab").leftmost_longest(true).build().unwrap();
+        assert_eq!(re.find("xab").unwrap().as_str(), "ab");
+        assert_eq!(Regex::new("a|ab").unwrap().find("xab").unwrap().as_str(), "a");
+
+        // 最も長いマッチのキャプチャグループを返す
+        let re: Regex = RegexBuilder::new("(a|ab)(c|bcd)?").leftmost_longest(true).build().unwrap();
+        let caps = re.captures("abcd").unwrap();
+        assert_eq!((&caps[0], &caps[1], &caps[2]), ("abcd", "a", "bcd"));
+
+        // 繰り返しの中の分岐も、全体が最も長くなるものを選ぶ
+        let re: Regex = RegexBuilder::new("x(a|ab)*").leftmost_longest(true).build().unwrap();
+        assert_eq!(re.find_iter("xabab-xa").map(|m| m.as_str()).collect::<Vec<&str>>(), vec!["xabab", "xa"]);
+        assert_eq!(Regex::new("x(a|ab)*").unwrap().find("xabab").unwrap().as_str(), "xa");
+    }
+
+    #[test]
+    fn test_size_limit() {
+        assert_eq!(
+            RegexBuilder::new("abc").size_limit(3).build().unwrap_err(),
+            Error::LimitExceeded { kind: LimitKind::ProgramSize, limit: 3 }
+        );
+        assert!(RegexBuilder::new("abc").size_limit(4).build().is_ok());
+    }
+
+    #[test]
+    fn test_nest_limit() {
+        let error: Error = Error::LimitExceeded { kind: LimitKind::NestDepth, limit: 250 };
+        assert_eq!(Regex::new(&"(".repeat(100_000)).unwrap_err(), error);
+        assert_eq!(Regex::new(&format!("{}{}", "(".repeat(100_000), ")".repeat(100_000))).unwrap_err(), error);
+        assert_eq!(Regex::new(&format!("a{}", "*".repeat(100_000))).unwrap_err(), error);
+        // 上限の近くまでネストしたパターンや、分岐の多い選択はコンパイルできる
+        assert!(Regex::new(&format!("{}a{}", "(".repeat(100), ")".repeat(100))).is_ok());
+        let words: Vec<String> = (0..2_000).map(|i| format!("w{i}")).collect();
+        assert!(Regex::new(&words.join("|")).unwrap().is_match("xw1999"));
+
+        let error: Error = Error::LimitExceeded { kind: LimitKind::NestDepth, limit: 4 };
+        assert_eq!(RegexBuilder::new("((a))").nest_limit(4).build().unwrap_err(), error);
+        assert_eq!(RegexBuilder::new("a***").nest_limit(4).build().unwrap_err(), error);
+        assert!(RegexBuilder::new("a**").nest_limit(4).build().is_ok());
+        assert!(RegexBuilder::new("(a)").nest_limit(4).build().is_ok());
+    }
+
+    #[test]
+    fn test_dfa() {
+        let re: Regex = RegexBuilder::new("^a(b|c)*d$").dfa(true).build().unwrap();
+        assert!(re.is_match("abcbd"));
+        assert!(!re.is_match("abcbdx"));
+        assert!(!re.is_match_at("xabd", 1));
+
+        // 逆順の DFA でマッチの開始位置を求めても、通常の評価器と同じマッチを返す
+        for pattern in ["a(b|c)*d", "b|abcd", "(a|ab)(c|bcd)", "^ab|b$", "(?i)é+", "a*"] {
+            let dfa: Regex = RegexBuilder::new(pattern).dfa(true).build().unwrap();
+            let nfa: Regex = Regex::new(pattern).unwrap();
+            for text in ["xabcd", "abcd", "ÉéÉb", "bab", ""] {
+                for start in (0..=text.len()).filter(|i| text.is_char_boundary(*i)) {
+                    assert_eq!(dfa.find_at(text, start), nfa.find_at(text, start), "{} / {} / {}", pattern, text, start);
+                }
+            }
+        }
+
+        // 状態の数が上限を超える場合は、通常の評価器で判定する
+        let re: Regex = RegexBuilder::new("a(b|c)*d").dfa(true).dfa_state_limit(1).build().unwrap();
+        assert!(re.is_match("xabcd"));
+    }
+
+    #[test]
+    fn test_lazy_dfa() {
+        let re: Regex = RegexBuilder::new("(a|b)*a(a|b)(a|b)(a|b)").lazy_dfa(true).build().unwrap();
+        assert!(re.is_match("bbabbb"));
+        assert!(!re.is_match("bbabb"));
+
+        // キャッシュが小さくても、作り直しながら判定できる
+        let re: Regex = RegexBuilder::new("(a|b)*a(a|b)(a|b)(a|b)").lazy_dfa(true).dfa_state_limit(2).build().unwrap();
+        assert!(re.is_match("bbabbb"));
+        assert!(!re.is_match("bbabb"));
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        // アトミックグループを含むため、バックトラックで評価する
+        let re: Regex = RegexBuilder::new("(?>a)(b|c)*d").depth_limit(100).build().unwrap();
+        assert!(re.is_match("abcbd"));
+        assert!(!re.is_match(&("a".to_string() + &"bc".repeat(100) + "d")));
+    }
+
+    #[test]
+    fn test_backtrack_limit() {
+        let re: Regex = RegexBuilder::new("(a|aa)*c").backtrack_limit(1000).build().unwrap();
+        assert!(re.is_match("aac"));
+        assert!(!re.is_match(&"a".repeat(30)));
+    }
+
+    #[test]
+    fn test_budget_exceeded() {
+        let re: Regex = RegexBuilder::new("(a|aa)*c").backtrack_limit(1000).build().unwrap();
+        let text: String = "a".repeat(1000) + "c";
+        assert_eq!(re.try_is_match("aac"), Ok(true));
+        assert_eq!(re.try_is_match("aab"), Ok(false));
+        assert_eq!(re.try_is_match(&text), Err(Error::BudgetExceeded));
+        assert_eq!(re.try_find("xaac").unwrap().map(|m| m.range()), Some(1..4));
+        assert_eq!(re.try_find(&text), Err(Error::BudgetExceeded));
+        assert_eq!(&re.try_captures("aac").unwrap().unwrap()[1], "a");
+        assert!(re.try_captures(&text).is_err());
+        // start を指定するメソッドも、上限に達したことを区別できる
+        let text: String = "b".to_string() + &text;
+        assert_eq!(re.try_is_match_at(&text, 1), Err(Error::BudgetExceeded));
+        assert_eq!(re.try_find_at(&text, 1), Err(Error::BudgetExceeded));
+        assert!(re.try_captures_at(&text, 1).is_err());
+        assert_eq!(re.try_find_at("baac", 1).unwrap().map(|m| m.range()), Some(1..4));
+        assert!(!re.is_match_at(&text, 1));
+
+        // バックトラックで評価する場合 (アトミックグループを含む) も同様
+        let re: Regex = RegexBuilder::new("(?>a|b)(a|aa)*c").depth_limit(10).build().unwrap();
+        assert_eq!(re.try_is_match("aac"), Ok(true));
+        assert_eq!(re.try_find(&text), Err(Error::BudgetExceeded));
+    }
+}
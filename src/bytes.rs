@@ -0,0 +1,237 @@
+//! バイト列 (`&[u8]`) を対象にマッチする正規表現の型  
+//! 入力を `&str` に変換せずに、バイナリのプロトコルやファイルの中身を直接探索できる。位置はすべてバイト単位  
+//! UTF-8 として正しい部分は文字として読み、不正なバイトは1バイトずつ、`.` やパターンの `\x80`..`\xFF` にマッチする文字として読む  
+//! `\x80`..`\xFF` は不正なバイトにだけマッチし、正しい UTF-8 の文字の一部にはマッチしない (`é` には `\xC3\xA9` ではなく `é` でマッチする)  
+//! is_match はリテラルのオートマトン、DFA、lazy DFA、Shift-Or を Regex と同様に使い、find, find_iter はリテラルのオートマトンと  
+//! 評価器の接頭辞による読み飛ばしを使う。find で使う逆順の DFA と、マッチの途中のリテラルによる絞り込みは使わない
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::aho_corasick::AhoCorasick;
+use crate::builder::RegexBuilder;
+use crate::compiler::Instruction;
+use crate::error::Error;
+use crate::evaluator::slots_len;
+use crate::matches::Searcher;
+use crate::regex;
+
+/// バイト列を対象にマッチするコンパイル済みの正規表現の型
+///
+/// ```
+/// use small_regex::bytes::Regex;
+///
+/// let re = Regex::new("ab+").unwrap();
+/// assert_eq!(re.find(b"xxabb").unwrap().range(), 2..5);
+///
+/// // UTF-8 として不正なバイトも探索でき、`\xFF` でそのバイトにマッチする
+/// let re = Regex::new("\\xff.a").unwrap();
+/// assert_eq!(re.find(b"\x00\xff\xfea").unwrap().range(), 1..4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Regex {
+    regex: regex::Regex,
+    allow_invalid_utf8: bool, // 不正な UTF-8 を含む入力を探索するかどうか
+}
+
+impl Regex {
+    /// パターンをパースし、命令列にコンパイルする  
+    /// 不正な UTF-8 を含む入力も探索する。そのような入力に何もマッチさせない場合は RegexBuilder::allow_invalid_utf8 を使う
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
+        RegexBuilder::new(pattern).build_bytes()
+    }
+
+    pub(crate) fn from_regex(regex: regex::Regex, allow_invalid_utf8: bool) -> Regex {
+        Regex { regex, allow_invalid_utf8 }
+    }
+
+    /// コンパイルする前のパターンを返す
+    pub fn as_str(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// haystack のいずれかの位置にパターンにマッチする部分があるかを判定する
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        self.accepts(haystack) && self.regex.is_match_bytes(haystack)
+    }
+
+    /// haystack の中で最初にパターンにマッチする部分を返す  
+    /// マッチする部分が無い場合は None を返す
+    pub fn find<'h>(&self, haystack: &'h [u8]) -> Option<Match<'h>> {
+        if !self.accepts(haystack) {
+            return None;
+        }
+        let (start, end) = self.regex.find_bytes(haystack)?;
+        Some(Match { haystack, start, end })
+    }
+
+    /// haystack の中でパターンにマッチする部分を、重ならないように先頭から順に返すイテレータを返す
+    pub fn find_iter<'r, 'h>(&'r self, haystack: &'h [u8]) -> Matches<'r, 'h> {
+        let instructions: &[Instruction] = self.regex.instructions();
+        let searcher: Option<Searcher<&[u8]>> =
+            self.accepts(haystack).then(|| Searcher::new(instructions, self.regex.options(), haystack));
+        Matches { haystack, searcher, slots: vec![None; slots_len(instructions)], literals: self.regex.literals(), pos: 0 }
+    }
+
+    /// haystack を探索の対象にするかどうか  
+    /// 不正な UTF-8 を含む入力は、allow_invalid_utf8 が true (初期値) なら探索し、false ならマッチしないものとする
+    fn accepts(&self, haystack: &[u8]) -> bool {
+        self.allow_invalid_utf8 || core::str::from_utf8(haystack).is_ok()
+    }
+}
+
+/// haystack の中でマッチした部分を表す型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'h> {
+    haystack: &'h [u8],
+    start: usize,
+    end: usize,
+}
+
+impl<'h> Match<'h> {
+    /// マッチした部分の開始位置
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// マッチした部分の終了位置 (この位置のバイトはマッチした部分に含まない)
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// マッチした部分の範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// マッチした部分の長さ
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// 空のバイト列にマッチしたかどうか
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// マッチした部分のバイト列
+    pub fn as_bytes(&self) -> &'h [u8] {
+        &self.haystack[self.range()]
+    }
+}
+
+/// haystack の中でマッチする部分を、重ならないように先頭から順に返すイテレータ  
+/// `bytes::Regex::find_iter` で生成する
+#[derive(Debug)]
+pub struct Matches<'r, 'h> {
+    haystack: &'h [u8],
+    searcher: Option<Searcher<'r, &'h [u8]>>, // 入力が不正な UTF-8 を含み、それを許さない場合は None
+    slots: Vec<Option<usize>>,
+    literals: Option<&'r AhoCorasick>, // パターンがリテラルの選択だけからなる場合のオートマトン
+    pos: usize,                        // literals で次に探索を開始する位置
+}
+
+impl<'h> Iterator for Matches<'_, 'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Match<'h>> {
+        let searcher: &mut Searcher<&[u8]> = self.searcher.as_mut()?;
+        if let Some(literals) = self.literals {
+            // リテラルは空でないため、マッチの終了位置から次の探索を始めればよい
+            let (start, end) = literals.find(self.haystack, self.pos)?;
+            self.pos = end;
+            return Some(Match { haystack: self.haystack, start, end });
+        }
+        if !searcher.next(&mut self.slots) {
+            return None;
+        }
+        Some(Match { haystack: self.haystack, start: self.slots[0]?, end: self.slots[1]? })
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use super::Regex;
+    use crate::builder::RegexBuilder;
+
+    #[test]
+    fn test_find() {
+        let re: Regex = Regex::new("b+").unwrap();
+        let m = re.find("αbb".as_bytes()).unwrap();
+        assert_eq!((m.start(), m.end()), (2, 4));
+        assert_eq!(m.as_bytes(), b"bb");
+        assert!(!re.is_match(b"xyz"));
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let re: Regex = Regex::new("a.").unwrap();
+        let found: Vec<&[u8]> = re.find_iter(b"abaca").map(|m| m.as_bytes()).collect();
+        assert_eq!(found, vec![b"ab", b"ac"]);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let haystack: &[u8] = b"\xffab\xfe";
+
+        // 初期状態では、不正なバイトは1バイトずつ `.` にマッチする文字として読む
+        let re: Regex = Regex::new("ab.").unwrap();
+        assert_eq!(re.find(haystack).unwrap().range(), 1..4);
+        assert!(re.is_match(haystack));
+        assert!(!Regex::new("\u{FFFD}").unwrap().is_match(haystack));
+
+        // 不正な UTF-8 を許さない場合は何もマッチしない
+        let re: Regex = RegexBuilder::new("ab").allow_invalid_utf8(false).build_bytes().unwrap();
+        assert!(!re.is_match(haystack));
+        assert_eq!(re.find(haystack), None);
+        assert_eq!(re.find_iter(haystack).count(), 0);
+        assert!(re.is_match(b"xab"));
+    }
+
+    #[test]
+    fn test_raw_bytes() {
+        // `\x80`..`\xFF` は、その値の不正なバイトにマッチする
+        let re: Regex = Regex::new("\\xff\\xfe+").unwrap();
+        assert_eq!(re.find(b"a\xff\xfe\xfe\xff").unwrap().range(), 1..4);
+        assert!(!re.is_match(b"\xfe\xfe"));
+        // 正しい UTF-8 の文字の一部にはマッチせず、`\x{..}` はコードポイントとしてマッチする
+        let re: Regex = Regex::new("\\xc3").unwrap();
+        assert!(re.is_match(b"\xc3x"));
+        assert!(!re.is_match("é".as_bytes()));
+        assert!(Regex::new("\\x{e9}").unwrap().is_match("é".as_bytes()));
+        // 先頭のバイトによる読み飛ばしでも、不正なバイトを見落とさない
+        let re: Regex = Regex::new("a|\\x80").unwrap();
+        assert_eq!(re.find_iter(b"x\x80ya").map(|m| m.range()).collect::<Vec<_>>(), vec![1..2, 3..4]);
+        // \x80 以上のバイトは、アンカーや `.` の判定でも1バイトの文字として扱う
+        let re: Regex = Regex::new("^.\\x81$").unwrap();
+        assert!(re.is_match(b"\x80\x81"));
+        assert!(!re.is_match(b"\x80\x80\x81"));
+    }
+
+    #[test]
+    fn test_prefilters() {
+        // リテラルの選択だけからなるパターンは、オートマトンで探索する
+        let re: Regex = Regex::new("foo|bar").unwrap();
+        let haystack: &[u8] = b"\xfffoo\x00bar\xfe";
+        assert!(re.is_match(haystack));
+        assert_eq!(re.find(haystack).unwrap().range(), 1..4);
+        assert_eq!(re.find_iter(haystack).map(|m| m.range()).collect::<Vec<_>>(), vec![1..4, 5..8]);
+
+        // DFA, lazy DFA, Shift-Or でも、不正なバイトを含む入力を判定できる
+        let haystack: &[u8] = b"\xffa\xfeb";
+        for builder in [
+            RegexBuilder::new("a.b").dfa(true).clone(),
+            RegexBuilder::new("a.b").lazy_dfa(true).clone(),
+            RegexBuilder::new("a.b"),
+            RegexBuilder::new("a\\xfeb").dfa(true).clone(),
+        ] {
+            let re: Regex = builder.build_bytes().unwrap();
+            assert!(re.is_match(haystack), "{}", re.as_str());
+            assert_eq!(re.find(haystack).unwrap().range(), 1..4);
+            assert!(!re.is_match(b"\xffa\xfe\xfeb"));
+        }
+    }
+}
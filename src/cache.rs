@@ -0,0 +1,32 @@
+//! 探索で使う作業領域を、呼び出し側で保持して使い回すための型
+
+use alloc::vec::Vec;
+
+use crate::evaluator::Scratch;
+
+/// 評価器の作業領域 (スレッドの集合、バックトラックのスタック、キャプチャの位置など) をまとめたもの  
+/// `Regex::create_cache` で生成し、`Regex::is_match_with_cache` などに繰り返し渡すと、  
+/// 2回目以降の探索ではほとんどメモリを確保しない。別の Regex に渡しても正しく動く
+///
+/// ```
+/// use small_regex::{Cache, Regex};
+///
+/// let re = Regex::new("(a|b)+c").unwrap();
+/// let mut cache: Cache = re.create_cache();
+/// let lines: [&str; 3] = ["xabc", "xyz", "bbc"];
+/// let count: usize = lines.iter().filter(|line| re.is_match_with_cache(&mut cache, line)).count();
+/// assert_eq!(count, 2);
+/// assert_eq!(re.find_at_with_cache(&mut cache, "xabc", 0).map(|m| m.range()), Some(1..4));
+/// ```
+#[derive(Debug, Default)]
+pub struct Cache {
+    pub(crate) scratch: Scratch,          // 評価器の作業領域
+    pub(crate) slots: Vec<Option<usize>>, // キャプチャグループがマッチした位置
+}
+
+impl Cache {
+    /// 空の作業領域を生成する (最初の探索で必要な大きさだけ確保する)
+    pub fn new() -> Cache {
+        Cache::default()
+    }
+}
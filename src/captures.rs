@@ -0,0 +1,211 @@
+//! キャプチャグループがマッチした結果を表す型
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Index;
+
+use crate::compiler::Instruction;
+use crate::evaluator::EvalOptions;
+use crate::matches::{Match, Searcher};
+
+/// マッチ全体と、各キャプチャグループがマッチした部分を表す型  
+/// 0番目のグループはマッチ全体を表す
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures<'t> {
+    text: &'t str,
+    slots: Vec<Option<usize>>,          // 各グループの開始位置・終了位置 (バイト単位)
+    names: Arc<[Option<String>]>,       // 各グループの名前
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl<'t> Captures<'t> {
+    pub(crate) fn new(text: &'t str, slots: Vec<Option<usize>>, names: Arc<[Option<String>]>) -> Captures<'t> {
+        Captures { text, slots, names }
+    }
+
+    /// i 番目のグループがマッチした部分を返す  
+    /// グループが存在しない場合や、グループがマッチに関与しなかった場合は None を返す
+    pub fn get(&self, i: usize) -> Option<Match<'t>> {
+        let start: usize = (*self.slots.get(i * 2)?)?;
+        let end: usize = (*self.slots.get(i * 2 + 1)?)?;
+        Some(Match::new(self.text, start, end))
+    }
+
+    /// name という名前のグループがマッチした部分を返す  
+    /// その名前のグループが存在しない場合や、グループがマッチに関与しなかった場合は None を返す
+    pub fn name(&self, name: &str) -> Option<Match<'t>> {
+        let i: usize = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        self.get(i)
+    }
+
+    /// グループの数 (マッチ全体を表す0番目のグループを含む)
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    /// 各グループがマッチした部分を、グループの番号の順に返すイテレータを返す
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match<'t>>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    /// 置換後の文字列のテンプレートを展開し、dst に追加する  
+    /// replace などで文字列を渡した場合と同じ規則で展開する  
+    /// テンプレートの中の `$1`, `${1}` は番号、`$name`, `${name}` は名前でグループを参照する  
+    /// `$$` は `$` そのものとし、存在しないグループやマッチに関与しなかったグループは空文字列とする
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("(?<y>(?:1|2)+)-((?:1|2)+)").unwrap();
+    /// let caps = re.captures("on 2112-12").unwrap();
+    /// let mut dst = String::new();
+    /// caps.expand("$2/${y} ($$)", &mut dst);
+    /// assert_eq!(dst, "12/2112 ($)");
+    /// ```
+    pub fn expand(&self, template: &str, dst: &mut String) {
+        let mut rest: &str = template;
+        while let Some(i) = rest.find('$') {
+            dst.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+
+            if let Some(after) = rest.strip_prefix('$') {
+                dst.push('$');
+                rest = after;
+                continue;
+            }
+
+            // `${name}` の形式の場合は `}` までを、そうでなければ英数字と `_` が続く部分を参照とする
+            let (reference, after): (&str, &str) = match rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+                Some((reference, after)) => (reference, after),
+                None => {
+                    let end: usize = rest
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                        .unwrap_or(rest.len());
+                    (&rest[..end], &rest[end..])
+                }
+            };
+            if reference.is_empty() {
+                // `$` の後に参照が続かない場合は `$` そのものとする
+                dst.push('$');
+                continue;
+            }
+
+            let m = match reference.parse::<usize>() {
+                Ok(i) => self.get(i),
+                Err(_) => self.name(reference),
+            };
+            if let Some(m) = m {
+                dst.push_str(m.as_str());
+            }
+            rest = after;
+        }
+        dst.push_str(rest);
+    }
+}
+
+impl Index<usize> for Captures<'_> {
+    type Output = str;
+
+    /// i 番目のグループがマッチした文字列を返す  
+    /// グループがマッチに関与しなかった場合はパニックする
+    fn index(&self, i: usize) -> &str {
+        self.get(i)
+            .map(|m| m.as_str())
+            .unwrap_or_else(|| panic!("no group at index '{i}'"))
+    }
+}
+
+impl<'n> Index<&'n str> for Captures<'_> {
+    type Output = str;
+
+    /// name という名前のグループがマッチした文字列を返す  
+    /// グループがマッチに関与しなかった場合はパニックする
+    fn index(&self, name: &'n str) -> &str {
+        self.name(name)
+            .map(|m| m.as_str())
+            .unwrap_or_else(|| panic!("no group named '{name}'"))
+    }
+}
+
+/// 各キャプチャグループがマッチした位置を記録するバッファ  
+/// `Regex::capture_locations` で生成し、`Regex::captures_read` に繰り返し渡して使い回す
+///
+/// ```
+/// use small_regex::{CaptureLocations, Regex};
+///
+/// let re = Regex::new("(a+)(b)?").unwrap();
+/// let mut locs: CaptureLocations = re.capture_locations();
+/// re.captures_read(&mut locs, "xaab").unwrap();
+/// assert_eq!(locs.get(1), Some((1, 3)));
+/// assert_eq!(locs.get(2), Some((3, 4)));
+///
+/// re.captures_read(&mut locs, "ya").unwrap();
+/// assert_eq!(locs.get(2), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureLocations {
+    slots: Vec<Option<usize>>, // 各グループの開始位置・終了位置 (バイト単位)
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl CaptureLocations {
+    pub(crate) fn new(len: usize) -> CaptureLocations {
+        CaptureLocations { slots: vec![None; len * 2] }
+    }
+
+    pub(crate) fn slots_mut(&mut self) -> &mut [Option<usize>] {
+        &mut self.slots
+    }
+
+    /// i 番目のグループがマッチした部分の (開始位置, 終了位置) を返す  
+    /// グループが存在しない場合や、直前の探索でグループがマッチに関与しなかった場合は None を返す
+    pub fn get(&self, i: usize) -> Option<(usize, usize)> {
+        let start: usize = (*self.slots.get(i * 2)?)?;
+        let end: usize = (*self.slots.get(i * 2 + 1)?)?;
+        Some((start, end))
+    }
+
+    /// グループの数 (マッチ全体を表す0番目のグループを含む)
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+}
+
+/// 重ならないマッチごとに Captures を返すイテレータ  
+/// `Regex::captures_iter` で生成する。探索に使うスロットは繰り返し使い回す
+#[derive(Debug)]
+pub struct CaptureMatches<'r, 't> {
+    text: &'t str,
+    searcher: Searcher<'r, &'t str>,
+    slots: Vec<Option<usize>>,
+    names: Arc<[Option<String>]>,
+}
+
+impl<'r, 't> CaptureMatches<'r, 't> {
+    pub(crate) fn new(
+        instructions: &'r [Instruction],
+        options: EvalOptions,
+        text: &'t str,
+        names: Arc<[Option<String>]>,
+    ) -> CaptureMatches<'r, 't> {
+        CaptureMatches {
+            text,
+            searcher: Searcher::new(instructions, options, text),
+            slots: vec![None; names.len() * 2],
+            names,
+        }
+    }
+}
+
+impl<'r, 't> Iterator for CaptureMatches<'r, 't> {
+    type Item = Captures<'t>;
+
+    fn next(&mut self) -> Option<Captures<'t>> {
+        if !self.searcher.next(&mut self.slots) {
+            return None;
+        }
+        Some(Captures::new(self.text, self.slots.clone(), Arc::clone(&self.names)))
+    }
+}
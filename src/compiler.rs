@@ -1,5 +1,5 @@
 //! AST を命令列(Instruction)にコンパイルするための型・関数  
-//! "ab(c|b)" が入力された場合、以下にコンパイルする
+//! "ab(?:c|d)" が入力された場合、以下にコンパイルする  
 //! (左の数字はプログラムカウンタ)
 //! 
 //! ```text
@@ -11,16 +11,85 @@
 //! 5 : Char(d)
 //! 6 : Match
 //! ```
+//!
+//! キャプチャグループ "(...)" は、グループの前後に Save を挿入して位置を記録する。  
+//! n 番目のグループの開始位置はスロット 2n、終了位置はスロット 2n+1 に記録する
+//!
+//! 上の形式の一覧は disassemble (Regex::debug_program) で出力できる
 
-use crate::parser::AST;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
 
-/// 命令列の型
-#[derive(Debug, PartialEq)]
-pub enum Instruction {
-    Char(char),
+use crate::error::CompileError;
+use crate::optimizer::optimize;
+use crate::parser::{capture_names, Assertion, AST};
+use crate::unicode::Property;
+
+/// 命令列の型  
+/// T は Char で比較する記号の型で、既定では文字 (char) とする
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Instruction<T = char> {
+    Char(T),
+    CharNoCase(char), // 小文字に畳み込んだ文字が引数と一致すればマッチする
+    AnyChar(bool),    // 任意の1文字にマッチする (引数が false の場合、改行にはマッチしない)
+    Property(Property, bool),
     Match,
     Jump(usize),
     Split(usize, usize),
+    Atomic(usize), // アトミックに実行する範囲の開始 (引数は範囲の次のカウンタ)
+    Commit,        // アトミックに実行する範囲の終了
+    Assert(Assertion),
+    Save(usize),             // 現在の位置を引数のスロットに記録する
+    Condition(usize, usize), // 引数1のグループがマッチしていれば次に、そうでなければ引数2に進む
+    MatchPattern(usize),     // RegexSet で、引数の番号のパターンにマッチしたことを記録する
+}
+
+impl Instruction {
+    /// Char の文字を f で記号に変換した命令を返す (Char 以外の命令はそのまま返す)
+    pub fn map_symbol<T, F: FnMut(char) -> T>(self, f: F) -> Instruction<T> {
+        let mut f: F = f;
+        match self {
+            Instruction::Char(c) => Instruction::Char(f(c)),
+            Instruction::CharNoCase(c) => Instruction::CharNoCase(c),
+            Instruction::AnyChar(dot_all) => Instruction::AnyChar(dot_all),
+            Instruction::Property(property, negated) => Instruction::Property(property, negated),
+            Instruction::Match => Instruction::Match,
+            Instruction::Jump(counter) => Instruction::Jump(counter),
+            Instruction::Split(counter1, counter2) => Instruction::Split(counter1, counter2),
+            Instruction::Atomic(next) => Instruction::Atomic(next),
+            Instruction::Commit => Instruction::Commit,
+            Instruction::Assert(assertion) => Instruction::Assert(assertion),
+            Instruction::Save(slot) => Instruction::Save(slot),
+            Instruction::Condition(group, else_counter) => Instruction::Condition(group, else_counter),
+            Instruction::MatchPattern(id) => Instruction::MatchPattern(id),
+        }
+    }
+}
+
+/// モジュールの先頭のコメントと同じ形式 (`Char(a)`, `Split 3, 5` など) で命令を表示する
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Char(c) => write!(f, "Char({})", c.escape_debug()),
+            Instruction::CharNoCase(c) => write!(f, "CharNoCase({})", c.escape_debug()),
+            Instruction::AnyChar(false) => write!(f, "AnyChar"),
+            Instruction::AnyChar(true) => write!(f, "AnyChar(dot_all)"),
+            Instruction::Property(property, negated) => write!(f, "Property({})", property.escape(*negated)),
+            Instruction::Match => write!(f, "Match"),
+            Instruction::Jump(counter) => write!(f, "Jump {counter}"),
+            Instruction::Split(counter1, counter2) => write!(f, "Split {counter1}, {counter2}"),
+            Instruction::Atomic(next) => write!(f, "Atomic {next}"),
+            Instruction::Commit => write!(f, "Commit"),
+            Instruction::Assert(assertion) => write!(f, "Assert({assertion:?})"),
+            Instruction::Save(slot) => write!(f, "Save {slot}"),
+            Instruction::Condition(group, else_counter) => write!(f, "Condition {group}, {else_counter}"),
+            Instruction::MatchPattern(id) => write!(f, "MatchPattern {id}"),
+        }
+    }
 }
 
 /// コンパイラの型
@@ -35,11 +104,19 @@ impl Compiler {
     fn gen_expr(&mut self, ast: &AST) {
         match ast {
             AST::Char(c) => self.gen_char(*c),
+            AST::CharNoCase(c) => self.gen_char_no_case(*c),
+            AST::AnyChar(dot_all) => self.gen_any_char(*dot_all),
+            AST::Property(property, negated) => self.gen_property(*property, *negated),
             AST::Or(e1, e2) => self.gen_or(e1, e2),
             AST::Plus(ast) => self.gen_plus(ast),
             AST::Star(ast) => self.gen_star(ast),
             AST::Question(ast) => self.gen_question(ast),
             AST::Seq(v) => self.gen_seq(v),
+            AST::Atomic(ast) => self.gen_atomic(ast),
+            AST::Assert(assertion) => self.gen_assert(*assertion),
+            AST::Capture(index, _, ast) => self.gen_capture(*index, ast),
+            AST::Conditional(group, then_ast, else_ast) => self.gen_conditional(*group, then_ast, else_ast),
+            AST::Empty => {} // 何も読まずに次の命令に進む
         }
     }
 
@@ -50,6 +127,34 @@ impl Compiler {
         self.instructions.push(inst);
     }
 
+    /// AST::CharNoCase 型に対応する Instruction を生成し、instructions に push する
+    fn gen_char_no_case(&mut self, c: char) {
+        let inst: Instruction = Instruction::CharNoCase(c);
+        self.p_counter += 1;
+        self.instructions.push(inst);
+    }
+
+    /// AST::AnyChar 型に対応する Instruction を生成し、instructions に push する
+    fn gen_any_char(&mut self, dot_all: bool) {
+        let inst: Instruction = Instruction::AnyChar(dot_all);
+        self.p_counter += 1;
+        self.instructions.push(inst);
+    }
+
+    /// AST::Property 型に対応する Instruction を生成し、instructions に push する
+    fn gen_property(&mut self, property: Property, negated: bool) {
+        let inst: Instruction = Instruction::Property(property, negated);
+        self.p_counter += 1;
+        self.instructions.push(inst);
+    }
+
+    /// AST::Assert 型に対応する Instruction を生成し、instructions に push する
+    fn gen_assert(&mut self, assertion: Assertion) {
+        let inst: Instruction = Instruction::Assert(assertion);
+        self.p_counter += 1;
+        self.instructions.push(inst);
+    }
+
     /// AST::Star 型に対応する Instruction を生成し、instructions に push する  
     /// a* 入力された場合、以下のような Instruction を生成する  
     /// 
@@ -169,6 +274,99 @@ impl Compiler {
         }
     }
 
+    /// AST::Atomic 型に対応する Instruction を生成し、instructions に push する  
+    /// a*+ が入力された場合、以下のような Instruction を生成する  
+    /// 
+    /// ```text
+    /// 0 : atomic 5
+    /// 1 : split 2, 4
+    /// 2 : Char(a)
+    /// 3 : jump 1
+    /// 4 : commit
+    /// 5 : ... 続き
+    /// ```
+    fn gen_atomic(&mut self, ast: &AST) {
+        let atomic_counter: usize = self.p_counter;
+
+        // カウンタをインクリメントし、atomic を挿入する
+        // 引数は commit の次のカウンタを指定するため、この時点では決まらない
+        // ここでは仮の数値(0)を入れて。数値は後で更新する
+        self.p_counter += 1;
+        self.instructions.push(Instruction::Atomic(0));
+
+        // AST を再帰的に処理する
+        self.gen_expr(ast);
+
+        // カウンタをインクリメントし、commit を挿入する
+        self.p_counter += 1;
+        self.instructions.push(Instruction::Commit);
+
+        // Atomic の引数を更新する
+        if let Some(Instruction::Atomic(next)) = self.instructions.get_mut(atomic_counter) {
+            *next = self.p_counter;
+        }
+    }
+
+    /// AST::Capture 型に対応する Instruction を生成し、instructions に push する  
+    /// 1番目のグループ (a) が入力された場合、以下のような Instruction を生成する  
+    /// 
+    /// ```text
+    /// 0 : save 2
+    /// 1 : Char(a)
+    /// 2 : save 3
+    /// 3 : ... 続き
+    /// ```
+    fn gen_capture(&mut self, index: usize, ast: &AST) {
+        self.p_counter += 1;
+        self.instructions.push(Instruction::Save(index * 2));
+
+        // AST を再帰的に処理する
+        self.gen_expr(ast);
+
+        self.p_counter += 1;
+        self.instructions.push(Instruction::Save(index * 2 + 1));
+    }
+
+    /// AST::Conditional 型に対応する Instruction を生成し、instructions に push する  
+    /// (?(1)a|b) が入力された場合、以下のような Instruction を生成する  
+    /// 
+    /// ```text
+    /// 0 : condition 1, 3
+    /// 1 : Char(a)
+    /// 2 : jump 4
+    /// 3 : Char(b)
+    /// 4 : ... 続き
+    /// ```
+    fn gen_conditional(&mut self, group: usize, then_ast: &AST, else_ast: &AST) {
+        let condition_counter: usize = self.p_counter;
+
+        // カウンタをインクリメントし、condition を挿入する
+        // 第二引数は、else のコードの開始のカウンタを指定するため、この時点では決まらない
+        // ここでは仮の数値(0)を入れて。数値は後で更新する
+        self.p_counter += 1;
+        self.instructions.push(Instruction::Condition(group, 0));
+
+        // then の AST を再帰的に処理する
+        self.gen_expr(then_ast);
+
+        let jump_counter: usize = self.p_counter;
+        self.p_counter += 1;
+        self.instructions.push(Instruction::Jump(0));
+
+        // Condition の第二引数を更新する
+        if let Some(Instruction::Condition(_, else_counter)) = self.instructions.get_mut(condition_counter) {
+            *else_counter = self.p_counter;
+        }
+
+        // else の AST を再帰的に処理する
+        self.gen_expr(else_ast);
+
+        // Jumpの引数を更新する
+        if let Some(Instruction::Jump(arg)) = self.instructions.get_mut(jump_counter) {
+            *arg = self.p_counter;
+        }
+    }
+
     /// AST::Seq 型に対応する Instruction を生成し、instructions に push する
     fn gen_seq(&mut self, vec:&Vec<AST>) {
         for ast in vec {
@@ -182,6 +380,43 @@ impl Compiler {
         self.gen_expr(ast);
         self.instructions.push(Instruction::Match);
     }
+
+    /// 複数の AST を1つの命令列にまとめ、instructions に push する  
+    /// 各 AST を Split で分岐させ、分岐の最後には Match の代わりに MatchPattern を置く  
+    /// a と b が入力された場合、以下のような Instruction を生成する
+    ///
+    /// ```text
+    /// 0 : split 1, 3
+    /// 1 : Char(a)
+    /// 2 : match_pattern 0
+    /// 3 : Char(b)
+    /// 4 : match_pattern 1
+    /// ```
+    fn gen_set(&mut self, asts: &[AST]) {
+        for (id, ast) in asts.iter().enumerate() {
+            // 最後の AST 以外は、次の AST への分岐を挿入する
+            // 第二引数は次の AST の開始のカウンタを指定するため、仮の数値(0)を入れて後で更新する
+            let split_counter: Option<usize> = if id + 1 < asts.len() {
+                let split_counter: usize = self.p_counter;
+                self.p_counter += 1;
+                self.instructions.push(Instruction::Split(self.p_counter, 0));
+                Some(split_counter)
+            } else {
+                None
+            };
+
+            // AST を再帰的に処理する
+            self.gen_expr(ast);
+
+            self.p_counter += 1;
+            self.instructions.push(Instruction::MatchPattern(id));
+
+            // Splitの第二引数を更新する
+            if let Some(Instruction::Split(_, right)) = split_counter.and_then(|c| self.instructions.get_mut(c)) {
+                *right = self.p_counter;
+            }
+        }
+    }
 }
 
 /// コード生成を行う関数
@@ -191,4 +426,221 @@ pub fn compile(ast: &AST) -> Vec<Instruction> {
     compiler.instructions
 }
 
+/// 検査済みの命令列と、各キャプチャグループの名前
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,     // 最適化した命令列
+    pub capture_names: Vec<Option<String>>, // 各キャプチャグループの名前 (0番目はマッチ全体)
+}
+
+/// AST を最適化した命令列にコンパイルし、評価器に渡せるかを検査する関数  
+/// 命令列の長さが size_limit を超えた場合、範囲外の位置やグループを参照する場合、  
+/// 開始と終了の両方が保存されないキャプチャグループがある場合はエラーを返す
+pub fn compile_program(ast: &AST, size_limit: usize) -> Result<Program, CompileError> {
+    let instructions: Vec<Instruction> = optimize(compile(ast));
+    if instructions.len() > size_limit {
+        return Err(CompileError::TooLarge { limit: size_limit });
+    }
+    let capture_names: Vec<Option<String>> = capture_names(ast);
+    let groups: usize = capture_names.len();
+    if !is_valid_program(&instructions, groups) {
+        return Err(CompileError::InvalidProgram);
+    }
+    if !has_dense_captures(&instructions, groups) {
+        return Err(CompileError::SparseCaptures);
+    }
+    Ok(Program { instructions, capture_names })
+}
+
+/// disassemble と同じ形式で命令列を表示する
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&disassemble(&self.instructions))
+    }
+}
+
+/// 命令列を、1行に1命令ずつ、左にプログラムカウンタを付けた一覧にする関数  
+/// プログラムカウンタは桁数をそろえて右寄せにする
+pub fn disassemble(instructions: &[Instruction]) -> String {
+    let width: usize = instructions.len().saturating_sub(1).to_string().len();
+    let mut listing: String = String::new();
+    for (pc, inst) in instructions.iter().enumerate() {
+        let _ = writeln!(listing, "{pc:>width$} : {inst}");
+    }
+    listing
+}
+
+/// 0番目を除く各キャプチャグループについて、開始と終了の両方のスロットに保存する命令があるかを返す  
+/// 0番目のグループ (マッチ全体) の位置は評価器が記録する
+fn has_dense_captures(instructions: &[Instruction], groups: usize) -> bool {
+    let mut saved: Vec<bool> = vec![false; groups * 2];
+    for inst in instructions {
+        if let Instruction::Save(slot) = inst {
+            saved[*slot] = true;
+        }
+    }
+    saved[2..].iter().all(|saved| *saved)
+}
+
+/// RegexSet のために、複数の AST を1つの命令列にコンパイルする関数
+pub fn compile_set(asts: &[AST]) -> Vec<Instruction> {
+    let mut compiler: Compiler = Compiler::default();
+    compiler.gen_set(asts);
+    compiler.instructions
+}
+
+/// AST を、逆順の文字列にマッチする AST に変換する関数  
+/// `^` と `$` (StartText と EndText) は入れ替える  
+/// 行のアンカー、`\Z`, `\G`、アトミックグループ、条件分岐は逆順にできないため None を返す
+pub fn reverse(ast: &AST) -> Option<AST> {
+    Some(match ast {
+        AST::Char(c) => AST::Char(*c),
+        AST::CharNoCase(c) => AST::CharNoCase(*c),
+        AST::AnyChar(dot_all) => AST::AnyChar(*dot_all),
+        AST::Property(property, negated) => AST::Property(*property, *negated),
+        AST::Empty => AST::Empty,
+        AST::Plus(ast) => AST::Plus(Box::new(reverse(ast)?)),
+        AST::Star(ast) => AST::Star(Box::new(reverse(ast)?)),
+        AST::Question(ast) => AST::Question(Box::new(reverse(ast)?)),
+        AST::Or(left, right) => AST::Or(Box::new(reverse(left)?), Box::new(reverse(right)?)),
+        AST::Seq(asts) => AST::Seq(asts.iter().rev().map(reverse).collect::<Option<_>>()?),
+        AST::Capture(index, name, ast) => AST::Capture(*index, name.clone(), Box::new(reverse(ast)?)),
+        AST::Assert(Assertion::StartText) => AST::Assert(Assertion::EndText),
+        AST::Assert(Assertion::EndText) => AST::Assert(Assertion::StartText),
+        AST::Atomic(_) | AST::Assert(_) | AST::Conditional(_, _, _) => return None,
+    })
+}
+
+/// AST を逆順にした命令列を生成する関数  
+/// マッチの終了位置から入力を逆に読み、マッチの開始位置を求めるために使う
+pub fn compile_reverse(ast: &AST) -> Option<Vec<Instruction>> {
+    Some(compile(&reverse(ast)?))
+}
+
+/// シリアライズした命令列などを読み込む際に、評価器が範囲外のカウンタやスロットを参照しないかを検査する  
+/// groups はキャプチャグループの数 (マッチ全体を表す0番目のグループを含む)  
+/// Match, Commit, Jump, Split 以外の命令は次のカウンタに進むため、最後の命令にはできない
+pub fn is_valid_program(instructions: &[Instruction], groups: usize) -> bool {
+    let len: usize = instructions.len();
+    len > 0
+        && groups > 0
+        && instructions.iter().enumerate().all(|(pc, inst)| match inst {
+            Instruction::Match | Instruction::Commit => true,
+            Instruction::Jump(counter) => *counter < len,
+            Instruction::Split(counter1, counter2) => *counter1 < len && *counter2 < len,
+            Instruction::Atomic(next) => *next < len && pc + 1 < len,
+            Instruction::Condition(group, next) => *group < groups && *next < len && pc + 1 < len,
+            Instruction::Save(slot) => *slot < groups * 2 && pc + 1 < len,
+            Instruction::MatchPattern(_) => false,
+            _ => pc + 1 < len,
+        })
+}
+
 // ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{compile, compile_program, compile_reverse, compile_set, disassemble, Instruction, Program};
+    use crate::error::CompileError;
+    use crate::parser::{parse, AST};
+
+    #[test]
+    fn test_possessive() {
+        let expect: Vec<Instruction> = vec![
+            Instruction::Atomic(5),
+            Instruction::Split(2, 4),
+            Instruction::Char('a'),
+            Instruction::Jump(1),
+            Instruction::Commit,
+            Instruction::Char('a'),
+            Instruction::Match,
+        ];
+        assert_eq!(compile(&parse("a*+a").unwrap()), expect);
+    }
+
+    #[test]
+    fn test_conditional() {
+        let expect: Vec<Instruction> = vec![
+            Instruction::Split(1, 4),
+            Instruction::Save(2),
+            Instruction::Char('a'),
+            Instruction::Save(3),
+            Instruction::Condition(1, 7),
+            Instruction::Char('b'),
+            Instruction::Jump(8),
+            Instruction::Char('c'),
+            Instruction::Match,
+        ];
+        assert_eq!(compile(&parse("(a)?(?(1)b|c)").unwrap()), expect);
+    }
+
+    #[test]
+    fn test_set() {
+        let expect: Vec<Instruction> = vec![
+            Instruction::Split(1, 3),
+            Instruction::Char('a'),
+            Instruction::MatchPattern(0),
+            Instruction::Split(4, 6),
+            Instruction::Char('b'),
+            Instruction::MatchPattern(1),
+            Instruction::Char('c'),
+            Instruction::MatchPattern(2),
+        ];
+        let asts = vec![parse("a").unwrap(), parse("b").unwrap(), parse("c").unwrap()];
+        assert_eq!(compile_set(&asts), expect);
+    }
+
+    #[test]
+    fn test_compile_reverse() {
+        assert_eq!(compile_reverse(&parse("ab$").unwrap()), Some(compile(&parse("^ba").unwrap())));
+        assert_eq!(compile_reverse(&parse("(a|bc)d").unwrap()), Some(compile(&parse("d(a|cb)").unwrap())));
+        assert_eq!(compile_reverse(&parse("(?m)^a").unwrap()), None);
+        assert_eq!(compile_reverse(&parse("(?>a)b").unwrap()), None);
+    }
+
+    #[test]
+    fn test_compile_program() {
+        let program: Program = compile_program(&parse("(a)(?<x>b)").unwrap(), 100).unwrap();
+        assert_eq!(program.instructions, compile(&parse("(a)(?<x>b)").unwrap()));
+        assert_eq!(program.capture_names, vec![None, None, Some("x".to_string())]);
+
+        assert_eq!(compile_program(&parse("abc").unwrap(), 3), Err(CompileError::TooLarge { limit: 3 }));
+
+        // 存在しないグループを参照する条件分岐
+        let ast: AST = AST::Conditional(1, Box::new(AST::Char('a')), Box::new(AST::Char('b')));
+        assert_eq!(compile_program(&ast, 100), Err(CompileError::InvalidProgram));
+
+        // 1番目のグループが無いまま、2番目のグループがある
+        let ast: AST = AST::Capture(2, None, Box::new(AST::Char('a')));
+        assert_eq!(compile_program(&ast, 100), Err(CompileError::SparseCaptures));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        // モジュールの先頭のコメントの例
+        let expect: &str = "0 : Char(a)\n1 : Char(b)\n2 : Split 3, 5\n3 : Char(c)\n4 : Jump 6\n5 : Char(d)\n6 : Match\n";
+        assert_eq!(disassemble(&compile(&parse("ab(?:c|d)").unwrap())), expect);
+
+        let listing: String = disassemble(&compile(&parse("(?s)(?>\\n.)+(a)$|x").unwrap()));
+        let expect: &str = concat!(
+            " 0 : Split 1, 11\n",
+            " 1 : Atomic 5\n",
+            " 2 : Char(\\n)\n",
+            " 3 : AnyChar(dot_all)\n",
+            " 4 : Commit\n",
+            " 5 : Split 1, 6\n",
+            " 6 : Save 2\n",
+            " 7 : Char(a)\n",
+            " 8 : Save 3\n",
+            " 9 : Assert(EndText)\n",
+            "10 : Jump 12\n",
+            "11 : Char(x)\n",
+            "12 : Match\n",
+        );
+        assert_eq!(listing, expect);
+
+        let program: Program = compile_program(&parse("(a)?(?(1)b|c)").unwrap(), 100).unwrap();
+        assert!(program.to_string().contains("Condition 1, 7\n"));
+        assert_eq!(Instruction::MatchPattern(2).to_string(), "MatchPattern 2");
+    }
+}
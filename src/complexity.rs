@@ -0,0 +1,286 @@
+//! パターンが、バックトラックで時間のかかる形 (ReDoS の原因になる形) をしていないかを調べるための実装  
+//! 簡約した AST をたどり、繰り返しの中の繰り返しや、繰り返す選択の分岐が同じ文字から始まる形などを探す  
+//! 各形は、分岐が先頭の文字を共有するかどうかだけで判定するため、実際には時間のかからないパターンを報告することがある
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::explain::describe;
+use crate::parser::AST;
+use crate::unicode::simple_fold;
+use crate::visit::{walk, Visit};
+
+/// バックトラックにかかる時間の、入力の長さに対する増え方の見積もり
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Risk {
+    Low,         // 見つかった形は無い
+    Polynomial,  // 入力の長さの多項式 (2乗など) で増えうる
+    Exponential, // 入力の長さの指数で増えうる
+}
+
+/// 時間のかかる形の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    NestedRepetition,        // `(a+)+` のように、繰り返しの末尾の繰り返しが、次の繰り返しの先頭と同じ文字を読める
+    OverlappingAlternatives, // `(a|ab)*` のように、繰り返す選択の複数の分岐が同じ文字から始まる
+    AdjacentRepetitions,     // `a*a*` のように、同じ文字を読める繰り返しが続いている
+}
+
+/// 見つかった時間のかかる形
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: FindingKind, // 形の種類
+    pub fragment: String,  // 形を含む部分の説明 (Regex::explain と同じ文章)
+}
+
+/// パターンを調べた結果 (RegexBuilder::analyze が返す)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplexityReport {
+    pub risk: Risk,             // 見つかった形のうち、最も時間のかかるものの見積もり
+    pub findings: Vec<Finding>, // 見つかった形 (パターンの中で外側にあるものから順に並ぶ)
+}
+
+impl FindingKind {
+    /// 形の種類から、時間の増え方を見積もる
+    pub fn risk(&self) -> Risk {
+        match self {
+            FindingKind::NestedRepetition | FindingKind::OverlappingAlternatives => Risk::Exponential,
+            FindingKind::AdjacentRepetitions => Risk::Polynomial,
+        }
+    }
+}
+
+/// `nested repetition: ...` のように、形の種類と部分の説明を1行で表示する
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind: &str = match self.kind {
+            FindingKind::NestedRepetition => "nested repetition",
+            FindingKind::OverlappingAlternatives => "overlapping alternatives",
+            FindingKind::AdjacentRepetitions => "adjacent repetitions",
+        };
+        write!(f, "{kind}: {}", self.fragment)
+    }
+}
+
+/// 簡約した AST から、バックトラックで時間のかかる形を探す (RegexBuilder::analyze で使う)
+pub(crate) fn analyze(ast: &AST) -> ComplexityReport {
+    let mut analyzer: Analyzer = Analyzer { findings: Vec::new() };
+    analyzer.visit(ast);
+    let findings: Vec<Finding> = analyzer.findings;
+    let risk: Risk = findings.iter().map(|finding| finding.kind.risk()).max().unwrap_or(Risk::Low);
+    ComplexityReport { risk, findings }
+}
+
+/// AST をたどり、時間のかかる形を findings に集める
+struct Analyzer {
+    findings: Vec<Finding>,
+}
+
+impl Visit for Analyzer {
+    /// ノードが時間のかかる形であれば findings に追加し、子のノードを訪れる
+    fn visit(&mut self, ast: &AST) {
+        match ast {
+            AST::Plus(body) | AST::Star(body) => {
+                let mut first_body: Vec<&AST> = Vec::new();
+                first(body, &mut first_body);
+                if tail_repeats(body).iter().any(|inner| overlaps(&first_of_repeat(inner), &first_body)) {
+                    self.findings.push(Finding { kind: FindingKind::NestedRepetition, fragment: describe(ast) });
+                }
+                let mut branches: Vec<&AST> = Vec::new();
+                collect_branches(body, &mut branches);
+                let firsts: Vec<Vec<&AST>> = branches
+                    .iter()
+                    .map(|branch| {
+                        let mut leaves: Vec<&AST> = Vec::new();
+                        first(branch, &mut leaves);
+                        leaves
+                    })
+                    .collect();
+                if firsts.iter().enumerate().any(|(i, a)| firsts[i + 1..].iter().any(|b| overlaps(a, b))) {
+                    self.findings.push(Finding { kind: FindingKind::OverlappingAlternatives, fragment: describe(ast) });
+                }
+            }
+            AST::Seq(asts) => {
+                for (i, ast) in asts.iter().enumerate() {
+                    let Some(left) = repeat(ast) else {
+                        continue;
+                    };
+                    // 間に空の文字列にマッチする要素だけを挟んで続く繰り返し
+                    for next in &asts[i + 1..] {
+                        if let Some(right) = repeat(next) {
+                            if overlaps(&first_of_repeat(left), &first_of_repeat(right)) {
+                                let fragment: String = describe(ast) + ", then " + &describe(next);
+                                self.findings.push(Finding { kind: FindingKind::AdjacentRepetitions, fragment });
+                                break;
+                            }
+                        }
+                        if !nullable(next) {
+                            break;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        walk(self, ast);
+    }
+}
+
+/// ast の末尾 (後に続く要素がすべて空の文字列にマッチする位置) にある `*`, `+` の繰り返しを返す  
+/// アトミックグループの中の繰り返しは、グループを抜けた後にバックトラックで戻らないため含めない
+fn tail_repeats(ast: &AST) -> Vec<&AST> {
+    match ast {
+        AST::Plus(_) | AST::Star(_) => Vec::from([ast]),
+        AST::Question(ast) | AST::Capture(_, _, ast) => tail_repeats(ast),
+        AST::Seq(asts) => {
+            let mut repeats: Vec<&AST> = Vec::new();
+            for ast in asts.iter().rev() {
+                repeats.extend(tail_repeats(ast));
+                if !nullable(ast) {
+                    break;
+                }
+            }
+            repeats
+        }
+        AST::Or(left, right) | AST::Conditional(_, left, right) => {
+            let mut repeats: Vec<&AST> = tail_repeats(left);
+            repeats.extend(tail_repeats(right));
+            repeats
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// ast が (キャプチャグループで囲んだ) `*`, `+` の繰り返しであれば、その繰り返しを返す
+fn repeat(ast: &AST) -> Option<&AST> {
+    match ast {
+        AST::Plus(_) | AST::Star(_) => Some(ast),
+        AST::Capture(_, _, ast) => repeat(ast),
+        _ => None,
+    }
+}
+
+/// 繰り返しの中身の先頭で読みうる文字の命令を返す
+fn first_of_repeat(ast: &AST) -> Vec<&AST> {
+    let mut leaves: Vec<&AST> = Vec::new();
+    if let AST::Plus(body) | AST::Star(body) = ast {
+        first(body, &mut leaves);
+    }
+    leaves
+}
+
+/// 選択の分岐を、キャプチャグループを通してたどって branches に追加する
+fn collect_branches<'a>(ast: &'a AST, branches: &mut Vec<&'a AST>) {
+    match ast {
+        AST::Or(left, right) => {
+            collect_branches(left, branches);
+            collect_branches(right, branches);
+        }
+        AST::Capture(_, _, ast) => collect_branches(ast, branches),
+        ast => branches.push(ast),
+    }
+}
+
+/// ast にマッチする文字列の先頭で読みうる文字の AST (Char, AnyChar など) を leaves に追加する
+fn first<'a>(ast: &'a AST, leaves: &mut Vec<&'a AST>) {
+    match ast {
+        AST::Char(_) | AST::CharNoCase(_) | AST::AnyChar(_) | AST::Property(_, _) => leaves.push(ast),
+        AST::Plus(ast) | AST::Star(ast) | AST::Question(ast) | AST::Atomic(ast) | AST::Capture(_, _, ast) => {
+            first(ast, leaves)
+        }
+        AST::Seq(asts) => {
+            for ast in asts {
+                first(ast, leaves);
+                if !nullable(ast) {
+                    break;
+                }
+            }
+        }
+        AST::Or(left, right) | AST::Conditional(_, left, right) => {
+            first(left, leaves);
+            first(right, leaves);
+        }
+        AST::Assert(_) | AST::Empty => {}
+    }
+}
+
+/// ast が空の文字列にマッチしうるかどうか
+fn nullable(ast: &AST) -> bool {
+    match ast {
+        AST::Char(_) | AST::CharNoCase(_) | AST::AnyChar(_) | AST::Property(_, _) => false,
+        AST::Star(_) | AST::Question(_) | AST::Assert(_) | AST::Empty => true,
+        AST::Plus(ast) | AST::Atomic(ast) | AST::Capture(_, _, ast) => nullable(ast),
+        AST::Seq(asts) => asts.iter().all(nullable),
+        AST::Or(left, right) | AST::Conditional(_, left, right) => nullable(left) || nullable(right),
+    }
+}
+
+/// 2つの文字の集合が、共通の文字を含みうるかどうか
+fn overlaps(a: &[&AST], b: &[&AST]) -> bool {
+    a.iter().any(|a| b.iter().any(|b| overlap(a, b)))
+}
+
+/// 2つの文字の命令が、共通の文字にマッチしうるかどうか (判定できない組み合わせは true とする)  
+/// 組み合わせを減らすため、Char, CharNoCase, AnyChar, Property の順に並べ替えてから判定する
+fn overlap(a: &AST, b: &AST) -> bool {
+    let rank = |ast: &AST| match ast {
+        AST::Char(_) => 0,
+        AST::CharNoCase(_) => 1,
+        AST::AnyChar(_) => 2,
+        _ => 3,
+    };
+    let (a, b) = if rank(a) <= rank(b) { (a, b) } else { (b, a) };
+    match (a, b) {
+        (AST::Char(a), AST::Char(b)) => a == b,
+        (AST::Char(a), AST::CharNoCase(b)) => simple_fold(*a) == *b,
+        (AST::CharNoCase(a), AST::CharNoCase(b)) => a == b,
+        (AST::Char(c), AST::AnyChar(dot_all)) => *dot_all || *c != '\n',
+        (AST::Char(c), AST::Property(property, negated)) => property.contains(*c) != *negated,
+        (AST::Property(a, false), AST::Property(b, false)) => {
+            a.ranges().iter().any(|(low, high)| b.ranges().iter().any(|(l, h)| low <= h && l <= high))
+        }
+        _ => true,
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    use super::{ComplexityReport, FindingKind, Risk};
+    use crate::builder::RegexBuilder;
+
+    fn analyze(pattern: &str) -> ComplexityReport {
+        RegexBuilder::new(pattern).analyze().unwrap()
+    }
+
+    #[test]
+    fn test_analyze() {
+        for pattern in ["(a+)+", "(a*)*b", "(x|a+)*", "((a|b)+)+", "(a|ab|b)*", "(a|a?b)+", "(?i)(a|Ab)*"] {
+            assert_eq!(analyze(pattern).risk, Risk::Exponential, "{}", pattern);
+        }
+        for pattern in ["a*a*", "(.*)x?.*", " ?a+a+"] {
+            assert_eq!(analyze(pattern).risk, Risk::Polynomial, "{}", pattern);
+        }
+        for pattern in ["abc", "(ab?)+", "(ab+)+", "(a+b)+", "(?:a+)+", "((?>a+))+", "(a|b)*", "a*b*", "a*ba*", "(a)(b)(?(1)c|d)", ""] {
+            assert_eq!(analyze(pattern).risk, Risk::Low, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_findings() {
+        let report: ComplexityReport = analyze("(a+)+|b*b*");
+        let kinds: Vec<FindingKind> = report.findings.iter().map(|finding| finding.kind).collect();
+        assert_eq!(kinds, [FindingKind::NestedRepetition, FindingKind::AdjacentRepetitions]);
+        assert_eq!(report.findings[0].to_string(), "nested repetition: 'a' one or more times (group 1) one or more times");
+        assert_eq!(report.findings[1].to_string(), "adjacent repetitions: 'b' zero or more times, then 'b' zero or more times");
+
+        // フラグは RegexBuilder で指定したものを使う
+        let report: ComplexityReport = RegexBuilder::new("a*A*").case_insensitive(true).analyze().unwrap();
+        assert_eq!(report.risk, Risk::Polynomial);
+    }
+}
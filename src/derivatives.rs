@@ -0,0 +1,297 @@
+//! 入力の各文字について式の微分 (Brzozowski derivative) を取り、その文字を読んだ後に残る式でマッチを判定する実験的な正規表現の型  
+//! 命令列を使わず式の書き換えだけで判定するため、命令列の評価器と結果を突き合わせるテストの基準に使える  
+//! 式の積 (intersection) と補集合 (complement) も、微分ではそのまま扱える
+//!
+//! キャプチャグループはただのグループとして扱い、マッチの位置は返さない  
+//! アトミックグループと条件分岐は、残りの式だけでは表せないため扱わない
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::builder::DEFAULT_NEST_LIMIT;
+use crate::compiler::Instruction;
+use crate::error::Error;
+use crate::evaluator::{is_assertion_satisfied, is_char_matched};
+use crate::parser::{parse_with_flags, Assertion, Flags, AST};
+
+/// 微分で判定する正規表現の型
+///
+/// ```
+/// use small_regex::derivatives::Regex;
+///
+/// let re = Regex::new("(a|b)*c").unwrap().unwrap();
+/// assert!(re.is_match("xabc"));
+/// assert!(!re.is_full_match("xabc"));
+///
+/// // "a" を含み、"aa" を含まない文字列
+/// let has_a = Regex::new(".*a.*").unwrap().unwrap();
+/// let has_aa = Regex::new(".*aa.*").unwrap().unwrap();
+/// let re = has_a.intersection(&has_aa.complement());
+/// assert!(re.is_full_match("bab"));
+/// assert!(!re.is_full_match("baab"));
+/// assert!(!re.is_full_match("bb"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regex {
+    term: Term,
+}
+
+impl Regex {
+    /// パターンをパースし、微分で判定する正規表現を生成する  
+    /// パターンが不正な場合はエラーを返し、アトミックグループや条件分岐を含む場合は None を返す
+    pub fn new(pattern: &str) -> Result<Option<Regex>, Error> {
+        let ast: AST = parse_with_flags(pattern, Flags::default(), DEFAULT_NEST_LIMIT)?;
+        Ok(to_term(&ast).map(|term| Regex { term }))
+    }
+
+    /// self と other の両方に全体がマッチする文字列にマッチする正規表現を返す
+    pub fn intersection(&self, other: &Regex) -> Regex {
+        Regex { term: Term::and(self.term.clone(), other.term.clone()) }
+    }
+
+    /// self に全体がマッチしない文字列にマッチする正規表現を返す
+    pub fn complement(&self) -> Regex {
+        Regex { term: Term::not(self.term.clone()) }
+    }
+
+    /// text 全体がマッチするかを判定する
+    pub fn is_full_match(&self, text: &str) -> bool {
+        run(self.term.clone(), text)
+    }
+
+    /// text のいずれかの部分がマッチするかを判定する  
+    /// 前後に任意の文字列 (改行を含む) を連結した式に、text 全体がマッチするかで判定する
+    pub fn is_match(&self, text: &str) -> bool {
+        let any: Term = Term::star(Term::Symbol(Instruction::AnyChar(true)));
+        run(Term::seq(any.clone(), Term::seq(self.term.clone(), any)), text)
+    }
+}
+
+/// term を text の各文字で順に微分し、最後に残った式が空文字列にマッチするかを返す
+fn run(mut term: Term, text: &str) -> bool {
+    for (pos, c) in text.char_indices() {
+        if term == Term::Empty {
+            return false;
+        }
+        term = term.derivative(c, text, pos);
+    }
+    term.nullable(text, text.len())
+}
+
+/// AST を微分で書き換える式に変換する  
+/// アトミックグループや条件分岐を含む場合は None を返す
+fn to_term(ast: &AST) -> Option<Term> {
+    let term: Term = match ast {
+        AST::Char(c) => Term::Symbol(Instruction::Char(*c)),
+        AST::CharNoCase(c) => Term::Symbol(Instruction::CharNoCase(*c)),
+        AST::AnyChar(dot_all) => Term::Symbol(Instruction::AnyChar(*dot_all)),
+        AST::Property(property, negated) => Term::Symbol(Instruction::Property(*property, *negated)),
+        AST::Plus(ast) => {
+            let term: Term = to_term(ast)?;
+            Term::seq(term.clone(), Term::star(term))
+        }
+        AST::Star(ast) => Term::star(to_term(ast)?),
+        AST::Question(ast) => Term::or(to_term(ast)?, Term::Epsilon),
+        AST::Or(left, right) => Term::or(to_term(left)?, to_term(right)?),
+        AST::Seq(asts) => {
+            let mut term: Term = Term::Epsilon;
+            for ast in asts.iter().rev() {
+                term = Term::seq(to_term(ast)?, term);
+            }
+            term
+        }
+        AST::Assert(assertion) => Term::Assert(*assertion),
+        AST::Capture(_, _, ast) => to_term(ast)?,
+        AST::Empty => Term::Epsilon,
+        AST::Atomic(_) | AST::Conditional(_, _, _) => return None,
+    };
+    Some(term)
+}
+
+/// 微分で書き換える式  
+/// 各要素を生成する関数 (seq, or など) で自明な部分を簡約し、微分を繰り返しても式が大きくなり続けないようにする
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Empty,                     // どの文字列にもマッチしない式
+    Epsilon,                   // 空文字列にのみマッチする式
+    Symbol(Instruction),       // 1文字を読む式 (Char, CharNoCase, AnyChar, Property の命令で表す)
+    Assert(Assertion),         // 位置のみにマッチする式
+    Seq(Box<Term>, Box<Term>), // 連結 (左の式は Seq にしない)
+    Or(Vec<Term>),             // 選択 (同じ式を重複して含まず、要素は2つ以上)
+    And(Vec<Term>),            // 積 (同上)
+    Not(Box<Term>),            // 補集合
+    Star(Box<Term>),           // 0回以上の繰り返し
+}
+
+impl Term {
+    fn seq(left: Term, right: Term) -> Term {
+        match (left, right) {
+            (Term::Empty, _) | (_, Term::Empty) => Term::Empty,
+            (Term::Epsilon, term) | (term, Term::Epsilon) => term,
+            (Term::Seq(first, second), right) => Term::seq(*first, Term::seq(*second, right)),
+            (left, right) => Term::Seq(Box::new(left), Box::new(right)),
+        }
+    }
+
+    fn or(left: Term, right: Term) -> Term {
+        let mut terms: Vec<Term> = Vec::new();
+        for term in [left, right] {
+            match term {
+                Term::Or(inner) => terms.extend(inner),
+                term => terms.push(term),
+            }
+        }
+        let mut unique: Vec<Term> = Vec::new();
+        for term in terms {
+            if term != Term::Empty && !unique.contains(&term) {
+                unique.push(term);
+            }
+        }
+        match unique.len() {
+            0 => Term::Empty,
+            1 => unique.remove(0),
+            _ => Term::Or(unique),
+        }
+    }
+
+    fn and(left: Term, right: Term) -> Term {
+        let mut terms: Vec<Term> = Vec::new();
+        for term in [left, right] {
+            match term {
+                Term::And(inner) => terms.extend(inner),
+                term => terms.push(term),
+            }
+        }
+        if terms.contains(&Term::Empty) {
+            return Term::Empty;
+        }
+        let mut unique: Vec<Term> = Vec::new();
+        for term in terms {
+            if !unique.contains(&term) {
+                unique.push(term);
+            }
+        }
+        match unique.len() {
+            1 => unique.remove(0),
+            _ => Term::And(unique),
+        }
+    }
+
+    fn not(term: Term) -> Term {
+        match term {
+            Term::Not(inner) => *inner,
+            term => Term::Not(Box::new(term)),
+        }
+    }
+
+    fn star(term: Term) -> Term {
+        match term {
+            Term::Empty | Term::Epsilon => Term::Epsilon,
+            Term::Star(inner) => Term::Star(inner),
+            term => Term::Star(Box::new(term)),
+        }
+    }
+
+    /// text の pos の位置で、式が空文字列にマッチするかを判定する (アサーションは pos の位置で判定する)
+    fn nullable(&self, text: &str, pos: usize) -> bool {
+        match self {
+            Term::Empty | Term::Symbol(_) => false,
+            Term::Epsilon | Term::Star(_) => true,
+            Term::Assert(assertion) => is_assertion_satisfied(*assertion, text, pos, 0),
+            Term::Seq(left, right) => left.nullable(text, pos) && right.nullable(text, pos),
+            Term::Or(terms) => terms.iter().any(|term| term.nullable(text, pos)),
+            Term::And(terms) => terms.iter().all(|term| term.nullable(text, pos)),
+            Term::Not(term) => !term.nullable(text, pos),
+        }
+    }
+
+    /// text の pos の位置の文字 c で式を微分し、c を読んだ後の残りの文字列にマッチする式を返す
+    fn derivative(&self, c: char, text: &str, pos: usize) -> Term {
+        match self {
+            Term::Empty | Term::Epsilon | Term::Assert(_) => Term::Empty,
+            Term::Symbol(inst) => {
+                if is_char_matched(inst, &c) {
+                    Term::Epsilon
+                } else {
+                    Term::Empty
+                }
+            }
+            Term::Seq(left, right) => {
+                let term: Term = Term::seq(left.derivative(c, text, pos), (**right).clone());
+                if left.nullable(text, pos) {
+                    Term::or(term, right.derivative(c, text, pos))
+                } else {
+                    term
+                }
+            }
+            Term::Or(terms) => {
+                terms.iter().fold(Term::Empty, |acc, term| Term::or(acc, term.derivative(c, text, pos)))
+            }
+            Term::And(terms) => terms
+                .iter()
+                .map(|term| term.derivative(c, text, pos))
+                .reduce(Term::and)
+                .unwrap_or(Term::Empty),
+            Term::Not(term) => Term::not(term.derivative(c, text, pos)),
+            Term::Star(term) => Term::seq(term.derivative(c, text, pos), self.clone()),
+        }
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use super::Regex;
+    use crate::regex;
+
+    #[test]
+    fn test_same_as_vm() {
+        let patterns: &[&str] = &[
+            "(a|b)*c",
+            "a(b|bc)c",
+            "(?i)ab+",
+            "x.y",
+            "(?s)x.y",
+            "^ab|cd$",
+            "(?m)^b$",
+            r"a\Z",
+            "(a*)*b",
+            r"\p{Greek}+",
+            "a?b?",
+        ];
+        let texts: &[&str] = &["", "abc", "xabbc", "AbB", "x\ny", "xay", "ab\ncd", "a\nb\n", "a\n", "aab", "αβγ", "c"];
+        for pattern in patterns {
+            // \p{..} は unicode フィーチャーが無効の場合はパースできない
+            if !cfg!(feature = "unicode") && pattern.contains("\\p") {
+                continue;
+            }
+            let re: Regex = Regex::new(pattern).unwrap().unwrap();
+            let vm: regex::Regex = regex::Regex::new(pattern).unwrap();
+            for text in texts {
+                assert_eq!(re.is_match(text), vm.is_match(text), "{} / {:?}", pattern, text);
+                assert_eq!(re.is_full_match(text), vm.is_full_match(text), "{} / {:?}", pattern, text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_intersection_and_complement() {
+        let even: Regex = Regex::new("(aa)*").unwrap().unwrap();
+        let three: Regex = Regex::new("(aaa)*").unwrap().unwrap();
+        let six: Regex = even.intersection(&three);
+        assert!(six.is_full_match(""));
+        assert!(six.is_full_match("aaaaaa"));
+        assert!(!six.is_full_match("aaaa"));
+        assert!(even.complement().is_full_match("aaa"));
+        assert!(!even.complement().is_full_match("aa"));
+        assert_eq!(even.complement().complement(), even);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert_eq!(Regex::new("(?>a|ab)c").unwrap(), None);
+        assert_eq!(Regex::new("(a)?(?(1)b|c)").unwrap(), None);
+        assert!(Regex::new("(a").is_err());
+    }
+}
@@ -0,0 +1,460 @@
+//! 命令列を決定性有限オートマトン (DFA) に変換し、マッチするかどうかだけを高速に判定するための型  
+//! DFA の各状態は、同じ位置で実行を待つ命令の集合に対応する。入力の文字はクラスに分け、状態とクラスの表で遷移する  
+//! LazyDfa は状態を前もって生成せず、探索中に辿った状態と遷移だけを生成してキャッシュする
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::compiler::Instruction;
+use crate::evaluator::{is_char_matched, EvalOptions};
+use crate::input::{symbols, Input};
+use crate::parser::Assertion;
+use crate::unicode::simple_fold;
+
+/// 状態を構成する命令の集合の要素 (カウンタ, フラグ)  
+/// フラグは Assert(EndText) の命令にのみ使い、その位置で `^`, `\G` が成り立っていたかを記録する
+pub(crate) type Member = (usize, u8);
+
+pub(crate) const START_TEXT: u8 = 1;   // 入力の先頭 (`^` が成り立つ)
+pub(crate) const START_SEARCH: u8 = 2; // 探索を開始した位置 (`\G` が成り立つ)
+
+/// 命令列を変換した DFA の型
+#[derive(Debug)]
+pub struct Dfa {
+    boundaries: Vec<u32>,   // 各クラスの先頭の文字 (昇順)
+    ascii: Vec<usize>,      // ASCII の各文字のクラス
+    classes: usize,         // クラスの数
+    table: Vec<usize>,      // 状態 * classes + クラス の位置に、遷移先の状態を記録する
+    matches: Vec<bool>,     // 各状態が Match を含むかどうか
+    end_matches: Vec<bool>, // 各状態で入力が終わった場合に、`$` を経て Match に到達するかどうか
+    dead: Option<usize>,    // 空の集合の状態 (以降マッチすることはない)
+    start_text: usize,      // 入力の先頭から探索を始める場合の開始状態
+    start_search: usize,    // 入力の途中から探索を始める場合の開始状態
+    anchored_end: bool,     // 入力の末尾で終わるマッチのみを認める
+}
+
+impl Dfa {
+    /// 命令列を DFA に変換する  
+    /// DFA で表せない命令 (キャプチャの結果に依る Condition、アトミックグループ、行のアンカーなど) を含む場合や、  
+    /// 状態の数が state_limit を超えた場合は None を返す
+    pub fn build(instructions: &[Instruction], options: EvalOptions, state_limit: usize) -> Option<Dfa> {
+        if !is_supported(instructions) {
+            return None;
+        }
+        let boundaries: Vec<u32> = class_boundaries(instructions);
+        let classes: usize = boundaries.len();
+        let representatives: Vec<Option<char>> =
+            (0..classes).map(|i| representative(boundaries[i], boundaries.get(i + 1).copied())).collect();
+        let ascii: Vec<usize> = (0..128).map(|c| boundaries.partition_point(|b| *b <= c) - 1).collect();
+
+        let mut builder: Builder = Builder::new(instructions, options, state_limit);
+        let start_text: usize = builder.add(builder.closure_from(&[0], START_TEXT | START_SEARCH))?;
+        let start_search: usize = builder.add(builder.closure_from(&[0], START_SEARCH))?;
+
+        let mut table: Vec<usize> = Vec::new();
+        let mut state: usize = 0;
+        while state < builder.sets.len() {
+            for rep in representatives.iter() {
+                let set: Vec<Member> = builder.step(state, rep.as_ref());
+                table.push(builder.add(set)?);
+            }
+            state += 1;
+        }
+
+        let matches: Vec<bool> = (0..builder.sets.len()).map(|state| builder.is_match_state(state)).collect();
+        let end_matches: Vec<bool> = builder.sets.iter().map(|set| builder.matches_at_end(set)).collect();
+        let dead: Option<usize> = builder.ids.get(&Vec::new()).copied();
+        Some(Dfa {
+            boundaries,
+            ascii,
+            classes,
+            table,
+            matches,
+            end_matches,
+            dead,
+            start_text,
+            start_search,
+            anchored_end: options.anchored_end,
+        })
+    }
+
+    /// text の start (バイト単位) 以降に、マッチする部分があるかを判定する
+    pub fn is_match_at<I: Input + ?Sized>(&self, text: &I, start: usize) -> bool {
+        let mut state: usize = if start == 0 { self.start_text } else { self.start_search };
+        for c in symbols(text, start) {
+            if self.matches[state] && !self.anchored_end {
+                return true;
+            }
+            if Some(state) == self.dead {
+                return false;
+            }
+            state = self.table[state * self.classes + self.class_of(c)];
+        }
+        self.matches[state] || self.end_matches[state]
+    }
+
+    /// パターンを逆順にした命令列から変換した DFA で、text の末尾から start (バイト単位) まで逆に読み、  
+    /// 元のパターンのマッチが始まる最も前の位置を返す (マッチが無い場合は None を返す)
+    pub fn leftmost_start(&self, text: &str, start: usize) -> Option<usize> {
+        let mut state: usize = self.start_text;
+        let mut pos: usize = text.len();
+        let mut found: Option<usize> = if self.matches[state] { Some(pos) } else { None };
+        for c in text[start..].chars().rev() {
+            if Some(state) == self.dead {
+                break;
+            }
+            state = self.table[state * self.classes + self.class_of(c)];
+            pos -= c.len_utf8();
+            if self.matches[state] {
+                found = Some(pos);
+            }
+        }
+        // 逆順の命令列の `$` は、元の入力の先頭でのみ成り立つ
+        if pos == 0 && self.end_matches[state] {
+            found = Some(0);
+        }
+        found
+    }
+
+    /// 文字 c のクラスを返す
+    fn class_of(&self, c: char) -> usize {
+        match self.ascii.get(c as usize) {
+            Some(class) => *class,
+            None => self.boundaries.partition_point(|b| *b <= c as u32) - 1,
+        }
+    }
+}
+
+/// 命令列を DFA で表せるかを判定する
+pub(crate) fn is_supported(instructions: &[Instruction]) -> bool {
+    instructions.iter().all(|inst| match inst {
+        Instruction::Atomic(_) | Instruction::Commit | Instruction::Condition(_, _) | Instruction::MatchPattern(_) => false,
+        Instruction::Assert(assertion) => {
+            matches!(assertion, Assertion::StartText | Assertion::EndText | Assertion::StartSearch)
+        }
+        _ => true,
+    })
+}
+
+/// 文字を読む命令がマッチする文字の範囲の境界から、文字のクラスを求める  
+/// 各クラスの先頭の文字を昇順に返す。同じクラスの文字には、どの命令もマッチするかどうかが一致する
+pub(crate) fn class_boundaries(instructions: &[Instruction]) -> Vec<u32> {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    let mut folded: Vec<char> = Vec::new();
+    for inst in instructions {
+        match inst {
+            Instruction::Char(c) => ranges.push((*c as u32, *c as u32)),
+            Instruction::CharNoCase(c) => folded.push(*c),
+            Instruction::AnyChar(_) => ranges.push(('\n' as u32, '\n' as u32)),
+            Instruction::Property(property, _) => {
+                ranges.extend(property.ranges().iter().map(|(start, end)| (*start as u32, *end as u32)));
+            }
+            _ => {}
+        }
+    }
+    if !folded.is_empty() {
+        // 小文字に畳み込むと CharNoCase の文字になる文字を、すべての文字から探す
+        for c in (0..=char::MAX as u32).filter_map(char::from_u32) {
+            if folded.contains(&simple_fold(c)) {
+                ranges.push((c as u32, c as u32));
+            }
+        }
+    }
+
+    let mut boundaries: Vec<u32> = vec![0];
+    for (start, end) in ranges {
+        boundaries.push(start);
+        boundaries.push(end + 1);
+    }
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    boundaries.retain(|b| *b <= char::MAX as u32);
+    boundaries
+}
+
+/// start から end の手前までのクラスに含まれる文字を1つ返す (サロゲートの範囲のみのクラスは None)
+pub(crate) fn representative(start: u32, end: Option<u32>) -> Option<char> {
+    let end: u32 = end.unwrap_or(char::MAX as u32 + 1);
+    (start..end).find_map(char::from_u32)
+}
+
+/// 探索しながら必要な状態だけを生成する DFA (lazy DFA) の型  
+/// 生成した状態と遷移は探索ごとのキャッシュに記録し、状態の数が上限に達したらキャッシュを空にして作り直す  
+/// そのため、すべての状態を生成すると数が膨大になるパターンでも、使うメモリは上限で抑えられる
+#[derive(Debug, Clone)]
+pub struct LazyDfa {
+    instructions: Arc<[Instruction]>,
+    options: EvalOptions,
+    capacity: usize, // キャッシュに記録する状態の数の上限
+}
+
+impl LazyDfa {
+    /// 命令列を lazy DFA で評価する準備をする  
+    /// DFA で表せない命令を含む場合は None を返す
+    pub fn new(instructions: Arc<[Instruction]>, options: EvalOptions, capacity: usize) -> Option<LazyDfa> {
+        if !is_supported(&instructions) {
+            return None;
+        }
+        Some(LazyDfa { instructions, options, capacity: capacity.max(1) })
+    }
+
+    /// text の start (バイト単位) 以降に、マッチする部分があるかを判定する
+    pub fn is_match_at<I: Input + ?Sized>(&self, text: &I, start: usize) -> bool {
+        let mut cache: Cache = Cache {
+            builder: Builder::new(&self.instructions, self.options, self.capacity),
+            ascii: Vec::new(),
+            others: BTreeMap::new(),
+            matches: Vec::new(),
+            clears: 0,
+        };
+        let flags: u8 = if start == 0 { START_TEXT | START_SEARCH } else { START_SEARCH };
+        let mut state: usize = cache.add(cache.builder.closure_from(&[0], flags));
+        for c in symbols(text, start) {
+            if cache.matches[state] && !self.options.anchored_end {
+                return true;
+            }
+            if cache.builder.sets[state].is_empty() {
+                return false;
+            }
+            state = cache.next(state, c);
+        }
+        cache.matches[state] || cache.builder.matches_at_end(&cache.builder.sets[state])
+    }
+}
+
+/// lazy DFA が1回の探索で生成した状態と遷移のキャッシュ
+struct Cache<'a> {
+    builder: Builder<'a>,
+    ascii: Vec<usize>,                      // 状態 * 128 + ASCII の文字 の位置に、遷移先の状態を記録する (未生成は usize::MAX)
+    others: BTreeMap<(usize, char), usize>, // ASCII 以外の文字による遷移
+    matches: Vec<bool>,                     // 各状態が Match を含むかどうか
+    clears: usize,                          // キャッシュを空にした回数
+}
+
+impl Cache<'_> {
+    /// 状態 state から文字 c で遷移した先の状態を返す (未生成の場合は生成する)
+    fn next(&mut self, state: usize, c: char) -> usize {
+        let cached: Option<usize> = match self.ascii.get(state * 128 + c as usize) {
+            Some(next) if c.is_ascii() => Some(*next).filter(|next| *next != usize::MAX),
+            _ => self.others.get(&(state, c)).copied(),
+        };
+        if let Some(next) = cached {
+            return next;
+        }
+        let set: Vec<Member> = self.builder.step(state, Some(&c));
+        let clears: usize = self.clears;
+        let next: usize = self.add(set);
+        if self.clears == clears {
+            // キャッシュを空にした場合は state の番号が無効になるため、遷移を記録しない
+            if c.is_ascii() {
+                self.ascii[state * 128 + c as usize] = next;
+            } else {
+                self.others.insert((state, c), next);
+            }
+        }
+        next
+    }
+
+    /// 命令の集合を状態として登録し、その番号を返す  
+    /// 状態の数が上限に達している場合は、キャッシュを空にしてから登録する
+    fn add(&mut self, set: Vec<Member>) -> usize {
+        let id: usize = match self.builder.add(set.clone()) {
+            Some(id) => id,
+            None => {
+                self.builder.clear();
+                self.ascii.clear();
+                self.others.clear();
+                self.matches.clear();
+                self.clears += 1;
+                self.builder.add(set).unwrap()
+            }
+        };
+        if self.matches.len() < self.builder.sets.len() {
+            self.ascii.resize(self.builder.sets.len() * 128, usize::MAX);
+            self.matches.push(self.builder.is_match_state(id));
+        }
+        id
+    }
+}
+
+/// DFA の状態を生成するための型
+pub(crate) struct Builder<'a> {
+    instructions: &'a [Instruction],
+    restart: Vec<Member>,              // 各位置で加える、先頭の命令からの集合 (anchored の場合は空)
+    ids: BTreeMap<Vec<Member>, usize>, // 命令の集合から状態の番号への対応
+    sets: Vec<Vec<Member>>,            // 各状態の命令の集合
+    state_limit: usize,
+}
+
+impl<'a> Builder<'a> {
+    pub(crate) fn new(instructions: &'a [Instruction], options: EvalOptions, state_limit: usize) -> Builder<'a> {
+        let mut builder: Builder =
+            Builder { instructions, restart: Vec::new(), ids: BTreeMap::new(), sets: Vec::new(), state_limit };
+        if !options.anchored {
+            // 探索を開始した位置より後ろから始まるマッチのため、各位置で先頭の命令からの集合を加える
+            builder.restart = builder.closure_from(&[0], 0);
+        }
+        builder
+    }
+
+    /// 登録した状態をすべて捨てる
+    fn clear(&mut self) {
+        self.ids.clear();
+        self.sets.clear();
+    }
+
+    /// 状態 state から文字 c を読んだ後の命令の集合を返す (c が None の場合は、どの命令もマッチしないものとする)
+    fn step(&self, state: usize, c: Option<&char>) -> Vec<Member> {
+        self.next_set(&self.sets[state], c)
+    }
+
+    /// 命令の集合 set から文字 c を読んだ後の命令の集合を返す (c が None の場合は、どの命令もマッチしないものとする)
+    pub(crate) fn next_set(&self, set: &[Member], c: Option<&char>) -> Vec<Member> {
+        let mut next: Vec<usize> = Vec::new();
+        if let Some(c) = c {
+            for (pc, _) in set.iter() {
+                if is_char_matched(&self.instructions[*pc], c) {
+                    next.push(pc + 1);
+                }
+            }
+        }
+        let mut set: Vec<Member> = self.closure_from(&next, 0);
+        set.extend_from_slice(&self.restart);
+        set.sort_unstable();
+        set.dedup();
+        set
+    }
+
+    /// 状態 state が Match を含むかどうか
+    fn is_match_state(&self, state: usize) -> bool {
+        self.sets[state].iter().any(|(pc, _)| self.instructions[*pc] == Instruction::Match)
+    }
+
+    /// 命令の集合を状態として登録し、その番号を返す  
+    /// 状態の数が上限を超えた場合は None を返す
+    fn add(&mut self, set: Vec<Member>) -> Option<usize> {
+        if let Some(id) = self.ids.get(&set) {
+            return Some(*id);
+        }
+        if self.sets.len() >= self.state_limit {
+            return None;
+        }
+        let id: usize = self.sets.len();
+        self.ids.insert(set.clone(), id);
+        self.sets.push(set);
+        Some(id)
+    }
+
+    /// pcs から Jump, Split, Save, 成り立つアンカーを辿り、文字を読む命令、Match、Assert(EndText) の集合を返す
+    pub(crate) fn closure_from(&self, pcs: &[usize], flags: u8) -> Vec<Member> {
+        let mut visited: Vec<bool> = vec![false; self.instructions.len()];
+        let mut stack: Vec<usize> = pcs.iter().rev().copied().collect();
+        let mut set: Vec<Member> = Vec::new();
+        while let Some(pc) = stack.pop() {
+            if visited[pc] {
+                continue;
+            }
+            visited[pc] = true;
+            match &self.instructions[pc] {
+                Instruction::Jump(counter) => stack.push(*counter),
+                Instruction::Split(counter1, counter2) => {
+                    stack.push(*counter2);
+                    stack.push(*counter1);
+                }
+                Instruction::Save(_) => stack.push(pc + 1),
+                Instruction::Assert(Assertion::StartText) if flags & START_TEXT != 0 => stack.push(pc + 1),
+                Instruction::Assert(Assertion::StartSearch) if flags & START_SEARCH != 0 => stack.push(pc + 1),
+                Instruction::Assert(Assertion::EndText) => set.push((pc, flags)),
+                Instruction::Assert(_) => {}
+                _ => set.push((pc, 0)),
+            }
+        }
+        set.sort_unstable();
+        set.dedup();
+        set
+    }
+
+    /// 命令の集合の位置で入力が終わった場合に、Assert(EndText) を経て Match に到達するかを判定する
+    pub(crate) fn matches_at_end(&self, set: &[Member]) -> bool {
+        let mut visited: Vec<bool> = vec![false; self.instructions.len()];
+        let mut stack: Vec<Member> = set
+            .iter()
+            .filter(|(pc, _)| self.instructions[*pc] == Instruction::Assert(Assertion::EndText))
+            .map(|(pc, flags)| (pc + 1, *flags))
+            .collect();
+        while let Some((pc, flags)) = stack.pop() {
+            if visited[pc] {
+                continue;
+            }
+            visited[pc] = true;
+            match &self.instructions[pc] {
+                Instruction::Match => return true,
+                Instruction::Jump(counter) => stack.push((*counter, flags)),
+                Instruction::Split(counter1, counter2) => {
+                    stack.push((*counter2, flags));
+                    stack.push((*counter1, flags));
+                }
+                Instruction::Save(_) | Instruction::Assert(Assertion::EndText) => stack.push((pc + 1, flags)),
+                Instruction::Assert(Assertion::StartText) if flags & START_TEXT != 0 => stack.push((pc + 1, flags)),
+                Instruction::Assert(Assertion::StartSearch) if flags & START_SEARCH != 0 => {
+                    stack.push((pc + 1, flags))
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use super::{Dfa, LazyDfa};
+    use crate::compiler::compile;
+    use crate::evaluator::{search, EvalOptions};
+    use crate::parser::parse;
+
+    /// DFA とバックトラックで、マッチするかどうかの判定が一致するかを確かめる
+    fn assert_same(pattern: &str, texts: &[&str], options: EvalOptions) {
+        let insts = compile(&parse(pattern).unwrap());
+        let dfa: Dfa = Dfa::build(&insts, options, 1000).unwrap();
+        let lazy_dfa: LazyDfa = LazyDfa::new(Arc::from(insts.clone()), options, 1000).unwrap();
+        let tiny: LazyDfa = LazyDfa::new(Arc::from(insts.clone()), options, 1).unwrap();
+        for text in texts {
+            for start in (0..=text.len()).filter(|i| text.is_char_boundary(*i)) {
+                let expected: bool = search(&insts, *text, start, options).is_some();
+                assert_eq!(dfa.is_match_at(*text, start), expected, "{} / {} / {}", pattern, text, start);
+                assert_eq!(lazy_dfa.is_match_at(*text, start), expected, "{} / {} / {}", pattern, text, start);
+                assert_eq!(tiny.is_match_at(*text, start), expected, "{} / {} / {}", pattern, text, start);
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_match() {
+        let texts: &[&str] = &["", "a", "abc", "xabcab", "ba\nc", "ÅβΒ", "aaaab"];
+        let patterns: &[&str] =
+            &["abc", "a(b|c)*", "^a", "b$", "^$", "$^", "\\Ga", "(?i)åβ", "a.c", "(?s)a.c", "a+b", "(^|x)a"];
+        for pattern in patterns {
+            assert_same(pattern, texts, EvalOptions::default());
+            assert_same(pattern, texts, EvalOptions { anchored: true, ..EvalOptions::default() });
+            assert_same(pattern, texts, EvalOptions { anchored_end: true, ..EvalOptions::default() });
+        }
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let insts = compile(&parse("(?>a)b").unwrap());
+        assert!(Dfa::build(&insts, EvalOptions::default(), 1000).is_none());
+        let insts = compile(&parse("(?m)^a").unwrap());
+        assert!(Dfa::build(&insts, EvalOptions::default(), 1000).is_none());
+        // 状態の数が上限を超える場合
+        let insts = compile(&parse("a(b|c)*d").unwrap());
+        assert!(Dfa::build(&insts, EvalOptions::default(), 2).is_none());
+    }
+}
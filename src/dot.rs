@@ -0,0 +1,109 @@
+//! コンパイルした命令列を、Graphviz の DOT 形式のグラフとして書き出すための実装  
+//! 各命令を1つの状態 (ノード) とし、文字を読む命令は次の命令への実線の辺、  
+//! Jump, Split など文字を読まずに進む命令は破線の辺 (ε 遷移) で表す。Match の状態は二重丸で表す
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::compiler::Instruction;
+use crate::regex::Regex;
+
+impl Regex {
+    /// コンパイルした命令列を DOT 形式のグラフにする (可視化・デバッグ用)  
+    /// `dot -Tsvg` などで画像にできる。Split の2つの辺には、先に試す順に 1, 2 のラベルを付ける
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let dot: String = Regex::new("a|b").unwrap().to_dot();
+    /// assert!(dot.starts_with("digraph regex {"));
+    /// assert!(dot.contains("0 -> 1 [label=\"1\", style=dashed];"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot: String = String::from("digraph regex {\n    rankdir=LR;\n    node [shape=circle];\n");
+        for (pc, inst) in self.instructions().iter().enumerate() {
+            let _ = match inst {
+                Instruction::Match => writeln!(dot, "    {pc} [shape=doublecircle];"),
+                Instruction::MatchPattern(id) => writeln!(dot, "    {pc} [shape=doublecircle, xlabel=\"{id}\"];"),
+                _ => writeln!(dot, "    {pc};"),
+            };
+        }
+        for (pc, inst) in self.instructions().iter().enumerate() {
+            let next: usize = pc + 1;
+            match inst {
+                Instruction::Char(c) => edge(&mut dot, pc, next, &format!("{c}"), false),
+                Instruction::CharNoCase(c) => edge(&mut dot, pc, next, &format!("(?i){c}"), false),
+                Instruction::AnyChar(false) => edge(&mut dot, pc, next, ".", false),
+                Instruction::AnyChar(true) => edge(&mut dot, pc, next, "(?s).", false),
+                Instruction::Property(property, negated) => edge(&mut dot, pc, next, &property.escape(*negated), false),
+                Instruction::Match | Instruction::MatchPattern(_) => {}
+                Instruction::Jump(counter) => edge(&mut dot, pc, *counter, "ε", true),
+                Instruction::Split(counter1, counter2) => {
+                    edge(&mut dot, pc, *counter1, "1", true);
+                    edge(&mut dot, pc, *counter2, "2", true);
+                }
+                Instruction::Atomic(_) => edge(&mut dot, pc, next, "atomic", true),
+                Instruction::Commit => edge(&mut dot, pc, next, "commit", true),
+                Instruction::Assert(assertion) => edge(&mut dot, pc, next, &format!("{assertion:?}"), true),
+                Instruction::Save(slot) => edge(&mut dot, pc, next, &format!("save {slot}"), true),
+                Instruction::Condition(group, else_counter) => {
+                    edge(&mut dot, pc, next, &format!("({group})"), true);
+                    edge(&mut dot, pc, *else_counter, &format!("!({group})"), true);
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// from から to への辺を書き出す (label は DOT の文字列としてエスケープする)  
+/// epsilon が true の場合は、文字を読まない遷移として破線にする
+fn edge(dot: &mut String, from: usize, to: usize, label: &str, epsilon: bool) {
+    let style: &str = if epsilon { ", style=dashed" } else { "" };
+    let _ = writeln!(dot, "    {from} -> {to} [label={label:?}{style}];");
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_to_dot() {
+        let expect: &str = concat!(
+            "digraph regex {\n",
+            "    rankdir=LR;\n",
+            "    node [shape=circle];\n",
+            "    0;\n",
+            "    1;\n",
+            "    2;\n",
+            "    3;\n",
+            "    4;\n",
+            "    5;\n",
+            "    6 [shape=doublecircle];\n",
+            "    0 -> 1 [label=\"a\"];\n",
+            "    1 -> 2 [label=\"1\", style=dashed];\n",
+            "    1 -> 4 [label=\"2\", style=dashed];\n",
+            "    2 -> 3 [label=\"\\\"\"];\n",
+            "    3 -> 6 [label=\"ε\", style=dashed];\n",
+            "    4 -> 5 [label=\"(?s).\"];\n",
+            "    5 -> 6 [label=\"EndText\", style=dashed];\n",
+            "}\n",
+        );
+        assert_eq!(Regex::new("a(?:\"|(?s).$)").unwrap().to_dot(), expect);
+    }
+
+    #[test]
+    fn test_to_dot_capture() {
+        let dot: String = Regex::new("(a)?(?(1)b|\\\\)").unwrap().to_dot();
+        assert!(dot.contains("    1 -> 2 [label=\"save 2\", style=dashed];\n"));
+        assert!(dot.contains("    4 -> 5 [label=\"(1)\", style=dashed];\n"));
+        assert!(dot.contains("    4 -> 7 [label=\"!(1)\", style=dashed];\n"));
+        assert!(dot.contains("    7 -> 8 [label=\"\\\\\"];\n"));
+    }
+}
@@ -0,0 +1,172 @@
+//! パース・コンパイル・評価の際に発生するエラーの型
+
+use alloc::string::String;
+use core::fmt;
+
+/// 正規表現のエラーの型
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Parse(ParseError),                               // 式のパースに失敗した
+    LimitExceeded { kind: LimitKind, limit: usize }, // パターンが RegexBuilder で設定した上限や、ストリームの探索で保持する入力の上限を超えた
+    BudgetExceeded,                                  // 探索が RegexBuilder::backtrack_limit, depth_limit の上限に達した (try_ で始まるメソッドが返す)
+    Decode(DecodeError),                             // Regex::from_bytes でバイト列を読み込めなかった
+    Compile(CompileError),                           // コンパイルした命令列が不正 (内部のバグを示す)
+}
+
+/// 上限の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    NestDepth,    // AST のネストの深さ (RegexBuilder::nest_limit)
+    ProgramSize,  // 命令列の長さ (RegexBuilder::size_limit)
+    StreamBuffer, // マッチが確定しないまま保持する入力のバイト数 (PushSearcher::buffer_limit, StreamSearcher::buffer_limit)
+}
+
+/// 式のパースに失敗した場合のエラーの型  
+/// 表示すると `unbalanced parenthesis at 2` のように、理由と位置を示す  
+/// position は式の先頭からのバイト単位のオフセットで、文字数ではない (`é(` の `(` は 2)  
+/// そのため `&pattern[position..]` で不正な箇所以降の文字列を取り出せる
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind, // 失敗した理由
+    pub position: usize,      // 式の中で不正な箇所の位置 (バイト単位のオフセット)
+}
+
+/// 式のパースに失敗した理由の型
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnbalancedParen,            // 対応するカッコが無い
+    InvalidEscape(char),        // 未知のエスケープ文字
+    TrailingBackslash,          // 式が `\` で終わっている
+    InvalidHex,                 // `\x`, `\u` の16進数が不正
+    UnknownProperty(String),    // `\p{..}` の名前が未知
+    UnterminatedProperty,       // `\p{` の後に `}` が無い
+    NothingToRepeat,            // 量指定子の前に式が無い
+    UnknownFlag(char),          // `(?..)` のフラグが未知
+    InvalidConditional,         // 条件分岐の書式が不正
+    UnknownGroup(usize),        // 条件分岐が存在しないグループを参照している
+    InvalidGroupName,           // グループの名前が不正
+    DuplicateGroupName(String), // グループの名前が重複している
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, position: usize) -> ParseError {
+        ParseError { kind, position }
+    }
+}
+
+/// Regex::from_bytes でバイト列を読み込めなかった理由の型
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    InvalidHeader,           // 先頭がマジックナンバーでない
+    UnsupportedVersion(u64), // 形式のバージョンが異なる
+    UnexpectedEnd,           // バイト列が途中で途切れている
+    InvalidData,             // 値が不正 (未知の命令、文字として不正な値、余分なバイトなど)
+    UnknownProperty(String), // Unicode プロパティの名前が未知 (unicode フィーチャーが無効な場合も含む)
+    InvalidProgram,          // 命令列が範囲外の位置やグループを参照している
+}
+
+/// コンパイルした命令列の検査に失敗した理由の型  
+/// TooLarge 以外は、パーサやコンパイラのバグで不正な命令列が生成されたことを示す
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    TooLarge { limit: usize }, // 命令列の長さが上限を超えた
+    InvalidProgram,            // 命令列が範囲外の位置やグループを参照している
+    SparseCaptures,            // 開始と終了の両方が保存されないキャプチャグループがある
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "parse error: {e}"),
+            Error::LimitExceeded { kind: LimitKind::NestDepth, limit } => {
+                write!(f, "pattern is nested too deeply (limit is {limit})")
+            }
+            Error::LimitExceeded { kind: LimitKind::ProgramSize, limit } => {
+                write!(f, "compiled program is too large (limit is {limit} instructions)")
+            }
+            Error::LimitExceeded { kind: LimitKind::StreamBuffer, limit } => {
+                write!(f, "unresolved match buffered too much input (limit is {limit} bytes)")
+            }
+            Error::BudgetExceeded => write!(f, "search exceeded the step budget"),
+            Error::Decode(e) => write!(f, "decode error: {e}"),
+            Error::Compile(e) => write!(f, "compile error: {e}"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.position)
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnbalancedParen => write!(f, "unbalanced parenthesis"),
+            ParseErrorKind::InvalidEscape(ch) => write!(f, "invalid escape '\\{ch}'"),
+            ParseErrorKind::TrailingBackslash => write!(f, "trailing backslash"),
+            ParseErrorKind::InvalidHex => write!(f, "invalid hexadecimal escape"),
+            ParseErrorKind::UnknownProperty(name) => write!(f, "unknown Unicode property '{name}'"),
+            ParseErrorKind::UnterminatedProperty => write!(f, "unterminated Unicode property name"),
+            ParseErrorKind::NothingToRepeat => write!(f, "nothing to repeat"),
+            ParseErrorKind::UnknownFlag(ch) => write!(f, "unknown flag '{ch}'"),
+            ParseErrorKind::InvalidConditional => write!(f, "invalid conditional"),
+            ParseErrorKind::UnknownGroup(group) => write!(f, "reference to undefined group {group}"),
+            ParseErrorKind::InvalidGroupName => write!(f, "invalid group name"),
+            ParseErrorKind::DuplicateGroupName(name) => write!(f, "duplicate group name '{name}'"),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidHeader => write!(f, "not a compiled regex"),
+            DecodeError::UnsupportedVersion(version) => write!(f, "unsupported format version {version}"),
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of data"),
+            DecodeError::InvalidData => write!(f, "invalid data"),
+            DecodeError::UnknownProperty(name) => write!(f, "unknown Unicode property '{name}'"),
+            DecodeError::InvalidProgram => write!(f, "invalid program"),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::TooLarge { limit } => write!(f, "compiled program is too large (limit is {limit} instructions)"),
+            CompileError::InvalidProgram => write!(f, "invalid program"),
+            CompileError::SparseCaptures => write!(f, "capture slots are not dense"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl core::error::Error for ParseError {}
+
+impl core::error::Error for DecodeError {}
+
+impl core::error::Error for CompileError {}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<CompileError> for Error {
+    fn from(e: CompileError) -> Self {
+        match e {
+            CompileError::TooLarge { limit } => Error::LimitExceeded { kind: LimitKind::ProgramSize, limit },
+            e => Error::Compile(e),
+        }
+    }
+}
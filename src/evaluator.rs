@@ -1,31 +1,1141 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::DerefMut;
 
 use crate::compiler::Instruction;
+use crate::input::{Input, Symbol};
+use crate::literal::{self, Prefilter};
+use crate::matches::Match;
+use crate::onepass;
+use crate::parser::Assertion;
+use crate::pikevm;
+use crate::unicode::simple_fold;
 
-pub fn evaluate(instructions: &[Instruction], chars: &Vec<char>, mut p_counter: usize, mut index: usize) -> bool {
+/// 探索の設定  
+/// RegexBuilder で指定した設定を評価器に渡す
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvalOptions {
+    pub anchored: bool,                 // 探索を開始した位置でのみマッチを試みる
+    pub backtrack_limit: Option<usize>, // 1回の探索で実行する命令の数の上限 (超えた場合はマッチしなかったものとする)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub anchored_end: bool,             // 入力の末尾で終わるマッチのみを認める
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub longest: bool,                  // 同じ開始位置のマッチのうち、最も長いものを選ぶ (leftmost-longest)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub depth_limit: Option<usize>,     // バックトラックのために積む作業の数の上限 (超えた場合はマッチしなかったものとする)
+}
+
+/// 命令列を p_counter から、index を開始位置として実行し、マッチするかを返す
+#[cfg(test)]
+pub fn evaluate<T: Symbol, I: Input<T> + ?Sized>(instructions: &[Instruction<T>], input: &I, p_counter: usize, index: usize) -> bool {
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(instructions)];
+    let mut scratch: Scratch = Scratch::default();
+    let mut evaluator: Evaluator<T, I> = Evaluator::new(instructions, input, index, &mut slots, None, &mut scratch);
+    evaluator.eval(p_counter, index).is_some()
+}
+
+/// index 以降の各位置を開始位置としてマッチを試み、最初にマッチした位置の (開始位置, 終了位置) を返す  
+/// 位置は input の単位で表し、index は文字の境界であること  
+/// どの位置でもマッチしなかった場合は None を返す
+pub fn search<T: Symbol, I: Input<T> + ?Sized>(
+    instructions: &[Instruction<T>],
+    input: &I,
+    index: usize,
+    options: EvalOptions,
+) -> Option<(usize, usize)> {
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(instructions)];
+    if search_slots(instructions, input, index, &mut slots, options) {
+        Some((slots[0]?, slots[1]?))
+    } else {
+        None
+    }
+}
+
+/// search と同様に探索し、マッチした場合はキャプチャグループの位置を slots に記録して true を返す  
+/// slots[0], slots[1] にはマッチ全体の開始位置・終了位置を記録する  
+/// slots は呼び出し側で確保したものを使い回せる (長さは slots_len 以上であること)  
+/// options.anchored が true の場合は index の位置でのみマッチを試みる  
+/// options.anchored_end が true の場合は、入力の末尾で終わるマッチが見つかるまでバックトラックする  
+/// options.longest が true の場合は、各開始位置ですべての分岐を試し、最も遠くで終わるマッチを選ぶ
+pub fn search_slots<T: Symbol, I: Input<T> + ?Sized>(
+    instructions: &[Instruction<T>],
+    input: &I,
+    index: usize,
+    slots: &mut [Option<usize>],
+    options: EvalOptions,
+) -> bool {
+    search_partial(instructions, input, index, index, slots, options, true) == Partial::Matched
+}
+
+/// 入力の続きがあり得る場合の探索の結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Partial {
+    Matched,          // マッチした (入力の続きによって結果が変わることはない)
+    NotMatched,       // マッチしなかった (入力の続きがあってもマッチしない)
+    NeedInput(usize), // この位置 (文字単位) からの探索は、入力の続きを読まないと結果が確定しない
+    Aborted,          // 実行する命令の数や、積む作業の数の上限に達したため、探索を打ち切った
+}
+
+/// search_slots と同様に探索するが、input の後ろに入力の続きがあり得るものとして扱う  
+/// index は探索を開始した位置 (\G がマッチする位置) で、from 以降の位置からマッチを試みる  
+/// is_last が false の場合、input の末尾を読もうとした開始位置で探索を打ち切り、その位置を NeedInput として返す  
+/// それより前の開始位置では、入力の続きに関わらずマッチしないことが確定している
+pub fn search_partial<T: Symbol, I: Input<T> + ?Sized>(
+    instructions: &[Instruction<T>],
+    input: &I,
+    index: usize,
+    from: usize,
+    slots: &mut [Option<usize>],
+    options: EvalOptions,
+    is_last: bool,
+) -> Partial {
+    search_partial_with(instructions, input, index, from, slots, options, is_last, &mut Scratch::default())
+}
+
+/// search_partial と同様に探索するが、評価器の作業領域は scratch のものを使い回す  
+/// 同じ scratch を続けて渡すと、2回目以降の探索ではほとんどメモリを確保しない
+#[allow(clippy::too_many_arguments)]
+pub fn search_partial_with<T: Symbol, I: Input<T> + ?Sized>(
+    instructions: &[Instruction<T>],
+    input: &I,
+    index: usize,
+    from: usize,
+    slots: &mut [Option<usize>],
+    options: EvalOptions,
+    is_last: bool,
+    scratch: &mut Scratch,
+) -> Partial {
+    let mut options: EvalOptions = options;
+    if is_last && !options.anchored && literal::is_anchored_start(instructions) {
+        // 入力の先頭からしか始まらないパターンは、開始位置を進めずに先頭から1度だけ評価する
+        if from > 0 {
+            slots.fill(None);
+            return Partial::NotMatched;
+        }
+        options.anchored = true;
+    }
+    if is_last && options.anchored && onepass::is_one_pass(instructions) {
+        // 開始位置が決まっていて分岐も高々1つしか成功しない場合は、入力を1度読むだけで評価する
+        return onepass::search(instructions, input, index, from, slots, options);
+    }
+    if is_last && pikevm::is_supported(instructions) {
+        // 入力の終わりまでがそろっている場合は、線形時間で終わる Pike VM で評価する
+        return pikevm::search(instructions, input, index, from, slots, options, &mut scratch.pikevm);
+    }
+    search_backtrack(instructions, input, index, from, slots, options, is_last, scratch)
+}
+
+/// search_partial_with と同様に、バックトラックで評価する  
+/// Atomic, Condition を含む命令列や、入力の続きがあり得る場合に使う  
+/// 作業のスタックやメモのビット集合は scratch から借り、終わった後に scratch に戻す
+#[allow(clippy::too_many_arguments)]
+pub fn search_backtrack<T: Symbol, I: Input<T> + ?Sized>(
+    instructions: &[Instruction<T>],
+    input: &I,
+    index: usize,
+    from: usize,
+    slots: &mut [Option<usize>],
+    options: EvalOptions,
+    is_last: bool,
+    scratch: &mut Scratch,
+) -> Partial {
+    slots.fill(None);
+    let last: usize = if options.anchored { index.min(input.len()) } else { input.len() };
+    let mut evaluator: Evaluator<T, I> =
+        Evaluator::new(instructions, input, index, slots, options.backtrack_limit, scratch);
+    evaluator.depth_limit = options.depth_limit;
+    evaluator.anchored_end = options.anchored_end;
+    evaluator.longest = options.longest;
+    // 入力の続きがあり得る場合は、接頭辞が入力の末尾で途切れている可能性があるため候補を探さない
+    let prefilter: Prefilter = if is_last && !options.anchored { Prefilter::new(instructions) } else { Prefilter::None };
+    let mut start: usize = from;
+    let partial: Partial = 'search: {
+        while start <= last {
+            // 接頭辞や先頭のバイトが現れる位置まで、開始位置を読み飛ばす
+            match prefilter.find(input, start) {
+                Some(candidate) => start = candidate,
+                None => break,
+            }
+            evaluator.hit_end = false;
+            let end: Option<usize> = evaluator.eval_from(start);
+            if evaluator.hit_end && !is_last {
+                break 'search Partial::NeedInput(start);
+            }
+            if let Some(end) = end {
+                evaluator.slots[0] = Some(start);
+                evaluator.slots[1] = Some(end);
+                break 'search Partial::Matched;
+            }
+            if evaluator.aborted {
+                // 実行する命令の数や、積む作業の数の上限に達したので、探索を打ち切る
+                break 'search Partial::Aborted;
+            }
+            start = next_pos(input, start);
+        }
+        if is_last || options.anchored {
+            Partial::NotMatched
+        } else {
+            // 入力の続きの中から始まるマッチがあり得る
+            Partial::NeedInput(input.len())
+        }
+    };
+    evaluator.release(scratch);
+    partial
+}
+
+/// index 以降で最も早く終わるマッチの終了位置を返す  
+/// 各位置で到達しうる命令の集合を1文字ずつ進め、いずれかが Match に到達した時点で打ち切る (マッチを延ばそうとはしない)  
+/// そのため、返す位置は search で得られるマッチの終了位置より前になることがある  
+/// Atomic や Condition は命令の集合では表せないため、これらを含む場合は search で得られる終了位置を返す
+pub fn shortest_search<T: Symbol, I: Input<T> + ?Sized>(
+    instructions: &[Instruction<T>],
+    input: &I,
+    index: usize,
+    options: EvalOptions,
+) -> Option<usize> {
+    if instructions.iter().any(|inst| matches!(inst, Instruction::Atomic(_) | Instruction::Condition(_, _))) {
+        return search(instructions, input, index, options).map(|(_, end)| end);
+    }
+
+    let mut budget: Option<usize> = options.backtrack_limit;
+    let mut threads: Vec<usize> = Vec::new(); // 現在の位置で実行する命令のカウンタ
+    let mut visited: Vec<bool> = vec![false; instructions.len()];
+    let mut i: usize = index;
     loop {
-        let instruction: &Instruction = instructions.get(p_counter).unwrap();
+        // 各位置を新たな開始位置とする (anchored の場合は index のみ)
+        if !options.anchored || i == index {
+            threads.push(0);
+        }
+        if threads.is_empty() {
+            break;
+        }
 
-        match instruction {
-            Instruction::Char(c) => {
-                let character = chars.get(index).unwrap();
-                if c == character {
-                    p_counter += 1;
-                    index += 1;
+        // Jump, Split などをたどり、文字を読む命令だけを残す
+        visited.fill(false);
+        let mut stack: Vec<usize> = core::mem::take(&mut threads);
+        let mut consumers: Vec<usize> = Vec::new();
+        while let Some(pc) = stack.pop() {
+            if visited[pc] {
+                continue;
+            }
+            visited[pc] = true;
+            if let Some(budget) = budget.as_mut() {
+                if *budget == 0 {
+                    return None
+                }
+                *budget -= 1;
+            }
+            match &instructions[pc] {
+                Instruction::Match => return Some(i),
+                Instruction::Jump(counter) => stack.push(*counter),
+                Instruction::Split(counter1, counter2) => {
+                    stack.push(*counter2);
+                    stack.push(*counter1);
+                }
+                Instruction::Save(_) => stack.push(pc + 1),
+                Instruction::Assert(assertion) => {
+                    if is_assertion_satisfied(*assertion, input, i, index) {
+                        stack.push(pc + 1);
+                    }
+                }
+                _ => consumers.push(pc),
+            }
+        }
+
+        // 1文字読み進める
+        let (c, next): (T, usize) = input.char_at(i)?;
+        threads.extend(consumers.into_iter().filter(|pc| is_char_matched(&instructions[*pc], &c)).map(|pc| pc + 1));
+        i = next;
+    }
+    None
+}
+
+/// RegexSet の命令列を評価し、input のいずれかの位置でマッチしたパターンの番号に true を立てた配列を返す  
+/// Pike VM で評価できる命令列は、入力を1度だけ読んで調べる  
+/// Atomic, Condition を含む場合は、各開始位置からバックトラックで評価し、MatchPattern に到達しても探索を続けてすべての分岐を調べる  
+/// すべてのパターンがマッチした時点で走査を打ち切る
+pub fn search_set<T: Symbol, I: Input<T> + ?Sized>(instructions: &[Instruction<T>], input: &I, patterns: usize) -> Vec<bool> {
+    if instructions.is_empty() {
+        return vec![false; patterns];
+    }
+    if pikevm::is_set_supported(instructions) {
+        return pikevm::search_set(instructions, input, patterns);
+    }
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(instructions)];
+    let mut scratch: Scratch = Scratch::default();
+    let mut evaluator: Evaluator<T, I> = Evaluator::new(instructions, input, 0, &mut slots, None, &mut scratch);
+    evaluator.matched = vec![false; patterns];
+    let mut start: usize = 0;
+    while start <= input.len() {
+        evaluator.eval(0, start);
+        if evaluator.matched.iter().all(|m| *m) {
+            break;
+        }
+        start = next_pos(input, start);
+    }
+    evaluator.matched
+}
+
+/// search_backtrack と同様にバックトラックで評価し、実行した命令をすべて記録して返す (Regex::trace で使う)  
+/// 結果を比べやすいよう、候補の絞り込み (Prefilter) をせずに from から1文字ずつ開始位置を進めて試す
+pub fn search_traced<T: Symbol, I: Input<T> + ?Sized>(
+    instructions: &[Instruction<T>],
+    input: &I,
+    from: usize,
+    options: EvalOptions,
+) -> (Option<(usize, usize)>, Vec<Step>) {
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(instructions)];
+    let mut scratch: Scratch = Scratch::default();
+    let mut evaluator: Evaluator<T, I> =
+        Evaluator::new(instructions, input, from, &mut slots, options.backtrack_limit, &mut scratch);
+    evaluator.depth_limit = options.depth_limit;
+    evaluator.anchored_end = options.anchored_end;
+    evaluator.longest = options.longest;
+    evaluator.trace = Some(Vec::new());
+    let last: usize = if options.anchored { from.min(input.len()) } else { input.len() };
+    let mut start: usize = from;
+    let mut found: Option<(usize, usize)> = None;
+    while start <= last {
+        if let Some(end) = evaluator.eval_from(start) {
+            found = Some((start, end));
+            break;
+        }
+        if evaluator.aborted {
+            break;
+        }
+        start = next_pos(input, start);
+    }
+    (found, evaluator.trace.take().unwrap_or_default())
+}
+
+/// バックトラックの評価器が実行した1つの命令の記録 (Regex::trace が返す)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub start: usize,       // マッチを試みている開始位置
+    pub p_counter: usize,   // 実行した命令のカウンタ
+    pub index: usize,       // 命令を実行した時点の入力の位置
+    pub result: StepResult, // 命令を実行した結果
+}
+
+/// 命令を実行した結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    Next(usize), // 引数のカウンタに進んだ (Split は先に試す分岐、Condition は選んだ分岐)
+    Failed,      // 失敗した (文字が一致しない、同じ位置で既に実行したなど)。直前に積んだ分岐に戻る
+    Matched,     // Match に到達した
+}
+
+/// `start 0: 2 @ 1 -> 3` のように、開始位置、カウンタ、位置、結果を1行で表示する
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "start {}: {} @ {} -> ", self.start, self.p_counter, self.index)?;
+        match self.result {
+            StepResult::Next(p_counter) => write!(f, "{p_counter}"),
+            StepResult::Failed => write!(f, "failed"),
+            StepResult::Matched => write!(f, "matched"),
+        }
+    }
+}
+
+/// バックトラックの評価器を1命令ずつ実行するデバッガ (Regex::debugger が返す)  
+/// 命令を実行するたびに Step を返し、次に実行する命令、キャプチャのスロット、バックトラックで戻る分岐を調べられる  
+/// ブレークポイントを設定したカウンタの命令の手前まで、まとめて実行することもできる
+pub struct Debugger<'a> {
+    evaluator: Evaluator<'a, char, str, Vec<Option<usize>>>, // スロットを所有する評価器
+    breakpoints: Vec<bool>,                   // 各カウンタにブレークポイントを設定したかどうか
+    start: usize,                             // マッチを試みている開始位置
+    last: usize,                              // マッチを試みる最後の開始位置
+    p_counter: usize,                         // 次に実行する命令のカウンタ
+    index: usize,                             // 次に実行する命令の入力の位置
+    finished: Option<Option<(usize, usize)>>, // 探索を終えた場合の結果 (見つけたマッチの (開始位置, 終了位置))
+}
+
+impl<'a> Debugger<'a> {
+    /// text の from 以降で、最初にマッチする部分を探す評価を、最初の命令の手前で止めた状態で作る
+    pub(crate) fn new(instructions: &'a [Instruction], text: &'a str, from: usize, options: EvalOptions) -> Self {
+        let slots: Vec<Option<usize>> = vec![None; slots_len(instructions)];
+        let mut evaluator: Evaluator<char, str, Vec<Option<usize>>> =
+            Evaluator::new(instructions, text, from, slots, options.backtrack_limit, &mut Scratch::default());
+        evaluator.depth_limit = options.depth_limit;
+        evaluator.anchored_end = options.anchored_end;
+        evaluator.longest = options.longest;
+        evaluator.trace = Some(Vec::new());
+        let last: usize = if options.anchored { from.min(text.len()) } else { text.len() };
+        let breakpoints: Vec<bool> = vec![false; instructions.len()];
+        Debugger { evaluator, breakpoints, start: from, last, p_counter: 0, index: from, finished: None }
+    }
+
+    /// 次の命令を1つ実行し、その記録を返す  
+    /// 開始位置でのマッチに失敗した場合は、次の開始位置の最初の命令に進む  
+    /// 既に探索を終えていた場合や、命令の数などの上限に達して探索を打ち切った場合は None を返す
+    pub fn step(&mut self) -> Option<Step> {
+        if self.finished.is_some() {
+            return None;
+        }
+        let flow: Flow = self.evaluator.step(self.start, self.p_counter, self.index);
+        let step: Option<Step> = self.evaluator.trace.as_mut().and_then(Vec::pop);
+        match flow {
+            Flow::Continue(p_counter, index) => (self.p_counter, self.index) = (p_counter, index),
+            Flow::Matched(end) => self.finished = Some(Some((self.start, end))),
+            Flow::Failed => {
+                if let Some(end) = self.evaluator.take_longest() {
+                    self.finished = Some(Some((self.start, end)));
+                } else if self.evaluator.aborted || next_pos(self.evaluator.input, self.start) > self.last {
+                    self.finished = Some(None);
                 } else {
-                    return false
+                    // 次の開始位置から試す
+                    self.start = next_pos(self.evaluator.input, self.start);
+                    (self.p_counter, self.index) = (0, self.start);
+                    self.evaluator.stack.clear();
+                    self.evaluator.atomics.clear();
+                    self.evaluator.slots.fill(None);
                 }
             }
-            Instruction::Match => return true,
-            Instruction::Jump(counter) => p_counter = *counter,
-            Instruction::Split(counter1, counter2 ) => {
-                if evaluate(instructions, chars, *counter1, index) || evaluate(instructions, chars, *counter2, index) {
-                    return true
+        }
+        step
+    }
+
+    /// ブレークポイントを設定した命令の手前か、探索を終えるまで実行し、ブレークポイントで止まったかどうかを返す  
+    /// 少なくとも1つの命令は実行するため、ブレークポイントで止まった状態から呼んでも先に進む
+    pub fn run(&mut self) -> bool {
+        while self.step().is_some() {
+            if self.finished.is_none() && self.breakpoints[self.p_counter] {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// カウンタ p_counter の命令にブレークポイントを設定する  
+    /// p_counter が命令の数以上の場合はパニックする
+    pub fn set_breakpoint(&mut self, p_counter: usize) {
+        self.breakpoints[p_counter] = true;
+    }
+
+    /// カウンタ p_counter の命令のブレークポイントを解除する  
+    /// p_counter が命令の数以上の場合はパニックする
+    pub fn remove_breakpoint(&mut self, p_counter: usize) {
+        self.breakpoints[p_counter] = false;
+    }
+
+    /// 次に実行する命令のカウンタ (debug_program の一覧と対応する)
+    pub fn p_counter(&self) -> usize {
+        self.p_counter
+    }
+
+    /// 次に実行する命令の入力の位置
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// マッチを試みている開始位置
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// 実行中の分岐のキャプチャのスロット (グループ i の開始位置は 2 * i、終了位置は 2 * i + 1 番目)
+    pub fn slots(&self) -> &[Option<usize>] {
+        &self.evaluator.slots
+    }
+
+    /// バックトラックで戻る分岐の (カウンタ, 位置) を、積んだ順に返す (失敗した場合は最後の分岐に戻る)
+    pub fn backtrack_stack(&self) -> Vec<(usize, usize)> {
+        self.evaluator
+            .stack
+            .iter()
+            .filter_map(|frame| match frame {
+                Frame::Retry(p_counter, index) => Some((*p_counter, *index)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 探索を終えたかどうか
+    pub fn is_finished(&self) -> bool {
+        self.finished.is_some()
+    }
+
+    /// 探索を終えた場合に、見つけたマッチを返す (マッチが無かった場合や、探索の途中の場合は None)
+    pub fn found(&self) -> Option<Match<'a>> {
+        let (start, end) = self.finished??;
+        Some(Match::new(self.evaluator.input, start, end))
+    }
+}
+
+/// 評価器の作業領域  
+/// 探索のたびに確保し直さないよう、Regex の Cache や Matches などの呼び出し側で保持して使い回す
+#[derive(Debug, Default)]
+pub struct Scratch {
+    stack: Vec<Frame>,          // バックトラックの作業のスタック
+    visited: Vec<u64>,          // バックトラックのメモのビット集合
+    loops: Loops,               // 命令列の中のループ
+    heads: Vec<(usize, usize)>, // 現在の分岐で実行したループの先頭
+    atomics: Vec<usize>,        // 実行中のアトミックグループの位置
+    pikevm: pikevm::Scratch,    // Pike VM の作業領域
+}
+
+/// 評価器の型  
+/// スロットは呼び出し側の領域を借りて使う (Debugger の場合は評価器が所有する)
+struct Evaluator<'a, T: Symbol, I: Input<T> + ?Sized, S: DerefMut<Target = [Option<usize>]> = &'a mut [Option<usize>]> {
+    instructions: &'a [Instruction<T>],
+    input: &'a I,
+    start: usize,                   // 探索を開始した位置 (\G がマッチする位置)
+    slots: S,                       // キャプチャグループがマッチした位置
+    budget: Option<usize>,          // 残りの実行できる命令の数 (None の場合は無制限)
+    depth_limit: Option<usize>,     // バックトラックのために積む作業の数の上限 (None の場合は無制限)
+    aborted: bool,                  // 命令の数や作業の数の上限に達し、探索を打ち切ったかどうか
+    stack: Vec<Frame>,              // バックトラックで戻るための作業
+    visited: Vec<u64>,              // 既に実行した (状態, 位置) の組のビット集合 (空の場合は記録しない)
+    loops: Loops,                   // 命令列の中のループ
+    heads: Vec<(usize, usize)>,     // 現在の分岐で実行したループの先頭の (カウンタ, 位置) (実行した順)
+    atomics: Vec<usize>,            // 実行中のアトミックグループの、stack の中での Frame::Atomic の位置
+    matched: Vec<bool>,             // RegexSet で、各パターンにマッチしたかどうか
+    anchored_end: bool,             // 入力の末尾以外で Match に到達した場合は失敗とする
+    longest: bool,                  // Match に到達しても探索を続け、最も長いマッチを選ぶ
+    longest_match: Option<(usize, Vec<Option<usize>>)>, // longest の場合、これまでで最も長いマッチの終了位置とスロット
+    hit_end: bool,                  // input の末尾を読もうとしたかどうか (入力の続きによって結果が変わりうる)
+    trace: Option<Vec<Step>>,       // 実行した命令の記録 (search_traced の場合のみ記録する)
+}
+
+/// 失敗した時に、バックトラックで戻って行う作業
+#[derive(Debug)]
+enum Frame {
+    Retry(usize, usize),            // Split の残りの分岐を、(カウンタ, 位置) から実行する
+    Restore(usize, Option<usize>),  // Save で書き換えたスロットを元に戻す
+    Atomic(Vec<Option<usize>>),     // アトミックグループの開始 (グループに入る前のスロット)
+    RestoreAll(Vec<Option<usize>>), // アトミックグループの後で失敗した場合に、グループに入る前のスロットに戻す
+    Head,                           // ループの先頭を実行した (バックトラックでは heads から取り除く)
+}
+
+/// 1命令を実行した後に、評価を続ける位置
+enum Flow {
+    Continue(usize, usize), // (カウンタ, 位置) から実行を続ける
+    Matched(usize),         // Match に到達した (引数はマッチの終了位置)
+    Failed,                 // 戻る分岐が無い、または上限に達して探索を打ち切った
+}
+
+impl<'a, T: Symbol, I: Input<T> + ?Sized, S: DerefMut<Target = [Option<usize>]>> Evaluator<'a, T, I, S> {
+    fn new(
+        instructions: &'a [Instruction<T>],
+        input: &'a I,
+        start: usize,
+        slots: S,
+        budget: Option<usize>,
+        scratch: &mut Scratch,
+    ) -> Self {
+        let mut stack: Vec<Frame> = core::mem::take(&mut scratch.stack);
+        stack.clear();
+        let mut atomics: Vec<usize> = core::mem::take(&mut scratch.atomics);
+        atomics.clear();
+        let mut loops: Loops = core::mem::take(&mut scratch.loops);
+        loops.build(instructions);
+        let visited: Vec<u64> = memo_table(instructions, loops.states(), input.len(), core::mem::take(&mut scratch.visited));
+        let mut heads: Vec<(usize, usize)> = core::mem::take(&mut scratch.heads);
+        heads.clear();
+        Evaluator {
+            instructions,
+            input,
+            start,
+            slots,
+            budget,
+            depth_limit: None,
+            aborted: false,
+            stack,
+            visited,
+            loops,
+            heads,
+            atomics,
+            matched: Vec::new(),
+            anchored_end: false,
+            longest: false,
+            longest_match: None,
+            hit_end: false,
+            trace: None,
+        }
+    }
+
+    /// new で借りた作業領域を scratch に戻す
+    fn release(self, scratch: &mut Scratch) {
+        scratch.stack = self.stack;
+        scratch.visited = self.visited;
+        scratch.loops = self.loops;
+        scratch.heads = self.heads;
+        scratch.atomics = self.atomics;
+    }
+
+    /// start を開始位置として命令列を実行し、マッチの終了位置を返す  
+    /// longest の場合はすべての分岐を試した後、最も長いマッチのスロットを slots に戻す  
+    /// 途中で上限に達して探索を打ち切った場合は、それまでに見つけたマッチを採用しない
+    fn eval_from(&mut self, start: usize) -> Option<usize> {
+        if !self.longest {
+            return self.eval(0, start);
+        }
+        self.eval(0, start);
+        self.take_longest()
+    }
+
+    /// longest の場合に、これまでで最も長いマッチのスロットを slots に戻し、その終了位置を返す  
+    /// 途中で上限に達して探索を打ち切った場合は、それまでに見つけたマッチを採用しない
+    fn take_longest(&mut self) -> Option<usize> {
+        let (end, slots) = self.longest_match.take()?;
+        if self.aborted {
+            return None;
+        }
+        self.slots.copy_from_slice(&slots);
+        Some(end)
+    }
+
+    /// 命令列を p_counter から実行し、Match に到達した時点の index を返す  
+    /// 分岐の残りは stack に積み、失敗した時は最後に積んだ分岐に戻る  
+    /// slots にはキャプチャグループがマッチした位置を記録し、バックトラックする際は元に戻す  
+    /// マッチしなかった場合や、実行できる命令の数・積める作業の数の上限に達した場合は None を返す
+    fn eval(&mut self, mut p_counter: usize, mut index: usize) -> Option<usize> {
+        self.stack.clear();
+        self.heads.clear();
+        self.atomics.clear();
+        let start: usize = index;
+        loop {
+            match self.step(start, p_counter, index) {
+                Flow::Continue(next_counter, next_index) => (p_counter, index) = (next_counter, next_index),
+                Flow::Matched(end) => return Some(end),
+                Flow::Failed => return None,
+            }
+        }
+    }
+
+    /// start から試しているマッチで、(p_counter, index) の命令を1つ実行し、次に実行する位置を返す  
+    /// 失敗した場合は、最後に積んだ分岐に戻る
+    #[inline]
+    fn step(&mut self, start: usize, mut p_counter: usize, mut index: usize) -> Flow {
+        if self.aborted {
+            return Flow::Failed;
+        }
+        if let Some(budget) = self.budget.as_mut() {
+            if *budget == 0 {
+                self.aborted = true;
+                return Flow::Failed;
+            }
+            *budget -= 1;
+        }
+        if !self.visit(p_counter, index) {
+            // 同じ (状態, 位置) からは既に実行し、マッチしないことが分かっている
+            self.record(start, p_counter, index, StepResult::Failed);
+            return self.fail();
+        }
+        if self.loops.is_head(p_counter) {
+            self.heads.push((p_counter, index));
+            self.stack.push(Frame::Head);
+        }
+        let instruction: &Instruction<T> = self.instructions.get(p_counter).unwrap();
+        let (executed, executed_index): (usize, usize) = (p_counter, index);
+
+        let failed: bool = match instruction {
+            Instruction::Char(_) | Instruction::CharNoCase(_) | Instruction::AnyChar(_) | Instruction::Property(_, _) => {
+                match self.input.char_at(index) {
+                    Some((c, next)) if is_char_matched(instruction, &c) => {
+                        p_counter += 1;
+                        index = next;
+                        false
+                    }
+                    Some(_) => true,
+                    None => {
+                        self.hit_end = true;
+                        true
+                    }
+                }
+            }
+            Instruction::Assert(assertion) => {
+                if is_end_assertion(*assertion) && next_pos(self.input, index) >= self.input.len() {
+                    // 末尾付近の判定は、入力の続きによって変わりうる
+                    self.hit_end = true;
+                }
+                p_counter += 1;
+                !is_assertion_satisfied(*assertion, self.input, index, self.start)
+            }
+            Instruction::Match if self.anchored_end && index != self.input.len() => true,
+            Instruction::Match if self.longest => {
+                // マッチを記録し、より長いマッチを探すため失敗として戻る
+                if self.longest_match.as_ref().is_none_or(|(end, _)| index > *end) {
+                    self.longest_match = Some((index, self.slots.to_vec()));
+                }
+                true
+            }
+            Instruction::Match => {
+                self.record(start, p_counter, index, StepResult::Matched);
+                return Flow::Matched(index);
+            }
+            Instruction::MatchPattern(id) => {
+                // マッチしたことを記録し、残りのパターンを探索するため失敗として戻る
+                self.matched[*id] = true;
+                true
+            }
+            Instruction::Jump(counter) => {
+                p_counter = *counter;
+                self.is_empty_iteration(executed, p_counter, index)
+            }
+            Instruction::Split(counter1, counter2) => {
+                let (counter1, counter2) = (*counter1, *counter2);
+                match (self.is_empty_iteration(executed, counter1, index), self.is_empty_iteration(executed, counter2, index)) {
+                    (false, false) => {
+                        self.push(Frame::Retry(counter2, index));
+                        p_counter = counter1;
+                        false
+                    }
+                    (false, true) => {
+                        p_counter = counter1;
+                        false
+                    }
+                    (true, false) => {
+                        p_counter = counter2;
+                        false
+                    }
+                    (true, true) => true,
+                }
+            }
+            Instruction::Atomic(_) => {
+                // グループの中身を Commit まで実行し、最初に成功した結果だけを採用する
+                self.atomics.push(self.stack.len());
+                self.push(Frame::Atomic(self.slots.to_vec()));
+                p_counter += 1;
+                false
+            }
+            Instruction::Commit => {
+                // グループの中の Split の残りの分岐には戻らない
+                // グループの後で失敗した場合は、グループに入る前のスロットに戻してから、グループより前の分岐に戻る
+                let mark: usize = self.atomics.pop().unwrap();
+                let dropped: usize = self.stack[mark + 1..].iter().filter(|frame| matches!(frame, Frame::Head)).count();
+                self.heads.truncate(self.heads.len() - dropped);
+                self.stack.truncate(mark + 1);
+                if let Some(Frame::Atomic(saved)) = self.stack.pop() {
+                    self.push(Frame::RestoreAll(saved));
+                }
+                p_counter += 1;
+                false
+            }
+            Instruction::Save(slot) => {
+                let slot: usize = *slot;
+                self.push(Frame::Restore(slot, self.slots[slot]));
+                self.slots[slot] = Some(index);
+                p_counter += 1;
+                false
+            }
+            Instruction::Condition(group, else_counter) => {
+                if self.slots[group * 2 + 1].is_some() {
+                    p_counter += 1;
                 } else {
-                    return false
+                    p_counter = *else_counter;
                 }
+                false
             }
+        };
+        let result: StepResult = if failed { StepResult::Failed } else { StepResult::Next(p_counter) };
+        self.record(start, executed, executed_index, result);
+        if failed {
+            return self.fail();
+        }
+        Flow::Continue(p_counter, index)
+    }
+
+    /// 最後に積んだ分岐に戻る (戻る分岐が無い場合は Flow::Failed を返す)
+    fn fail(&mut self) -> Flow {
+        match self.backtrack() {
+            Some((p_counter, index)) => Flow::Continue(p_counter, index),
+            None => Flow::Failed,
+        }
+    }
+
+    /// trace を記録する場合に、start から試しているマッチで、(p_counter, index) の命令を実行した結果を記録する
+    fn record(&mut self, start: usize, p_counter: usize, index: usize, result: StepResult) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(Step { start, p_counter, index, result });
+        }
+    }
+
+    /// (p_counter, index) に到達した分岐の状態と位置の組を実行したことを記録する  
+    /// 既に実行していた場合は false を返す
+    fn visit(&mut self, p_counter: usize, index: usize) -> bool {
+        if self.visited.is_empty() {
+            return true;
+        }
+        let started = self.heads.iter().rev().take_while(|(_, pos)| *pos == index).map(|(pc, _)| *pc);
+        let state: usize = self.loops.state(p_counter, started);
+        let bit: usize = state * (self.input.len() + 1) + index;
+        let (word, mask) = (bit / 64, 1u64 << (bit % 64));
+        let first: bool = self.visited[word] & mask == 0;
+        self.visited[word] |= mask;
+        first
+    }
+
+    /// p_counter から target に進む分岐が、位置 index で始めた繰り返しの中からループの先頭 target に戻る分岐かを判定する  
+    /// 入力を読まずに繰り返しを続けても同じ繰り返しになるため、この分岐は失敗とする (空の文字列にマッチした繰り返しは最後の1回とする)  
+    /// heads は位置の順に並ぶため、後ろから位置が index のものだけを調べる
+    fn is_empty_iteration(&self, p_counter: usize, target: usize, index: usize) -> bool {
+        target <= p_counter && self.heads.iter().rev().take_while(|(_, pos)| *pos == index).any(|(pc, _)| *pc == target)
+    }
+
+    /// 作業を stack に積む  
+    /// 積んだ作業の数が上限を超えた場合は、探索を打ち切る
+    fn push(&mut self, frame: Frame) {
+        self.stack.push(frame);
+        if self.depth_limit.is_some_and(|limit| self.stack.len() > limit) {
+            self.aborted = true;
         }
     }
+
+    /// 最後に積んだ分岐まで stack を戻し、その分岐の (カウンタ, 位置) を返す  
+    /// 戻る分岐が無い場合は None を返す
+    fn backtrack(&mut self) -> Option<(usize, usize)> {
+        loop {
+            match self.stack.pop()? {
+                Frame::Retry(p_counter, index) => return Some((p_counter, index)),
+                Frame::Restore(slot, old) => self.slots[slot] = old,
+                Frame::Atomic(_) => {
+                    // グループの中身が失敗した
+                    self.atomics.pop();
+                }
+                Frame::RestoreAll(saved) => self.slots.copy_from_slice(&saved),
+                Frame::Head => {
+                    self.heads.pop();
+                }
+            }
+        }
+    }
+}
+
+/// 文字を読む命令 (Char, Property など) が記号 c にマッチするかを判定する  
+/// Char 以外の命令は、文字として読める記号にのみマッチする (`.` は文字として読めない記号にもマッチする)
+pub fn is_char_matched<T: Symbol>(instruction: &Instruction<T>, c: &T) -> bool {
+    if let Instruction::Char(expected) = instruction {
+        return expected == c;
+    }
+    let Some(c) = c.as_char() else {
+        return matches!(instruction, Instruction::AnyChar(_));
+    };
+    match instruction {
+        Instruction::CharNoCase(expected) => *expected == simple_fold(c),
+        Instruction::AnyChar(dot_all) => *dot_all || c != '\n',
+        Instruction::Property(property, negated) => property.contains(c) != *negated,
+        _ => false,
+    }
+}
+
+/// index の位置がアンカーの条件を満たすかを判定する  
+/// start は探索を開始した位置 (\G がマッチする位置)
+pub fn is_assertion_satisfied<T: Symbol, I: Input<T> + ?Sized>(assertion: Assertion, input: &I, index: usize, start: usize) -> bool {
+    match assertion {
+        Assertion::StartText => index == 0,
+        Assertion::EndText => index == input.len(),
+        Assertion::EndTextNewline => {
+            index == input.len()
+                || matches!(input.char_at(index), Some((c, next)) if next == input.len() && is_newline(&c))
+        }
+        Assertion::StartLine => index == 0 || input.char_before(index).is_some_and(|c| is_newline(&c)),
+        Assertion::EndLine => index == input.len() || input.char_at(index).is_some_and(|(c, _)| is_newline(&c)),
+        Assertion::StartSearch => index == start,
+    }
+}
+
+/// (状態, 位置) の組を記録するビット集合の大きさの上限 (ビット数)
+const MEMO_CAPACITY: usize = 1 << 23;
+
+/// バックトラックで実行した (状態, 位置) の組を記録するビット集合を生成する (状態は Loops::state の番号)  
+/// 一度失敗した組は、もう一度実行しても失敗するため、実行し直さずに失敗とできる (記録するのは成否だけで、キャプチャは最初に見つけたものを返す)  
+/// 成否がスロットに依る Condition や、範囲の中の分岐を試さずに捨てる Atomic を含む場合、ビット集合が上限より大きくなる場合は記録しない (空のビット集合を返す)  
+/// ビット集合には table の領域を再利用する
+fn memo_table<T>(instructions: &[Instruction<T>], states: usize, len: usize, mut table: Vec<u64>) -> Vec<u64> {
+    table.clear();
+    let bits: Option<usize> = states.checked_mul(len + 1);
+    if instructions.iter().any(|inst| matches!(inst, Instruction::Atomic(_) | Instruction::Condition(_, _)))
+        || bits.is_none_or(|bits| bits > MEMO_CAPACITY)
+    {
+        return table;
+    }
+    table.resize(bits.unwrap_or(0).div_ceil(64), 0);
+    table
+}
+
+/// 命令列の中のループ  
+/// ループの先頭 (自身以前に戻る Jump, Split の行き先) から、その先頭に戻る最後の命令までを1つのループとする  
+/// 入力を読まずに同じ命令に戻る繰り返しは、必ずループの先頭に戻る分岐 (後ろへ戻る Jump, Split) を通る  
+/// 入力を読まずに先頭に戻れないループ (`a*` など) はこの規則の影響を受けないため、ループとして扱わない  
+/// 各評価器は、同じ位置で始めた繰り返しの中から先頭に戻る分岐を失敗とする (空の文字列にマッチした繰り返しは最後の1回とする)  
+/// そのため、分岐の結果は (カウンタ, 同じ位置で繰り返しを始めた、そのカウンタを囲むループの数) の組 (状態) で決まる
+#[derive(Debug, Default)]
+pub(crate) struct Loops {
+    ends: Vec<Option<usize>>,  // 各カウンタがループの先頭の場合に、そのループの最後の命令のカウンタ
+    depths: Vec<usize>,        // 各カウンタを囲むループの数
+    offsets: Vec<usize>,       // 各カウンタの最初の状態の番号 (カウンタごとに depths + 1 個の状態がある)
+    order: Vec<usize>,         // build の作業領域: 各カウンタを訪れた順番 (未訪問は usize::MAX)
+    lowlinks: Vec<usize>,      // build の作業領域: 各カウンタから戻れる最も早く訪れたカウンタの順番 (成分が決まったら usize::MAX)
+    components: Vec<usize>,    // build の作業領域: 成分が決まっていないカウンタ
+    path: Vec<(usize, usize)>, // build の作業領域: 辿っているカウンタと、次に調べる行き先の番号
+}
+
+impl Loops {
+    /// instructions のループを調べ直す (これまでに確保した領域は再利用する)
+    pub(crate) fn build<T>(&mut self, instructions: &[Instruction<T>]) {
+        self.ends.clear();
+        self.ends.resize(instructions.len(), None);
+        for (pc, inst) in instructions.iter().enumerate() {
+            let targets: [Option<usize>; 2] = match inst {
+                Instruction::Jump(counter) => [Some(*counter), None],
+                Instruction::Split(counter1, counter2) => [Some(*counter1), Some(*counter2)],
+                _ => [None, None],
+            };
+            for counter in targets.into_iter().flatten().filter(|counter| *counter <= pc) {
+                self.ends[counter] = Some(pc);
+            }
+        }
+        self.drop_nonempty_loops(instructions);
+        // depths には、各カウンタの直前で終わるループの数を数えてから、前から順に囲むループの数で上書きする
+        self.depths.clear();
+        self.depths.resize(instructions.len() + 1, 0);
+        for end in self.ends.iter().flatten() {
+            self.depths[end + 1] += 1;
+        }
+        self.offsets.clear();
+        let (mut depth, mut offset): (usize, usize) = (0, 0);
+        for pc in 0..instructions.len() {
+            depth -= self.depths[pc];
+            if self.ends[pc].is_some() {
+                depth += 1;
+            }
+            self.depths[pc] = depth;
+            self.offsets.push(offset);
+            offset += depth + 1;
+        }
+        self.depths.truncate(instructions.len());
+        self.offsets.push(offset);
+    }
+
+    /// 入力を読まずに自身に戻れない先頭を ends から外す  
+    /// 入力を読まない遷移のグラフを強連結成分に分解し (Tarjan のアルゴリズム)、閉路を含む成分にない先頭を外す
+    fn drop_nonempty_loops<T>(&mut self, instructions: &[Instruction<T>]) {
+        const UNVISITED: usize = usize::MAX;
+        const DONE: usize = usize::MAX;
+        self.order.clear();
+        self.order.resize(instructions.len(), UNVISITED);
+        self.lowlinks.clear();
+        self.lowlinks.resize(instructions.len(), DONE);
+        self.components.clear();
+        self.path.clear();
+        let mut visited: usize = 0;
+        for root in 0..instructions.len() {
+            if self.order[root] != UNVISITED {
+                continue;
+            }
+            self.order[root] = visited;
+            self.lowlinks[root] = visited;
+            visited += 1;
+            self.components.push(root);
+            self.path.push((root, 0));
+            while let Some((pc, next)) = self.path.last_mut() {
+                let pc: usize = *pc;
+                if let Some(target) = epsilon_targets(pc, &instructions[pc]).get(*next).copied() {
+                    *next += 1;
+                    let Some(target) = target.filter(|target| *target < instructions.len()) else {
+                        continue;
+                    };
+                    if self.order[target] == UNVISITED {
+                        self.order[target] = visited;
+                        self.lowlinks[target] = visited;
+                        visited += 1;
+                        self.components.push(target);
+                        self.path.push((target, 0));
+                    } else if self.lowlinks[target] != DONE {
+                        self.lowlinks[pc] = self.lowlinks[pc].min(self.order[target]);
+                    }
+                    continue;
+                }
+                self.path.pop();
+                if let Some((parent, _)) = self.path.last() {
+                    self.lowlinks[*parent] = self.lowlinks[*parent].min(self.lowlinks[pc]);
+                }
+                if self.lowlinks[pc] != self.order[pc] {
+                    continue;
+                }
+                let start: usize = self.components.iter().rposition(|member| *member == pc).unwrap();
+                let cyclic: bool = self.components.len() - start > 1
+                    || epsilon_targets(pc, &instructions[pc]).contains(&Some(pc));
+                for member in self.components.drain(start..) {
+                    self.lowlinks[member] = DONE;
+                    if !cyclic {
+                        self.ends[member] = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// 状態の数
+    pub(crate) fn states(&self) -> usize {
+        self.offsets.last().copied().unwrap_or(0)
+    }
+
+    /// pc がループの先頭かどうか
+    pub(crate) fn is_head(&self, pc: usize) -> bool {
+        self.ends[pc].is_some()
+    }
+
+    /// pc に到達した分岐の状態の番号を返す  
+    /// started は、その分岐が同じ位置で実行したループの先頭 (同じものを複数含んでもよい)
+    pub(crate) fn state(&self, pc: usize, started: impl Iterator<Item = usize> + Clone) -> usize {
+        if self.depths[pc] == 0 || started.clone().next().is_none() {
+            return self.offsets[pc];
+        }
+        let enclosing = started.filter(|head| *head <= pc && self.ends[*head].is_some_and(|end| pc <= end));
+        let level: usize =
+            enclosing.clone().enumerate().filter(|(i, head)| !enclosing.clone().take(*i).any(|other| other == *head)).count();
+        self.offsets[pc] + level.min(self.depths[pc])
+    }
+}
+
+/// pc の命令から入力を読まずに進む先のカウンタ
+fn epsilon_targets<T>(pc: usize, inst: &Instruction<T>) -> [Option<usize>; 2] {
+    match inst {
+        Instruction::Jump(counter) => [Some(*counter), None],
+        Instruction::Split(counter1, counter2) => [Some(*counter1), Some(*counter2)],
+        Instruction::Condition(_, counter) => [Some(pc + 1), Some(*counter)],
+        Instruction::Save(_) | Instruction::Assert(_) | Instruction::Atomic(_) | Instruction::Commit => [Some(pc + 1), None],
+        _ => [None, None],
+    }
+}
+
+/// 記号が改行文字かどうか
+fn is_newline<T: Symbol>(c: &T) -> bool {
+    c.as_char() == Some('\n')
+}
+
+/// pos の次の文字の位置を返す (pos が入力の末尾の場合は、末尾より後ろの位置を返す)
+pub fn next_pos<T: Symbol, I: Input<T> + ?Sized>(input: &I, pos: usize) -> usize {
+    input.char_at(pos).map_or(pos + 1, |(_, next)| next)
+}
+
+/// 入力の末尾や、後ろの文字を参照するアンカーかどうか
+fn is_end_assertion(assertion: Assertion) -> bool {
+    matches!(assertion, Assertion::EndText | Assertion::EndLine | Assertion::EndTextNewline)
+}
+
+/// 命令列が使うキャプチャのスロットの数を返す (マッチ全体の位置を記録するスロットを含む)
+pub fn slots_len<T>(instructions: &[Instruction<T>]) -> usize {
+    instructions
+        .iter()
+        .map(|inst| match inst {
+            Instruction::Save(slot) => slot + 1,
+            Instruction::Condition(group, _) => group * 2 + 2,
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+        .max(2)
+}
+
+#[test]
+fn test_search() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    let insts: Vec<Instruction> = compile(&parse("bc").unwrap());
+    assert_eq!(search(&insts, "abc", 0, EvalOptions::default()), Some((1, 3)));
+    assert_eq!(search(&insts, "abc", 2, EvalOptions::default()), None);
+    assert_eq!(search(&insts, "ab", 0, EvalOptions::default()), None);
+
+    // \G は探索を開始した位置にのみマッチする
+    let insts: Vec<Instruction> = compile(&parse("\\Gb").unwrap());
+    assert_eq!(search(&insts, "ab", 0, EvalOptions::default()), None);
+    assert_eq!(search(&insts, "ab", 1, EvalOptions::default()), Some((1, 2)));
+}
+
+#[test]
+fn test_search_slots() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    let insts: Vec<Instruction> = compile(&parse("(a+)(b)?c").unwrap());
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+    assert!(search_slots(&insts, "xaac", 0, &mut slots, EvalOptions::default()));
+    assert_eq!(slots, vec![Some(1), Some(4), Some(1), Some(3), None, None]);
+
+    // 同じ slots を使い回しても、前回の結果は残らない
+    assert!(search_slots(&insts, "abc", 0, &mut slots, EvalOptions::default()));
+    assert_eq!(slots, vec![Some(0), Some(3), Some(0), Some(1), Some(1), Some(2)]);
+    assert!(!search_slots(&insts, "ab", 0, &mut slots, EvalOptions::default()));
+}
+
+#[test]
+fn test_eval_start_search() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // \G は探索を開始した位置にのみマッチする
+    let insts: Vec<Instruction> = compile(&parse("\\Gb").unwrap());
+    let chars: Vec<char> = vec!['a', 'b', 'b'];
+    assert!(evaluate(&insts, &chars[..], 0, 1));
+
+    let insts: Vec<Instruction> = compile(&parse("b\\Gb").unwrap());
+    assert!(!evaluate(&insts, &chars[..], 0, 1));
+}
+
+#[test]
+fn test_eval_assertion() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    let insts: Vec<Instruction> = compile(&parse("\\Aab\\Z").unwrap());
+    assert!(evaluate(&insts, "ab", 0, 0));
+    assert!(evaluate(&insts, "ab\n", 0, 0));
+    assert!(!evaluate(&insts, "ab\n\n", 0, 0));
+    assert!(!evaluate(&insts, "xab", 0, 1));
+
+    let insts: Vec<Instruction> = compile(&parse("ab\\z").unwrap());
+    assert!(!evaluate(&insts, "ab\n", 0, 0));
+}
+
+#[test]
+fn test_eval_conditional() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // '<' で始まる場合のみ '>' で閉じる
+    let insts: Vec<Instruction> = compile(&parse("(<)?a(?(1)>|b)").unwrap());
+    assert!(evaluate(&insts, "<a>", 0, 0));
+    assert!(evaluate(&insts, "ab", 0, 0));
+    assert!(!evaluate(&insts, "<ab", 0, 0));
+    assert!(!evaluate(&insts, "a>", 0, 0));
+}
+
+#[test]
+fn test_eval_atomic() {
+    // "a*+a" が入力された Instraction
+    let insts: Vec<Instruction> = vec![
+        Instruction::Atomic(5),
+        Instruction::Split(2, 4),
+        Instruction::Char('a'),
+        Instruction::Jump(1),
+        Instruction::Commit,
+        Instruction::Char('a'),
+        Instruction::Match
+    ];
+
+    // a*+ が "aa" をすべて消費し、後続の 'a' のために戻らないので false
+    let chars:Vec<char> = vec!['a', 'a', 'b'];
+    assert!(!evaluate(&insts, &chars[..], 0, 0));
+}
+
+#[test]
+fn test_eval_atomic_group() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // (?>ab|a) は "ab" にマッチした時点で確定し、"a" の分岐には戻らない
+    let insts: Vec<Instruction> = compile(&parse("(?>ab|a)bc").unwrap());
+    let chars: Vec<char> = vec!['a', 'b', 'c', 'x'];
+    assert!(!evaluate(&insts, &chars[..], 0, 0));
+
+    let insts: Vec<Instruction> = compile(&parse("(ab|a)bc").unwrap());
+    assert!(evaluate(&insts, &chars[..], 0, 0));
 }
 
 #[test]
@@ -43,22 +1153,250 @@ fn test_eval() {
 
     // "abc" とマッチするケース = true
     let chars1:Vec<char> = vec!['a', 'b', 'c'];
-    assert_eq!(
-        evaluate(&insts, &chars1, 0, 0),
-        true
-    );
+    assert!(evaluate(&insts, &chars1[..], 0, 0));
 
     // "abd"とマッチするケース = true
     let chars2:Vec<char> = vec!['a', 'b', 'd'];
-    assert_eq!(
-        evaluate(&insts, &chars2, 0, 0),
-        true
-    );
+    assert!(evaluate(&insts, &chars2[..], 0, 0));
 
     // "abx" とマッチするケース
     let chars3:Vec<char> = vec!['a', 'b', 'X'];
-    assert_eq!(
-        evaluate(&insts, &chars3, 0, 0),
-        false
-    );
-}
\ No newline at end of file
+    assert!(!evaluate(&insts, &chars3[..], 0, 0));
+}
+#[test]
+fn test_search_options() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // anchored の場合は開始位置でのみマッチを試みる
+    let insts: Vec<Instruction> = compile(&parse("b").unwrap());
+    let anchored: EvalOptions = EvalOptions { anchored: true, ..EvalOptions::default() };
+    assert_eq!(search(&insts, "ab", 0, anchored), None);
+    assert_eq!(search(&insts, "ab", 1, anchored), Some((1, 2)));
+
+    // 実行する命令の数が上限を超えた場合はマッチしなかったものとする
+    let insts: Vec<Instruction> = compile(&parse("(a|aa)*c").unwrap());
+    let chars: Vec<char> = vec!['a'; 20];
+    let limited: EvalOptions = EvalOptions { backtrack_limit: Some(1000), ..EvalOptions::default() };
+    assert_eq!(search(&insts, &chars[..], 0, limited), None);
+    let chars: Vec<char> = vec!['a', 'a', 'c'];
+    assert_eq!(search(&insts, &chars[..], 0, limited), Some((0, 3)));
+}
+
+#[test]
+fn test_eval_line_and_case() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    let insts: Vec<Instruction> = compile(&parse("(?m)^b$").unwrap());
+    let chars: Vec<char> = vec!['a', '\n', 'b', '\n'];
+    assert_eq!(search(&insts, &chars[..], 0, EvalOptions::default()), Some((2, 3)));
+
+    let insts: Vec<Instruction> = compile(&parse("(?i)a.c").unwrap());
+    assert!(evaluate(&insts, "AbC", 0, 0));
+    assert!(!evaluate(&insts, "a\nc", 0, 0));
+
+    let insts: Vec<Instruction> = compile(&parse("(?s)a.c").unwrap());
+    assert!(evaluate(&insts, "a\nc", 0, 0));
+}
+
+#[test]
+fn test_shortest_search() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    let insts: Vec<Instruction> = compile(&parse("(a|b)+$").unwrap());
+    assert_eq!(shortest_search(&insts, "cab", 0, EvalOptions::default()), Some(3));
+
+    let insts: Vec<Instruction> = compile(&parse("\\Gab*").unwrap());
+    assert_eq!(shortest_search(&insts, "abab", 2, EvalOptions::default()), Some(3));
+    assert_eq!(shortest_search(&insts, "abca", 1, EvalOptions::default()), None);
+
+    // anchored の場合は開始位置からのマッチのみ
+    let insts: Vec<Instruction> = compile(&parse("b").unwrap());
+    let anchored: EvalOptions = EvalOptions { anchored: true, ..EvalOptions::default() };
+    assert_eq!(shortest_search(&insts, "ab", 0, anchored), None);
+}
+
+#[test]
+fn test_search_backtrack_deep() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // 長い入力でも、再帰せずに分岐を積むためスタックが溢れない
+    let insts: Vec<Instruction> = compile(&parse("(a|b)*c").unwrap());
+    let text: String = "ab".repeat(50_000) + "c";
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+    let mut scratch: Scratch = Scratch::default();
+    let options: EvalOptions = EvalOptions::default();
+    assert_eq!(search_backtrack(&insts, text.as_str(), 0, 0, &mut slots, options, true, &mut scratch), Partial::Matched);
+    assert_eq!(slots[1], Some(text.len()));
+
+    // 積む作業の数が上限を超えた場合は、探索を打ち切る
+    let limited: EvalOptions = EvalOptions { depth_limit: Some(1000), ..EvalOptions::default() };
+    assert_eq!(search_backtrack(&insts, text.as_str(), 0, 0, &mut slots, limited, true, &mut scratch), Partial::Aborted);
+    assert_eq!(search_backtrack(&insts, "abc", 0, 0, &mut slots, limited, true, &mut scratch), Partial::Matched);
+
+    // アトミックグループの後で失敗した場合は、グループより前の分岐に戻る
+    let insts: Vec<Instruction> = compile(&parse("(a|ab)(?>(b|c)+)d").unwrap());
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+    assert_eq!(search_backtrack(&insts, "abbcd", 0, 0, &mut slots, options, true, &mut scratch), Partial::Matched);
+    assert_eq!(&slots[..4], &[Some(0), Some(5), Some(0), Some(1)]);
+}
+
+#[test]
+fn test_search_backtrack_memo() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // 一度失敗した (カウンタ, 位置) の組を実行し直さないため、指数的な分岐があっても上限までに後の分岐に進める
+    let insts: Vec<Instruction> = compile(&parse("(a|a)*b|a*c").unwrap());
+    let text: String = "a".repeat(40) + "c";
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+    let mut scratch: Scratch = Scratch::default();
+    let limited: EvalOptions = EvalOptions { backtrack_limit: Some(10_000), ..EvalOptions::default() };
+    assert_eq!(search_backtrack(&insts, text.as_str(), 0, 0, &mut slots, limited, true, &mut scratch), Partial::Matched);
+    assert_eq!(slots[1], Some(41));
+
+    // キャプチャはバックトラックで最初に見つけたものを返す
+    let insts: Vec<Instruction> = compile(&parse("(a|ab)(b*)c").unwrap());
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+    let options: EvalOptions = EvalOptions::default();
+    assert_eq!(search_backtrack(&insts, "abbc", 0, 0, &mut slots, options, true, &mut scratch), Partial::Matched);
+    assert_eq!(slots, vec![Some(0), Some(4), Some(0), Some(1), Some(1), Some(3)]);
+
+    // Atomic を含む場合は、範囲の中の分岐を試さずに捨てた組を失敗として記録しない
+    let insts: Vec<Instruction> = compile(&parse("(?>A*|.)b").unwrap());
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+    assert_eq!(search_backtrack(&insts, "Aab", 0, 0, &mut slots, options, true, &mut scratch), Partial::Matched);
+    assert_eq!(slots[..2], [Some(2), Some(3)]);
+    let insts: Vec<Instruction> = compile(&parse("((b+)*+)(?m)(b$)").unwrap());
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+    assert_eq!(search_backtrack(&insts, "bb\n", 0, 0, &mut slots, options, true, &mut scratch), Partial::NotMatched);
+}
+
+#[test]
+fn test_search_backtrack_empty_loop() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // Condition を含む命令列は、実行した (カウンタ, 位置) の組を記録しないため、空の文字列にマッチする繰り返しの繰り返しでも止まること
+    let options: EvalOptions = EvalOptions::default();
+    let mut scratch: Scratch = Scratch::default();
+    let cases: &[(&str, &str, Partial, Option<usize>)] = &[
+        ("(x)?(?(1)y|(a*)*b)", "aac", Partial::NotMatched, None),
+        ("(x)?(?(1)y|(|a)+b)", "aab", Partial::Matched, Some(3)),
+        ("(x)?(?(1)y|(a?)*?b)", "aab", Partial::Matched, Some(3)),
+        ("(x)?(?(1)y|((a*)*)*$)", "aa", Partial::Matched, Some(2)),
+    ];
+    for (pattern, text, expected, end) in cases {
+        let insts: Vec<Instruction> = compile(&parse(pattern).unwrap());
+        let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+        assert_eq!(search_backtrack(&insts, *text, 0, 0, &mut slots, options, true, &mut scratch), *expected, "{}", pattern);
+        assert_eq!(slots[1], *end, "{}", pattern);
+    }
+
+    // 記録する場合と同じキャプチャを返す (Condition のためのグループは、末尾に加える)
+    let cases: &[(&str, &str, &[&str])] = &[
+        ("(a*)*b", "(?:(a*)*b|(x)(?(2)x))", &["b", "aab", "c"]),
+        ("(a\n?|)+", "(?:(a\n?|)+|(x)(?(2)x))", &["a\n", "", "aa"]),
+        ("(aa*||)+", "(?:(aa*||)+|(x)(?(2)x))", &["a", "aab"]),
+        ("(?:aa|((?:)))+()", "(?:(?:aa|((?:)))+()|(x)(?(3)x))", &["aa", "aaa"]),
+    ];
+    for (pattern, with_condition, texts) in cases {
+        let insts: Vec<Instruction> = compile(&parse(pattern).unwrap());
+        let with_condition: Vec<Instruction> = compile(&parse(with_condition).unwrap());
+        let mut expected: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+        let mut slots: Vec<Option<usize>> = vec![None; slots_len(&with_condition)];
+        for text in texts.iter() {
+            let matched: Partial = search_backtrack(&insts, *text, 0, 0, &mut expected, options, true, &mut scratch);
+            assert_eq!(search_backtrack(&with_condition, *text, 0, 0, &mut slots, options, true, &mut scratch), matched);
+            assert_eq!(&slots[..expected.len()], &expected[..], "{} / {}", pattern, text);
+        }
+    }
+}
+
+#[test]
+fn test_loops() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // 入力を読まずに先頭に戻れるループの先頭だけを数える
+    let cases: &[(&str, usize)] = &[("a*", 0), ("(a|b)+c", 0), ("(a|)*", 1), ("(a*)*", 2), ("((a|)+b)*", 1), ("(?:a?b?)+", 1)];
+    let mut loops: Loops = Loops::default();
+    for (pattern, heads) in cases {
+        let insts: Vec<Instruction> = compile(&parse(pattern).unwrap());
+        loops.build(&insts);
+        assert_eq!((0..insts.len()).filter(|pc| loops.is_head(*pc)).count(), *heads, "{}", pattern);
+    }
+
+    // 0 : Jump 2 / 1 : Jump 3 / 2 : Jump 1 / 3 : Split 0, 4 / 4 : Match
+    // 入れ子になっていない命令列でも、入力を読まずに戻る閉路の先頭はループの先頭とする
+    let insts: Vec<Instruction> =
+        vec![Instruction::Jump(2), Instruction::Jump(3), Instruction::Jump(1), Instruction::Split(0, 4), Instruction::Match];
+    loops.build(&insts);
+    assert!(loops.is_head(0) && loops.is_head(1));
+    let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+    let result: Partial = search_backtrack(&insts, "", 0, 0, &mut slots, EvalOptions::default(), true, &mut Scratch::default());
+    assert_eq!(result, Partial::Matched);
+}
+
+#[test]
+fn test_search_traced() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // 0 : Split 1, 3 / 1 : Char(a) / 2 : Jump 4 / 3 : Char(b) / 4 : Match
+    let insts: Vec<Instruction> = compile(&parse("a|b").unwrap());
+    let step = |start: usize, p_counter: usize, index: usize, result: StepResult| Step { start, p_counter, index, result };
+    let expect: Vec<Step> = vec![
+        step(0, 0, 0, StepResult::Next(1)),
+        step(0, 1, 0, StepResult::Failed),
+        step(0, 3, 0, StepResult::Failed),
+        step(1, 0, 1, StepResult::Next(1)),
+        step(1, 1, 1, StepResult::Failed),
+        step(1, 3, 1, StepResult::Next(4)),
+        step(1, 4, 2, StepResult::Matched),
+    ];
+    assert_eq!(search_traced(&insts, "xb", 0, EvalOptions::default()), (Some((1, 2)), expect));
+    assert_eq!(step(1, 3, 1, StepResult::Next(4)).to_string(), "start 1: 3 @ 1 -> 4");
+
+    // 記録しても結果は search と同じ
+    let insts: Vec<Instruction> = compile(&parse("(a|ab)(c|bcd)(d*)").unwrap());
+    for text in ["abcd", "xabcdd", "ab", ""] {
+        let (found, steps) = search_traced(&insts, text, 0, EvalOptions::default());
+        assert_eq!(found, search(&insts, text, 0, EvalOptions::default()));
+        assert!(!steps.is_empty());
+    }
+}
+
+#[test]
+fn test_debugger() {
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    // 0 : Save 2 / 1 : Char(a) / 2 : Split 1, 3 / 3 : Save 3 / 4 : Char(b) / 5 : Match
+    let insts: Vec<Instruction> = compile(&parse("(a+)b").unwrap());
+    let mut debugger: Debugger = Debugger::new(&insts, "xaab", 0, EvalOptions::default());
+    debugger.set_breakpoint(4);
+    assert!(debugger.run());
+    assert_eq!((debugger.start(), debugger.p_counter(), debugger.index()), (1, 4, 3));
+    assert_eq!(debugger.slots(), [None, None, Some(1), Some(3)]);
+    assert_eq!(debugger.backtrack_stack(), vec![(3, 2)]);
+    assert_eq!(debugger.step(), Some(Step { start: 1, p_counter: 4, index: 3, result: StepResult::Next(5) }));
+    assert!(!debugger.is_finished());
+    assert!(!debugger.run());
+    assert!(debugger.is_finished());
+    assert_eq!(debugger.step(), None);
+    assert_eq!(debugger.found().map(|m| m.range()), Some(1..4));
+
+    // 1命令ずつ実行した記録は、search_traced の記録と同じ
+    for (pattern, text) in [("(a|ab)(c|bcd)(d*)", "xabcdd"), ("(?>a+)b", "aab"), ("(a)?(?(1)b|c)", "c"), ("a|b", "")] {
+        let insts: Vec<Instruction> = compile(&parse(pattern).unwrap());
+        let (found, expect) = search_traced(&insts, text, 0, EvalOptions::default());
+        let mut debugger: Debugger = Debugger::new(&insts, text, 0, EvalOptions::default());
+        let steps: Vec<Step> = core::iter::from_fn(|| debugger.step()).collect();
+        assert_eq!(steps, expect, "{}", pattern);
+        assert_eq!(debugger.found().map(|m| (m.start(), m.end())), found, "{}", pattern);
+    }
+}
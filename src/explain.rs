@@ -0,0 +1,168 @@
+//! パターンを、英語の文章で説明するための実装  
+//! AST をたどり、`a*(de|fg)` を "match 'a' zero or more times, then either 'de' or 'fg' (group 1)" のように説明する  
+//! 文字の連結は1つの文字列にまとめ、繰り返す対象が複数の要素からなる場合は括弧で囲む
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::error::Error;
+use crate::parser::{parse_with_flags, Assertion, AST};
+use crate::regex::Regex;
+use crate::simplify::simplify;
+
+impl Regex {
+    /// パターンの意味を、英語の文章で説明する (学習・デバッグ用)  
+    /// RegexBuilder で指定したフラグでパースし直し、簡約した AST を説明する。文章は安定しておらず、バージョンによって変わることがある
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("a*(?:de|fg)").unwrap();
+    /// assert_eq!(re.explain().unwrap(), "match 'a' zero or more times, then either 'de' or 'fg'");
+    /// ```
+    pub fn explain(&self) -> Result<String, Error> {
+        let ast: AST = simplify(parse_with_flags(self.as_str(), self.flags(), usize::MAX)?);
+        Ok(format!("match {}", describe(&ast)))
+    }
+}
+
+/// AST を説明する句を返す
+pub(crate) fn describe(ast: &AST) -> String {
+    match ast {
+        AST::Char(c) => quote(&c.to_string()),
+        AST::CharNoCase(c) => format!("{} (ignoring case)", quote(&c.to_string())),
+        AST::AnyChar(false) => "any character except newline".to_string(),
+        AST::AnyChar(true) => "any character".to_string(),
+        AST::Property(property, false) if property.is_case_insensitive() => {
+            format!("a character in \\p{{{}}} (ignoring case)", property.name)
+        }
+        AST::Property(property, true) if property.is_case_insensitive() => {
+            format!("a character not in \\p{{{}}} (ignoring case)", property.name)
+        }
+        AST::Property(property, false) => format!("a character in \\p{{{}}}", property.name),
+        AST::Property(property, true) => format!("a character not in \\p{{{}}}", property.name),
+        AST::Plus(ast) => format!("{} one or more times", describe_operand(ast)),
+        AST::Star(ast) => format!("{} zero or more times", describe_operand(ast)),
+        AST::Question(ast) => format!("{} optionally", describe_operand(ast)),
+        AST::Or(_, _) => {
+            let mut branches: Vec<String> = Vec::new();
+            collect_branches(ast, &mut branches);
+            let last: String = branches.pop().unwrap();
+            format!("either {} or {}", branches.join(", "), last)
+        }
+        AST::Seq(asts) => describe_seq(asts),
+        AST::Atomic(ast) => format!("{} without backtracking", describe_operand(ast)),
+        AST::Assert(assertion) => describe_assertion(*assertion).to_string(),
+        AST::Capture(index, None, ast) => format!("{} (group {index})", describe(ast)),
+        AST::Capture(index, Some(name), ast) => format!("{} (group {index} '{name}')", describe(ast)),
+        AST::Conditional(group, then_ast, else_ast) => {
+            format!("if group {group} matched, {}, otherwise {}", describe(then_ast), describe(else_ast))
+        }
+        AST::Empty => "the empty string".to_string(),
+    }
+}
+
+/// 繰り返しなどの対象になる AST を説明する句を返す  
+/// 文字列以外の連結と選択は、どこまでが対象かを示すため括弧で囲む
+fn describe_operand(ast: &AST) -> String {
+    match ast {
+        AST::Seq(asts) if literal(asts).is_none() => format!("({})", describe(ast)),
+        AST::Or(_, _) | AST::Conditional(_, _, _) | AST::Atomic(_) => format!("({})", describe(ast)),
+        _ => describe(ast),
+    }
+}
+
+/// 連結を説明する句を返す (続く文字は1つの文字列にまとめる)
+fn describe_seq(asts: &[AST]) -> String {
+    if let Some(literal) = literal(asts) {
+        return literal;
+    }
+    let mut parts: Vec<String> = Vec::new();
+    let mut i: usize = 0;
+    while i < asts.len() {
+        // 同じ種類の文字 (Char または CharNoCase) が続く範囲
+        let len: usize = asts[i..].iter().take_while(|ast| same_kind(ast, &asts[i])).count().max(1);
+        parts.push(match literal(&asts[i..i + len]) {
+            Some(literal) if len > 1 => literal,
+            _ => describe(&asts[i]),
+        });
+        i += len;
+    }
+    parts.join(", then ")
+}
+
+/// 連結がすべて Char、またはすべて CharNoCase からなる場合に、それを説明する句を返す
+fn literal(asts: &[AST]) -> Option<String> {
+    let text: String = asts.iter().map(|ast| match ast {
+        AST::Char(c) | AST::CharNoCase(c) => Some(*c),
+        _ => None,
+    }).collect::<Option<String>>()?;
+    match asts.first()? {
+        first if !asts.iter().all(|ast| same_kind(ast, first)) => None,
+        AST::CharNoCase(_) => Some(format!("{} (ignoring case)", quote(&text))),
+        _ => Some(quote(&text)),
+    }
+}
+
+/// 2つの AST が、1つの文字列にまとめられる同じ種類の文字かどうか
+fn same_kind(ast: &AST, other: &AST) -> bool {
+    matches!((ast, other), (AST::Char(_), AST::Char(_)) | (AST::CharNoCase(_), AST::CharNoCase(_)))
+}
+
+/// 選択の分岐を、優先度の順に説明する句を branches に追加する
+fn collect_branches(ast: &AST, branches: &mut Vec<String>) {
+    match ast {
+        AST::Or(left, right) => {
+            collect_branches(left, branches);
+            collect_branches(right, branches);
+        }
+        ast => branches.push(describe(ast)),
+    }
+}
+
+/// アサーションを説明する句を返す
+fn describe_assertion(assertion: Assertion) -> &'static str {
+    match assertion {
+        Assertion::StartText => "the start of the text",
+        Assertion::EndText => "the end of the text",
+        Assertion::StartLine => "the start of a line",
+        Assertion::EndLine => "the end of a line",
+        Assertion::EndTextNewline => "the end of the text (or before a final newline)",
+        Assertion::StartSearch => "the position where the search started",
+    }
+}
+
+/// 文字列を `'` で囲む (制御文字などはエスケープする)
+fn quote(text: &str) -> String {
+    format!("'{}'", text.escape_debug())
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::RegexBuilder;
+    use crate::regex::Regex;
+
+    fn explain(pattern: &str) -> String {
+        Regex::new(pattern).unwrap().explain().unwrap()
+    }
+
+    #[test]
+    fn test_explain() {
+        assert_eq!(explain("abc"), "match 'abc'");
+        assert_eq!(explain("a*(de|fg)"), "match 'a' zero or more times, then either 'de' or 'fg' (group 1)");
+        assert_eq!(explain("^(?:ab)+x?$"), "match the start of the text, then 'ab' one or more times, then 'x' optionally, then the end of the text");
+        assert_eq!(explain("(?:a.)*|b|"), "match either ('a', then any character except newline) zero or more times, 'b' or the empty string");
+        assert_eq!(explain("(?<y>\\n)(?(1)a|b)"), "match '\\n' (group 1 'y'), then if group 1 matched, 'a', otherwise 'b'");
+        assert_eq!(explain("(?>a|b)+"), "match ((either 'a' or 'b') without backtracking) one or more times");
+        assert_eq!(explain(""), "match the empty string");
+    }
+
+    #[test]
+    fn test_explain_flags() {
+        let re: Regex = RegexBuilder::new("ab1.").case_insensitive(true).dot_matches_new_line(true).build().unwrap();
+        assert_eq!(re.explain().unwrap(), "match 'ab' (ignoring case), then '1', then any character");
+    }
+}
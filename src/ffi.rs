@@ -0,0 +1,171 @@
+//! C から正規表現を使うための関数  
+//! `ffi` フィーチャが有効な場合のみ組み込む。宣言は include/small_regex.h にある  
+//! エラーは負の値の戻り値 (SMALL_REGEX_ERROR_*) で返す
+
+use alloc::boxed::Box;
+use core::ffi::{c_char, c_int, CStr};
+use core::ptr;
+
+use crate::error::Error;
+use crate::regex::Regex;
+
+/// 成功した
+pub const SMALL_REGEX_OK: c_int = 0;
+/// 引数にヌルポインタが渡された
+pub const SMALL_REGEX_ERROR_NULL: c_int = -1;
+/// 文字列が UTF-8 として不正
+pub const SMALL_REGEX_ERROR_UTF8: c_int = -2;
+/// パターンのパースに失敗した
+pub const SMALL_REGEX_ERROR_PARSE: c_int = -3;
+/// パターンのネストの深さか、命令列の長さが上限を超えた
+pub const SMALL_REGEX_ERROR_TOO_LARGE: c_int = -4;
+/// 探索が実行する命令の数の上限に達した
+pub const SMALL_REGEX_ERROR_BUDGET: c_int = -5;
+
+/// C の文字列を &str に変換する
+unsafe fn to_str<'a>(s: *const c_char) -> Result<&'a str, c_int> {
+    if s.is_null() {
+        return Err(SMALL_REGEX_ERROR_NULL);
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| SMALL_REGEX_ERROR_UTF8)
+}
+
+/// pattern をコンパイルし、生成した Regex を out に書き込む  
+/// 成功した場合は SMALL_REGEX_OK を、失敗した場合は負の値を返す (out には NULL を書き込む)
+///
+/// # Safety
+///
+/// pattern は NUL 終端の文字列、out は書き込み可能なポインタであること。
+/// 生成した Regex は small_regex_free で解放すること
+#[no_mangle]
+pub unsafe extern "C" fn small_regex_compile(pattern: *const c_char, out: *mut *mut Regex) -> c_int {
+    if out.is_null() {
+        return SMALL_REGEX_ERROR_NULL;
+    }
+    *out = ptr::null_mut();
+    let pattern: &str = match to_str(pattern) {
+        Ok(pattern) => pattern,
+        Err(code) => return code,
+    };
+    match Regex::new(pattern) {
+        Ok(regex) => {
+            *out = Box::into_raw(Box::new(regex));
+            SMALL_REGEX_OK
+        }
+        Err(Error::Parse(_) | Error::Decode(_) | Error::Compile(_)) => SMALL_REGEX_ERROR_PARSE,
+        Err(Error::LimitExceeded { .. }) => SMALL_REGEX_ERROR_TOO_LARGE,
+        Err(Error::BudgetExceeded) => SMALL_REGEX_ERROR_BUDGET,
+    }
+}
+
+/// text のいずれかの位置にパターンにマッチする部分があれば 1 を、無ければ 0 を返す  
+/// 引数が不正な場合は負の値を返す
+///
+/// # Safety
+///
+/// regex は small_regex_compile で生成したもの、text は NUL 終端の文字列であること
+#[no_mangle]
+pub unsafe extern "C" fn small_regex_is_match(regex: *const Regex, text: *const c_char) -> c_int {
+    let Some(regex) = regex.as_ref() else {
+        return SMALL_REGEX_ERROR_NULL;
+    };
+    match to_str(text) {
+        Ok(text) => match regex.try_is_match(text) {
+            Ok(matched) => c_int::from(matched),
+            Err(_) => SMALL_REGEX_ERROR_BUDGET,
+        },
+        Err(code) => code,
+    }
+}
+
+/// text の中で最初にパターンにマッチする部分の開始位置・終了位置 (バイト単位) を start, end に書き込み、1 を返す  
+/// マッチする部分が無い場合は 0 を、引数が不正な場合は負の値を返す
+///
+/// # Safety
+///
+/// regex は small_regex_compile で生成したもの、text は NUL 終端の文字列、
+/// start と end は書き込み可能なポインタであること
+#[no_mangle]
+pub unsafe extern "C" fn small_regex_find(
+    regex: *const Regex,
+    text: *const c_char,
+    start: *mut usize,
+    end: *mut usize,
+) -> c_int {
+    let Some(regex) = regex.as_ref() else {
+        return SMALL_REGEX_ERROR_NULL;
+    };
+    if start.is_null() || end.is_null() {
+        return SMALL_REGEX_ERROR_NULL;
+    }
+    let text: &str = match to_str(text) {
+        Ok(text) => text,
+        Err(code) => return code,
+    };
+    match regex.find(text) {
+        Some(m) => {
+            *start = m.start();
+            *end = m.end();
+            1
+        }
+        None => 0,
+    }
+}
+
+/// small_regex_compile で生成した Regex を解放する (NULL の場合は何もしない)
+///
+/// # Safety
+///
+/// regex は small_regex_compile で生成し、まだ解放していないものであること
+#[no_mangle]
+pub unsafe extern "C" fn small_regex_free(regex: *mut Regex) {
+    if !regex.is_null() {
+        drop(Box::from_raw(regex));
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use core::ptr;
+    use std::ffi::CString;
+
+    use super::*;
+
+    #[test]
+    fn test_compile_and_match() {
+        let pattern: CString = CString::new("b(c|d)+").unwrap();
+        let mut regex: *mut Regex = ptr::null_mut();
+        unsafe {
+            assert_eq!(small_regex_compile(pattern.as_ptr(), &mut regex), SMALL_REGEX_OK);
+
+            let text: CString = CString::new("abdc").unwrap();
+            assert_eq!(small_regex_is_match(regex, text.as_ptr()), 1);
+
+            let (mut start, mut end): (usize, usize) = (0, 0);
+            assert_eq!(small_regex_find(regex, text.as_ptr(), &mut start, &mut end), 1);
+            assert_eq!((start, end), (1, 4));
+
+            let text: CString = CString::new("xyz").unwrap();
+            assert_eq!(small_regex_is_match(regex, text.as_ptr()), 0);
+
+            small_regex_free(regex);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let pattern: CString = CString::new("a(b").unwrap();
+        let mut regex: *mut Regex = ptr::null_mut();
+        unsafe {
+            assert_eq!(small_regex_compile(pattern.as_ptr(), &mut regex), SMALL_REGEX_ERROR_PARSE);
+            assert!(regex.is_null());
+            assert_eq!(small_regex_compile(ptr::null(), &mut regex), SMALL_REGEX_ERROR_NULL);
+            assert_eq!(small_regex_is_match(ptr::null(), pattern.as_ptr()), SMALL_REGEX_ERROR_NULL);
+
+            let invalid: CString = CString::new(vec![0xff]).unwrap();
+            assert_eq!(small_regex_compile(invalid.as_ptr(), &mut regex), SMALL_REGEX_ERROR_UTF8);
+        }
+    }
+}
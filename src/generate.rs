@@ -0,0 +1,298 @@
+//! パターンにマッチする文字列を、ランダムに生成するための実装  
+//! AST をたどり、選択ではランダムに分岐を選び、繰り返しではランダムな回数だけ繰り返して文字列を組み立てる  
+//! アンカーを満たせない選び方をした場合や、アトミックグループなどで実際にはマッチしない文字列になった場合は、生成し直す  
+//! 生成した文字列は、部分ではなく全体がパターンにマッチするか (Regex::is_full_match) で確かめる
+
+use alloc::string::String;
+
+use crate::error::Error;
+use crate::parser::{parse_with_flags, Assertion, AST};
+use crate::regex::Regex;
+use crate::simplify::simplify;
+use crate::unicode::{simple_fold, single};
+
+/// 1回の generate で文字列の生成を試みる回数の上限
+const MAX_ATTEMPTS: usize = 100;
+
+/// `.` で生成する ASCII 以外の文字 (UTF-8 で長さの異なる文字を含める)
+const NON_ASCII: [char; 4] = ['é', 'ß', 'あ', '😀'];
+
+/// パターンにマッチする文字列をランダムに生成する型 (Regex::generator が返す)  
+/// 乱数の種が同じであれば、同じ順に同じ文字列を生成する
+#[derive(Debug)]
+pub struct Generator<'r> {
+    regex: &'r Regex,
+    ast: AST,
+    max_repeat: usize, // `*`, `+` で繰り返す回数の上限
+    state: u64,        // 乱数の状態
+}
+
+/// 生成中の文字列と、アンカーによる制約
+struct Output {
+    text: String,
+    ended: bool,        // 入力の末尾のアンカーを通ったため、これ以上文字を続けられない
+    need_newline: bool, // 行末のアンカーを通ったため、次の文字は改行でなければならない
+    groups: u64,        // マッチしたキャプチャグループの集合 (64 番目以降のグループは記録しない)
+}
+
+impl Regex {
+    /// パターンにマッチする文字列をランダムに生成する Generator を返す (テストの入力の生成などに使う)  
+    /// RegexBuilder で指定したフラグでパースし直し、簡約した AST をもとに生成する
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("(a|b|c)+(x|yz)?").unwrap();
+    /// let mut generator = re.generator().unwrap();
+    /// generator.seed(42).max_repeat(3);
+    /// for _ in 0..10 {
+    ///     let text: String = generator.generate().unwrap();
+    ///     assert!(re.is_full_match(&text));
+    /// }
+    /// ```
+    pub fn generator(&self) -> Result<Generator<'_>, Error> {
+        let ast: AST = simplify(parse_with_flags(self.as_str(), self.flags(), usize::MAX)?);
+        Ok(Generator { regex: self, ast, max_repeat: 8, state: 0 })
+    }
+}
+
+impl Generator<'_> {
+    /// 乱数の種を設定する (デフォルトは 0)
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.state = seed;
+        self
+    }
+
+    /// `*`, `+` で繰り返す回数の上限を設定する (デフォルトは 8)  
+    /// `*` は 0 回から、`+` は 1 回から上限までの回数を選ぶ (`+` は上限が 0 でも 1 回は繰り返す)
+    pub fn max_repeat(&mut self, max: usize) -> &mut Self {
+        self.max_repeat = max;
+        self
+    }
+
+    /// 全体がパターンにマッチする文字列を1つ生成する  
+    /// `a^b` のようにマッチする文字列が無いパターンなど、一定の回数試しても生成できなかった場合は None を返す
+    pub fn generate(&mut self) -> Option<String> {
+        // walk の間は self を書き換えるため、AST を一時的に取り出す
+        let ast: AST = core::mem::replace(&mut self.ast, AST::Empty);
+        let text: Option<String> = (0..MAX_ATTEMPTS).find_map(|_| {
+            let mut out: Output = Output { text: String::new(), ended: false, need_newline: false, groups: 0 };
+            (self.walk(&ast, &mut out) && self.regex.is_full_match(&out.text)).then_some(out.text)
+        });
+        self.ast = ast;
+        text
+    }
+
+    /// ast にマッチする文字列を out に追加する  
+    /// アンカーの条件を満たせない場合は false を返す
+    fn walk(&mut self, ast: &AST, out: &mut Output) -> bool {
+        match ast {
+            AST::Char(c) => out.push(*c),
+            AST::CharNoCase(c) => {
+                let upper: Option<char> = single(c.to_uppercase()).filter(|upper| simple_fold(*upper) == *c);
+                match upper {
+                    Some(upper) if self.below(2) == 0 => out.push(upper),
+                    _ => out.push(*c),
+                }
+            }
+            AST::AnyChar(dot_all) => {
+                let c: char = self.any_char(*dot_all);
+                out.push(c)
+            }
+            AST::Property(property, false) => {
+                let ranges: &[(char, char)] = property.ranges();
+                if ranges.is_empty() {
+                    return false;
+                }
+                let (low, high) = ranges[self.below(ranges.len())];
+                let code: u32 = low as u32 + self.below((high as u32 - low as u32 + 1) as usize) as u32;
+                out.push(char::from_u32(code).unwrap_or(low))
+            }
+            AST::Property(property, true) => {
+                let c: Option<char> = (0..MAX_ATTEMPTS)
+                    .map(|_| self.any_char(true))
+                    .chain((0..=char::MAX as u32).filter_map(char::from_u32))
+                    .find(|c| !property.contains(*c));
+                c.is_some_and(|c| out.push(c))
+            }
+            AST::Plus(ast) => {
+                let count: usize = 1 + self.below(self.max_repeat.max(1));
+                (0..count).all(|_| self.walk(ast, out))
+            }
+            AST::Star(ast) => {
+                let count: usize = self.below(self.max_repeat + 1);
+                (0..count).all(|_| self.walk(ast, out))
+            }
+            AST::Question(ast) => self.below(2) == 0 || self.walk(ast, out),
+            AST::Or(left, right) => {
+                if self.below(2) == 0 {
+                    self.walk(left, out)
+                } else {
+                    self.walk(right, out)
+                }
+            }
+            AST::Seq(asts) => asts.iter().all(|ast| self.walk(ast, out)),
+            AST::Atomic(ast) => self.walk(ast, out),
+            AST::Assert(assertion) => out.assert(*assertion),
+            AST::Capture(index, _, ast) => {
+                if !self.walk(ast, out) {
+                    return false;
+                }
+                if *index < 64 {
+                    out.groups |= 1 << index;
+                }
+                true
+            }
+            AST::Conditional(group, then_ast, else_ast) => {
+                if *group < 64 && out.groups & (1 << group) != 0 {
+                    self.walk(then_ast, out)
+                } else {
+                    self.walk(else_ast, out)
+                }
+            }
+            AST::Empty => true,
+        }
+    }
+
+    /// `.` にマッチする文字をランダムに選ぶ (主に表示できる ASCII 文字、dot_all の場合は改行も選ぶ)
+    fn any_char(&mut self, dot_all: bool) -> char {
+        match self.below(16) {
+            0 => NON_ASCII[self.below(NON_ASCII.len())],
+            1 if dot_all => '\n',
+            _ => char::from(b' ' + self.below(95) as u8),
+        }
+    }
+
+    /// 0 以上 n 未満の乱数を返す (n は 1 以上)
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// 次の乱数を返す (SplitMix64)
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z: u64 = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl Output {
+    /// 文字を追加する (アンカーの条件により続けられない場合は false を返す)
+    fn push(&mut self, c: char) -> bool {
+        if self.ended || (self.need_newline && c != '\n') {
+            return false;
+        }
+        self.need_newline = false;
+        self.text.push(c);
+        true
+    }
+
+    /// 現在の位置でアンカーの条件を満たせるかを判定し、以降の文字への制約を記録する  
+    /// 生成した文字列は先頭から探索するため、`\G` は入力の先頭でのみ満たせる
+    fn assert(&mut self, assertion: Assertion) -> bool {
+        match assertion {
+            Assertion::StartText | Assertion::StartSearch => self.text.is_empty(),
+            Assertion::StartLine => self.text.is_empty() || self.text.ends_with('\n'),
+            Assertion::EndText | Assertion::EndTextNewline => {
+                self.ended = true;
+                true
+            }
+            Assertion::EndLine => {
+                self.need_newline = true;
+                true
+            }
+        }
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::Generator;
+    use crate::builder::RegexBuilder;
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_generate() {
+        let patterns: &[&str] = &[
+            "abc",
+            "a*(de|fg)+",
+            "^(?:ab)?x$",
+            "(?m)^a$\\n^b",
+            "(?i)straße",
+            "(a)?(?(1)b|c)",
+            "(?>a|ab)c",
+            "(a|b|c)\\t\\..",
+            "(?s).+é",
+            "",
+        ];
+        for pattern in patterns {
+            let re: Regex = Regex::new(pattern).unwrap();
+            let mut generator: Generator = re.generator().unwrap();
+            for _ in 0..50 {
+                let text: String = generator.generate().expect(pattern);
+                assert!(re.is_full_match(&text), "{} / {:?}", pattern, text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_atomic() {
+        // アトミックグループは最初にマッチした分岐から戻らないため、walk で選んだ分岐では全体がマッチしない場合がある
+        let patterns: &[&str] = &["(?>|}) ", "(?>\\G|\\n)|}", "(?>a|ab)c", "(?>a*)a|b", "x(?>y|yz)+z?"];
+        for pattern in patterns {
+            let re: Regex = Regex::new(pattern).unwrap();
+            let mut generator: Generator = re.generator().unwrap();
+            for _ in 0..50 {
+                let text: String = generator.generate().expect(pattern);
+                assert!(re.is_full_match(&text), "{} / {:?}", pattern, text);
+            }
+        }
+        assert_eq!(Regex::new("(?>a*)a").unwrap().generator().unwrap().generate(), None);
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_generate_property() {
+        let re: Regex = Regex::new("\\p{Greek}\\P{L}+").unwrap();
+        let mut generator: Generator = re.generator().unwrap();
+        for _ in 0..50 {
+            let text: String = generator.generate().unwrap();
+            assert!(re.is_full_match(&text), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_generate_options() {
+        let re: Regex = Regex::new("a+b*").unwrap();
+        let texts = |seed: u64, max: usize| -> Vec<String> {
+            let mut generator: Generator = re.generator().unwrap();
+            generator.seed(seed).max_repeat(max);
+            (0..20).map(|_| generator.generate().unwrap()).collect()
+        };
+        // 同じ種からは同じ文字列を生成する
+        assert_eq!(texts(7, 3), texts(7, 3));
+        assert_ne!(texts(7, 3), texts(8, 3));
+        assert!(texts(7, 3).iter().all(|text| (1..=6).contains(&text.len())));
+        assert!(texts(7, 0).iter().all(|text| text == "a"));
+
+        // フラグは RegexBuilder で指定したものを使う
+        let re: Regex = RegexBuilder::new("x.").dot_matches_new_line(true).build().unwrap();
+        let mut generator: Generator = re.generator().unwrap();
+        assert!((0..200).any(|_| generator.generate().unwrap() == "x\n"));
+    }
+
+    #[test]
+    fn test_generate_impossible() {
+        let re: Regex = Regex::new("a^b").unwrap();
+        assert_eq!(re.generator().unwrap().generate(), None);
+        let re: Regex = Regex::new("a$b|c").unwrap();
+        assert!(re.generator().unwrap().generate().is_some());
+    }
+}
@@ -0,0 +1,193 @@
+//! 評価器が読む入力と、入力を構成する記号を表すトレイト
+
+/// UTF-8 として不正なバイトを読んだ場合の文字の基準 (バイト b を U+10FF00 + b とする)
+const RAW_BYTE_BASE: u32 = 0x10FF00;
+
+/// 入力を構成する記号を表すトレイト  
+/// 命令列の Char は記号の比較 (PartialEq) でマッチする  
+/// 文字として読める記号は as_char で文字を返し、`.` や文字クラス、`^`, `$` などの行のアンカーの判定に使う
+pub trait Symbol: Clone + PartialEq {
+    /// 記号を文字として読む場合の文字を返す  
+    /// 文字として読めない記号 (トークンなど) は None を返し、`.` 以外の文字クラスにはマッチしない
+    fn as_char(&self) -> Option<char> {
+        None
+    }
+}
+
+impl Symbol for char {
+    fn as_char(&self) -> Option<char> {
+        Some(*self)
+    }
+}
+
+/// 評価器が読む入力を表すトレイト  
+/// T は入力を構成する記号の型で、既定では文字 (char) とする  
+/// 位置は入力ごとの単位で表す (`str`, `[u8]` はバイト単位、`[T]` は記号単位)  
+/// 評価器は文字の境界の位置だけを扱うため、入力全体を文字の列に変換する必要は無い
+pub trait Input<T: Symbol = char> {
+    /// 入力の長さ (位置の単位で数える)
+    fn len(&self) -> usize;
+
+    /// 入力が空かどうか
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// pos の位置の記号と、その次の記号の位置を返す  
+    /// pos が入力の末尾の場合は None を返す
+    fn char_at(&self, pos: usize) -> Option<(T, usize)>;
+
+    /// pos の直前の記号を返す  
+    /// pos が入力の先頭の場合は None を返す
+    fn char_before(&self, pos: usize) -> Option<T>;
+
+    /// 入力を UTF-8 のバイト列として見られる場合は、そのバイト列を返す (位置はバイト単位であること)  
+    /// リテラルの候補の位置を memchr で探すために使う
+    fn as_bytes(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+impl Input for str {
+    fn len(&self) -> usize {
+        str::len(self)
+    }
+
+    fn char_at(&self, pos: usize) -> Option<(char, usize)> {
+        let c: char = self.get(pos..)?.chars().next()?;
+        Some((c, pos + c.len_utf8()))
+    }
+
+    fn char_before(&self, pos: usize) -> Option<char> {
+        self[..pos].chars().next_back()
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        Some(str::as_bytes(self))
+    }
+}
+
+/// 評価器が input の pos 以降を1つずつ読む記号を、順に返すイテレータを返す  
+/// DFA や Shift-Or のように、位置を使わずに記号だけを読む判定器で使う
+pub(crate) fn symbols<T: Symbol, I: Input<T> + ?Sized>(input: &I, pos: usize) -> impl Iterator<Item = T> + '_ {
+    let mut pos: usize = pos;
+    core::iter::from_fn(move || {
+        let (c, next): (T, usize) = input.char_at(pos)?;
+        pos = next;
+        Some(c)
+    })
+}
+
+/// バイト列の入力で、UTF-8 として不正なバイト b (0x80..=0xFF) を読んだ場合の文字  
+/// 私用面の末尾の U+10FF80..=U+10FFFF に割り当て、bytes::Regex のパターンの `\x80`..`\xFF` もこの文字にする
+pub(crate) fn raw_byte(b: u8) -> char {
+    char::from_u32(RAW_BYTE_BASE + b as u32).unwrap()
+}
+
+/// c が UTF-8 として不正なバイトを表す文字 (raw_byte で作る文字) かどうか
+pub(crate) fn is_raw_byte(c: char) -> bool {
+    (RAW_BYTE_BASE + 0x80..=RAW_BYTE_BASE + 0xFF).contains(&(c as u32))
+}
+
+/// UTF-8 として不正なバイトは、1バイトずつ raw_byte の文字として読む  
+/// 正しい UTF-8 の U+10FF80..=U+10FFFF も同じ文字になるため、これらの文字と不正なバイトは区別しない
+impl Input for [u8] {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn char_at(&self, pos: usize) -> Option<(char, usize)> {
+        let first: u8 = *self.get(pos)?;
+        let width: usize = match first {
+            0x00..=0x7F => return Some((first as char, pos + 1)),
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return Some((raw_byte(first), pos + 1)),
+        };
+        match self.get(pos..pos + width).and_then(|bytes| core::str::from_utf8(bytes).ok()) {
+            Some(s) => Some((s.chars().next()?, pos + width)),
+            None => Some((raw_byte(first), pos + 1)),
+        }
+    }
+
+    fn char_before(&self, pos: usize) -> Option<char> {
+        if pos == 0 {
+            return None;
+        }
+        // 直前の最大4バイトから文字の先頭を探し、pos でちょうど終わる文字を読む
+        for start in (pos.saturating_sub(4)..pos).rev() {
+            if self[start] & 0xC0 != 0x80 {
+                return match self.char_at(start) {
+                    Some((c, next)) if next == pos => Some(c),
+                    _ => Some(raw_byte(self[pos - 1])),
+                };
+            }
+        }
+        Some(raw_byte(self[pos - 1]))
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+}
+
+impl<T: Symbol> Input<T> for [T] {
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn char_at(&self, pos: usize) -> Option<(T, usize)> {
+        self.get(pos).map(|c| (c.clone(), pos + 1))
+    }
+
+    fn char_before(&self, pos: usize) -> Option<T> {
+        self.get(pos.checked_sub(1)?).cloned()
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{is_raw_byte, raw_byte, symbols, Input};
+
+    #[test]
+    fn test_str() {
+        let text: &str = "aα";
+        assert_eq!(text.char_at(0), Some(('a', 1)));
+        assert_eq!(text.char_at(1), Some(('α', 3)));
+        assert_eq!(text.char_at(3), None);
+        assert_eq!(text.char_before(3), Some('α'));
+        assert_eq!(text.char_before(0), None);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bytes: &[u8] = b"a\xce\xb1\xff\xe3\x81";
+        assert_eq!(bytes.char_at(1), Some(('α', 3)));
+        assert_eq!(bytes.char_at(3), Some((raw_byte(0xff), 4)));
+        // 途中で終わっている文字は、1バイトずつ不正なバイトとして読む
+        assert_eq!(bytes.char_at(4), Some((raw_byte(0xe3), 5)));
+        assert_eq!(bytes.char_at(5), Some((raw_byte(0x81), 6)));
+        assert_eq!(bytes.char_before(3), Some('α'));
+        assert_eq!(bytes.char_before(2), Some(raw_byte(0xce)));
+        assert_eq!(bytes.char_before(6), Some(raw_byte(0x81)));
+        assert_eq!(symbols(bytes, 1).collect::<Vec<char>>(), ['α', raw_byte(0xff), raw_byte(0xe3), raw_byte(0x81)]);
+
+        assert_eq!(raw_byte(0x80), '\u{10ff80}');
+        assert!(is_raw_byte(raw_byte(0xff)));
+        assert!(!is_raw_byte('\u{10ff7f}'));
+        assert!(!is_raw_byte(char::REPLACEMENT_CHARACTER));
+    }
+
+    #[test]
+    fn test_chars() {
+        let chars: &[char] = &['a', 'α'];
+        assert_eq!(chars.char_at(1), Some(('α', 2)));
+        assert_eq!(chars.char_at(2), None);
+        assert_eq!(chars.char_before(1), Some('a'));
+    }
+}
@@ -0,0 +1,153 @@
+//! 2つのパターンがマッチする文字列の集合 (言語) を比べるための実装  
+//! パターン全体にマッチする文字列 (先頭から末尾までがマッチする文字列) の集合を言語とし、キャプチャは無視する  
+//! 2つの命令列の、同じ入力を読んだ後の命令の集合の組を状態とする積オートマトンを、先頭から辿れる状態だけ生成して比べる
+
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::vec::Vec;
+
+use crate::compiler::Instruction;
+use crate::dfa::{class_boundaries, is_supported, representative, Builder, Member, START_SEARCH, START_TEXT};
+use crate::evaluator::EvalOptions;
+use crate::regex::Regex;
+
+/// 積オートマトンの状態の数の上限 (超えた場合は比べられなかったものとする)
+const STATE_LIMIT: usize = 100_000;
+
+impl Regex {
+    /// self と other が、同じ文字列の集合にマッチするかを判定する (パターン全体にマッチするかで比べ、キャプチャは無視する)  
+    /// `a|ab` と `ab|a` のように、探索で見つかるマッチが異なっても、文字列全体にマッチするかが一致すれば等しいとする  
+    /// アトミックグループ、条件分岐、行のアンカーを含む場合や、比べる状態の数が多すぎる場合は None を返す
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("(a|b)*").unwrap();
+    /// assert_eq!(re.is_equivalent(&Regex::new("(a*b*)*").unwrap()), Some(true));
+    /// assert_eq!(re.is_equivalent(&Regex::new("(ab)*").unwrap()), Some(false));
+    /// ```
+    pub fn is_equivalent(&self, other: &Regex) -> Option<bool> {
+        includes(self.instructions(), other.instructions(), true)
+    }
+
+    /// self の全体にマッチする文字列が、すべて other の全体にもマッチするかを判定する (キャプチャは無視する)  
+    /// 規則の集合の中で、他の規則に含まれて不要になっている規則を見つけるために使う  
+    /// アトミックグループ、条件分岐、行のアンカーを含む場合や、比べる状態の数が多すぎる場合は None を返す
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("ab+").unwrap();
+    /// assert_eq!(re.is_subset_of(&Regex::new("a.*").unwrap()), Some(true));
+    /// assert_eq!(re.is_subset_of(&Regex::new("ab").unwrap()), Some(false));
+    /// ```
+    pub fn is_subset_of(&self, other: &Regex) -> Option<bool> {
+        includes(self.instructions(), other.instructions(), false)
+    }
+}
+
+/// a の言語が b の言語に含まれるかを判定する (both の場合は、b の言語が a の言語に含まれるかも判定する)  
+/// 両方の命令列を同じ入力で進めた状態の組を幅優先で辿り、一方でのみ入力の末尾でマッチする状態があれば false を返す
+fn includes(a: &[Instruction], b: &[Instruction], both: bool) -> Option<bool> {
+    if !is_supported(a) || !is_supported(b) {
+        return None;
+    }
+    // 両方の命令列の文字のクラスを細かくしたクラスでは、どの命令もマッチするかどうかが一致する
+    let mut boundaries: Vec<u32> = class_boundaries(a);
+    boundaries.extend(class_boundaries(b));
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    let representatives: Vec<char> =
+        (0..boundaries.len()).filter_map(|i| representative(boundaries[i], boundaries.get(i + 1).copied())).collect();
+
+    let options: EvalOptions = EvalOptions { anchored: true, ..EvalOptions::default() };
+    let builders: [Builder; 2] = [Builder::new(a, options, 0), Builder::new(b, options, 0)];
+    // 入力の先頭では `^`, `\G` が成り立つ
+    let flags: u8 = START_TEXT | START_SEARCH;
+    let start: (Vec<Member>, Vec<Member>) = (builders[0].closure_from(&[0], flags), builders[1].closure_from(&[0], flags));
+    let mut visited: BTreeSet<(Vec<Member>, Vec<Member>)> = BTreeSet::new();
+    let mut queue: VecDeque<(Vec<Member>, Vec<Member>)> = VecDeque::new();
+    visited.insert(start.clone());
+    queue.push_back(start);
+    while let Some((set_a, set_b)) = queue.pop_front() {
+        let (accept_a, accept_b) = (accepts(&builders[0], a, &set_a), accepts(&builders[1], b, &set_b));
+        if (accept_a && !accept_b) || (both && accept_b && !accept_a) {
+            return Some(false);
+        }
+        // a が先に進めない状態からは、both でなければ a の言語の文字列は続かない
+        if set_a.is_empty() && (!both || set_b.is_empty()) {
+            continue;
+        }
+        for c in representatives.iter() {
+            let next: (Vec<Member>, Vec<Member>) =
+                (builders[0].next_set(&set_a, Some(c)), builders[1].next_set(&set_b, Some(c)));
+            if visited.contains(&next) {
+                continue;
+            }
+            if visited.len() >= STATE_LIMIT {
+                return None;
+            }
+            visited.insert(next.clone());
+            queue.push_back(next);
+        }
+    }
+    Some(true)
+}
+
+/// 命令の集合の位置で入力が終わった場合に、Match に到達するかどうか
+fn accepts(builder: &Builder, instructions: &[Instruction], set: &[Member]) -> bool {
+    set.iter().any(|(pc, _)| instructions[*pc] == Instruction::Match) || builder.matches_at_end(set)
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::RegexBuilder;
+    use crate::regex::Regex;
+
+    fn regex(pattern: &str) -> Regex {
+        Regex::new(pattern).unwrap()
+    }
+
+    #[test]
+    fn test_is_equivalent() {
+        let same: &[(&str, &str)] = &[
+            ("a|ab", "ab|a"),
+            ("(a|b)*", "(a*b*)*"),
+            ("a+", "aa*"),
+            ("(x)(y)", "xy"),
+            ("^a$", "a"),
+            ("\\Ga", "a"),
+            ("(?i)ab", "(a|A)(b|B)"),
+            ("a(?s).", "a(.|\\n)"),
+            ("a$b", "a^b"),
+            ("", "(a*)?^"),
+        ];
+        for (a, b) in same {
+            assert_eq!(regex(a).is_equivalent(&regex(b)), Some(true), "{} / {}", a, b);
+        }
+        let different: &[(&str, &str)] = &[("a*", "a+"), ("a.", "a(.|\\n)"), ("(ab)*", "(a|b)*"), ("é", "e"), ("a", "")];
+        for (a, b) in different {
+            assert_eq!(regex(a).is_equivalent(&regex(b)), Some(false), "{} / {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_is_subset_of() {
+        assert_eq!(regex("a+").is_subset_of(&regex("a*")), Some(true));
+        assert_eq!(regex("a*").is_subset_of(&regex("a+")), Some(false));
+        assert_eq!(regex("a^b").is_subset_of(&regex("x")), Some(true));
+        assert_eq!(regex("(?i)k").is_subset_of(&regex("k|K|\u{212a}")), Some(true));
+        assert_eq!(regex("k|K|\u{212a}").is_subset_of(&regex("(?i)k")), Some(true));
+        let re: Regex = RegexBuilder::new("ab").case_insensitive(true).build().unwrap();
+        assert_eq!(regex("AB").is_subset_of(&re), Some(true));
+        assert_eq!(re.is_subset_of(&regex("AB")), Some(false));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert_eq!(regex("(?>a)b").is_equivalent(&regex("ab")), None);
+        assert_eq!(regex("a").is_subset_of(&regex("(?m)^a")), None);
+        assert_eq!(regex("(a)?(?(1)b|c)").is_subset_of(&regex(".*")), None);
+    }
+}
@@ -0,0 +1,97 @@
+//! 小さな正規表現エンジン  
+//! パターンを AST にパースし、命令列にコンパイルしてから、バックトラックで評価する
+//!
+//! ```
+//! use small_regex::Regex;
+//!
+//! let re = Regex::new("ab*(de|fg)").unwrap();
+//! assert!(re.is_match("abbbfg"));
+//! ```
+//!
+//! `std` フィーチャ (デフォルトで有効) を無効にすると、`alloc` のみを使う `no_std` のクレートとしてビルドできる
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "pattern", feature(pattern))]
+
+extern crate alloc;
+
+#[doc(hidden)]
+pub mod __private;
+mod aho_corasick;
+mod ast;
+mod binary;
+mod builder;
+pub mod bytes;
+mod cache;
+mod captures;
+mod compiler;
+mod complexity;
+pub mod derivatives;
+mod dfa;
+mod dot;
+mod error;
+mod evaluator;
+mod explain;
+mod generate;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod input;
+mod language;
+mod literal;
+#[cfg(feature = "std")]
+mod lines;
+mod matches;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod offsets;
+mod onepass;
+mod optimizer;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod parser;
+mod pikevm;
+#[cfg(feature = "pattern")]
+mod pattern;
+mod regex;
+mod replace;
+#[cfg(feature = "serde")]
+mod serialize;
+mod set;
+mod shift_or;
+mod simplify;
+mod split;
+mod stream;
+pub mod tokens;
+mod unicode;
+#[cfg(feature = "unicode")]
+mod unicode_tables;
+mod unparse;
+mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use crate::ast::Ast;
+pub use crate::builder::RegexBuilder;
+pub use crate::cache::Cache;
+pub use crate::captures::{CaptureLocations, CaptureMatches, Captures};
+pub use crate::complexity::{ComplexityReport, Finding, FindingKind, Risk};
+pub use crate::error::{CompileError, DecodeError, Error, LimitKind, ParseError, ParseErrorKind};
+pub use crate::evaluator::{Debugger, Step, StepResult};
+pub use crate::generate::Generator;
+pub use crate::input::{Input, Symbol};
+#[cfg(feature = "std")]
+pub use crate::lines::MatchingLines;
+pub use crate::matches::{Match, Matches, OverlappingMatches};
+#[cfg(feature = "mmap")]
+pub use crate::mmap::FileMatches;
+pub use crate::offsets::{OffsetUnit, Offsets};
+pub use crate::parser::escape;
+#[cfg(feature = "pattern")]
+pub use crate::pattern::RegexSearcher;
+pub use crate::regex::Regex;
+pub use crate::replace::{NoExpand, Replacer};
+pub use crate::set::{RegexSet, SetMatches};
+pub use crate::split::{Split, SplitN};
+pub use crate::stream::{PushMatches, PushSearcher, StreamMatch};
+#[cfg(feature = "std")]
+pub use crate::stream::StreamSearcher;
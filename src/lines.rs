@@ -0,0 +1,87 @@
+//! BufRead から1行ずつ読み込み、パターンにマッチする行を返すイテレータ
+
+use std::io::{self, BufRead};
+
+use alloc::string::String;
+
+use crate::regex::Regex;
+
+/// reader から1行ずつ読み込み、パターンにマッチする部分を含む行を (行番号, 行) の組で返すイテレータ  
+/// `Regex::matching_lines` で生成する  
+/// 行番号は1から数え、行の末尾の改行 (`\n`, `\r\n`) は取り除いてからマッチを試みる  
+/// 読み込みに失敗した場合や、行が UTF-8 として不正な場合はエラーを返して終了する
+///
+/// ```
+/// use small_regex::Regex;
+///
+/// let re = Regex::new("ERROR|WARN").unwrap();
+/// let log: &[u8] = b"INFO start\nWARN disk\nINFO ok\nERROR down\n";
+/// let lines: Vec<(usize, String)> = re.matching_lines(log).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(lines, vec![(2, "WARN disk".to_string()), (4, "ERROR down".to_string())]);
+/// ```
+#[derive(Debug)]
+pub struct MatchingLines<'r, R> {
+    regex: &'r Regex,
+    reader: R,
+    line: String,  // 読み込んだ行 (read_line で使い回す)
+    number: usize, // 最後に読み込んだ行の行番号
+    done: bool,    // 入力の終わりに達したか、エラーを返したかどうか
+}
+
+impl<'r, R: BufRead> MatchingLines<'r, R> {
+    pub(crate) fn new(regex: &'r Regex, reader: R) -> MatchingLines<'r, R> {
+        MatchingLines { regex, reader, line: String::new(), number: 0, done: false }
+    }
+}
+
+impl<'r, R: BufRead> Iterator for MatchingLines<'r, R> {
+    type Item = io::Result<(usize, String)>;
+
+    fn next(&mut self) -> Option<io::Result<(usize, String)>> {
+        while !self.done {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.number += 1;
+                    let line: &str = self.line.strip_suffix('\n').unwrap_or(&self.line);
+                    let line: &str = line.strip_suffix('\r').unwrap_or(line);
+                    if self.regex.is_match(line) {
+                        return Some(Ok((self.number, String::from(line))));
+                    }
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_matching_lines() {
+        let re: Regex = Regex::new("^a|b$").unwrap();
+        let text: &[u8] = b"ab\r\nxb\nbx\n\na";
+        let lines: Vec<(usize, String)> = re.matching_lines(text).map(Result::unwrap).collect();
+        assert_eq!(lines, vec![(1, "ab".into()), (2, "xb".into()), (5, "a".into())]);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let re: Regex = Regex::new("a").unwrap();
+        let text: &[u8] = b"a\n\xff\na\n";
+        let results: Vec<io::Result<(usize, String)>> = re.matching_lines(text).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,410 @@
+//! 命令列から、マッチの先頭に必ず現れるリテラル (接頭辞) を取り出し、入力の中でマッチが始まり得る位置を探すための関数  
+//! 接頭辞が無い場合も、マッチの先頭のバイトが数個に限られれば、そのバイトを memchr (SIMD で複数バイトずつ調べる) で探して読み飛ばす  
+//! 先頭のバイトが4個以上の場合や、文字の集合 (`\p{..}` など) で始まる場合は、256 ビットの表を引いて先頭になり得ないバイトを読み飛ばす  
+//! マッチの途中に必ず現れるリテラルがあれば InnerLiteral で候補の位置を絞り込む
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::compiler::{compile, reverse, Instruction};
+use crate::evaluator::{is_char_matched, search_slots, slots_len, EvalOptions};
+use crate::input::{is_raw_byte, Input, Symbol};
+use crate::parser::{Assertion, AST};
+
+/// 命令列のどのマッチも必ずこの文字列で始まる、という接頭辞を UTF-8 のバイト列で返す  
+/// 先頭の命令から Save と Jump だけを辿り、続けて現れる Char の文字をつなげる (無い場合は空を返す)  
+/// UTF-8 として不正なバイトを表す文字は、入力の中のバイト列が決まらないため、その手前までとする
+pub fn prefix<T: Symbol>(instructions: &[Instruction<T>]) -> Vec<u8> {
+    let mut prefix: Vec<u8> = Vec::new();
+    let mut buf: [u8; 4] = [0; 4];
+    let mut pc: usize = 0;
+    loop {
+        match &instructions[pc] {
+            Instruction::Save(_) => pc += 1,
+            Instruction::Jump(counter) if *counter > pc => pc = *counter,
+            Instruction::Char(c) => match c.as_char() {
+                Some(c) if !is_raw_byte(c) => {
+                    prefix.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    pc += 1;
+                }
+                _ => return prefix,
+            },
+            _ => return prefix,
+        }
+    }
+}
+
+/// 命令列のどのマッチも入力の先頭 (`\A`, 複数行モードでない `^`) から始まるかを判定する  
+/// 先頭の命令から Save と Jump だけを辿り、最初に StartText のアサーションに到達する場合に true を返す
+pub fn is_anchored_start<T>(instructions: &[Instruction<T>]) -> bool {
+    let mut pc: usize = 0;
+    loop {
+        match instructions.get(pc) {
+            Some(Instruction::Save(_)) => pc += 1,
+            Some(Instruction::Jump(counter)) if *counter > pc => pc = *counter,
+            Some(Instruction::Assert(Assertion::StartText)) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// input の from 以降で、prefix が現れる最初の位置を返す  
+/// prefix が空の場合や、input をバイト列として見られない場合は from を返す。prefix が現れない場合は None を返す
+pub fn find_prefix<T: Symbol, I: Input<T> + ?Sized>(input: &I, prefix: &[u8], from: usize) -> Option<usize> {
+    let haystack: &[u8] = match input.as_bytes() {
+        Some(bytes) if !prefix.is_empty() => bytes.get(from..)?,
+        _ => return Some(from),
+    };
+    let found: Option<usize> = match prefix {
+        [byte] => memchr::memchr(*byte, haystack),
+        _ => memchr::memmem::find(haystack, prefix),
+    };
+    found.map(|pos| from + pos)
+}
+
+/// 命令列のどのマッチも、先頭のバイトがこの集合のいずれかになる、というバイトの集合を返す  
+/// 先頭の命令から Save, Jump, Split を辿り、到達する Char, CharNoCase, Property の文字の先頭になり得るバイトを集める  
+/// ASCII 以外の文字の集合は、UTF-8 の先頭のバイトと UTF-8 として不正なバイトのすべて (0x80 以上) になり得るものとする  
+/// それ以外の命令 (`.`, アンカー, Match など) に到達する場合や、すべてのバイトを含む場合は None を返す
+pub fn first_byte_set<T: Symbol>(instructions: &[Instruction<T>]) -> Option<ByteSet> {
+    let mut set: ByteSet = ByteSet::default();
+    let mut visited: Vec<bool> = vec![false; instructions.len()];
+    let mut stack: Vec<usize> = vec![0];
+    let mut buf: [u8; 4] = [0; 4];
+    while let Some(pc) = stack.pop() {
+        if visited[pc] {
+            continue;
+        }
+        visited[pc] = true;
+        match &instructions[pc] {
+            Instruction::Save(_) => stack.push(pc + 1),
+            Instruction::Jump(counter) => stack.push(*counter),
+            Instruction::Split(counter1, counter2) => {
+                stack.push(*counter2);
+                stack.push(*counter1);
+            }
+            // UTF-8 として不正なバイトを表す文字は、そのバイトと、同じ文字の UTF-8 のバイト列 (先頭は 0xF4) の両方から始まり得る
+            Instruction::Char(c) => match c.as_char()? {
+                c if is_raw_byte(c) => {
+                    set.insert(c as u32 as u8);
+                    set.insert(0xf4);
+                }
+                c => set.insert(c.encode_utf8(&mut buf).as_bytes()[0]),
+            },
+            // ASCII の文字以外で、畳み込むと ASCII の文字になるのは KELVIN SIGN (U+212A) と LONG S (U+017F) だけ
+            Instruction::CharNoCase(c) => match c.as_char()? {
+                'k' => b"kK\xe2".iter().for_each(|byte| set.insert(*byte)),
+                's' => b"sS\xc5".iter().for_each(|byte| set.insert(*byte)),
+                c if c.is_ascii() => {
+                    set.insert(c.to_ascii_lowercase() as u8);
+                    set.insert(c.to_ascii_uppercase() as u8);
+                }
+                _ => set.insert_non_ascii(),
+            },
+            Instruction::Property(property, negated) => {
+                for byte in (0..0x80u8).filter(|byte| property.contains(*byte as char) != *negated) {
+                    set.insert(byte);
+                }
+                set.insert_non_ascii();
+            }
+            _ => return None,
+        }
+    }
+    if set.len() == 256 {
+        None
+    } else {
+        Some(set)
+    }
+}
+
+/// 256 個のバイトのそれぞれを含むかどうかを、256 ビットの表で表すバイトの集合
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ByteSet([u64; 4]);
+
+impl ByteSet {
+    /// byte を集合に加える
+    fn insert(&mut self, byte: u8) {
+        self.0[byte as usize / 64] |= 1 << (byte % 64);
+    }
+
+    /// 0x80 以上のすべてのバイトを集合に加える
+    fn insert_non_ascii(&mut self) {
+        self.0[2] = u64::MAX;
+        self.0[3] = u64::MAX;
+    }
+
+    /// byte が集合に含まれるかどうか
+    fn contains(&self, byte: u8) -> bool {
+        self.0[byte as usize / 64] >> (byte % 64) & 1 == 1
+    }
+
+    /// 集合に含まれるバイトの数
+    fn len(&self) -> usize {
+        self.0.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    /// 集合に含まれるバイトを小さい順に返す
+    fn bytes(&self) -> Vec<u8> {
+        (0..=u8::MAX).filter(|byte| self.contains(*byte)).collect()
+    }
+}
+
+/// 探索で、マッチが始まり得る位置まで開始位置を読み飛ばすための手がかり
+#[derive(Debug)]
+pub enum Prefilter {
+    None,                // 手がかりが無い (すべての位置から評価する)
+    Prefix(Vec<u8>),     // マッチの先頭に必ず現れるリテラル
+    FirstBytes(Vec<u8>), // マッチの先頭のバイトになり得るバイト (1〜3個)
+    ByteSet(ByteSet),    // マッチの先頭のバイトになり得るバイトの集合 (4個以上)
+}
+
+impl Prefilter {
+    /// 命令列から、接頭辞かマッチの先頭のバイトの集合を求める (接頭辞を優先する)  
+    /// 先頭のバイトが3個以下の場合は memchr で、4個以上の場合は表を引いて探す
+    pub fn new<T: Symbol>(instructions: &[Instruction<T>]) -> Prefilter {
+        let prefix: Vec<u8> = prefix(instructions);
+        if !prefix.is_empty() {
+            return Prefilter::Prefix(prefix);
+        }
+        match first_byte_set(instructions) {
+            None => Prefilter::None,
+            Some(set) if set.len() <= 3 => Prefilter::FirstBytes(set.bytes()),
+            Some(set) => Prefilter::ByteSet(set),
+        }
+    }
+
+    /// input の from 以降で、マッチが始まり得る最初の位置を返す  
+    /// 手がかりが無い場合や、input をバイト列として見られない場合は from を返す。マッチが始まり得る位置が無い場合は None を返す
+    pub fn find<T: Symbol, I: Input<T> + ?Sized>(&self, input: &I, from: usize) -> Option<usize> {
+        if let Prefilter::None = self {
+            return Some(from);
+        }
+        if let Prefilter::Prefix(prefix) = self {
+            return find_prefix(input, prefix, from);
+        }
+        let Some(haystack) = input.as_bytes() else {
+            return Some(from);
+        };
+        let haystack: &[u8] = haystack.get(from..)?;
+        let found: Option<usize> = match self {
+            Prefilter::FirstBytes(bytes) => match **bytes {
+                [b1] => memchr::memchr(b1, haystack),
+                [b1, b2] => memchr::memchr2(b1, b2, haystack),
+                [b1, b2, b3] => memchr::memchr3(b1, b2, b3, haystack),
+                _ => Some(0),
+            },
+            Prefilter::ByteSet(set) => haystack.iter().position(|byte| set.contains(*byte)),
+            _ => Some(0),
+        };
+        found.map(|pos| from + pos)
+    }
+}
+
+/// マッチの途中に必ず現れるリテラル (`\p{L}+@\p{L}+` の `@` など) を手がかりに、マッチが始まり得る位置を探す型  
+/// パターンの先頭の連結を、リテラルより前の部分 P、リテラル L、残りに分け、P を逆順にした命令列を持つ  
+/// P が L の先頭の文字にマッチしない場合に限り、L の最初の出現の直前で終わる P のマッチから、最も前の開始位置が求まる
+#[derive(Debug)]
+pub struct InnerLiteral {
+    literal: Vec<u8>,          // L (UTF-8 のバイト列)
+    first_len: usize,          // L の先頭の文字のバイト数
+    reverse: Vec<Instruction>, // P を逆順にした命令列
+}
+
+impl InnerLiteral {
+    /// パターンの AST から、マッチの途中に必ず現れるリテラルを探す  
+    /// 先頭の連結の2番目以降に文字が続く部分が無い場合や、P がアンカー、アトミックグループ、条件分岐、L の先頭の文字にマッチする命令を含む場合は None を返す
+    pub fn new(ast: &AST) -> Option<InnerLiteral> {
+        let AST::Seq(asts) = ast else {
+            return None;
+        };
+        let (i, len) = longest_chars(asts)?;
+        let mut literal: Vec<u8> = Vec::new();
+        let mut buf: [u8; 4] = [0; 4];
+        for ast in asts[i..i + len].iter() {
+            if let AST::Char(c) = ast {
+                literal.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+        let AST::Char(first) = asts[i] else {
+            return None;
+        };
+        let reversed: Vec<AST> = asts[..i].iter().rev().map(reverse).collect::<Option<_>>()?;
+        let reverse: Vec<Instruction> = compile(&AST::Seq(reversed));
+        let has_assert: bool = reverse.iter().any(|inst| matches!(inst, Instruction::Assert(_)));
+        if has_assert || reverse.iter().any(|inst| is_char_matched(inst, &first)) {
+            return None;
+        }
+        Some(InnerLiteral { literal, first_len: first.len_utf8(), reverse })
+    }
+
+    /// text の from 以降で、マッチが始まり得る最初の位置を返す  
+    /// その位置より前 (from 以降) から始まるマッチは無い。リテラルが現れない場合などマッチが無いことが確定した場合は None を返す
+    pub fn candidate(&self, text: &str, from: usize) -> Option<usize> {
+        let mut start: usize = from;
+        let mut slots: Vec<Option<usize>> = vec![None; slots_len(&self.reverse)];
+        let options: EvalOptions = EvalOptions { anchored: true, longest: true, ..EvalOptions::default() };
+        loop {
+            let found: usize = start + memchr::memmem::find(text.as_bytes().get(start..)?, &self.literal)?;
+            // P は L の先頭の文字にマッチしないため、found より前から始まるマッチは、この出現の直前で P が終わる
+            let reversed: Reversed = Reversed { text, start, end: found };
+            if search_slots(&self.reverse, &reversed, 0, &mut slots, options) {
+                return Some(found - slots[1]?);
+            }
+            start = found + self.first_len;
+        }
+    }
+}
+
+/// asts の2番目以降で、文字 (AST::Char) が最も長く続く部分の (開始位置, 長さ) を返す
+fn longest_chars(asts: &[AST]) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize)> = None;
+    let mut i: usize = 1;
+    while i < asts.len() {
+        let len: usize = asts[i..].iter().take_while(|ast| matches!(ast, AST::Char(_))).count();
+        if len > 0 && best.is_none_or(|(_, best_len)| len > best_len) {
+            best = Some((i, len));
+        }
+        i += len.max(1);
+    }
+    best
+}
+
+/// text の start..end の範囲を、end から逆に読む入力 (位置は end から数えたバイト数)  
+/// アンカーを含まない命令列の評価にのみ使うため、範囲の外の文字は読まない
+struct Reversed<'t> {
+    text: &'t str,
+    start: usize,
+    end: usize,
+}
+
+impl Input for Reversed<'_> {
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    fn char_at(&self, pos: usize) -> Option<(char, usize)> {
+        let c: char = self.text[self.start..self.end.checked_sub(pos)?].chars().next_back()?;
+        Some((c, pos + c.len_utf8()))
+    }
+
+    fn char_before(&self, pos: usize) -> Option<char> {
+        self.text[self.end - pos..self.end].chars().next()
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{find_prefix, first_byte_set, is_anchored_start, prefix, ByteSet, InnerLiteral, Prefilter};
+    use crate::compiler::{compile, Instruction};
+    use crate::parser::parse;
+    use crate::unicode::simple_fold;
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(prefix(&compile(&parse("abc(d|e)*").unwrap())), b"abc");
+        assert_eq!(prefix(&compile(&parse("(αβ)+").unwrap())), "αβ".as_bytes());
+        assert_eq!(prefix(&compile(&parse("ab?").unwrap())), b"a");
+        assert!(prefix(&compile(&parse("a|b").unwrap())).is_empty());
+        assert!(prefix(&compile(&parse("^ab").unwrap())).is_empty());
+        assert!(prefix(&compile(&parse("(?i)ab").unwrap())).is_empty());
+        assert_eq!(prefix(&compile(&parse("a\u{10ff80}").unwrap())), b"a");
+    }
+
+    #[test]
+    fn test_is_anchored_start() {
+        assert!(is_anchored_start(&compile(&parse("^ab").unwrap())));
+        // 選択の各分岐がアンカーで始まる場合は判定しない
+        assert!(!is_anchored_start(&compile(&parse(r"\Aa|^b").unwrap())));
+        assert!(is_anchored_start(&compile(&parse(r"(^a)+").unwrap())));
+        assert!(!is_anchored_start(&compile(&parse("(?m)^ab").unwrap())));
+        assert!(!is_anchored_start(&compile(&parse("a^").unwrap())));
+    }
+
+    #[test]
+    fn test_find_prefix() {
+        assert_eq!(find_prefix("xxabcab", b"ab".as_slice(), 0), Some(2));
+        assert_eq!(find_prefix("xxabcab", b"ab".as_slice(), 3), Some(5));
+        assert_eq!(find_prefix("xxabcab", b"c".as_slice(), 5), None);
+        assert_eq!(find_prefix("xx", b"".as_slice(), 1), Some(1));
+        assert_eq!(find_prefix(b"\xffab".as_slice(), b"ab".as_slice(), 0), Some(1));
+    }
+
+    #[test]
+    fn test_first_byte_set() {
+        let bytes = |pattern: &str| -> Option<Vec<u8>> { first_byte_set(&compile(&parse(pattern).unwrap())).map(|set| set.bytes()) };
+        assert_eq!(bytes("ab|cd|ef").unwrap(), b"ace");
+        assert_eq!(bytes("(a|b)*c").unwrap(), b"abc");
+        assert_eq!(bytes("(?i)xy").unwrap(), b"Xx");
+        assert_eq!(bytes("(?i)k").unwrap(), b"Kk\xe2");
+        assert_eq!(bytes("(?i)s").unwrap(), b"Ss\xc5");
+        assert_eq!(bytes("α|β").unwrap(), [0xce]);
+        assert_eq!(bytes("a|b|c|d").unwrap(), b"abcd");
+        assert_eq!(bytes("a|\u{10ffff}").unwrap(), b"a\xf4\xff");
+        assert_eq!(bytes("a").unwrap(), b"a");
+        assert!(bytes("a|.").is_none());
+        assert!(bytes("a*").is_none());
+        assert!(bytes("(?m)^a|b").is_none());
+
+        // 文字の集合は、含まれる ASCII の文字と、0x80 以上のすべてのバイトになり得る
+        #[cfg(feature = "unicode")]
+        {
+            let digits: Vec<u8> = bytes("\\p{Nd}").unwrap();
+            assert_eq!(&digits[..10], b"0123456789");
+            assert_eq!(digits.len(), 10 + 128);
+            assert!(bytes("\\P{Nd}").unwrap().iter().all(|byte| !byte.is_ascii_digit()));
+        }
+
+        // 大文字・小文字を区別しない ASCII の文字は、小文字にするとその文字になるすべての文字の先頭のバイトを含む
+        for c in ('a'..='z').chain('0'..='9') {
+            let set: ByteSet = first_byte_set::<char>(&[Instruction::CharNoCase(c), Instruction::Match]).unwrap();
+            for other in (0..=0x10ffff).filter_map(char::from_u32).filter(|other| simple_fold(*other) == c) {
+                assert!(set.contains(other.encode_utf8(&mut [0; 4]).as_bytes()[0]), "{} / {}", c, other);
+            }
+        }
+    }
+
+    #[test]
+    fn test_prefilter() {
+        let prefilter: Prefilter = Prefilter::new(&compile(&parse("x|(?i)y").unwrap()));
+        assert_eq!(prefilter.find("aaYax", 0), Some(2));
+        assert_eq!(prefilter.find("aaYax", 3), Some(4));
+        assert_eq!(prefilter.find("aaYa", 3), None);
+        assert_eq!(prefilter.find("aaYa", 5), None);
+        assert_eq!(prefilter.find(['a', 'b'].as_slice(), 1), Some(1));
+        assert!(matches!(Prefilter::new(&compile(&parse("ab(c|d)").unwrap())), Prefilter::Prefix(_)));
+        assert!(matches!(Prefilter::new(&compile(&parse(".a").unwrap())), Prefilter::None));
+
+        // 先頭のバイトが4個以上の場合は、表を引いて読み飛ばす
+        let prefilter: Prefilter = Prefilter::new(&compile(&parse("(a|b|c|d)x").unwrap()));
+        assert!(matches!(prefilter, Prefilter::ByteSet(_)));
+        assert_eq!(prefilter.find("xyzdx", 0), Some(3));
+        assert_eq!(prefilter.find("xyzdx", 4), None);
+        assert_eq!(prefilter.find(b"\xffc".as_slice(), 0), Some(1));
+        #[cfg(feature = "unicode")]
+        {
+            let prefilter: Prefilter = Prefilter::new(&compile(&parse("\\p{Nd}+").unwrap()));
+            assert_eq!(prefilter.find("abc 42", 0), Some(4));
+            assert_eq!(prefilter.find("abc ٤٢", 0), Some(4));
+            assert_eq!(prefilter.find("abc", 0), None);
+        }
+    }
+
+    #[test]
+    fn test_inner_literal() {
+        let inner: InnerLiteral = InnerLiteral::new(&parse("(a|b)+@(a|b)+").unwrap()).unwrap();
+        assert_eq!(inner.candidate("xx ab@ba", 0), Some(3));
+        assert_eq!(inner.candidate("xx ab@ba", 4), Some(4));
+        // 直前に P がマッチしない出現は読み飛ばす
+        assert_eq!(inner.candidate("x@ @ b@a", 0), Some(5));
+        assert_eq!(inner.candidate("ab ba", 0), None);
+
+        // P がリテラルの先頭の文字にマッチし得る場合や、先頭がリテラルの場合は使わない
+        assert!(InnerLiteral::new(&parse(".+@a").unwrap()).is_none());
+        assert!(InnerLiteral::new(&parse("ab+").unwrap()).is_none());
+        assert!(InnerLiteral::new(&parse("^a+@").unwrap()).is_none());
+    }
+}
@@ -1,20 +1,718 @@
-mod parser;
-mod compiler;
-mod evaluator;
+//! grep のように、パターンにマッチする行を表示するコマンド  
+//! `small-regex [-i] [-v] [-c] [-n] [-r] <pattern> [files...]` の形で使い、ファイルを指定しない場合 (または `-` の場合) は標準入力から読む  
+//! `-i` は大文字・小文字を区別せず、`-v` はマッチしない行を選び、`-c` は選んだ行の数を、`-n` は行番号を付けて表示する  
+//! `-r` はディレクトリの中のファイルを再帰的に探索する (隠しファイルと、`.gitignore` の規則に当てはまるファイルは飛ばす)  
+//! 複数のファイルは複数のスレッドで探索し、結果は常にファイルの順に表示する (順が来たファイルの結果は、探索しながら表示する)  
+//! `--color=auto|always|never` で、マッチした部分を ANSI のエスケープシーケンスで色付けする (auto は出力が端末の場合のみ)  
+//! 終了コードは、マッチする行があれば 0、無ければ 1、パターンが不正な場合や読み書きに失敗した場合は 2 とする
 
-use parser::parse;
-use compiler::compile;
-use evaluator::evaluate;
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
-fn main() {
-    println!("{}", pattern_match("ab*(de|fg)", "abbbfg")); // true
-    println!("{}", pattern_match("a?b(d*e|fg)", "bdde"));  // true
-    println!("{}", pattern_match("a?b(d*e|fg)", "cbfg"));  // false
+use small_regex::bytes::Regex;
+use small_regex::RegexBuilder;
+
+const USAGE: &str = "usage: small-regex [-i] [-v] [-c] [-n] [-r] [--color=auto|always|never] <pattern> [files...]";
+
+/// マッチした部分、ファイル名、行番号の色と、色を戻すエスケープシーケンス
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const NAME_COLOR: &str = "\x1b[35m";
+const NUMBER_COLOR: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// スレッドで探索している入力の表示する内容を、まとめて送る大きさ (バイト)
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// コマンドライン引数
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    pattern: String,
+    paths: Vec<String>,
+    case_insensitive: bool, // -i : 大文字・小文字を区別しない (RegexBuilder::case_insensitive)
+    recursive: bool,        // -r : ディレクトリの中のファイルを再帰的に探索する
+    color: ColorChoice,     // --color : 色付けするかどうか
+    options: Options,
+}
+
+/// `--color` の指定
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum ColorChoice {
+    #[default]
+    Auto, // 標準出力が端末の場合 (TERM が dumb でない場合) のみ色付けする
+    Always,
+    Never,
+}
+
+/// 選んだ行の表示の設定
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Options {
+    invert: bool,      // -v : マッチしない行を選ぶ
+    count: bool,       // -c : 行の代わりに、選んだ行の数を表示する
+    line_number: bool, // -n : 行の前に行番号を付ける
+    color: bool,       // マッチした部分、ファイル名、行番号を色付けする
+}
+
+/// 探索する入力
+#[derive(Debug, PartialEq)]
+struct Input {
+    path: Option<PathBuf>, // ファイルのパス (None は標準入力)
+    name: String,          // 行の前に付けて表示する名前
+}
+
+/// `.gitignore` の1行の規則
+#[derive(Debug, PartialEq)]
+struct Rule {
+    base: PathBuf,   // `.gitignore` のあるディレクトリ
+    glob: Vec<char>, // パスまたはファイル名に当てはめるパターン (`*`, `**`, `?` を使える)
+    negated: bool,   // `!` で始まり、飛ばさないファイルを指定する規則
+    dir_only: bool,  // `/` で終わり、ディレクトリにだけ当てはまる規則
+    anchored: bool,  // `/` を含み、base からのパスに当てはめる規則 (含まない場合はファイル名に当てはめる)
+}
+
+/// スレッドから送る、探索している入力の表示する内容の一部、または探索の結果 (マッチした行があったかどうか)
+#[derive(Debug)]
+enum Message {
+    Output(Vec<u8>),
+    Done(Result<bool, Failure>),
+}
+
+/// 書いた内容を CHUNK_SIZE ごとにまとめ、入力の番号を付けて送る Write
+struct ChunkWriter {
+    index: usize,
+    buffer: Vec<u8>,
+    sender: mpsc::Sender<(usize, Message)>,
+}
+
+/// 探索が失敗した理由
+#[derive(Debug)]
+enum Failure {
+    Read(io::Error),  // 入力の読み込みに失敗した
+    Write(io::Error), // 出力に失敗した (パイプの先が閉じられた場合など)
+}
+
+fn main() -> ExitCode {
+    let args: Args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("small-regex: {message}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    // 不正な UTF-8 を含む行も、不正なバイトを1バイトの文字として探索する
+    let re: Regex = match RegexBuilder::new(&args.pattern).case_insensitive(args.case_insensitive).build_bytes() {
+        Ok(re) => re,
+        Err(e) => {
+            eprintln!("small-regex: {e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let stdout: io::Stdout = io::stdout();
+    let mut options: Options = args.options;
+    options.color = match args.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => stdout.is_terminal() && env::var_os("TERM").is_some_and(|term| term != "dumb"),
+    };
+    let mut out: BufWriter<io::StdoutLock> = BufWriter::new(stdout.lock());
+    let mut matched: bool = false;
+    let mut failed: bool = false;
+    let (inputs, walked): (Vec<Input>, bool) = collect_inputs(&args.paths, args.recursive, &mut failed);
+    // 複数のファイルを探索する場合は、行の前にファイル名を付ける
+    let with_name: bool = inputs.len() > 1 || walked;
+    // 入力ごとの結果を報告し、残りの入力を探索するかどうかを返す
+    let mut report = |input: &Input, result: Result<bool, Failure>| -> bool {
+        match result {
+            Ok(found) => matched |= found,
+            Err(Failure::Read(e)) => {
+                eprintln!("small-regex: {}: {e}", input.name);
+                failed = true;
+            }
+            // 出力に失敗した場合は、残りの入力を探索せずにエラーで終了する (パイプの先が閉じられた場合は、メッセージを表示しない)
+            Err(Failure::Write(e)) => {
+                if e.kind() != io::ErrorKind::BrokenPipe {
+                    eprintln!("small-regex: {e}");
+                }
+                failed = true;
+                return false;
+            }
+        }
+        true
+    };
+    if inputs.len() > 1 {
+        search_parallel(&re, &inputs, with_name, options, &mut out, report);
+    } else {
+        // 入力が1つの場合は、標準入力などを読みながら表示できるよう、スレッドを使わずに直接書く
+        for input in &inputs {
+            let name: Option<&str> = with_name.then_some(input.name.as_str());
+            if !report(input, search_input(&re, input, name, options, &mut out)) {
+                break;
+            }
+        }
+    }
+    if let Err(e) = out.flush() {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("small-regex: {e}");
+        }
+        failed = true;
+    }
+
+    match (failed, matched) {
+        (true, _) => ExitCode::from(2),
+        (false, true) => ExitCode::SUCCESS,
+        (false, false) => ExitCode::from(1),
+    }
+}
+
+/// コマンドライン引数を読む (パターンより前の `-` で始まる引数をフラグとし、`-in` のようにまとめて書ける)  
+/// `--color` は `--color=always` のように値を指定し、値を省略した場合は auto とする  
+/// `--` より後の引数と、`-` だけの引数はフラグとして扱わない。不正な場合はエラーの説明を返す
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed: Args = Args::default();
+    let mut args = args.into_iter();
+    let mut pattern: Option<String> = None;
+    for arg in args.by_ref() {
+        if arg == "--" {
+            break;
+        }
+        if let Some(option) = arg.strip_prefix("--") {
+            parsed.color = match option {
+                "color" | "color=auto" => ColorChoice::Auto,
+                "color=always" => ColorChoice::Always,
+                "color=never" => ColorChoice::Never,
+                _ => return Err(format!("unknown option {arg}")),
+            };
+            continue;
+        }
+        let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            pattern = Some(arg);
+            break;
+        };
+        for flag in flags.chars() {
+            match flag {
+                'i' => parsed.case_insensitive = true,
+                'v' => parsed.options.invert = true,
+                'c' => parsed.options.count = true,
+                'n' => parsed.options.line_number = true,
+                'r' => parsed.recursive = true,
+                _ => return Err(format!("unknown option -{flag}")),
+            }
+        }
+    }
+    parsed.pattern = match pattern.or_else(|| args.next()) {
+        Some(pattern) => pattern,
+        None => return Err("no pattern given".to_string()),
+    };
+    parsed.paths = args.collect();
+    Ok(parsed)
+}
+
+/// コマンドライン引数のパスから、探索する入力を順に集める (`-` は標準入力とする)  
+/// recursive の場合、ディレクトリは中のファイルに展開し、パスを指定しなければ現在のディレクトリを探索する  
+/// ディレクトリを展開したかどうかも返す。ディレクトリを読めなかった場合は、エラーを表示して failed にする
+fn collect_inputs(paths: &[String], recursive: bool, failed: &mut bool) -> (Vec<Input>, bool) {
+    let stdin = || Input { path: None, name: "(standard input)".to_string() };
+    let mut inputs: Vec<Input> = Vec::new();
+    let mut walked: bool = false;
+    if paths.is_empty() {
+        if recursive {
+            walk(Path::new("."), &mut Vec::new(), &mut inputs, failed);
+            walked = true;
+        } else {
+            inputs.push(stdin());
+        }
+    }
+    for path in paths {
+        if path == "-" {
+            inputs.push(stdin());
+        } else if recursive && Path::new(path).is_dir() {
+            walk(Path::new(path), &mut Vec::new(), &mut inputs, failed);
+            walked = true;
+        } else {
+            inputs.push(Input { path: Some(PathBuf::from(path)), name: path.clone() });
+        }
+    }
+    (inputs, walked)
+}
+
+/// ディレクトリの中のファイルを、名前の順に再帰的に inputs に追加する  
+/// 隠しファイル・隠しディレクトリ (`.` で始まる名前) とシンボリックリンク、rules と `.gitignore` の規則に当てはまるものは飛ばす  
+/// `.gitignore` は、dir とその中のディレクトリにあるものを読む (dir より上のディレクトリのものは読まない)
+fn walk(dir: &Path, rules: &mut Vec<Rule>, inputs: &mut Vec<Input>, failed: &mut bool) {
+    let entries: io::Result<Vec<fs::DirEntry>> = fs::read_dir(dir).and_then(|entries| entries.collect());
+    let mut entries: Vec<fs::DirEntry> = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("small-regex: {}: {e}", dir.display());
+            *failed = true;
+            return;
+        }
+    };
+    entries.sort_by_key(|entry| entry.file_name());
+    let outer: usize = rules.len();
+    if let Ok(text) = fs::read_to_string(dir.join(".gitignore")) {
+        rules.extend(text.lines().filter_map(|line| parse_rule(dir, line)));
+    }
+    for entry in entries {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path: PathBuf = entry.path();
+        if file_type.is_symlink() || is_ignored(rules, &path, file_type.is_dir()) {
+            continue;
+        }
+        if file_type.is_dir() {
+            walk(&path, rules, inputs, failed);
+        } else {
+            // 現在のディレクトリを探索する場合は、先頭の `./` を付けずに表示する
+            let name: String = path.strip_prefix(".").unwrap_or(&path).display().to_string();
+            inputs.push(Input { path: Some(path), name });
+        }
+    }
+    rules.truncate(outer);
+}
+
+/// `.gitignore` の1行を規則にする (空行と `#` で始まるコメントは None を返す)
+fn parse_rule(base: &Path, line: &str) -> Option<Rule> {
+    let line: &str = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, line): (bool, &str) = match line.strip_prefix('!') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (dir_only, line): (bool, &str) = match line.strip_suffix('/') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let anchored: bool = line.contains('/');
+    let glob: Vec<char> = line.strip_prefix('/').unwrap_or(line).chars().collect();
+    Some(Rule { base: base.to_path_buf(), glob, negated, dir_only, anchored })
+}
+
+/// path が規則によって飛ばすファイル・ディレクトリかどうか (後の規則ほど優先する)
+fn is_ignored(rules: &[Rule], path: &Path, is_dir: bool) -> bool {
+    for rule in rules.iter().rev() {
+        if rule.dir_only && !is_dir {
+            continue;
+        }
+        let target: Option<&Path> =
+            if rule.anchored { path.strip_prefix(&rule.base).ok() } else { path.file_name().map(Path::new) };
+        let Some(target) = target else {
+            continue;
+        };
+        let text: Vec<char> = target.to_string_lossy().replace('\\', "/").chars().collect();
+        if glob_match(&rule.glob, &text) {
+            return !rule.negated;
+        }
+    }
+    false
+}
+
+/// `.gitignore` のパターンが text 全体に当てはまるかどうか  
+/// `*` は `/` 以外の0文字以上、`?` は `/` 以外の1文字、`**` は `/` を含む0文字以上に当てはまり、`\` は次の文字をそのまま使う
+fn glob_match(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        ['*', '*'] => true,
+        ['*', '*', rest @ ..] => {
+            // `**/` は0個以上のディレクトリに当てはまる
+            let rest: &[char] = rest.strip_prefix(&['/']).unwrap_or(rest);
+            (0..=text.len()).any(|i| (i == 0 || text[i - 1] == '/') && glob_match(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => {
+            let len: usize = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+            (0..=len).any(|i| glob_match(rest, &text[i..]))
+        }
+        ['?', rest @ ..] => text.first().is_some_and(|c| *c != '/') && glob_match(rest, &text[1..]),
+        ['\\', c, rest @ ..] | [c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
 }
 
-fn pattern_match(pattern: &str, line: &str) -> bool {
-    let ast = parse(pattern);
-    let instructions = compile(&ast);
-    let chars: Vec<char> = line.chars().collect();
-    evaluate(&instructions, &chars, 0, 0)
+/// 入力を開き、search で探索する
+fn search_input<W: Write>(
+    re: &Regex,
+    input: &Input,
+    name: Option<&str>,
+    options: Options,
+    out: &mut W,
+) -> Result<bool, Failure> {
+    match &input.path {
+        None => search(re, io::stdin().lock(), name, options, out),
+        Some(path) => {
+            let file: File = File::open(path).map_err(Failure::Read)?;
+            search(re, BufReader::new(file), name, options, out)
+        }
+    }
+}
+
+/// 複数の入力を複数のスレッドで探索し、結果を入力の順に out に書く  
+/// 各スレッドは、まだ探索していない入力を1つずつ取って探索し、表示する内容を CHUNK_SIZE ごとに送る  
+/// 順が来た入力 (それより前の入力をすべて書き終えた入力) の内容は受け取るたびに書き、後の入力の内容は順が来るまでためておく  
+/// 入力ごとに report を呼び、report が false を返した場合は残りの入力を探索しない
+fn search_parallel<W: Write>(
+    re: &Regex,
+    inputs: &[Input],
+    with_name: bool,
+    options: Options,
+    out: &mut W,
+    mut report: impl FnMut(&Input, Result<bool, Failure>) -> bool,
+) {
+    let workers: usize = thread::available_parallelism().map_or(1, |n| n.get()).min(inputs.len());
+    let next: AtomicUsize = AtomicUsize::new(0); // 次に探索する入力の番号
+    let (sender, receiver) = mpsc::channel::<(usize, Message)>();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender: mpsc::Sender<(usize, Message)> = sender.clone();
+            let next: &AtomicUsize = &next;
+            scope.spawn(move || loop {
+                let index: usize = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(index) else {
+                    break;
+                };
+                let mut writer: ChunkWriter = ChunkWriter { index, buffer: Vec::new(), sender: sender.clone() };
+                let name: Option<&str> = with_name.then_some(input.name.as_str());
+                let result: Result<bool, Failure> = search_input(re, input, name, options, &mut writer)
+                    .and_then(|found| writer.flush().map(|_| found).map_err(Failure::Write));
+                if sender.send((index, Message::Done(result))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending: BTreeMap<usize, VecDeque<Message>> = BTreeMap::new();
+        let mut current: usize = 0; // 順が来た入力の番号
+        for (index, message) in receiver.iter() {
+            pending.entry(index).or_default().push_back(message);
+            while let Some(message) = pending.get_mut(&current).and_then(|messages| messages.pop_front()) {
+                let result: Result<bool, Failure> = match message {
+                    Message::Output(chunk) => match out.write_all(&chunk) {
+                        Ok(()) => continue,
+                        Err(e) => Err(Failure::Write(e)),
+                    },
+                    Message::Done(result) => result,
+                };
+                pending.remove(&current);
+                if !report(&inputs[current], result) {
+                    next.store(inputs.len(), Ordering::Relaxed);
+                    return;
+                }
+                current += 1;
+            }
+        }
+    });
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    /// ためた内容を送る (受け取る側が探索をやめた場合は、BrokenPipe のエラーとする)
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            let chunk: Vec<u8> = mem::take(&mut self.buffer);
+            self.sender.send((self.index, Message::Output(chunk))).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        }
+        Ok(())
+    }
+}
+
+/// reader から1行ずつ読み込み、options に従って選んだ行を out に書く (name があれば `name:` を前に付ける)  
+/// 行の末尾の改行 (`\n`, `\r\n`) は取り除いてからマッチを試みる。選んだ行があったかどうかを返す
+fn search<R: BufRead, W: Write>(
+    re: &Regex,
+    mut reader: R,
+    name: Option<&str>,
+    options: Options,
+    out: &mut W,
+) -> Result<bool, Failure> {
+    let mut line: Vec<u8> = Vec::new();
+    let mut number: usize = 0;
+    let mut count: usize = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).map_err(Failure::Read)? == 0 {
+            break;
+        }
+        number += 1;
+        let text: &[u8] = line.strip_suffix(b"\n").unwrap_or(&line);
+        let text: &[u8] = text.strip_suffix(b"\r").unwrap_or(text);
+        // 色付けする場合は、マッチした部分の位置を求める (マッチしない行を選ぶ場合は色付けしない)
+        let spans: Vec<Range<usize>> = if options.color && !options.invert {
+            re.find_iter(text).map(|m| m.range()).collect()
+        } else {
+            Vec::new()
+        };
+        let is_match: bool = if options.color && !options.invert { !spans.is_empty() } else { re.is_match(text) };
+        if is_match == options.invert {
+            continue;
+        }
+        count += 1;
+        if options.count {
+            continue;
+        }
+        let number: Option<usize> = options.line_number.then_some(number);
+        write_line(out, name, number, text, &spans, options.color).map_err(Failure::Write)?;
+    }
+    if options.count {
+        write_prefix(out, name, None, options.color).and_then(|_| writeln!(out, "{count}")).map_err(Failure::Write)?;
+    }
+    Ok(count > 0)
+}
+
+/// 選んだ行を、ファイル名と行番号を前に付けて書く (color の場合は、spans の範囲を色付けする)
+fn write_line<W: Write>(
+    out: &mut W,
+    name: Option<&str>,
+    number: Option<usize>,
+    text: &[u8],
+    spans: &[Range<usize>],
+    color: bool,
+) -> io::Result<()> {
+    write_prefix(out, name, number, color)?;
+    let mut last: usize = 0;
+    // 空のマッチは色付けしない
+    for span in spans.iter().filter(|span| !span.is_empty()) {
+        out.write_all(&text[last..span.start])?;
+        write!(out, "{MATCH_COLOR}")?;
+        out.write_all(&text[span.clone()])?;
+        write!(out, "{RESET}")?;
+        last = span.end;
+    }
+    out.write_all(&text[last..])?;
+    out.write_all(b"\n")
+}
+
+/// 行の前に付ける `name:` と `number:` を書く (color の場合は、名前と行番号を色付けする)
+fn write_prefix<W: Write>(out: &mut W, name: Option<&str>, number: Option<usize>, color: bool) -> io::Result<()> {
+    let (name_color, number_color, reset): (&str, &str, &str) =
+        if color { (NAME_COLOR, NUMBER_COLOR, RESET) } else { ("", "", "") };
+    if let Some(name) = name {
+        write!(out, "{name_color}{name}{reset}:")?;
+    }
+    if let Some(number) = number {
+        write!(out, "{number_color}{number}{reset}:")?;
+    }
+    Ok(())
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use small_regex::bytes::Regex;
+    use small_regex::RegexBuilder;
+
+    use std::fs;
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+
+    use super::{
+        collect_inputs, glob_match, is_ignored, parse_args, parse_rule, search, search_parallel, Args, Failure, Input,
+        Options, ColorChoice, Rule,
+    };
+
+    fn grep(pattern: &str, input: &[u8], name: Option<&str>, options: Options) -> (bool, Vec<u8>) {
+        let re: Regex = RegexBuilder::new(pattern).build_bytes().unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        let matched: bool = search(&re, input, name, options, &mut out).unwrap();
+        (matched, out)
+    }
+
+    fn args(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_search() {
+        let none: Options = Options::default();
+        assert_eq!(grep("b+$", b"abb\r\nbc\nb", None, none), (true, b"abb\nb\n".to_vec()));
+        assert_eq!(grep("x", b"abc\n", None, none), (false, Vec::new()));
+        assert_eq!(grep("^a", b"ab\nba\n", Some("f.txt"), none), (true, b"f.txt:ab\n".to_vec()));
+        // 不正な UTF-8 を含む行もそのまま書く
+        assert_eq!(grep("a.c", b"a\xffc\n", None, none), (true, b"a\xffc\n".to_vec()));
+        assert_eq!(grep("", b"", None, none), (false, Vec::new()));
+    }
+
+    #[test]
+    fn test_search_options() {
+        let input: &[u8] = b"ab\nc\nb\n";
+        let invert: Options = Options { invert: true, ..Options::default() };
+        assert_eq!(grep("b", input, None, invert), (true, b"c\n".to_vec()));
+        assert_eq!(grep(".", b"a\n", None, invert), (false, Vec::new()));
+        let count: Options = Options { count: true, ..Options::default() };
+        assert_eq!(grep("b", input, Some("f"), count), (true, b"f:2\n".to_vec()));
+        assert_eq!(grep("x", input, None, count), (false, b"0\n".to_vec()));
+        let number: Options = Options { line_number: true, invert: true, ..Options::default() };
+        assert_eq!(grep("c", input, Some("f"), number), (true, b"f:1:ab\nf:3:b\n".to_vec()));
+    }
+
+    #[test]
+    fn test_color() {
+        let color: Options = Options { color: true, line_number: true, ..Options::default() };
+        let expect: &[u8] = concat!(
+            "\x1b[35mf\x1b[0m:\x1b[32m1\x1b[0m:a\x1b[1;31mbb\x1b[0mc\x1b[1;31mb\x1b[0m\n",
+            // 空のマッチだけの行は、色付けせずに書く
+            "\x1b[35mf\x1b[0m:\x1b[32m2\x1b[0m:zz\n",
+        )
+        .as_bytes();
+        assert_eq!(grep("b+|x*", b"abbcb\nzz\n", Some("f"), color), (true, expect.to_vec()));
+        // マッチしない行を選ぶ場合は、行の中を色付けしない
+        let invert: Options = Options { color: true, invert: true, ..Options::default() };
+        assert_eq!(grep("b", b"abc\nxyz\n", None, invert), (true, b"xyz\n".to_vec()));
+        let count: Options = Options { color: true, count: true, ..Options::default() };
+        assert_eq!(grep("b", b"abc\n", Some("f"), count), (true, b"\x1b[35mf\x1b[0m:1\n".to_vec()));
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed: Args = args(&["-in", "-v", "a", "x.txt", "-c"]).unwrap();
+        assert_eq!(parsed.pattern, "a");
+        assert_eq!(parsed.paths, ["x.txt", "-c"]);
+        assert!(parsed.case_insensitive && !parsed.recursive);
+        assert!(args(&["-r", "a"]).unwrap().recursive);
+        assert_eq!(parsed.color, ColorChoice::Auto);
+        assert_eq!(args(&["--color=always", "-n", "a"]).unwrap().color, ColorChoice::Always);
+        assert_eq!(args(&["--color=never", "a"]).unwrap().color, ColorChoice::Never);
+        assert_eq!(args(&["--colour", "a"]), Err("unknown option --colour".to_string()));
+        assert_eq!(parsed.options, Options { invert: true, count: false, line_number: true, color: false });
+
+        assert_eq!(args(&["--", "-v"]).unwrap().pattern, "-v");
+        assert_eq!(args(&["-", "f"]).unwrap().pattern, "-");
+        assert_eq!(args(&["-x", "a"]), Err("unknown option -x".to_string()));
+        assert_eq!(args(&["-i"]), Err("no pattern given".to_string()));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let glob = |glob: &str, text: &str| -> bool {
+            glob_match(&glob.chars().collect::<Vec<char>>(), &text.chars().collect::<Vec<char>>())
+        };
+        assert!(glob("*.log", "a.log"));
+        assert!(!glob("*.log", "a/b.log"));
+        assert!(glob("a?c", "abc"));
+        assert!(!glob("a?c", "a/c"));
+        assert!(glob("**/gen", "gen"));
+        assert!(glob("**/gen", "a/b/gen"));
+        assert!(!glob("**/gen", "a/xgen"));
+        assert!(glob("a/**", "a/b/c"));
+        assert!(glob("a/**/b", "a/x/y/b"));
+        assert!(glob("\\*x", "*x"));
+        assert!(!glob("\\*x", "ax"));
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let base: &Path = Path::new("root");
+        let rules: Vec<Rule> = ["# comment", "", "target/", "*.log", "!keep.log", "/src/gen_*"]
+            .iter()
+            .filter_map(|line| parse_rule(base, line))
+            .collect();
+        assert_eq!(rules.len(), 4);
+        assert!(is_ignored(&rules, Path::new("root/target"), true));
+        assert!(!is_ignored(&rules, Path::new("root/target"), false));
+        assert!(is_ignored(&rules, Path::new("root/a/b.log"), false));
+        assert!(!is_ignored(&rules, Path::new("root/a/keep.log"), false));
+        assert!(is_ignored(&rules, Path::new("root/src/gen_a.rs"), false));
+        assert!(!is_ignored(&rules, Path::new("root/a/src/gen_a.rs"), false));
+    }
+
+    #[test]
+    fn test_walk() {
+        let root: PathBuf = std::env::temp_dir().join(format!("small-regex-walk-{}", std::process::id()));
+        for dir in ["b/target", "a", ".git"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.join("b/.gitignore"), "target/\n").unwrap();
+        for file in ["c.txt", "a/x.log", "a/y.txt", "b/target/z.txt", "b/w.txt", ".git/config", ".hidden"] {
+            fs::write(root.join(file), file).unwrap();
+        }
+        let mut failed: bool = false;
+        let (inputs, walked): (Vec<Input>, bool) = collect_inputs(&[root.display().to_string()], true, &mut failed);
+        let names: Vec<PathBuf> = inputs.iter().map(|input| input.path.clone().unwrap()).collect();
+        assert_eq!(names, [root.join("a/y.txt"), root.join("b/w.txt"), root.join("c.txt")]);
+        assert!(walked && !failed);
+
+        // -r を付けなければ、ディレクトリを展開しない
+        let (inputs, walked): (Vec<Input>, bool) = collect_inputs(&[root.display().to_string()], false, &mut failed);
+        assert_eq!(inputs.len(), 1);
+        assert!(!walked);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_search_parallel() {
+        let root: PathBuf = std::env::temp_dir().join(format!("small-regex-parallel-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let mut inputs: Vec<Input> = Vec::new();
+        for i in 0..20 {
+            let path: PathBuf = root.join(format!("{i}.txt"));
+            // 前のファイルほど大きくし、後のファイルが先に探索し終わるようにする
+            fs::write(&path, "x\n".repeat((20 - i) * 1000) + "ab\n").unwrap();
+            inputs.push(Input { path: Some(path), name: i.to_string() });
+        }
+        inputs.push(Input { path: Some(root.join("missing")), name: "missing".to_string() });
+        let re: Regex = Regex::new("b").unwrap();
+        let options: Options = Options { count: true, ..Options::default() };
+        let mut out: Vec<u8> = Vec::new();
+        let mut reported: Vec<String> = Vec::new();
+        search_parallel(&re, &inputs, true, options, &mut out, |input, result| {
+            reported.push(format!("{}:{}", input.name, result.is_ok()));
+            true
+        });
+        let expect: String = (0..20).map(|i| format!("{i}:1\n")).collect();
+        assert_eq!(String::from_utf8(out).unwrap(), expect);
+        assert_eq!(reported.last().unwrap(), "missing:false");
+        assert_eq!(reported[..20], (0..20).map(|i| format!("{i}:true")).collect::<Vec<String>>());
+
+        // CHUNK_SIZE を超える内容も、入力の順に書く
+        let options: Options = Options::default();
+        let re: Regex = Regex::new("x").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        search_parallel(&re, &inputs[..3], true, options, &mut out, |_, _| true);
+        let expect: String = (0..3).map(|i| format!("{i}:x\n").repeat((20 - i) * 1000)).collect();
+        assert_eq!(String::from_utf8(out).unwrap(), expect);
+
+        // 書き込みに失敗した場合は、失敗を報告して残りの入力を探索しない
+        let mut reported: Vec<String> = Vec::new();
+        search_parallel(&re, &inputs, true, options, &mut Full, |input, result| {
+            reported.push(format!("{}:{}", input.name, matches!(result, Err(Failure::Write(_)))));
+            false
+        });
+        assert_eq!(reported, ["0:true"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// 常に書き込みに失敗する Write
+    struct Full;
+
+    impl Write for Full {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::StorageFull))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,322 @@
+//! マッチした結果を表す型
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Deref, Range};
+
+use crate::aho_corasick::AhoCorasick;
+use crate::compiler::Instruction;
+use crate::evaluator::{search_partial_with, search_slots, slots_len, EvalOptions, Partial, Scratch};
+use crate::input::{Input, Symbol};
+use crate::offsets::{OffsetUnit, Offsets};
+
+/// text の中でマッチした部分を表す型  
+/// 位置はすべて text の先頭からのバイト単位の位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'t> {
+    text: &'t str,
+    start: usize,
+    end: usize,
+}
+
+impl<'t> Match<'t> {
+    pub(crate) fn new(text: &'t str, start: usize, end: usize) -> Match<'t> {
+        Match { text, start, end }
+    }
+
+    /// マッチした部分の開始位置
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// マッチした部分の終了位置 (この位置の文字はマッチした部分に含まない)
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// マッチした部分の範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// マッチした部分の範囲を、unit の単位で text の先頭から数えた範囲に変換して返す  
+    /// 複数のマッチを変換する場合は、Offsets を使い回すと text を先頭から数え直さずに済む
+    ///
+    /// ```
+    /// use small_regex::{OffsetUnit, Regex};
+    ///
+    /// let m = Regex::new("b+").unwrap().find("😀bb").unwrap();
+    /// assert_eq!(m.range(), 4..6);
+    /// assert_eq!(m.range_in(OffsetUnit::Char), 1..3);
+    /// assert_eq!(m.range_in(OffsetUnit::Utf16), 2..4);
+    /// ```
+    pub fn range_in(&self, unit: OffsetUnit) -> Range<usize> {
+        Offsets::new(self.text, unit).convert_range(self.range())
+    }
+
+    /// マッチした部分の長さ (バイト単位)
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// 空の文字列にマッチしたかどうか
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// マッチした部分の文字列
+    pub fn as_str(&self) -> &'t str {
+        &self.text[self.range()]
+    }
+}
+
+/// text の中でマッチする部分を、重ならないように先頭から順に返すイテレータ  
+/// `Regex::find_iter` で生成する
+#[derive(Debug)]
+pub struct Matches<'r, 't> {
+    text: &'t str,
+    searcher: Searcher<'r, &'t str>,
+    slots: Vec<Option<usize>>,
+    literals: Option<&'r AhoCorasick>, // パターンがリテラルの選択だけからなる場合のオートマトン
+    pos: usize,                        // literals で次に探索を開始する位置
+}
+
+impl<'r, 't> Matches<'r, 't> {
+    pub(crate) fn new(
+        instructions: &'r [Instruction],
+        options: EvalOptions,
+        text: &'t str,
+        literals: Option<&'r AhoCorasick>,
+    ) -> Matches<'r, 't> {
+        Matches {
+            text,
+            searcher: Searcher::new(instructions, options, text),
+            slots: vec![None; slots_len(instructions)],
+            literals,
+            pos: 0,
+        }
+    }
+}
+
+impl<'r, 't> Iterator for Matches<'r, 't> {
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Match<'t>> {
+        if let Some(literals) = self.literals {
+            // リテラルは空でないため、マッチの終了位置から次の探索を始めればよい
+            let (start, end) = literals.find(self.text.as_bytes(), self.pos)?;
+            self.pos = end;
+            return Some(Match::new(self.text, start, end));
+        }
+        if !self.searcher.next(&mut self.slots) {
+            return None;
+        }
+        let start: usize = self.slots[0]?;
+        let end: usize = self.slots[1]?;
+        Some(Match::new(self.text, start, end))
+    }
+}
+
+/// text の中でマッチする部分を、重なるものも含めて開始位置の順に返すイテレータ  
+/// 各マッチの開始位置の1文字後から次の探索を始める  
+/// `Regex::find_overlapping_iter` で生成する
+#[derive(Debug)]
+pub struct OverlappingMatches<'r, 't> {
+    text: &'t str,
+    instructions: &'r [Instruction],
+    options: EvalOptions,
+    index: Option<usize>, // 次に探索を開始する位置 (探索を終えた場合は None)
+    slots: Vec<Option<usize>>,
+}
+
+impl<'r, 't> OverlappingMatches<'r, 't> {
+    pub(crate) fn new(instructions: &'r [Instruction], options: EvalOptions, text: &'t str) -> OverlappingMatches<'r, 't> {
+        OverlappingMatches { text, instructions, options, index: Some(0), slots: vec![None; slots_len(instructions)] }
+    }
+}
+
+impl<'r, 't> Iterator for OverlappingMatches<'r, 't> {
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Match<'t>> {
+        let index: usize = self.index?;
+        if !search_slots(self.instructions, self.text, index, &mut self.slots, self.options) {
+            self.index = None;
+            return None;
+        }
+        let start: usize = self.slots[0]?;
+        let end: usize = self.slots[1]?;
+        self.index = self.text.char_at(start).map(|(_, next)| next);
+        Some(Match::new(self.text, start, end))
+    }
+}
+
+/// 重ならないマッチを先頭から順に探索する型  
+/// find_iter, captures_iter などのイテレータで共有する  
+/// 入力は Input を実装した型を参照するもの (`&str`, `&[u8]` など) を受け取り、位置はその単位で表す (S は命令列の記号の型)  
+/// streaming で生成した場合は、push で入力を少しずつ追加しながら探索する
+#[derive(Debug)]
+pub(crate) struct Searcher<'r, T, S = char> {
+    instructions: &'r [Instruction<S>],
+    options: EvalOptions,
+    input: T,
+    base: usize,             // input の先頭の、入力全体の中での位置 (push で捨てた入力の長さ)
+    index: usize,            // 次に探索を開始する位置
+    resume: Option<usize>,   // 入力の続きを待つ間、次にマッチを試みる位置
+    last_end: Option<usize>, // 直前のマッチの終了位置
+    skip: bool,              // 空マッチの後で、次の探索を1文字進める必要があるかどうか
+    is_last: bool,           // input が入力の終わりまでを含むかどうか
+    scratch: Scratch,        // 評価器の作業領域 (マッチごとに確保し直さず使い回す)
+}
+
+impl<'r, T, S> Searcher<'r, T, S>
+where
+    T: Deref,
+    T::Target: Input<S>,
+    S: Symbol,
+{
+    pub(crate) fn new(instructions: &'r [Instruction<S>], options: EvalOptions, input: T) -> Searcher<'r, T, S> {
+        Searcher {
+            instructions,
+            options,
+            input,
+            base: 0,
+            index: 0,
+            resume: None,
+            last_end: None,
+            skip: false,
+            is_last: true,
+            scratch: Scratch::default(),
+        }
+    }
+
+    /// state の状態から探索を続ける Searcher を生成する  
+    /// input が入力の先頭からの一部である場合は、is_last を false にする
+    #[cfg(feature = "parallel")]
+    pub(crate) fn with_state(
+        instructions: &'r [Instruction<S>],
+        options: EvalOptions,
+        input: T,
+        state: SearchState,
+        is_last: bool,
+    ) -> Searcher<'r, T, S> {
+        Searcher {
+            index: state.index,
+            resume: state.resume,
+            last_end: state.last_end,
+            skip: state.skip,
+            is_last,
+            ..Searcher::new(instructions, options, input)
+        }
+    }
+
+    /// 探索の状態を返す (push で入力を捨てていない場合のみ使う)
+    #[cfg(feature = "parallel")]
+    pub(crate) fn state(&self) -> SearchState {
+        SearchState { index: self.index, resume: self.resume, last_end: self.last_end, skip: self.skip }
+    }
+
+    /// 次のマッチを探索し、マッチした場合は slots に入力全体の中での位置を記録して true を返す
+    pub(crate) fn next(&mut self, slots: &mut [Option<usize>]) -> bool {
+        self.poll(slots) == Partial::Matched
+    }
+
+    /// next と同様に探索し、入力の続きを読まないと結果が確定しない場合は NeedInput を返す  
+    /// NeedInput の位置は input の中での位置
+    pub(crate) fn poll(&mut self, slots: &mut [Option<usize>]) -> Partial {
+        loop {
+            if self.skip {
+                // 空の文字列にマッチした後は、無限ループにならないよう次の探索を1文字進める
+                match self.input.char_at(self.index) {
+                    Some((_, next)) => {
+                        self.index = next;
+                        self.skip = false;
+                    }
+                    None if self.is_last => return Partial::NotMatched,
+                    None => return Partial::NeedInput(self.index),
+                }
+            }
+            let from: usize = self.resume.take().unwrap_or(self.index);
+            let partial: Partial = search_partial_with(
+                self.instructions,
+                &*self.input,
+                self.index,
+                from,
+                slots,
+                self.options,
+                self.is_last,
+                &mut self.scratch,
+            );
+            match partial {
+                Partial::Matched => {}
+                Partial::NeedInput(resume) => {
+                    // resume より前の位置からはマッチしないことが確定している
+                    self.resume = Some(resume);
+                    return Partial::NeedInput(resume);
+                }
+                partial @ (Partial::NotMatched | Partial::Aborted) => return partial,
+            }
+            let (start, end) = (slots[0].unwrap(), slots[1].unwrap());
+
+            self.index = end;
+            if start == end {
+                self.skip = true;
+                // 直前のマッチの直後の空マッチは読み飛ばす
+                if self.last_end == Some(end) {
+                    continue;
+                }
+            }
+            self.last_end = Some(end);
+            if self.base > 0 {
+                for slot in slots.iter_mut() {
+                    *slot = slot.map(|pos| self.base + pos);
+                }
+            }
+            return Partial::Matched;
+        }
+    }
+}
+
+/// Searcher の探索の状態  
+/// 同じ入力と状態から探索を続けると、同じマッチが同じ順に見つかる
+#[cfg(feature = "parallel")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SearchState {
+    pub(crate) index: usize,            // 次に探索を開始する位置
+    pub(crate) resume: Option<usize>,   // 入力の続きを待つ間、次にマッチを試みる位置
+    pub(crate) last_end: Option<usize>, // 直前のマッチの終了位置
+    pub(crate) skip: bool,              // 空マッチの後で、次の探索を1文字進める必要があるかどうか
+}
+
+impl<'r> Searcher<'r, String> {
+    /// 空の入力から始め、push で入力を追加しながら探索する Searcher を生成する  
+    /// 入力の終わりに達したら finish を呼ぶ
+    pub(crate) fn streaming(instructions: &'r [Instruction], options: EvalOptions) -> Searcher<'r, String> {
+        Searcher { is_last: false, ..Searcher::new(instructions, options, String::new()) }
+    }
+
+    /// 入力の続き text を追加する  
+    /// 次の探索に必要な、次にマッチを試みる位置の1文字前より後ろの入力だけを残す
+    pub(crate) fn push(&mut self, text: &str) {
+        let from: usize = self.resume.unwrap_or(self.index).min(self.input.len());
+        let drop: usize = from - self.input[..from].chars().next_back().map_or(0, char::len_utf8);
+        self.input.drain(..drop);
+        self.base += drop;
+        // 探索を開始した位置を捨てた場合は、\G はもうマッチしない
+        self.index = self.index.checked_sub(drop).unwrap_or(usize::MAX);
+        self.resume = self.resume.map(|resume| resume - drop);
+        self.last_end = self.last_end.and_then(|end| end.checked_sub(drop));
+        self.input.push_str(text);
+    }
+
+    /// 次の探索のために残している入力のうち、マッチを試みる位置より後ろの長さ (バイト)
+    pub(crate) fn buffered(&self) -> usize {
+        self.input.len() - self.resume.unwrap_or(self.index).min(self.input.len())
+    }
+
+    /// 入力の終わりに達したことを設定する
+    pub(crate) fn finish(&mut self) {
+        self.is_last = true;
+    }
+}
@@ -0,0 +1,147 @@
+//! ファイルをメモリにマップして探索するための型
+
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use memmap2::Mmap;
+
+use crate::evaluator::{slots_len, Partial};
+use crate::matches::Searcher;
+use crate::regex::Regex;
+use crate::stream::StreamMatch;
+
+/// 1回に Searcher に渡すバイト数
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// メモリにマップしたファイルの中で、マッチする部分を重ならないように先頭から順に返すイテレータ  
+/// `Regex::find_in_file` で生成する  
+/// マップした領域を少しずつ直接 Searcher に渡すため、ファイル全体を読み込んでコピーすることは無い  
+/// ファイルが UTF-8 として不正な場合は、不正な箇所より前のマッチを返した後にエラーを返して終了する
+#[derive(Debug)]
+pub struct FileMatches<'r> {
+    mmap: Mmap,
+    searcher: Searcher<'r, String>,
+    slots: Vec<Option<usize>>,
+    pushed: usize, // Searcher に渡したバイト数
+    invalid: bool, // pushed の位置から UTF-8 として不正かどうか
+    done: bool,    // ファイルの終わりに達したか、エラーを返したかどうか
+}
+
+impl<'r> FileMatches<'r> {
+    pub(crate) fn open(regex: &'r Regex, path: &Path) -> io::Result<FileMatches<'r>> {
+        let file: File = File::open(path)?;
+        // SAFETY: マップしている間にファイルが変更・切り詰められた場合の動作は保証できない
+        // find_in_file のドキュメントで、探索中にファイルを変更しないことを呼び出し側に求めている
+        let mmap: Mmap = unsafe { Mmap::map(&file)? };
+        Ok(FileMatches {
+            mmap,
+            searcher: Searcher::streaming(regex.instructions(), regex.options()),
+            slots: vec![None; slots_len(regex.instructions())],
+            pushed: 0,
+            invalid: false,
+            done: false,
+        })
+    }
+
+    /// マップした領域の続きを、文字の境界まで Searcher に渡す  
+    /// ファイルの終わりに達した場合は Searcher に終わりを伝える
+    fn fill(&mut self) -> io::Result<()> {
+        if self.invalid {
+            return Err(io::Error::new(ErrorKind::InvalidData, "file did not contain valid UTF-8"));
+        }
+        let rest: &[u8] = &self.mmap[self.pushed..];
+        if rest.is_empty() {
+            self.searcher.finish();
+            return Ok(());
+        }
+
+        // 文字の途中で区切らないよう、UTF-8 の継続バイトの前まで戻す
+        let mut end: usize = rest.len().min(CHUNK_SIZE);
+        while end < rest.len() && end > 0 && rest[end] & 0xC0 == 0x80 {
+            end -= 1;
+        }
+        let valid: usize = match core::str::from_utf8(&rest[..end]) {
+            Ok(text) => text.len(),
+            Err(e) => {
+                self.invalid = true;
+                e.valid_up_to()
+            }
+        };
+        let text: &str = core::str::from_utf8(&rest[..valid]).unwrap();
+        self.searcher.push(text);
+        self.pushed += valid;
+        Ok(())
+    }
+}
+
+impl Iterator for FileMatches<'_> {
+    type Item = io::Result<StreamMatch>;
+
+    fn next(&mut self) -> Option<io::Result<StreamMatch>> {
+        while !self.done {
+            match self.searcher.poll(&mut self.slots) {
+                Partial::Matched => return Some(Ok(StreamMatch::new(self.slots[0]?, self.slots[1]?))),
+                Partial::NotMatched | Partial::Aborted => self.done = true,
+                Partial::NeedInput(_) => {
+                    if let Err(e) = self.fill() {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::path::PathBuf;
+
+    use crate::regex::Regex;
+
+    /// テスト用のファイルを一時ディレクトリに作成し、そのパスを返す
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path: PathBuf = std::env::temp_dir().join(format!("small-regex-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_find_in_file() {
+        // チャンクの境界をまたぐマッチと、マルチバイト文字を含む
+        let mut text: String = "x".repeat(super::CHUNK_SIZE - 2);
+        text.push_str("abbα\nab");
+        let path: PathBuf = temp_file("find", text.as_bytes());
+
+        let re: Regex = Regex::new("ab+α?").unwrap();
+        let expected: Vec<(usize, usize)> = re.find_iter(&text).map(|m| (m.start(), m.end())).collect();
+        let ranges: Vec<(usize, usize)> =
+            re.find_in_file(&path).unwrap().map(|m| m.map(|m| (m.start(), m.end())).unwrap()).collect();
+        assert_eq!(ranges, expected);
+        std::fs::remove_file(&path).unwrap();
+
+        let path: PathBuf = temp_file("empty", b"");
+        assert_eq!(Regex::new("a*").unwrap().find_in_file(&path).unwrap().count(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        assert!(Regex::new("a").unwrap().find_in_file("/nonexistent/small-regex").is_err());
+
+        let path: PathBuf = temp_file("invalid", b"a\xffa");
+        let results: Vec<io::Result<(usize, usize)>> =
+            Regex::new("a").unwrap().find_in_file(&path).unwrap().map(|m| m.map(|m| (m.start(), m.end()))).collect();
+        assert_eq!(results[0].as_ref().unwrap(), &(0, 1));
+        assert_eq!(results[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+//! マッチの位置 (バイト単位) を、文字単位や UTF-16 のコード単位の位置に変換するための型
+
+use core::ops::Range;
+
+/// 位置を数える単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OffsetUnit {
+    /// UTF-8 のバイト単位 (`&str` をスライスする位置)
+    #[default]
+    Byte,
+    /// 文字 (Unicode のスカラー値) 単位
+    Char,
+    /// UTF-16 のコード単位 (エディタや LSP の位置)
+    Utf16,
+}
+
+impl OffsetUnit {
+    /// 文字 c を、この単位でいくつに数えるかを返す
+    fn width(self, c: char) -> usize {
+        match self {
+            OffsetUnit::Byte => c.len_utf8(),
+            OffsetUnit::Char => 1,
+            OffsetUnit::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+/// text の中のバイト単位の位置を、unit の単位の位置に変換する型  
+/// 前回変換した位置から続けて数えるため、find_iter のマッチのように昇順に変換すれば、text 全体を1度走査するだけで済む
+///
+/// ```
+/// use small_regex::{OffsetUnit, Offsets, Regex};
+///
+/// let text = "😀a😀a";
+/// let re = Regex::new("a").unwrap();
+/// let mut offsets = Offsets::new(text, OffsetUnit::Utf16);
+/// let ranges: Vec<_> = re.find_iter(text).map(|m| offsets.convert_range(m.range())).collect();
+/// assert_eq!(ranges, vec![2..3, 5..6]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Offsets<'t> {
+    text: &'t str,
+    unit: OffsetUnit,
+    byte: usize,      // 前回変換したバイト単位の位置
+    converted: usize, // byte を unit の単位で数えた位置
+}
+
+impl<'t> Offsets<'t> {
+    pub fn new(text: &'t str, unit: OffsetUnit) -> Offsets<'t> {
+        Offsets { text, unit, byte: 0, converted: 0 }
+    }
+
+    /// バイト単位の位置 byte を、unit の単位の位置に変換する  
+    /// byte が text の中の文字の境界でない場合や、text の長さを超える場合はパニックする
+    pub fn convert(&mut self, byte: usize) -> usize {
+        if self.unit == OffsetUnit::Byte {
+            assert!(self.text.is_char_boundary(byte), "byte offset ({}) is not a char boundary of the text", byte);
+            return byte;
+        }
+        if byte < self.byte {
+            // 前回より前の位置は、先頭から数え直す
+            self.byte = 0;
+            self.converted = 0;
+        }
+        let unit: OffsetUnit = self.unit;
+        self.converted += self.text[self.byte..byte].chars().map(|c| unit.width(c)).sum::<usize>();
+        self.byte = byte;
+        self.converted
+    }
+
+    /// バイト単位の範囲 range を、unit の単位の範囲に変換する
+    pub fn convert_range(&mut self, range: Range<usize>) -> Range<usize> {
+        let start: usize = self.convert(range.start);
+        let end: usize = self.convert(range.end);
+        start..end
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use super::{OffsetUnit, Offsets};
+
+    #[test]
+    fn test_convert() {
+        let text: &str = "aα😀b";
+        let mut chars: Offsets = Offsets::new(text, OffsetUnit::Char);
+        assert_eq!(chars.convert_range(3..7), 2..3);
+        assert_eq!(chars.convert(8), 4);
+        // 前回より前の位置も変換できる
+        assert_eq!(chars.convert(1), 1);
+
+        let mut utf16: Offsets = Offsets::new(text, OffsetUnit::Utf16);
+        assert_eq!(utf16.convert_range(3..7), 2..4);
+        assert_eq!(utf16.convert(8), 5);
+
+        let mut bytes: Offsets = Offsets::new(text, OffsetUnit::Byte);
+        assert_eq!(bytes.convert(7), 7);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_convert_not_boundary() {
+        Offsets::new("α", OffsetUnit::Utf16).convert(1);
+    }
+}
@@ -0,0 +1,299 @@
+//! 各位置で成功しうる分岐が高々1つに決まる命令列 (one-pass) を評価する評価器  
+//! 分岐を覚えておく必要が無いため、バックトラックもスレッドの集合も使わずに、入力を1度読むだけでキャプチャの位置まで求まる  
+//! 開始位置を1つに決める必要があるため、anchored の探索にのみ使う  
+//! 空の文字列にマッチする繰り返しは、バックトラックと同じく、同じ位置でループの先頭に戻る分岐を失敗とする
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::compiler::Instruction;
+use crate::evaluator::{is_assertion_satisfied, is_char_matched, next_pos, EvalOptions, Loops, Partial};
+use crate::input::{Input, Symbol};
+use crate::pikevm;
+
+/// 命令列が one-pass であるかを判定する  
+/// 先頭の命令と、文字を読む命令の次の命令のそれぞれから Jump, Split, Save, Assert を辿り、  
+/// 到達する文字を読む命令どうしが同じ文字にマッチし得ない場合に one-pass とする (Assert はすべて成り立つものとして辿る)
+pub fn is_one_pass<T: Symbol>(instructions: &[Instruction<T>]) -> bool {
+    if !pikevm::is_supported(instructions) {
+        return false;
+    }
+    let mut visited: Vec<bool> = vec![false; instructions.len()];
+    let entries = core::iter::once(0).chain(
+        instructions.iter().enumerate().filter(|(_, inst)| is_consumer(inst)).map(|(pc, _)| pc + 1),
+    );
+    for entry in entries {
+        visited.fill(false);
+        let mut stack: Vec<usize> = vec![entry];
+        let mut consumers: Vec<&Instruction<T>> = Vec::new();
+        while let Some(pc) = stack.pop() {
+            if visited[pc] {
+                continue;
+            }
+            visited[pc] = true;
+            match &instructions[pc] {
+                Instruction::Jump(counter) => stack.push(*counter),
+                Instruction::Split(counter1, counter2) => {
+                    stack.push(*counter2);
+                    stack.push(*counter1);
+                }
+                Instruction::Save(_) | Instruction::Assert(_) => stack.push(pc + 1),
+                Instruction::Match => {}
+                inst => {
+                    if consumers.iter().any(|other| overlaps(other, inst)) {
+                        return false;
+                    }
+                    consumers.push(inst);
+                }
+            }
+        }
+    }
+    true
+}
+
+/// 文字を読む命令かどうか
+fn is_consumer<T>(instruction: &Instruction<T>) -> bool {
+    matches!(
+        instruction,
+        Instruction::Char(_) | Instruction::CharNoCase(_) | Instruction::AnyChar(_) | Instruction::Property(_, _)
+    )
+}
+
+/// 2つの文字を読む命令が、同じ文字にマッチし得るかを判定する (判定できない組み合わせは true とする)
+fn overlaps<T: Symbol>(inst1: &Instruction<T>, inst2: &Instruction<T>) -> bool {
+    match (inst1, inst2) {
+        (Instruction::Char(c), other) | (other, Instruction::Char(c)) => is_char_matched(other, c),
+        (Instruction::CharNoCase(c1), Instruction::CharNoCase(c2)) => c1 == c2,
+        _ => true,
+    }
+}
+
+/// search_partial の is_last と options.anchored が true の場合と同様に、from から index までの位置で始まるマッチを探索する  
+/// 命令列は is_one_pass で one-pass と判定したものであること  
+/// マッチした場合は slots にキャプチャグループがマッチした位置を記録する
+pub fn search<T: Symbol, I: Input<T> + ?Sized>(
+    instructions: &[Instruction<T>],
+    input: &I,
+    index: usize,
+    from: usize,
+    slots: &mut [Option<usize>],
+    options: EvalOptions,
+) -> Partial {
+    slots.fill(None);
+    let mut loops: Loops = Loops::default();
+    loops.build(instructions);
+    let mut onepass: OnePass<T, I> = OnePass {
+        instructions,
+        input,
+        start: index,
+        options,
+        budget: options.backtrack_limit,
+        visited: vec![false; loops.states()],
+        loops,
+        heads: Vec::new(),
+        stack: Vec::new(),
+    };
+    let mut start: usize = from;
+    while start <= index.min(input.len()) {
+        match onepass.eval_from(start, slots) {
+            Some(true) => return Partial::Matched,
+            Some(false) => start = next_pos(input, start),
+            None => {
+                slots.fill(None);
+                return Partial::Aborted;
+            }
+        }
+    }
+    slots.fill(None);
+    Partial::NotMatched
+}
+
+/// eval_from で辿る作業
+enum Frame {
+    Explore(usize),                // カウンタの命令を辿る
+    Restore(usize, Option<usize>), // Save で書き換えたスロットを元に戻す
+    Leave,                         // 最後に辿ったループの先頭を heads から取り除く
+}
+
+/// one-pass の評価器の型
+struct OnePass<'a, T, I: ?Sized> {
+    instructions: &'a [Instruction<T>],
+    input: &'a I,
+    start: usize,          // 探索を開始した位置 (\G がマッチする位置)
+    options: EvalOptions,
+    budget: Option<usize>, // 残りの実行できる命令の数 (None の場合は無制限)
+    visited: Vec<bool>,    // 各状態 (Loops::state の番号) を現在の位置で既に辿ったかどうか
+    loops: Loops,          // 命令列の中のループ
+    heads: Vec<usize>,     // 辿っている分岐が、現在の位置で実行したループの先頭
+    stack: Vec<Frame>,
+}
+
+impl<T: Symbol, I: Input<T> + ?Sized> OnePass<'_, T, I> {
+    /// start の位置から始まるマッチを探し、マッチした場合は slots に記録して Some(true) を返す  
+    /// 実行できる命令の数の上限に達した場合は None を返す
+    fn eval_from(&mut self, start: usize, slots: &mut [Option<usize>]) -> Option<bool> {
+        let mut current: Vec<Option<usize>> = vec![None; slots.len()];
+        let mut next: Vec<Option<usize>> = vec![None; slots.len()];
+        current[0] = Some(start);
+        let mut matched: bool = false;
+        let mut pc: usize = 0;
+        let mut pos: usize = start;
+        loop {
+            let symbol: Option<(T, usize)> = self.input.char_at(pos);
+            // 入力の文字を読む命令は高々1つしかマッチしないため、最初に見つけたものだけを覚えておく
+            let mut next_pc: Option<usize> = None;
+            self.visited.fill(false);
+            self.heads.clear();
+            self.stack.push(Frame::Explore(pc));
+            while let Some(frame) = self.stack.pop() {
+                let pc: usize = match frame {
+                    Frame::Explore(pc) => pc,
+                    Frame::Restore(slot, old) => {
+                        current[slot] = old;
+                        continue;
+                    }
+                    Frame::Leave => {
+                        self.heads.pop();
+                        continue;
+                    }
+                };
+                // Pike VM と同じく、命令ごとではなく状態ごとに1度だけ辿る
+                let state: usize = match &self.instructions[pc] {
+                    Instruction::Jump(_) | Instruction::Split(_, _) | Instruction::Save(_) | Instruction::Assert(_) => {
+                        self.loops.state(pc, self.heads.iter().copied())
+                    }
+                    _ => self.loops.state(pc, core::iter::empty()),
+                };
+                if self.visited[state] {
+                    continue;
+                }
+                self.visited[state] = true;
+                if self.loops.is_head(pc) {
+                    self.heads.push(pc);
+                    self.stack.push(Frame::Leave);
+                }
+                if let Some(budget) = self.budget.as_mut() {
+                    if *budget == 0 {
+                        self.stack.clear();
+                        return None;
+                    }
+                    *budget -= 1;
+                }
+                match &self.instructions[pc] {
+                    Instruction::Jump(counter) => self.explore(pc, *counter),
+                    Instruction::Split(counter1, counter2) => {
+                        self.explore(pc, *counter2);
+                        self.explore(pc, *counter1);
+                    }
+                    Instruction::Save(slot) => {
+                        self.stack.push(Frame::Restore(*slot, current[*slot]));
+                        current[*slot] = Some(pos);
+                        self.stack.push(Frame::Explore(pc + 1));
+                    }
+                    Instruction::Assert(assertion) => {
+                        if is_assertion_satisfied(*assertion, self.input, pos, self.start) {
+                            self.stack.push(Frame::Explore(pc + 1));
+                        }
+                    }
+                    Instruction::Match if self.options.anchored_end && pos != self.input.len() => {}
+                    Instruction::Match => {
+                        slots.copy_from_slice(&current);
+                        slots[1] = Some(pos);
+                        matched = true;
+                        if !self.options.longest {
+                            // 優先度の低い分岐は試さない
+                            self.stack.clear();
+                        }
+                    }
+                    instruction => {
+                        if let Some((c, _)) = &symbol {
+                            if next_pc.is_none() && is_char_matched(instruction, c) {
+                                next_pc = Some(pc + 1);
+                                next.copy_from_slice(&current);
+                            }
+                        }
+                    }
+                }
+            }
+            // Match より優先度の高い分岐で文字を読めた場合は、その分岐を進める (Match は失敗した場合のために記録してある)
+            match (next_pc, &symbol) {
+                (Some(counter), Some((_, next_index))) => {
+                    pc = counter;
+                    pos = *next_index;
+                    core::mem::swap(&mut current, &mut next);
+                }
+                _ => return Some(matched),
+            }
+        }
+    }
+
+    /// pc から target に進む分岐を stack に積む  
+    /// 同じ位置で始めた繰り返しの中から、ループの先頭に戻る分岐は積まない
+    fn explore(&mut self, pc: usize, target: usize) {
+        if target > pc || !self.heads.contains(&target) {
+            self.stack.push(Frame::Explore(target));
+        }
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::{is_one_pass, search};
+    use crate::compiler::{compile, Instruction};
+    use crate::evaluator::{search_backtrack, slots_len, EvalOptions, Partial, Scratch};
+    use crate::parser::parse;
+
+    /// one-pass の評価器とバックトラックで、同じマッチとキャプチャを返すかを確かめる
+    fn assert_same(pattern: &str, text: &str, options: EvalOptions) {
+        let insts: Vec<Instruction> = compile(&parse(pattern).unwrap());
+        assert!(is_one_pass(&insts), "{}", pattern);
+        let mut scratch: Scratch = Scratch::default();
+        for index in (0..=text.len()).filter(|i| text.is_char_boundary(*i)) {
+            let mut expected: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+            let matched: bool =
+                search_backtrack(&insts, text, index, index, &mut expected, options, true, &mut scratch) == Partial::Matched;
+            let mut actual: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+            let result: Partial = search(&insts, text, index, index, &mut actual, options);
+            assert_eq!(result == Partial::Matched, matched, "{} / {} / {}", pattern, text, index);
+            assert_eq!(actual, expected, "{} / {} / {}", pattern, text, index);
+        }
+    }
+
+    #[test]
+    fn test_same_as_backtrack() {
+        let cases: &[(&str, &str)] = &[
+            ("(a+)b(c*)", "xaabccd"),
+            ("(?<x>a|b)(c)?d", "bdacd"),
+            ("a*", "aab"),
+            ("(a)*b?", "aab"),
+            ("ab|cd", "abcd"),
+            ("(k+)=(v+)$", "kk=vv"),
+            ("^x(y)", "xy"),
+            ("(?i)(k)é", "KÉ"),
+            ("α(β)", "αβ"),
+            ("(a\n?|)+", "a\n"),
+            ("(a|)+b", "ab"),
+        ];
+        for (pattern, text) in cases {
+            let anchored: EvalOptions = EvalOptions { anchored: true, ..EvalOptions::default() };
+            assert_same(pattern, text, anchored);
+            assert_same(pattern, text, EvalOptions { longest: true, ..anchored });
+            assert_same(pattern, text, EvalOptions { anchored_end: true, ..anchored });
+        }
+    }
+
+    #[test]
+    fn test_is_one_pass() {
+        assert!(is_one_pass(&compile(&parse("a(b|c)*d").unwrap())));
+        // 同じ位置で、2つの分岐が同じ文字を読み得る
+        assert!(!is_one_pass(&compile(&parse("a*a").unwrap())));
+        assert!(!is_one_pass(&compile(&parse("(ab|ac)").unwrap())));
+        assert!(!is_one_pass(&compile(&parse(".*b").unwrap())));
+        assert!(!is_one_pass(&compile(&parse("(?>a)b").unwrap())));
+    }
+}
@@ -0,0 +1,198 @@
+//! コンパイルした命令列を最適化する関数  
+//! ネストしたグループや選択から生成される冗長な命令を取り除き、評価器が辿る命令の数を減らす
+//!
+//! - Jump の飛び先が Jump の場合は、最後の飛び先に直接飛ぶ (Split, Condition の飛び先も同様)  
+//! - 2つの飛び先が同じ Split は Jump にする  
+//! - 到達しない命令と、次の命令に飛ぶだけの Jump を取り除き、カウンタを詰める
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::compiler::Instruction;
+
+/// 命令列を最適化する関数  
+/// 評価したときのマッチとキャプチャの結果は変わらない
+pub fn optimize<T>(mut instructions: Vec<Instruction<T>>) -> Vec<Instruction<T>> {
+    let len: usize = instructions.len();
+
+    // Jump の連鎖を辿り、飛び先を付け替える
+    for pc in 0..len {
+        let inst: Instruction<T> = match &instructions[pc] {
+            Instruction::Jump(counter) => Instruction::Jump(resolve(&instructions, *counter)),
+            Instruction::Split(counter1, counter2) => {
+                let (counter1, counter2): (usize, usize) =
+                    (resolve(&instructions, *counter1), resolve(&instructions, *counter2));
+                if counter1 == counter2 {
+                    Instruction::Jump(counter1)
+                } else {
+                    Instruction::Split(counter1, counter2)
+                }
+            }
+            Instruction::Condition(group, else_counter) => {
+                Instruction::Condition(*group, resolve(&instructions, *else_counter))
+            }
+            _ => continue,
+        };
+        instructions[pc] = inst;
+    }
+
+    // 先頭の命令から到達する命令を求める
+    let mut keep: Vec<bool> = vec![false; len];
+    let mut stack: Vec<usize> = vec![0];
+    while let Some(pc) = stack.pop() {
+        if pc >= len || keep[pc] {
+            continue;
+        }
+        keep[pc] = true;
+        match &instructions[pc] {
+            Instruction::Match | Instruction::MatchPattern(_) => {}
+            Instruction::Jump(counter) => stack.push(*counter),
+            Instruction::Split(counter1, counter2) => {
+                stack.push(*counter1);
+                stack.push(*counter2);
+            }
+            Instruction::Condition(_, else_counter) => {
+                stack.push(pc + 1);
+                stack.push(*else_counter);
+            }
+            _ => stack.push(pc + 1),
+        }
+    }
+
+    // 飛び先までの命令をすべて取り除く Jump は、次の命令に進むのと同じため取り除く
+    // 後ろの Jump から判定すると、取り除いた Jump を挟む手前の Jump もまとめて取り除ける
+    for pc in (0..len).rev() {
+        if let Instruction::Jump(counter) = instructions[pc] {
+            if keep[pc] && counter > pc && !keep[pc + 1..counter].iter().any(|k| *k) {
+                keep[pc] = false;
+            }
+        }
+    }
+
+    // 古いカウンタから、それ以降で最初に残る命令の新しいカウンタを引く表
+    let mut map: Vec<usize> = vec![0; len + 1];
+    let mut counter: usize = 0;
+    for pc in 0..len {
+        map[pc] = counter;
+        if keep[pc] {
+            counter += 1;
+        }
+    }
+    map[len] = counter;
+
+    instructions
+        .into_iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(inst, _)| match inst {
+            Instruction::Jump(counter) => Instruction::Jump(map[counter]),
+            Instruction::Split(counter1, counter2) => Instruction::Split(map[counter1], map[counter2]),
+            Instruction::Atomic(next) => Instruction::Atomic(map[next]),
+            Instruction::Condition(group, else_counter) => Instruction::Condition(group, map[else_counter]),
+            inst => inst,
+        })
+        .collect()
+}
+
+/// counter から Jump を辿り、最初に到達する Jump 以外の命令のカウンタを返す  
+/// Jump だけの循環がある場合は、辿り始めたカウンタを返す
+fn resolve<T>(instructions: &[Instruction<T>], counter: usize) -> usize {
+    let mut current: usize = counter;
+    for _ in 0..instructions.len() {
+        match instructions.get(current) {
+            Some(Instruction::Jump(next)) => current = *next,
+            _ => return current,
+        }
+    }
+    counter
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::optimize;
+    use crate::compiler::{compile, Instruction};
+    use crate::evaluator::{search_backtrack, slots_len, EvalOptions, Partial, Scratch};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_jump_chain() {
+        let insts: Vec<Instruction> = vec![
+            Instruction::Split(1, 4),
+            Instruction::Char('a'),
+            Instruction::Jump(3),
+            Instruction::Jump(5),
+            Instruction::Char('b'),
+            Instruction::Match,
+        ];
+        let expect: Vec<Instruction> = vec![
+            Instruction::Split(1, 3),
+            Instruction::Char('a'),
+            Instruction::Jump(4),
+            Instruction::Char('b'),
+            Instruction::Match,
+        ];
+        assert_eq!(optimize(insts), expect);
+
+        // ネストした選択の内側の Jump は、外側の Jump を経由せずに飛ぶ
+        let insts: Vec<Instruction> = compile(&parse("(?:a|b)|c").unwrap());
+        assert_eq!(insts[3], Instruction::Jump(5));
+        assert_eq!(insts[5], Instruction::Jump(7));
+        assert_eq!(optimize(insts)[3], Instruction::Jump(7));
+    }
+
+    #[test]
+    fn test_split_and_unreachable() {
+        let insts: Vec<Instruction> = vec![
+            Instruction::Split(1, 2),
+            Instruction::Jump(3),
+            Instruction::Jump(3),
+            Instruction::Char('a'),
+            Instruction::Jump(6),
+            Instruction::Char('b'),
+            Instruction::Match,
+        ];
+        let expect: Vec<Instruction> = vec![Instruction::Char('a'), Instruction::Match];
+        assert_eq!(optimize(insts), expect);
+    }
+
+    #[test]
+    fn test_jump_to_next() {
+        // "a|" の左の分岐の最後の Jump は、次の命令に進むだけになる
+        let insts: Vec<Instruction> =
+            vec![Instruction::Split(1, 3), Instruction::Char('a'), Instruction::Jump(3), Instruction::Match];
+        let expect: Vec<Instruction> = vec![Instruction::Split(1, 2), Instruction::Char('a'), Instruction::Match];
+        assert_eq!(optimize(insts), expect);
+    }
+
+    #[test]
+    fn test_same_as_unoptimized() {
+        let cases: &[(&str, &str)] = &[
+            ("((a|b)|(c|d))+e", "xxabdce"),
+            ("(?:(?:a|b)*|c)d", "cabd"),
+            ("a(?:b|)c", "abcac"),
+            ("(a)?(?(1)b|c)", "abc"),
+            ("(?>a|ab)c", "abc ac"),
+            ("((a*)*)b", "aab"),
+        ];
+        for (pattern, text) in cases {
+            let insts: Vec<Instruction> = compile(&parse(pattern).unwrap());
+            let optimized: Vec<Instruction> = optimize(insts.clone());
+            assert!(optimized.len() <= insts.len(), "{}", pattern);
+            for index in (0..=text.len()).filter(|i| text.is_char_boundary(*i)) {
+                let mut expected: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+                let mut actual: Vec<Option<usize>> = vec![None; slots_len(&optimized)];
+                let options: EvalOptions = EvalOptions::default();
+                let mut scratch: Scratch = Scratch::default();
+                let matched: Partial = search_backtrack(&insts, *text, index, index, &mut expected, options, true, &mut scratch);
+                let result: Partial = search_backtrack(&optimized, *text, index, index, &mut actual, options, true, &mut scratch);
+                assert_eq!(result, matched, "{} / {} / {}", pattern, text, index);
+                assert_eq!(actual, expected, "{} / {} / {}", pattern, text, index);
+            }
+        }
+    }
+}
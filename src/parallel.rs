@@ -0,0 +1,248 @@
+//! 大きな入力を複数のスレッドで分けて探索するための実装  
+//! 入力を文字の境界でチャンクに分け、各スレッドがチャンクの先頭から探索したマッチを、先頭から順に1つの列にまとめる
+//!
+//! チャンクの探索は、チャンクの末尾より後ろを読まないと結果が決まらない位置 (チャンクをまたぐマッチなど) で止める  
+//! まとめる際は、前のチャンクから続く探索の状態と、チャンクの先頭から始めた探索の結果が一致する位置を探し、一致した位置から後のマッチをそのまま使う  
+//! 一致しない範囲 (チャンクの境界の付近) だけは、まとめる側で続きの入力を読みながら探索し直すため、結果は find_iter と同じになる
+//!
+//! スレッドには rayon のスレッドプールではなく、std::thread::scope で起動するスレッドを使う  
+//! parallel フィーチャーで必要なものを std だけに保ち、外部のクレートに依存しないためで、  
+//! チャンクは available_parallelism のスレッドの数に合わせて分け、各スレッドが1つのチャンクを探索する  
+//! 呼び出し側が rayon を使う場合も、そのスレッドプールとはスレッドを共有しない (プールの中から呼ぶと、その間はスレッドの数が増える)
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use std::panic;
+use std::thread;
+
+use crate::compiler::Instruction;
+use crate::evaluator::{slots_len, Partial};
+use crate::matches::{Match, SearchState, Searcher};
+use crate::parser::Assertion;
+use crate::regex::Regex;
+
+/// 1つのスレッドに渡すチャンクの最小のバイト数 (これより小さい入力はスレッドに分けない)
+const MIN_CHUNK_LEN: usize = 256 * 1024;
+
+/// チャンクを先頭から探索した結果
+#[derive(Debug)]
+struct Chunk {
+    start: usize,                 // チャンクの開始位置
+    end: usize,                   // チャンクの終了位置
+    matches: Vec<(usize, usize)>, // 見つけたマッチの (開始位置, 終了位置)
+    resume: Option<usize>,        // 最後のマッチの後、この位置より前から始まるマッチは無い (入力の終わりまで無い場合は usize::MAX、探索を打ち切った場合は None)
+    state: SearchState,           // 探索を終えた時点の状態
+}
+
+/// 探索の状態から、チャンクの結果をどこまで使えるか
+enum Adopt {
+    Matches(usize),   // matches のこの位置以降のマッチをそのまま使える
+    SkipEmpty(usize), // matches のこの位置の空マッチは、直前のマッチの直後の空マッチなので読み飛ばす
+    NoMatch(usize),   // この位置より前から始まるマッチは無い
+    Unknown,          // チャンクの結果からは決まらない
+}
+
+impl Regex {
+    /// text の中でパターンにマッチする部分を、重ならないように先頭から順に返す (find_iter と同じ結果になる)  
+    /// text を利用できるスレッドの数のチャンクに分け、各チャンクを別のスレッドで探索する  
+    /// text が小さい場合や、anchored を指定した場合、`\G` を含む場合は、分けずに find_iter で探索する
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("(a|b)+c").unwrap();
+    /// let text: String = "abcx".repeat(100_000);
+    /// let matches: Vec<&str> = re.find_iter_parallel(&text).iter().map(|m| m.as_str()).collect();
+    /// assert_eq!(matches.len(), 100_000);
+    /// assert!(matches.iter().all(|m| *m == "abc"));
+    /// ```
+    pub fn find_iter_parallel<'t>(&self, text: &'t str) -> Vec<Match<'t>> {
+        let threads: usize = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        self.find_iter_chunked(text, text.len().div_ceil(threads).max(MIN_CHUNK_LEN))
+    }
+
+    /// text を chunk_len バイトほどのチャンクに分けて探索する
+    fn find_iter_chunked<'t>(&self, text: &'t str, chunk_len: usize) -> Vec<Match<'t>> {
+        let instructions: &[Instruction] = self.instructions();
+        let has_start_search: bool = instructions.iter().any(|inst| matches!(inst, Instruction::Assert(Assertion::StartSearch)));
+        if text.len() <= chunk_len || self.options().anchored || has_start_search {
+            return self.find_iter(text).collect();
+        }
+
+        let mut bounds: Vec<usize> = vec![0];
+        let mut end: usize = 0;
+        while end < text.len() {
+            end = (end + chunk_len).min(text.len());
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+            bounds.push(end);
+        }
+        let chunks: Vec<Chunk> = thread::scope(|scope| {
+            let handles: Vec<_> = bounds
+                .windows(2)
+                .map(|bound| scope.spawn(move || self.search_chunk(text, bound[0], bound[1])))
+                .collect();
+            // スレッドが panic した場合は、その payload のまま呼び出し側で panic し直す
+            handles.into_iter().map(|handle| handle.join().unwrap_or_else(|payload| panic::resume_unwind(payload))).collect()
+        });
+        self.merge(text, &chunks)
+    }
+
+    /// text の start..end のチャンクを、start から探索する  
+    /// end より後ろを読まないと結果が決まらない位置で探索を止める
+    fn search_chunk(&self, text: &str, start: usize, end: usize) -> Chunk {
+        let state: SearchState = SearchState { index: start, resume: None, last_end: None, skip: false };
+        let mut searcher: Searcher<&str> =
+            Searcher::with_state(self.instructions(), self.options(), &text[..end], state, end == text.len());
+        let mut slots: Vec<Option<usize>> = vec![None; slots_len(self.instructions())];
+        let mut matches: Vec<(usize, usize)> = Vec::new();
+        let resume: Option<usize> = loop {
+            match searcher.poll(&mut slots) {
+                Partial::Matched => matches.push((slots[0].unwrap(), slots[1].unwrap())),
+                Partial::NeedInput(resume) => break Some(resume),
+                Partial::NotMatched => break Some(usize::MAX),
+                Partial::Aborted => break None,
+            }
+        };
+        Chunk { start, end, matches, resume, state: searcher.state() }
+    }
+
+    /// 各チャンクの結果を、先頭から探索した場合と同じマッチの列にまとめる
+    fn merge<'t>(&self, text: &'t str, chunks: &[Chunk]) -> Vec<Match<'t>> {
+        let mut result: Vec<Match> = Vec::new();
+        let mut state: SearchState = SearchState { index: 0, resume: None, last_end: None, skip: false };
+        let mut slots: Vec<Option<usize>> = vec![None; slots_len(self.instructions())];
+        loop {
+            let from: usize = next_start(text, state);
+            if from > text.len() {
+                return result;
+            }
+            let k: usize = chunks.partition_point(|chunk| chunk.start <= from) - 1;
+            let chunk: &Chunk = &chunks[k];
+            match adopt(text, chunk, state, from) {
+                Adopt::Matches(i) => {
+                    result.extend(chunk.matches[i..].iter().map(|(start, end)| Match::new(text, *start, *end)));
+                    if chunk.resume == Some(usize::MAX) {
+                        return result;
+                    }
+                    state = chunk.state;
+                    continue;
+                }
+                Adopt::SkipEmpty(i) => {
+                    let end: usize = chunk.matches[i].1;
+                    state = SearchState { index: end, resume: None, last_end: Some(end), skip: true };
+                    continue;
+                }
+                Adopt::NoMatch(usize::MAX) => return result,
+                Adopt::NoMatch(resume) => {
+                    state = SearchState { resume: Some(resume), skip: false, ..state };
+                    continue;
+                }
+                Adopt::Unknown => {}
+            }
+
+            // チャンクの結果からは決まらないため、続くチャンクの入力も読みながら次のマッチまで探索する
+            let mut next: usize = k + 1;
+            loop {
+                let bound: usize = chunks.get(next).map_or(text.len(), |chunk| chunk.end);
+                let mut searcher: Searcher<&str> =
+                    Searcher::with_state(self.instructions(), self.options(), &text[..bound], state, bound == text.len());
+                match searcher.poll(&mut slots) {
+                    Partial::Matched => {
+                        result.push(Match::new(text, slots[0].unwrap(), slots[1].unwrap()));
+                        state = searcher.state();
+                        break;
+                    }
+                    Partial::NeedInput(_) if next_start(text, searcher.state()) > from => {
+                        state = searcher.state();
+                        break;
+                    }
+                    Partial::NeedInput(_) => next += 1,
+                    Partial::NotMatched | Partial::Aborted => return result,
+                }
+            }
+        }
+    }
+}
+
+/// state の状態から探索を続けた場合に、次にマッチを試みる位置を返す (入力の終わりを越える場合は text.len() + 1)
+fn next_start(text: &str, state: SearchState) -> usize {
+    match state.resume {
+        Some(resume) => resume,
+        None if state.skip => text[state.index..].chars().next().map_or(text.len() + 1, |c| state.index + c.len_utf8()),
+        None => state.index,
+    }
+}
+
+/// state の状態 (次にマッチを試みる位置は from) から探索を続けた場合に、チャンクの結果をどこまで使えるかを返す  
+/// チャンクの探索の途中の状態で、次にマッチを試みる位置が from 以前で、次に見つけたマッチが from 以降から始まる場合は、  
+/// from 以降から始まる最初のマッチはそのマッチであり、その後の探索もチャンクの探索と一致する
+fn adopt(text: &str, chunk: &Chunk, state: SearchState, from: usize) -> Adopt {
+    for i in 0..=chunk.matches.len() {
+        // チャンクの探索で、i 番目のマッチを見つける前に、次にマッチを試みた位置
+        let tried: bool = match i.checked_sub(1).map(|j| chunk.matches[j]) {
+            None => chunk.start <= from,
+            // 空でないマッチの直後の空マッチは読み飛ばすため、その位置から始まる最初のマッチとは限らない
+            Some((start, end)) if start < end => end < from || (end == from && state.last_end == Some(end)),
+            Some((_, end)) => next_start(text, SearchState { index: end, resume: None, last_end: None, skip: true }) <= from,
+        };
+        if !tried {
+            continue;
+        }
+        match chunk.matches.get(i) {
+            Some((start, end)) if from <= *start => {
+                if start == end && state.last_end == Some(*start) {
+                    return Adopt::SkipEmpty(i);
+                }
+                return Adopt::Matches(i);
+            }
+            Some(_) => {}
+            None => {
+                return match chunk.resume {
+                    Some(resume) if from < resume => Adopt::NoMatch(resume),
+                    _ => Adopt::Unknown,
+                };
+            }
+        }
+    }
+    Adopt::Unknown
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use crate::matches::Match;
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_same_as_find_iter() {
+        let patterns: &[&str] = &["", "a+b", "ab|b", "(a|b)*c", "x*", "$", "(?m)^", "é+", "(?s).*", "a(b|bc)c", "(a)?(?(1)b|c)", "(?>a+)b"];
+        let texts: &[&str] = &["", "aab", "abcabbbc", "éaéébé", "a\nbc\n\nab", "xaxxbx", "abcc acbcab", "aaaaaaaaab"];
+        for pattern in patterns {
+            let re: Regex = Regex::new(pattern).unwrap();
+            for text in texts {
+                let expected: Vec<Match> = re.find_iter(text).collect();
+                for chunk_len in 1..6 {
+                    assert_eq!(re.find_iter_chunked(text, chunk_len), expected, "{} / {:?} / {}", pattern, text, chunk_len);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_match() {
+        // チャンクをいくつもまたぐマッチ
+        let re: Regex = Regex::new("a+b|a").unwrap();
+        let text: String = "a".repeat(50) + "b" + &"a".repeat(30);
+        let expected: Vec<Match> = re.find_iter(&text).collect();
+        assert_eq!(expected.len(), 31);
+        assert_eq!(re.find_iter_chunked(&text, 7), expected);
+        assert_eq!(re.find_iter_parallel(&text), expected);
+    }
+}
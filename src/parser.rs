@@ -7,42 +7,298 @@
 //!     Char(a),
 //!     Plus(Char(b)),
 //!     Star(Char(c)),
-//!     Or(
-//!         Seq(
-//!             Char(d),
-//!             Char(e),
-//!             Char(f)
-//!         ),
-//!         Seq(
-//!             Char(g),
-//!             Char(h),
-//!             Char(i)
+//!     Capture(1,
+//!         Or(
+//!             Seq(
+//!                 Char(d),
+//!                 Char(e),
+//!                 Char(f)
+//!             ),
+//!             Seq(
+//!                 Char(g),
+//!                 Char(h),
+//!                 Char(i)
+//!             )
 //!         )
 //!     )
 //! )
 //! ```
 
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter::Peekable;
+use core::ops::Range;
+use core::str::CharIndices;
+
+use crate::error::{Error, LimitKind, ParseError, ParseErrorKind};
+use crate::input::raw_byte;
+use crate::unicode::{simple_fold, Property};
+use crate::visit::{walk, Visit};
+
 /// AST の型
-#[derive(Debug, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum AST {
     Char(char),             // 通常の文字に対応する型
+    CharNoCase(char),       // 大文字・小文字を区別しない文字に対応する型 (小文字に畳み込んだ文字を持つ)
+    AnyChar(bool),          // '.'に対応する型 (bool は改行にもマッチするかどうか)
+    Property(Property, bool), // '\p{..}', '\P{..}'に対応する型 (bool は否定かどうか)
     Plus(Box<AST>),         // '+'に対応する型
     Star(Box<AST>),         // '*'に対応する型
     Question(Box<AST>),     // '?'に対応する型
     Or(Box<AST>, Box<AST>), // '|'に対応する型
     Seq(Vec<AST>),          // 連結に対応する型
+    Atomic(Box<AST>),       // 強欲な量指定子 ('*+' など) と '(?>...)' に対応する型
+    Assert(Assertion),      // '^', '$', '\A' などの位置のみにマッチする型
+    Capture(usize, Option<String>, Box<AST>), // キャプチャグループ '(...)', '(?P<name>...)' に対応する型 (番号と名前を持つ)
+    Conditional(usize, Box<AST>, Box<AST>), // '(?(1)then|else)' に対応する型
+    Empty,                  // 空の分岐 ('a|', '(|b)' など) に対応する型 (空文字列にマッチする)
 }
 
-/// エスケープ文字から AST を生成
-fn parse_escape(c: char) -> AST {
-    match c {
-        '\\' | '(' | ')' | '|' | '+' | '*' | '?'=> AST::Char(c),
-        _ => panic!(),
+impl AST {
+    /// AST を、1行に1つのノードを書き、子のノードを字下げした木の形の文字列にする (デバッグ用)  
+    /// 文字は Char(a) のように、Capture は番号と名前を付けて書く
+    #[cfg(test)]
+    pub fn to_tree(&self) -> String {
+        let mut tree: String = String::new();
+        self.write_tree(&mut tree, 0, None);
+        tree
+    }
+
+    /// to_tree と同様に木の形の文字列にし、各ノードの後ろに `Char(a) @ 0..1` のようにパターンの中の範囲を書く  
+    /// spans は、parse_with_spans で AST とともに得たもの
+    pub fn to_tree_with_spans(&self, spans: &Spans) -> String {
+        let mut tree: String = String::new();
+        self.write_tree(&mut tree, 0, Some(spans));
+        tree
+    }
+
+    fn write_tree(&self, tree: &mut String, depth: usize, spans: Option<&Spans>) {
+        let (label, children): (String, Vec<&AST>) = match self {
+            AST::Char(c) => (format!("Char({})", c.escape_debug()), vec![]),
+            AST::CharNoCase(c) => (format!("CharNoCase({})", c.escape_debug()), vec![]),
+            AST::AnyChar(false) => ("AnyChar".to_string(), vec![]),
+            AST::AnyChar(true) => ("AnyChar(dot_all)".to_string(), vec![]),
+            AST::Property(property, negated) => (format!("Property({})", property.escape(*negated)), vec![]),
+            AST::Plus(ast) => ("Plus".to_string(), vec![ast]),
+            AST::Star(ast) => ("Star".to_string(), vec![ast]),
+            AST::Question(ast) => ("Question".to_string(), vec![ast]),
+            AST::Or(left, right) => ("Or".to_string(), vec![left, right]),
+            AST::Seq(asts) => ("Seq".to_string(), asts.iter().collect()),
+            AST::Atomic(ast) => ("Atomic".to_string(), vec![ast]),
+            AST::Assert(assertion) => (format!("Assert({assertion:?})"), vec![]),
+            AST::Capture(index, None, ast) => (format!("Capture({index})"), vec![ast]),
+            AST::Capture(index, Some(name), ast) => (format!("Capture({index}, {name})"), vec![ast]),
+            AST::Conditional(group, then_ast, else_ast) => (format!("Conditional({group})"), vec![then_ast, else_ast]),
+            AST::Empty => ("Empty".to_string(), vec![]),
+        };
+        tree.extend(core::iter::repeat_n("  ", depth));
+        tree.push_str(&label);
+        if let Some(spans) = spans {
+            tree.push_str(&format!(" @ {}..{}", spans.span.start, spans.span.end));
+        }
+        tree.push('\n');
+        for (i, child) in children.into_iter().enumerate() {
+            child.write_tree(tree, depth + 1, spans.map(|spans| &spans.children[i]));
+        }
+    }
+}
+
+/// AST の各ノードに対応するパターンの範囲 (バイト単位) を、AST と同じ形の木で持つ型  
+/// children は、AST の子のノード (Seq の要素、Or の左右、Conditional の then と else など) と同じ順に並ぶ  
+/// 量指定子のノードは対象を含む範囲 (`a+` の全体)、グループのノードはカッコを含む範囲を持つ。`(?:...)` は中身のノードがカッコを含む範囲を持つ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spans {
+    pub span: Range<usize>,   // ノードに対応するパターンの範囲
+    pub children: Vec<Spans>, // 子のノードの範囲
+}
+
+impl Spans {
+    /// 子のノードの無いノードの範囲
+    fn leaf(span: Range<usize>) -> Spans {
+        Spans { span, children: Vec::new() }
+    }
+}
+
+/// 位置のみにマッチするアンカーの種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Assertion {
+    StartText,        // '^', '\A' : 入力の先頭
+    EndText,          // '$', '\z' : 入力の末尾
+    StartLine,        // '^' (?m) : 入力の先頭、または改行の直後
+    EndLine,          // '$' (?m) : 入力の末尾、または改行の直前
+    EndTextNewline,   // '\Z' : 入力の末尾、または末尾の改行の直前
+    StartSearch,      // '\G' : 探索を開始した位置 (直前のマッチの終了位置)
+}
+
+/// パターンの解釈を切り替えるフラグ  
+/// パターンの中では `(?imsx)` で、RegexBuilder では各メソッドで指定する
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flags {
+    pub case_insensitive: bool, // (?i) : 大文字・小文字を区別しない
+    pub multi_line: bool,       // (?m) : '^', '$' が各行の先頭・末尾にマッチする
+    pub dot_all: bool,          // (?s) : '.' が改行にもマッチする
+    pub extended: bool,         // (?x) : 空白と '#' からのコメントを無視する
+    pub lenient_escapes: bool,  // 未知のエスケープ文字をその文字として扱う (パターンの中では切り替えられない)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub raw_bytes: bool,        // 括弧の無い `\x80`..`\xFF` を UTF-8 として不正なバイトとする (bytes::Regex で使う。パターンの中では切り替えられない)
+}
+
+/// カッコの種類
+#[derive(Debug, PartialEq)]
+enum Group {
+    Capture(usize, Option<String>), // (...), (?P<name>...), (?<name>...)
+    NonCapture,         // (?:...)
+    Atomic,             // (?>...)
+    Conditional(usize), // (?(1)...)
+}
+
+/// カッコの中をパースする間、スタックに退避させるコンテキスト
+struct Context {
+    seq: Vec<AST>,
+    seq_or: Vec<AST>,
+    spans: Vec<Spans>,    // seq の各要素の範囲
+    spans_or: Vec<Spans>, // seq_or の各要素の範囲
+    branch_start: usize,  // seq の分岐の開始位置
+    flags: Flags,
+    group: Group,
+    pos: usize, // `(` の位置
+    depth: Depth,
+}
+
+/// パース中のコンテキストの AST のネストの深さ
+#[derive(Debug, Default, Clone, Copy)]
+struct Depth {
+    max: usize,      // コンテキストの要素の深さの最大値
+    last: usize,     // 最後の要素の深さ (量指定子を付けると深くなる)
+    branches: usize, // コンテキストの `|` の数
+}
+
+impl Depth {
+    /// 要素 (深さは depth) を追加する
+    fn push(&mut self, depth: usize) {
+        self.last = depth;
+        self.max = self.max.max(depth);
+    }
+
+    /// コンテキストをまとめた AST (Seq と Or) の深さ  
+    /// Or は fold_or で分岐を半分ずつに分けるため、分岐の数の log2 だけ深くなる
+    fn total(&self) -> usize {
+        self.max + (self.branches + 1).next_power_of_two().trailing_zeros() as usize + 1
+    }
+}
+
+/// パーサが特別に扱う文字 (`\` を前に付けると、その文字自身にマッチする)  
+/// `#` と空白は、フリースペーシングモードでのみ特別に扱う
+const META_CHARACTERS: &[char] = &['\\', '(', ')', '|', '+', '*', '?', '#', ' ', '^', '$', '.'];
+
+/// エスケープ文字から AST を生成  
+/// pos はエラーを報告する際の `\` の位置  
+/// 未知のエスケープ文字は、lenient が true の場合はその文字そのものとし (`\d` は `d`)、false の場合はエラーとする
+fn parse_escape(c: char, pos: usize, lenient: bool) -> Result<AST, ParseError> {
+    let ast: AST = match c {
+        c if META_CHARACTERS.contains(&c) => AST::Char(c),
+        'n' => AST::Char('\n'),
+        't' => AST::Char('\t'),
+        'r' => AST::Char('\r'),
+        '0' => AST::Char('\0'),
+        'A' => AST::Assert(Assertion::StartText),
+        'z' => AST::Assert(Assertion::EndText),
+        'Z' => AST::Assert(Assertion::EndTextNewline),
+        'G' => AST::Assert(Assertion::StartSearch),
+        _ if lenient => AST::Char(c),
+        _ => return Err(ParseError::new(ParseErrorKind::InvalidEscape(c), pos)),
+    };
+    Ok(ast)
+}
+
+/// `\p{..}`,`\P{..}` から AST を生成  
+/// `\pL` のように、1文字の名前は括弧を省略できる  
+/// `}` が無いまま式が終わった場合は、UnterminatedProperty のエラーとする
+fn parse_property(negated: bool, chars: &mut Peekable<CharIndices>, pos: usize) -> Result<AST, ParseError> {
+    let name: String = match chars.next() {
+        Some((_, '{')) => match parse_braced(chars) {
+            Some(name) => name,
+            None => return Err(ParseError::new(ParseErrorKind::UnterminatedProperty, pos)),
+        },
+        Some((_, c)) => c.to_string(),
+        None => return Err(ParseError::new(ParseErrorKind::InvalidEscape(if negated { 'P' } else { 'p' }), pos)),
+    };
+    match Property::lookup(&name) {
+        Some(property) => Ok(AST::Property(property, negated)),
+        None => Err(ParseError::new(ParseErrorKind::UnknownProperty(name), pos)),
+    }
+}
+
+/// `{` の後を `}` まで読み、間の文字列を返す (`}` が無いまま式が終わった場合は None)
+fn parse_braced(chars: &mut Peekable<CharIndices>) -> Option<String> {
+    let mut text: String = String::new();
+    for (_, c) in chars.by_ref() {
+        if c == '}' {
+            return Some(text);
+        }
+        text.push(c);
+    }
+    None
+}
+
+/// `\xHH`,`\x{HHHH}`,`\u{HHHH}` から AST を生成  
+/// 括弧が無い `\x` は2桁の16進数、括弧で囲んだ場合は1〜6桁の16進数を Unicode スカラ値として扱う (`\u` は括弧が必須)  
+/// `}` が無いまま式が終わった場合も、InvalidHex のエラーとする  
+/// raw_bytes が true の場合、括弧が無い `\x80`..`\xFF` は、その値の UTF-8 として不正なバイトにマッチする文字とする
+fn parse_hex(c: char, chars: &mut Peekable<CharIndices>, pos: usize, raw_bytes: bool) -> Result<AST, ParseError> {
+    let braced: bool = chars.next_if(|(_, c)| *c == '{').is_some();
+    let digits: String = match (c, braced) {
+        ('x', false) => chars.by_ref().map(|(_, c)| c).take(2).collect(),
+        (_, true) => match parse_braced(chars) {
+            Some(digits) => digits,
+            None => return Err(ParseError::new(ParseErrorKind::InvalidHex, pos)),
+        },
+        _ => return Err(ParseError::new(ParseErrorKind::InvalidHex, pos)),
+    };
+
+    let is_valid: bool = if braced { (1..=6).contains(&digits.len()) } else { digits.len() == 2 };
+    if !is_valid || !digits.chars().all(|d| d.is_ascii_hexdigit()) {
+        return Err(ParseError::new(ParseErrorKind::InvalidHex, pos))
+    }
+
+    match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+        Some(c) if raw_bytes && !braced && !c.is_ascii() => Ok(AST::Char(raw_byte(c as u8))),
+        Some(c) => Ok(AST::Char(c)),
+        None => Err(ParseError::new(ParseErrorKind::InvalidHex, pos)),
+    }
+}
+
+/// `\Q` から `\E` (または式の終わり) までを、リテラルの文字の AST として生成 (各文字の位置とともに返す)
+fn parse_quote(chars: &mut Peekable<CharIndices>) -> Vec<(AST, usize)> {
+    let mut seq: Vec<(AST, usize)> = Vec::new();
+    while let Some((pos, c)) = chars.next() {
+        if c == '\\' && chars.next_if(|(_, c)| *c == 'E').is_some() {
+            break;
+        }
+        seq.push((AST::Char(c), pos));
+    }
+    seq
+}
+
+/// 文字の AST を生成  
+/// 大文字・小文字を区別しない場合、大文字・小文字のある文字は小文字に畳み込んだ CharNoCase とする
+pub(crate) fn literal(c: char, flags: Flags) -> AST {
+    let has_case: bool = simple_fold(c) != c || !c.to_uppercase().eq(core::iter::once(c));
+    if flags.case_insensitive && has_case {
+        AST::CharNoCase(simple_fold(c))
+    } else {
+        AST::Char(c)
     }
 }
 
 /// `+`,`*`,`?`から AST を生成
-fn parse_qualifier(c: char, prev: AST) -> AST{
+pub fn parse_qualifier(c: char, prev: AST) -> AST{
     match c {
         '+' => AST::Plus(Box::new(prev)),
         '*' => AST::Star(Box::new(prev)),
@@ -51,73 +307,393 @@ fn parse_qualifier(c: char, prev: AST) -> AST{
     }
 }
 
-/// `|` を含む式から AST を生成
-fn fold_or(mut seq_or: Vec<AST>) -> AST {
-    if seq_or.len() > 1 {
-        let mut ast: AST = seq_or.pop().unwrap();
-        seq_or.reverse();
-        for s in seq_or {
-            ast = AST::Or(Box::new(s), Box::new(ast));
+/// `(?ix)` のようなフラグの指定をパースし、`(?` 以降を `)` まで読み進める  
+/// `(?-x)` のように `-` 以降に書いたフラグは無効にする
+fn parse_flags(chars: &mut Peekable<CharIndices>, flags: &mut Flags, pos: usize) -> Result<(), ParseError> {
+    let mut enable: bool = true;
+    loop {
+        match chars.next() {
+            Some((_, 'i')) => flags.case_insensitive = enable,
+            Some((_, 'm')) => flags.multi_line = enable,
+            Some((_, 's')) => flags.dot_all = enable,
+            Some((_, 'x')) => flags.extended = enable,
+            Some((_, '-')) if enable => enable = false,
+            Some((_, ')')) => return Ok(()),
+            Some((pos, c)) => return Err(ParseError::new(ParseErrorKind::UnknownFlag(c), pos)),
+            None => return Err(ParseError::new(ParseErrorKind::UnbalancedParen, pos)),
+        }
+    }
+}
+
+/// `(?P<name>`, `(?<name>` のグループ名を `>` まで読み、名前を返す  
+/// 名前は英数字と `_` からなり、数字で始まってはならない
+fn parse_group_name(chars: &mut Peekable<CharIndices>, names: &[String], pos: usize) -> Result<String, ParseError> {
+    let mut name: String = String::new();
+    loop {
+        match chars.next() {
+            Some((_, '>')) => break,
+            Some((_, c)) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+            _ => return Err(ParseError::new(ParseErrorKind::InvalidGroupName, pos)),
+        }
+    }
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(ParseError::new(ParseErrorKind::InvalidGroupName, pos));
+    }
+    if names.contains(&name) {
+        return Err(ParseError::new(ParseErrorKind::DuplicateGroupName(name), pos));
+    }
+    Ok(name)
+}
+
+/// `(` の直後を読み、カッコの種類を判定する  
+/// キャプチャグループの場合は group_count をインクリメントし、グループの番号とする  
+/// 名前付きのグループの場合は、names に名前を追加する  
+/// `(?i)` のようにフラグを指定するだけのカッコは、フラグを更新して None を返す  
+/// `(?#...)` はコメントとして `)` まで読み飛ばし、None を返す  
+/// pos はエラーを報告する際の `(` の位置
+fn parse_group(
+    chars: &mut Peekable<CharIndices>,
+    flags: &mut Flags,
+    group_count: &mut usize,
+    names: &mut Vec<String>,
+    pos: usize,
+) -> Result<Option<Group>, ParseError> {
+    if chars.next_if(|(_, c)| *c == '?').is_none() {
+        *group_count += 1;
+        return Ok(Some(Group::Capture(*group_count, None)));
+    }
+    let is_python_name: bool = chars.next_if(|(_, c)| *c == 'P').is_some();
+    if chars.next_if(|(_, c)| *c == '<').is_some() {
+        let name: String = parse_group_name(chars, names, pos)?;
+        names.push(name.clone());
+        *group_count += 1;
+        return Ok(Some(Group::Capture(*group_count, Some(name))));
+    }
+    if is_python_name {
+        // `(?P` の後は `<` のみ (`(?P)` などはフラグとして読まない)
+        return Err(ParseError::new(ParseErrorKind::InvalidGroupName, pos));
+    }
+    if chars.next_if(|(_, c)| *c == ':').is_some() {
+        return Ok(Some(Group::NonCapture));
+    }
+    if chars.next_if(|(_, c)| *c == '>').is_some() {
+        return Ok(Some(Group::Atomic));
+    }
+    if chars.next_if(|(_, c)| *c == '(').is_some() {
+        let digits: String = chars.by_ref().map(|(_, c)| c).take_while(|c| *c != ')').collect();
+        return match digits.parse::<usize>() {
+            Ok(group) if group > 0 => Ok(Some(Group::Conditional(group))),
+            _ => Err(ParseError::new(ParseErrorKind::InvalidConditional, pos)),
+        };
+    }
+    if chars.next_if(|(_, c)| *c == '#').is_some() {
+        if !chars.any(|(_, c)| c == ')') {
+            // コメントが閉じられていない
+            return Err(ParseError::new(ParseErrorKind::UnbalancedParen, pos));
         }
-        ast
+        return Ok(None);
+    }
+    parse_flags(chars, flags, pos)?;
+    Ok(None)
+}
+
+/// 条件分岐 `(?(1)then|else)` の AST を生成  
+/// else の分岐が無い場合は、Empty (空文字列にマッチする) を else とする
+fn fold_conditional(group: usize, mut seq_or: Vec<AST>, pos: usize) -> Result<AST, ParseError> {
+    if seq_or.len() > 2 {
+        // 分岐は then と else の2つまで
+        return Err(ParseError::new(ParseErrorKind::InvalidConditional, pos))
+    }
+    let mut branches = seq_or.drain(..);
+    let then_ast: AST = branches.next().unwrap_or(AST::Empty);
+    let else_ast: AST = branches.next().unwrap_or(AST::Empty);
+    Ok(AST::Conditional(group, Box::new(then_ast), Box::new(else_ast)))
+}
+
+/// `|` を含む式から AST を生成  
+/// 分岐が多い場合も AST が深くならないよう、分岐を半分ずつに分けて Or を入れ子にする (分岐の優先度の順は変わらない)  
+/// 分岐が1つも無い場合は Empty を返す
+pub fn fold_or(mut seq_or: Vec<AST>) -> AST {
+    if seq_or.len() > 1 {
+        let right: Vec<AST> = seq_or.split_off(seq_or.len() / 2);
+        AST::Or(Box::new(fold_or(seq_or)), Box::new(fold_or(right)))
     } else {
-        seq_or.pop().unwrap()
+        seq_or.pop().unwrap_or(AST::Empty)
     }
 }
 
-/// 式をパースし、ASTを生成
-pub fn parse(pattern: &str) -> AST {
+/// `|` で区切った1つの分岐の AST を生成  
+/// 空の分岐 (`a|` の `|` の後など) は Empty とする
+fn fold_branch(seq: Vec<AST>) -> AST {
+    if seq.is_empty() {
+        AST::Empty
+    } else {
+        AST::Seq(seq)
+    }
+}
+
+/// fold_or で生成した AST と同じ形に、分岐の範囲をまとめる
+fn fold_or_spans(mut spans_or: Vec<Spans>) -> Spans {
+    if spans_or.len() > 1 {
+        let right: Vec<Spans> = spans_or.split_off(spans_or.len() / 2);
+        let (left, right): (Spans, Spans) = (fold_or_spans(spans_or), fold_or_spans(right));
+        Spans { span: left.span.start..right.span.end, children: vec![left, right] }
+    } else {
+        spans_or.pop().unwrap_or_default()
+    }
+}
+
+/// text の中のメタ文字をすべてエスケープし、text そのものにマッチするパターンを返す  
+/// 空白以外の空白文字は、フリースペーシングモードでも無視されないよう `\u{..}` で表す
+///
+/// ```
+/// use small_regex::{escape, Regex};
+///
+/// assert_eq!(escape("1+1=2?"), "1\\+1=2\\?");
+/// let re = Regex::new(&format!("^{}$", escape("(a|b)*"))).unwrap();
+/// assert!(re.is_match("(a|b)*"));
+/// ```
+pub fn escape(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        if META_CHARACTERS.contains(&c) {
+            escaped.push('\\');
+            escaped.push(c);
+        } else if c.is_whitespace() {
+            escaped.push_str(&format!("\\u{{{:x}}}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// 式をパースし、ASTを生成  
+/// 式が不正な場合は、不正な箇所の位置 (バイト単位) を含む ParseError を返す  
+/// フラグはすべて無効の状態から始める
+#[cfg(test)]
+pub fn parse(pattern: &str) -> Result<AST, ParseError> {
+    match parse_with_flags(pattern, Flags::default(), usize::MAX) {
+        Ok(ast) => Ok(ast),
+        Err(Error::Parse(e)) => Err(e),
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// flags を初期値として式をパースし、ASTを生成  
+/// 式の中の `(?i)` などで指定したフラグは、flags より優先する  
+/// AST のネストの深さが nest_limit を超える場合は、AST を生成する前に Error::LimitExceeded を返す
+pub fn parse_with_flags(pattern: &str, flags: Flags, nest_limit: usize) -> Result<AST, Error> {
+    parse_with_spans(pattern, flags, nest_limit).map(|(ast, _)| ast)
+}
+
+/// parse_with_flags と同様に式をパースし、AST と、AST の各ノードに対応するパターンの範囲を返す
+pub fn parse_with_spans(pattern: &str, mut flags: Flags, nest_limit: usize) -> Result<(AST, Spans), Error> {
     let mut seq: Vec<AST> = Vec::new(); // 現在のコンテキスト
     let mut seq_or: Vec<AST> = Vec::new(); // Orのコンテキスト
-    let mut stack: Vec<(Vec<AST>, Vec<AST>)> = Vec::new(); // コンテキストを一時的に退避させるスタック
-    let mut is_escape: bool = false; // エスケープ文字を処理中かどうか
+    let mut spans: Vec<Spans> = Vec::new(); // seq の各要素の範囲
+    let mut spans_or: Vec<Spans> = Vec::new(); // seq_or の各要素の範囲
+    let mut branch_start: usize = 0; // seq の分岐の開始位置
+    let mut stack: Vec<Context> = Vec::new(); // コンテキストを一時的に退避させるスタック
+    let mut escape_pos: Option<usize> = None; // エスケープ文字を処理中の場合は `\` の位置
+    let mut group_count: usize = 0; // キャプチャグループの数
+    let mut names: Vec<String> = Vec::new(); // グループの名前
+    let mut references: Vec<(usize, usize)> = Vec::new(); // 条件分岐で参照しているグループの番号と、条件分岐の位置
+    let mut depth: Depth = Depth::default(); // 現在のコンテキストのネストの深さ
+    let check = |depth: usize| -> Result<(), Error> {
+        if depth > nest_limit {
+            return Err(Error::LimitExceeded { kind: LimitKind::NestDepth, limit: nest_limit });
+        }
+        Ok(())
+    };
 
-    for c in pattern.chars() {
-        if is_escape {
-            is_escape = false;
-            seq.push(parse_escape(c));
+    let mut chars: Peekable<CharIndices> = pattern.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        if let Some(escape_pos) = escape_pos.take() {
+            if c == 'Q' {
+                for (ast, pos) in parse_quote(&mut chars) {
+                    if let AST::Char(c) = ast {
+                        seq.push(literal(c, flags));
+                        spans.push(Spans::leaf(pos..pos + c.len_utf8()));
+                    }
+                }
+                depth.push(1);
+                continue;
+            }
+            let ast: AST = match c {
+                'p' | 'P' => parse_property(c == 'P', &mut chars, escape_pos)?,
+                'x' | 'u' => parse_hex(c, &mut chars, escape_pos, flags.raw_bytes)?,
+                _ => parse_escape(c, escape_pos, flags.lenient_escapes)?,
+            };
+            seq.push(match ast {
+                AST::Char(c) => literal(c, flags),
+                // (?i) の中では、大文字・小文字で同一視する文字を加えたプロパティにする
+                AST::Property(property, negated) if flags.case_insensitive => {
+                    AST::Property(property.case_insensitive(), negated)
+                }
+                ast => ast,
+            });
+            spans.push(Spans::leaf(escape_pos..next_pos(&mut chars, pattern)));
+            depth.push(1);
             continue;
         }
+
+        // フリースペーシングモードでは、空白を無視し、`#` から行末まではコメントとして読み飛ばす
+        if flags.extended && c.is_whitespace() {
+            continue;
+        }
+        if flags.extended && c == '#' {
+            for (_, c) in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        if !matches!(c, '+' | '*' | '?' | '|' | '(' | ')' | '\\') {
+            depth.push(1);
+        }
         match c {
             '+' | '*' | '?' => {
-                let prev_ast: AST = seq.pop().unwrap();
-                let ast: AST = parse_qualifier(c, prev_ast);
+                let prev_ast: AST = seq.pop().ok_or(ParseError::new(ParseErrorKind::NothingToRepeat, pos))?;
+                let prev_spans: Spans = spans.pop().unwrap_or_default();
+                let mut ast: AST = parse_qualifier(c, prev_ast);
+                let mut ast_spans: Spans = Spans { span: prev_spans.span.start..pos + 1, children: vec![prev_spans] };
+                // 直後の `+` は強欲な量指定子として扱う
+                if chars.next_if(|(_, c)| *c == '+').is_some() {
+                    ast = AST::Atomic(Box::new(ast));
+                    ast_spans = Spans { span: ast_spans.span.start..pos + 2, children: vec![ast_spans] };
+                    depth.push(depth.last + 1);
+                }
                 seq.push(ast);
+                spans.push(ast_spans);
+                depth.push(depth.last + 1);
+                check(depth.last)?;
             }
             '|' => {
-                seq_or.push(AST::Seq(seq));
+                seq_or.push(fold_branch(seq));
+                spans_or.push(Spans { span: branch_start..pos, children: spans });
                 seq = Vec::new();
+                spans = Vec::new();
+                branch_start = pos + 1;
+                depth.branches += 1;
+                check(depth.total())?;
             }
             '(' => {
-                stack.push((seq, seq_or));
-                seq = Vec::new();
-                seq_or = Vec::new();
+                if let Some(group) = parse_group(&mut chars, &mut flags, &mut group_count, &mut names, pos)? {
+                    if let Group::Conditional(reference) = group {
+                        references.push((reference, pos));
+                    }
+                    stack.push(Context { seq, seq_or, spans, spans_or, branch_start, flags, group, pos, depth });
+                    seq = Vec::new();
+                    seq_or = Vec::new();
+                    spans = Vec::new();
+                    spans_or = Vec::new();
+                    branch_start = next_pos(&mut chars, pattern);
+                    depth = Depth::default();
+                    // 開いているグループの数だけ、AST は少なくとも深くなる
+                    check(stack.len())?;
+                }
             }
             ')' => {
-                let (mut prev, prev_or) = stack.pop().unwrap();
+                let mut prev: Context = stack.pop().ok_or(ParseError::new(ParseErrorKind::UnbalancedParen, pos))?;
 
-                if !seq.is_empty() {
-                    seq_or.push(AST::Seq(seq));
-                }
-                prev.push(fold_or(seq_or));
+                seq_or.push(fold_branch(seq));
+                spans_or.push(Spans { span: branch_start..pos, children: spans });
+                let group_span: Range<usize> = prev.pos..pos + 1;
+                let (ast, ast_spans): (AST, Spans) = match prev.group {
+                    Group::Capture(index, name) => (
+                        AST::Capture(index, name, Box::new(fold_or(seq_or))),
+                        Spans { span: group_span, children: vec![fold_or_spans(spans_or)] },
+                    ),
+                    Group::NonCapture => (fold_or(seq_or), Spans { span: group_span, ..fold_or_spans(spans_or) }),
+                    Group::Atomic => (
+                        AST::Atomic(Box::new(fold_or(seq_or))),
+                        Spans { span: group_span, children: vec![fold_or_spans(spans_or)] },
+                    ),
+                    Group::Conditional(reference) => {
+                        // else の分岐が無い場合の Empty は、`)` の直前の空の範囲とする
+                        spans_or.resize(2, Spans::leaf(pos..pos));
+                        (fold_conditional(reference, seq_or, prev.pos)?, Spans { span: group_span, children: spans_or })
+                    }
+                };
+                prev.seq.push(ast);
+                prev.spans.push(ast_spans);
 
-                seq = prev;
-                seq_or = prev_or;
+                seq = prev.seq;
+                seq_or = prev.seq_or;
+                spans = prev.spans;
+                spans_or = prev.spans_or;
+                branch_start = prev.branch_start;
+                flags = prev.flags;
+                // グループの AST (Capture など) の分だけ深くなる
+                let group_depth: usize = depth.total() + 1;
+                depth = prev.depth;
+                depth.push(group_depth);
+                check(group_depth)?;
             }
-            '\\' => is_escape = true,
-            _ => seq.push(AST::Char(c))
+            '^' if flags.multi_line => seq.push(AST::Assert(Assertion::StartLine)),
+            '^' => seq.push(AST::Assert(Assertion::StartText)),
+            '$' if flags.multi_line => seq.push(AST::Assert(Assertion::EndLine)),
+            '$' => seq.push(AST::Assert(Assertion::EndText)),
+            '.' => seq.push(AST::AnyChar(flags.dot_all)),
+            '\\' => escape_pos = Some(pos),
+            _ => seq.push(literal(c, flags))
         };
+        if seq.len() > spans.len() {
+            // 1文字からなる要素 (`.`, `^` や通常の文字) を追加した
+            spans.push(Spans::leaf(pos..pos + c.len_utf8()));
+        }
+    }
+
+    // 式が `\` で終わっている
+    if let Some(pos) = escape_pos {
+        return Err(ParseError::new(ParseErrorKind::TrailingBackslash, pos).into());
     }
 
     // stack が空ではない = 閉じカッコが足りない
-    if !stack.is_empty() {
-        panic!()
+    if let Some(context) = stack.pop() {
+        return Err(ParseError::new(ParseErrorKind::UnbalancedParen, context.pos).into());
+    }
+
+    // 条件分岐が存在しないグループを参照している
+    if let Some((group, pos)) = references.into_iter().find(|(group, _)| *group > group_count) {
+        return Err(ParseError::new(ParseErrorKind::UnknownGroup(group), pos).into());
     }
 
-    if !seq.is_empty() {
-        seq_or.push(AST::Seq(seq));
+    check(depth.total())?;
+    seq_or.push(fold_branch(seq));
+    spans_or.push(Spans { span: branch_start..pattern.len(), children: spans });
+    Ok((fold_or(seq_or), fold_or_spans(spans_or)))
+}
+
+/// 次に読む文字の位置を返す (式の終わりに達した場合は式の長さ)
+fn next_pos(chars: &mut Peekable<CharIndices>, pattern: &str) -> usize {
+    chars.peek().map_or(pattern.len(), |(pos, _)| *pos)
+}
+
+/// AST に含まれるキャプチャグループの名前を、グループの番号の順に返す  
+/// 0番目 (マッチ全体) と名前の無いグループは None とする
+pub fn capture_names(ast: &AST) -> Vec<Option<String>> {
+    let mut names: CaptureNames = CaptureNames(vec![None]);
+    names.visit(ast);
+    names.0
+}
+
+/// キャプチャグループの名前を、グループの番号の位置に集める
+struct CaptureNames(Vec<Option<String>>);
+
+impl Visit for CaptureNames {
+    fn visit(&mut self, ast: &AST) {
+        if let AST::Capture(index, name, _) = ast {
+            if self.0.len() <= *index {
+                self.0.resize(index + 1, None);
+            }
+            self.0[*index] = name.clone();
+        }
+        walk(self, ast);
     }
-    fold_or(seq_or)
 }
 
 // --- テストコード ---
@@ -125,18 +701,152 @@ pub fn parse(pattern: &str) -> AST {
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::{parse, AST};
+    use crate::error::{ParseError, ParseErrorKind};
+    use crate::input::raw_byte;
+    use crate::parser::{escape, parse, parse_with_flags, parse_with_spans, Assertion, Flags, Spans, AST};
 
     use super::parse_qualifier;
 
     #[test]
     fn test_escape() {
         assert_eq!(
-            parse("\\*"),
+            parse("\\*").unwrap(),
             AST::Seq(vec![AST::Char('*')])
         );
     }
 
+    #[test]
+    fn test_escape_text() {
+        assert_eq!(escape("a.b*c"), "a\\.b\\*c");
+        assert_eq!(escape("a\tb"), "a\\u{9}b");
+
+        // エスケープした文字列は、どのフラグでも元の文字列そのものとしてパースされる
+        let text: &str = "\\()|+*?# ^$.\n\u{3000}{}[]あ";
+        let flags: Flags = Flags { extended: true, multi_line: true, ..Flags::default() };
+        assert_eq!(
+            parse_with_flags(&escape(text), flags, usize::MAX).unwrap(),
+            AST::Seq(text.chars().map(AST::Char).collect())
+        );
+    }
+
+    #[test]
+    fn test_assertion() {
+        assert_eq!(
+            parse("^\\Aa$\\z\\Z\\$").unwrap(),
+            AST::Seq(vec![
+                AST::Assert(Assertion::StartText),
+                AST::Assert(Assertion::StartText),
+                AST::Char('a'),
+                AST::Assert(Assertion::EndText),
+                AST::Assert(Assertion::EndText),
+                AST::Assert(Assertion::EndTextNewline),
+                AST::Char('$'),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(
+            parse("\\Qa*(\\E+").unwrap(),
+            AST::Seq(vec![AST::Char('a'), AST::Char('*'), AST::Plus(Box::new(AST::Char('(')))])
+        );
+        // \E が無い場合は式の終わりまでをリテラルとして扱う
+        assert_eq!(
+            parse("a\\Q|b").unwrap(),
+            AST::Seq(vec![AST::Char('a'), AST::Char('|'), AST::Char('b')])
+        );
+    }
+
+    #[test]
+    fn test_control_escape() {
+        assert_eq!(
+            parse("\\n\\t\\r\\0").unwrap(),
+            AST::Seq(vec![AST::Char('\n'), AST::Char('\t'), AST::Char('\r'), AST::Char('\0')])
+        );
+    }
+
+    #[test]
+    fn test_extended() {
+        // 空白とコメントは無視され、グループを抜けるとフラグが元に戻る
+        let pattern: &str = "(?x) a b # comment\n ( c\\ (?-x)d e ) f";
+        let expect_ast: AST = AST::Seq(vec![
+            AST::Char('a'), AST::Char('b'),
+            AST::Capture(1, None, Box::new(AST::Seq(vec![
+                AST::Char('c'), AST::Char(' '), AST::Char('d'), AST::Char(' '), AST::Char('e'), AST::Char(' ')
+            ]))),
+            AST::Char('f'),
+        ]);
+        assert_eq!(parse(pattern).unwrap(), expect_ast);
+    }
+
+    #[test]
+    fn test_flags() {
+        // (?i) は大文字・小文字のある文字だけを畳み込む
+        assert_eq!(
+            parse("(?i)aB1(?-i)c").unwrap(),
+            AST::Seq(vec![AST::CharNoCase('a'), AST::CharNoCase('b'), AST::Char('1'), AST::Char('c')])
+        );
+        assert_eq!(
+            parse("(?ms)^.$(?-ms)^.$").unwrap(),
+            AST::Seq(vec![
+                AST::Assert(Assertion::StartLine), AST::AnyChar(true), AST::Assert(Assertion::EndLine),
+                AST::Assert(Assertion::StartText), AST::AnyChar(false), AST::Assert(Assertion::EndText),
+            ])
+        );
+        // 引数で指定したフラグを初期値とする
+        let flags: Flags = Flags { case_insensitive: true, ..Flags::default() };
+        assert_eq!(
+            parse_with_flags("A\\.", flags, usize::MAX).unwrap(),
+            AST::Seq(vec![AST::CharNoCase('a'), AST::Char('.')])
+        );
+    }
+
+    #[test]
+    fn test_lenient_escapes() {
+        // 未知のエスケープ文字は、その文字そのものにマッチする
+        let flags: Flags = Flags { lenient_escapes: true, ..Flags::default() };
+        assert_eq!(
+            parse_with_flags("\\d\\n\\.", flags, usize::MAX).unwrap(),
+            AST::Seq(vec![AST::Char('d'), AST::Char('\n'), AST::Char('.')])
+        );
+        let flags: Flags = Flags { lenient_escapes: true, case_insensitive: true, ..Flags::default() };
+        assert_eq!(parse_with_flags("\\D", flags, usize::MAX).unwrap(), AST::Seq(vec![AST::CharNoCase('d')]));
+        // 16進数のエスケープの誤りは、エスケープ文字が既知のためエラーのまま
+        let flags: Flags = Flags { lenient_escapes: true, ..Flags::default() };
+        assert_eq!(parse_with_flags("\\xZ", flags, usize::MAX), Err(ParseError::new(ParseErrorKind::InvalidHex, 0).into()));
+    }
+
+    #[test]
+    fn test_hex_escape() {
+        assert_eq!(
+            parse("\\x41\\u{1F600}").unwrap(),
+            AST::Seq(vec![AST::Char('A'), AST::Char('😀')])
+        );
+        assert_eq!(
+            parse("\\x{41}\\x{1f600}\\x{0}").unwrap(),
+            AST::Seq(vec![AST::Char('A'), AST::Char('😀'), AST::Char('\0')])
+        );
+        // bytes::Regex では、括弧の無い 0x80 以上の値は UTF-8 として不正なバイトとする
+        let flags: Flags = Flags { raw_bytes: true, ..Flags::default() };
+        assert_eq!(
+            parse_with_flags("\\x41\\xff\\x{ff}", flags, usize::MAX).unwrap(),
+            AST::Seq(vec![AST::Char('A'), AST::Char(raw_byte(0xff)), AST::Char('ÿ')])
+        );
+        assert_eq!(parse("\\xff").unwrap(), AST::Seq(vec![AST::Char('ÿ')]));
+    }
+
+    #[test]
+    fn test_hex_escape_invalid() {
+        assert_eq!(parse("a\\u{D800}"), Err(ParseError::new(ParseErrorKind::InvalidHex, 1)));
+        assert_eq!(parse("\\x4"), Err(ParseError::new(ParseErrorKind::InvalidHex, 0)));
+        // `}` が無い
+        assert_eq!(parse("\\u{41"), Err(ParseError::new(ParseErrorKind::InvalidHex, 0)));
+        assert_eq!(parse("a\\x{41"), Err(ParseError::new(ParseErrorKind::InvalidHex, 1)));
+        assert_eq!(parse("\\x{}"), Err(ParseError::new(ParseErrorKind::InvalidHex, 0)));
+        assert_eq!(parse("\\u41"), Err(ParseError::new(ParseErrorKind::InvalidHex, 0)));
+    }
+
     #[test]
     fn test_qualifier() {
         let plus_ast: AST = AST::Plus(Box::new(AST::Char('a')));
@@ -149,19 +859,269 @@ mod tests {
         assert_eq!(parse_qualifier('?', AST::Char('a')), question_ast);
     }
 
+    #[test]
+    fn test_possessive() {
+        let expect_ast: AST = AST::Seq(vec![
+            AST::Atomic(Box::new(AST::Star(Box::new(AST::Char('a'))))),
+            AST::Atomic(Box::new(AST::Plus(Box::new(AST::Char('b'))))),
+        ]);
+        assert_eq!(parse("a*+b++").unwrap(), expect_ast);
+    }
+
+    #[test]
+    fn test_comment_group() {
+        assert_eq!(
+            parse("a(?# comment (x|y)b").unwrap(),
+            AST::Seq(vec![AST::Char('a'), AST::Char('b')])
+        );
+    }
+
+    #[test]
+    fn test_comment_group_unclosed() {
+        assert_eq!(parse("a(?# comment"), Err(ParseError::new(ParseErrorKind::UnbalancedParen, 1)));
+    }
+
+    #[test]
+    fn test_atomic_group() {
+        let expect_ast: AST = AST::Seq(vec![
+            AST::Atomic(Box::new(AST::Or(
+                Box::new(AST::Seq(vec![AST::Char('a'), AST::Char('b')])),
+                Box::new(AST::Seq(vec![AST::Char('a')])),
+            ))),
+            AST::Char('c'),
+        ]);
+        assert_eq!(parse("(?>ab|a)c").unwrap(), expect_ast);
+    }
+
+    #[test]
+    fn test_conditional() {
+        let expect_ast: AST = AST::Seq(vec![
+            AST::Question(Box::new(AST::Capture(1, None, Box::new(AST::Seq(vec![AST::Char('<')]))))),
+            AST::Conditional(
+                1,
+                Box::new(AST::Seq(vec![AST::Char('a'), AST::Char('>')])),
+                Box::new(AST::Seq(vec![AST::Char('b')])),
+            ),
+        ]);
+        assert_eq!(parse("(<)?(?(1)a>|b)").unwrap(), expect_ast);
+
+        // else が無い場合
+        let expect_ast: AST = AST::Seq(vec![
+            AST::Capture(1, None, Box::new(AST::Seq(vec![AST::Char('a')]))),
+            AST::Conditional(1, Box::new(AST::Seq(vec![AST::Char('b')])), Box::new(AST::Empty)),
+        ]);
+        assert_eq!(parse("(a)(?(1)b)").unwrap(), expect_ast);
+
+        // 非キャプチャグループは番号を持たない
+        let expect_ast: AST = AST::Seq(vec![
+            AST::Seq(vec![AST::Char('a')]),
+            AST::Capture(1, None, Box::new(AST::Seq(vec![AST::Char('b')]))),
+        ]);
+        assert_eq!(parse("(?:a)(b)").unwrap(), expect_ast);
+    }
+
+    #[test]
+    fn test_named_group() {
+        let expect_ast: AST = AST::Seq(vec![
+            AST::Capture(1, Some("first".to_string()), Box::new(AST::Seq(vec![AST::Char('a')]))),
+            AST::Capture(2, None, Box::new(AST::Seq(vec![AST::Char('b')]))),
+            AST::Capture(3, Some("third".to_string()), Box::new(AST::Seq(vec![AST::Char('c')]))),
+        ]);
+        assert_eq!(parse("(?P<first>a)(b)(?<third>c)").unwrap(), expect_ast);
+
+        assert_eq!(parse("(?P<1a>a)"), Err(ParseError::new(ParseErrorKind::InvalidGroupName, 0)));
+        assert_eq!(parse("(?<>a)"), Err(ParseError::new(ParseErrorKind::InvalidGroupName, 0)));
+        assert_eq!(parse("(?P)a"), Err(ParseError::new(ParseErrorKind::InvalidGroupName, 0)));
+        assert_eq!(parse("b(?P=x)"), Err(ParseError::new(ParseErrorKind::InvalidGroupName, 1)));
+        assert_eq!(parse("(?P"), Err(ParseError::new(ParseErrorKind::InvalidGroupName, 0)));
+        assert_eq!(
+            parse("(?<x>a)(?<x>b)"),
+            Err(ParseError::new(ParseErrorKind::DuplicateGroupName("x".to_string()), 7))
+        );
+    }
+
+    #[test]
+    fn test_conditional_invalid() {
+        assert_eq!(parse("(a)(?(2)b|c)"), Err(ParseError::new(ParseErrorKind::UnknownGroup(2), 3)));
+        assert_eq!(parse("(a)(?(1)b|c|d)"), Err(ParseError::new(ParseErrorKind::InvalidConditional, 3)));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(parse("ab(c"), Err(ParseError::new(ParseErrorKind::UnbalancedParen, 2)));
+        assert_eq!(parse("ab\\d"), Err(ParseError::new(ParseErrorKind::InvalidEscape('d'), 2)));
+        assert_eq!(parse("ab\\"), Err(ParseError::new(ParseErrorKind::TrailingBackslash, 2)));
+        assert_eq!(parse("*a"), Err(ParseError::new(ParseErrorKind::NothingToRepeat, 0)));
+        assert_eq!(parse("(?q)a"), Err(ParseError::new(ParseErrorKind::UnknownFlag('q'), 2)));
+        assert_eq!(
+            parse("\\p{Unknown}"),
+            Err(ParseError::new(ParseErrorKind::UnknownProperty("Unknown".to_string()), 0))
+        );
+        assert_eq!(parse("a\\p{Lu"), Err(ParseError::new(ParseErrorKind::UnterminatedProperty, 1)));
+        assert_eq!(parse("\\P{"), Err(ParseError::new(ParseErrorKind::UnterminatedProperty, 0)));
+    }
+
+    #[test]
+    fn test_to_tree() {
+        let expect: &str = concat!(
+            "Seq\n",
+            "  Char(a)\n",
+            "  Plus\n",
+            "    CharNoCase(b)\n",
+            "  Capture(1, x)\n",
+            "    Or\n",
+            "      Seq\n",
+            "        Char(\\n)\n",
+            "        AnyChar\n",
+            "      Empty\n",
+            "  Conditional(1)\n",
+            "    Seq\n",
+            "      Assert(EndText)\n",
+            "    Empty\n",
+        );
+        assert_eq!(parse("a(?i)b+(?-i)(?<x>\\n.|)(?(1)$)").unwrap().to_tree(), expect);
+    }
+
+    #[test]
+    fn test_spans() {
+        let spans = |pattern: &str| -> String {
+            let (ast, spans): (AST, Spans) = parse_with_spans(pattern, Flags::default(), usize::MAX).unwrap();
+            ast.to_tree_with_spans(&spans)
+        };
+        let expect: &str = concat!(
+            "Seq @ 0..17\n",
+            "  Char(é) @ 0..2\n",
+            "  Atomic @ 2..5\n",
+            "    Plus @ 2..4\n",
+            "      Char(b) @ 2..3\n",
+            "  Capture(1) @ 5..11\n",
+            "    Or @ 6..10\n",
+            "      Seq @ 6..8\n",
+            "        Char(\\n) @ 6..8\n",
+            "      Seq @ 9..10\n",
+            "        AnyChar @ 9..10\n",
+            "  Conditional(1) @ 11..17\n",
+            "    Empty @ 16..16\n",
+            "    Empty @ 16..16\n",
+        );
+        assert_eq!(spans("éb++(\\n|.)(?(1))"), expect);
+        let expect: &str = concat!(
+            "Or @ 0..16\n",
+            "  Seq @ 0..12\n",
+            "    Star @ 0..12\n",
+            "      Or @ 0..11\n",
+            "        Seq @ 3..4\n",
+            "          Char(x) @ 3..4\n",
+            "        Seq @ 5..10\n",
+            "          Char(y) @ 7..8\n",
+            "  Or @ 13..16\n",
+            "    Seq @ 13..15\n",
+            "      Char(a) @ 13..14\n",
+            "      Char(b) @ 14..15\n",
+            "    Empty @ 16..16\n",
+        );
+        assert_eq!(spans("(?:x|\\Qy\\E)*|ab|"), expect);
+        assert_eq!(spans("(?i)\\Qa\\E"), "Seq @ 0..9\n  CharNoCase(a) @ 6..7\n");
+    }
+
+    #[test]
+    fn test_empty_branch() {
+        let a = || AST::Seq(vec![AST::Char('a')]);
+        assert_eq!(parse("").unwrap(), AST::Empty);
+        assert_eq!(parse("a|").unwrap(), AST::Or(Box::new(a()), Box::new(AST::Empty)));
+        assert_eq!(parse("|a").unwrap(), AST::Or(Box::new(AST::Empty), Box::new(a())));
+        assert_eq!(
+            parse("(|a)").unwrap(),
+            AST::Seq(vec![AST::Capture(1, None, Box::new(AST::Or(Box::new(AST::Empty), Box::new(a()))))])
+        );
+        assert_eq!(
+            parse("a||").unwrap(),
+            AST::Or(Box::new(a()), Box::new(AST::Or(Box::new(AST::Empty), Box::new(AST::Empty))))
+        );
+    }
+
+    #[test]
+    fn test_error_position() {
+        // 位置は文字数ではなくバイト単位のオフセット (é は 2 バイト、日 は 3 バイト)
+        let cases: &[(&str, ParseErrorKind, usize)] = &[
+            ("é(a", ParseErrorKind::UnbalancedParen, 2),
+            ("ab(c|(d)", ParseErrorKind::UnbalancedParen, 2),
+            ("日本)", ParseErrorKind::UnbalancedParen, 6),
+            ("日\\q", ParseErrorKind::InvalidEscape('q'), 3),
+            ("a(b\\y)", ParseErrorKind::InvalidEscape('y'), 3),
+            ("éé\\", ParseErrorKind::TrailingBackslash, 4),
+            ("é|*", ParseErrorKind::NothingToRepeat, 3),
+            ("(?:日+|?)", ParseErrorKind::NothingToRepeat, 8),
+            ("**", ParseErrorKind::NothingToRepeat, 0),
+        ];
+        for (pattern, kind, position) in cases {
+            let error: ParseError = parse(pattern).unwrap_err();
+            assert_eq!(error, ParseError::new(kind.clone(), *position), "{}", pattern);
+            assert!(pattern.is_char_boundary(error.position), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_unbalanced_paren() {
+        let cases: &[(&str, usize)] = &[(")", 0), ("ab)", 2), ("(()", 0), ("())", 2), ("a|)", 2), ("((a)))", 5), ("(?i))", 4)];
+        for (pattern, pos) in cases {
+            assert_eq!(parse(pattern), Err(ParseError::new(ParseErrorKind::UnbalancedParen, *pos)), "{}", pattern);
+        }
+        // 空のグループ
+        assert_eq!(parse("()"), Ok(AST::Seq(vec![AST::Capture(1, None, Box::new(AST::Empty))])));
+    }
+
+    #[test]
+    fn test_nothing_to_repeat() {
+        // 量指定子の前に式が無い位置 (式の先頭、`|` やグループの開始の直後)
+        let cases: &[(&str, usize)] = &[
+            ("*abc", 0),
+            ("+", 0),
+            ("?a", 0),
+            ("a|*", 2),
+            ("a|+b", 2),
+            ("(*a)", 1),
+            ("(a|?)", 3),
+            ("(?:*)", 3),
+            ("(?i)*", 4),
+            ("(?<n>+)", 5),
+            ("(?>*)", 3),
+            ("(a)(?(1)*|b)", 8),
+        ];
+        for (pattern, pos) in cases {
+            assert_eq!(parse(pattern), Err(ParseError::new(ParseErrorKind::NothingToRepeat, *pos)), "{}", pattern);
+        }
+    }
+
     #[test]
     fn test_parse() {
         // "abc(def|ghi)" が入力されたケース
         let expect_ast: AST = AST::Seq(vec![
             AST::Char('a'), AST::Char('b'), AST::Char('c'),
-            AST::Or(
+            AST::Capture(1, None, Box::new(AST::Or(
                 Box::new(AST::Seq(vec![AST::Char('d'), AST::Char('e'), AST::Char('f'),])),
                 Box::new(AST::Seq(vec![AST::Char('g'), AST::Char('h'), AST::Char('i'),]))
-            )
+            )))
         ]);
 
-        let actual_ast: AST = parse("abc(def|ghi)");
+        let actual_ast: AST = parse("abc(def|ghi)").unwrap();
     
         assert_eq!(actual_ast, expect_ast);
     }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_property() {
+        use crate::unicode::Property;
+
+        let lu: Property = Property::lookup("Lu").unwrap();
+        let greek: Property = Property::lookup("Greek").unwrap();
+        let letter: Property = Property::lookup("L").unwrap();
+        let expect_ast: AST = AST::Seq(vec![
+            AST::Property(lu, false),
+            AST::Property(greek, true),
+            AST::Property(letter, false),
+        ]);
+        assert_eq!(parse("\\p{Lu}\\P{Greek}\\pL").unwrap(), expect_ast);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,79 @@
+//! `&Regex` を文字列のメソッド (`str::find`, `str::split` など) のパターンとして使うための実装  
+//! `std::str::pattern` は nightly でのみ使えるため、`pattern` フィーチャが有効な場合のみ組み込む
+
+use core::str::pattern::{Pattern, SearchStep, Searcher};
+
+use crate::matches::Matches;
+use crate::regex::Regex;
+
+impl<'r> Pattern for &'r Regex {
+    type Searcher<'t> = RegexSearcher<'r, 't>;
+
+    fn into_searcher(self, haystack: &str) -> RegexSearcher<'r, '_> {
+        RegexSearcher {
+            haystack,
+            matches: self.find_iter(haystack),
+            last_step_end: 0,
+            next_match: None,
+        }
+    }
+}
+
+/// `&Regex` で haystack を探索する Searcher  
+/// find_iter で得たマッチの間を Reject、マッチした部分を Match として順に返す
+#[derive(Debug)]
+pub struct RegexSearcher<'r, 't> {
+    haystack: &'t str,
+    matches: Matches<'r, 't>,
+    last_step_end: usize,                // 直前に返した範囲の終了位置
+    next_match: Option<(usize, usize)>,  // Reject の次に返すマッチの範囲
+}
+
+unsafe impl<'t> Searcher<'t> for RegexSearcher<'_, 't> {
+    fn haystack(&self) -> &'t str {
+        self.haystack
+    }
+
+    fn next(&mut self) -> SearchStep {
+        if let Some((start, end)) = self.next_match.take() {
+            self.last_step_end = end;
+            return SearchStep::Match(start, end);
+        }
+        match self.matches.next() {
+            Some(m) if m.start() == self.last_step_end => {
+                self.last_step_end = m.end();
+                SearchStep::Match(m.start(), m.end())
+            }
+            Some(m) => {
+                // マッチの前の部分を Reject として返し、マッチは次に返す
+                self.next_match = Some((m.start(), m.end()));
+                let last: usize = self.last_step_end;
+                self.last_step_end = m.start();
+                SearchStep::Reject(last, m.start())
+            }
+            None if self.last_step_end < self.haystack.len() => {
+                let last: usize = self.last_step_end;
+                self.last_step_end = self.haystack.len();
+                SearchStep::Reject(last, self.haystack.len())
+            }
+            None => SearchStep::Done,
+        }
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_str_methods() {
+        let re: Regex = Regex::new("a+").unwrap();
+        assert_eq!("xyaab".find(&re), Some(2));
+        assert!(!"xyz".contains(&re));
+        assert_eq!("baab".split(&re).collect::<Vec<&str>>(), vec!["b", "b"]);
+        assert_eq!("aaxaa".trim_start_matches(&re), "xaa");
+        assert_eq!("xaay".replace(&re, "-"), "x-y");
+    }
+}
@@ -0,0 +1,446 @@
+//! 入力を1文字ずつ読み、到達しうるすべての分岐を同時に進める評価器 (Pike VM)  
+//! 各位置で状態 (evaluator::Loops) ごとに1度だけ命令を辿るため、最悪でも (状態の数 × 入力の長さ) に比例する時間で終わる  
+//! スレッドは優先度の順に並べ、バックトラックで評価した場合と同じマッチ (leftmost-first) とキャプチャを返す  
+//! バックトラックと同じく、同じ位置で始めた繰り返しの中からループの先頭に戻る分岐は失敗とする (空の文字列にマッチした繰り返しは最後の1回とする)
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::compiler::Instruction;
+use crate::evaluator::{is_assertion_satisfied, is_char_matched, next_pos, slots_len, EvalOptions, Loops, Partial};
+use crate::input::{Input, Symbol};
+use crate::literal::Prefilter;
+
+/// 命令列を Pike VM で評価できるかを判定する  
+/// Atomic, Condition はスレッドの集合では表せず、MatchPattern は RegexSet 専用のため、これらを含む場合はバックトラックで評価する
+pub fn is_supported<T>(instructions: &[Instruction<T>]) -> bool {
+    is_set_supported(instructions) && !instructions.iter().any(|inst| matches!(inst, Instruction::MatchPattern(_)))
+}
+
+/// RegexSet の命令列を search_set で評価できるか (Atomic, Condition を含まないか) を判定する
+pub fn is_set_supported<T>(instructions: &[Instruction<T>]) -> bool {
+    !instructions.iter().any(|inst| matches!(inst, Instruction::Atomic(_) | Instruction::Condition(_, _)))
+}
+
+/// search_partial の is_last が true の場合と同様に、from 以降の位置からマッチを探索する  
+/// index は探索を開始した位置 (\G がマッチする位置)  
+/// マッチした場合は slots にキャプチャグループがマッチした位置を記録する  
+/// スレッドの集合などの作業領域は scratch のものを使い回す
+#[allow(clippy::too_many_arguments)]
+pub fn search<T: Symbol, I: Input<T> + ?Sized>(
+    instructions: &[Instruction<T>],
+    input: &I,
+    index: usize,
+    from: usize,
+    slots: &mut [Option<usize>],
+    options: EvalOptions,
+    scratch: &mut Scratch,
+) -> Partial {
+    slots.fill(None);
+    let Scratch { current, next, thread_slots, stack, heads, loops } = scratch;
+    loops.build(instructions);
+    let last: usize = if options.anchored { index.min(input.len()) } else { input.len() };
+    let mut vm: PikeVM<T, I> = PikeVM {
+        instructions,
+        input,
+        start: index,
+        budget: options.backtrack_limit,
+        loops,
+        stack,
+        heads,
+    };
+    current.reset(loops.states(), slots.len());
+    next.reset(loops.states(), slots.len());
+    thread_slots.clear();
+    thread_slots.resize(slots.len(), None);
+    let best: &mut [Option<usize>] = slots; // これまでに見つけたマッチのスロット
+    let mut found: bool = false;
+
+    let prefilter: Prefilter = if options.anchored { Prefilter::None } else { Prefilter::new(instructions) };
+    let mut pos: usize = from;
+    loop {
+        if !found && pos <= last {
+            if current.is_empty() {
+                // 実行中のスレッドが無い場合は、接頭辞や先頭のバイトが現れる位置まで読み飛ばす
+                match prefilter.find(input, pos) {
+                    Some(candidate) => pos = candidate,
+                    None => break,
+                }
+            }
+            // 新しい開始位置のスレッドを、最も低い優先度で加える
+            thread_slots.fill(None);
+            thread_slots[0] = Some(pos);
+            if !vm.add_thread(current, 0, pos, thread_slots) {
+                return Partial::Aborted;
+            }
+        }
+        if current.is_empty() && (found || pos >= last) {
+            break;
+        }
+
+        let symbol: Option<(T, usize)> = input.char_at(pos);
+        let next_index: usize = symbol.as_ref().map_or(pos + 1, |(_, next)| *next);
+        for i in 0..current.pcs.len() {
+            let pc: usize = current.pcs[i];
+            let thread: &mut [Option<usize>] = current.thread_mut(i);
+            if options.longest && found && thread[0] > best[0] {
+                // 最も長いマッチを探す場合は、より後ろから始まるスレッドを捨てる
+                continue;
+            }
+            match &instructions[pc] {
+                Instruction::Match if options.anchored_end && pos != input.len() => {}
+                Instruction::Match => {
+                    thread[1] = Some(pos);
+                    if !options.longest {
+                        // より優先度の低いスレッドは捨てる
+                        best.copy_from_slice(thread);
+                        found = true;
+                        break;
+                    }
+                    if !found || thread[0] < best[0] || thread[1] > best[1] {
+                        best.copy_from_slice(thread);
+                        found = true;
+                    }
+                }
+                instruction => {
+                    if let Some((c, _)) = &symbol {
+                        if is_char_matched(instruction, c) && !vm.add_thread(next, pc + 1, next_index, thread) {
+                            return Partial::Aborted;
+                        }
+                    }
+                }
+            }
+        }
+        if symbol.is_none() && pos >= last {
+            break;
+        }
+        core::mem::swap(current, next);
+        next.clear();
+        pos = next_pos(input, pos);
+    }
+
+    if found {
+        Partial::Matched
+    } else {
+        Partial::NotMatched
+    }
+}
+
+/// RegexSet の命令列を評価し、input のいずれかの位置でマッチしたパターンの番号に true を立てた配列を返す  
+/// 各位置で開始位置のスレッドを加えながら入力を1度だけ読み、MatchPattern に到達したスレッドのパターンを記録する  
+/// 開始位置ごとに評価し直さないため、(命令の数 × 入力の長さ) に比例する時間で終わる。すべてのパターンがマッチした時点で打ち切る
+pub fn search_set<T: Symbol, I: Input<T> + ?Sized>(instructions: &[Instruction<T>], input: &I, patterns: usize) -> Vec<bool> {
+    let mut matched: Vec<bool> = vec![false; patterns];
+    if instructions.is_empty() {
+        return matched;
+    }
+    let mut remaining: usize = patterns; // まだマッチしていないパターンの数
+    let mut scratch: Scratch = Scratch::default();
+    let Scratch { current, next, thread_slots, stack, heads, loops } = &mut scratch;
+    loops.build(instructions);
+    let stride: usize = slots_len(instructions);
+    let mut vm: PikeVM<T, I> = PikeVM {
+        instructions,
+        input,
+        start: 0,
+        budget: None,
+        loops,
+        stack,
+        heads,
+    };
+    current.reset(loops.states(), stride);
+    next.reset(loops.states(), stride);
+    thread_slots.resize(stride, None);
+
+    let mut pos: usize = 0;
+    loop {
+        // 新しい開始位置のスレッドを加える (マッチの位置は返さないため、優先度は問わない)
+        thread_slots.fill(None);
+        vm.add_thread(current, 0, pos, thread_slots);
+
+        let symbol: Option<(T, usize)> = input.char_at(pos);
+        let next_index: usize = symbol.as_ref().map_or(pos + 1, |(_, next)| *next);
+        for i in 0..current.pcs.len() {
+            let pc: usize = current.pcs[i];
+            match &instructions[pc] {
+                Instruction::MatchPattern(id) => {
+                    if !matched[*id] {
+                        matched[*id] = true;
+                        remaining -= 1;
+                    }
+                }
+                instruction => {
+                    if let Some((c, _)) = &symbol {
+                        if is_char_matched(instruction, c) {
+                            vm.add_thread(next, pc + 1, next_index, current.thread_mut(i));
+                        }
+                    }
+                }
+            }
+        }
+        if remaining == 0 || pos >= input.len() {
+            break;
+        }
+        core::mem::swap(current, next);
+        next.clear();
+        pos = next_pos(input, pos);
+    }
+    matched
+}
+
+/// Pike VM の作業領域  
+/// 探索のたびに確保し直さないよう、呼び出し側で保持して使い回す
+#[derive(Debug, Default)]
+pub struct Scratch {
+    current: Threads,                 // 現在の位置で実行を待つスレッド
+    next: Threads,                    // 次の位置で実行を待つスレッド
+    thread_slots: Vec<Option<usize>>, // 新しい開始位置のスレッドのスロット
+    stack: Vec<Frame>,                // add_thread で辿る作業のスタック
+    heads: Vec<usize>,                // add_thread で辿っている分岐が、現在の位置で実行したループの先頭
+    loops: Loops,                     // 命令列の中のループ
+}
+
+/// 同じ位置で実行を待つスレッドの集合  
+/// 命令ごとに最初に加えたスレッド (優先度の最も高いもの) だけを残す
+#[derive(Debug, Default)]
+struct Threads {
+    pcs: Vec<usize>,           // 各スレッドのカウンタ (優先度の高い順)
+    slots: Vec<Option<usize>>, // 各スレッドのスロットを順に並べたもの (0番目には開始位置を記録する)
+    stride: usize,             // 1つのスレッドのスロットの数
+    visited: Vec<bool>,        // 各状態 (Loops::state の番号) をこの位置で既に辿ったかどうか
+}
+
+impl Threads {
+    /// 状態の数が len、スロットの数が stride の探索のために空にする
+    fn reset(&mut self, len: usize, stride: usize) {
+        self.pcs.clear();
+        self.slots.clear();
+        self.stride = stride;
+        self.visited.clear();
+        self.visited.resize(len, false);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pcs.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.pcs.clear();
+        self.slots.clear();
+        self.visited.fill(false);
+    }
+
+    /// i 番目のスレッドのスロット
+    fn thread_mut(&mut self, i: usize) -> &mut [Option<usize>] {
+        &mut self.slots[i * self.stride..(i + 1) * self.stride]
+    }
+}
+
+/// add_thread で辿る作業
+#[derive(Debug)]
+enum Frame {
+    Explore(usize),                // カウンタの命令を辿る
+    Restore(usize, Option<usize>), // Save で書き換えたスロットを元に戻す
+    Leave,                         // 最後に辿ったループの先頭を heads から取り除く
+}
+
+/// Pike VM の評価器の型
+struct PikeVM<'a, T, I: ?Sized> {
+    instructions: &'a [Instruction<T>],
+    input: &'a I,
+    start: usize,               // 探索を開始した位置 (\G がマッチする位置)
+    budget: Option<usize>,      // 残りの実行できる命令の数 (None の場合は無制限)
+    loops: &'a Loops,           // 命令列の中のループ
+    stack: &'a mut Vec<Frame>,  // add_thread で辿る作業のスタック
+    heads: &'a mut Vec<usize>,  // add_thread で辿っている分岐が、現在の位置で実行したループの先頭
+}
+
+impl<T: Symbol, I: Input<T> + ?Sized> PikeVM<'_, T, I> {
+    /// pc から Jump, Split, Save, Assert を辿り、文字を読む命令と Match のスレッドを優先度の順に threads に加える  
+    /// バックトラックと同じく、同じ位置で始めた繰り返しの中からループの先頭に戻る分岐は失敗とする  
+    /// 辿った先は命令ごとではなく状態ごとに1度だけ辿る (文字を読む命令と Match は、読んだ後の状態が同じため命令ごとに1度だけ加える)  
+    /// 実行できる命令の数の上限に達した場合は false を返す
+    fn add_thread(&mut self, threads: &mut Threads, pc: usize, pos: usize, slots: &mut [Option<usize>]) -> bool {
+        self.heads.clear();
+        self.stack.push(Frame::Explore(pc));
+        while let Some(frame) = self.stack.pop() {
+            let pc: usize = match frame {
+                Frame::Explore(pc) => pc,
+                Frame::Restore(slot, old) => {
+                    slots[slot] = old;
+                    continue;
+                }
+                Frame::Leave => {
+                    self.heads.pop();
+                    continue;
+                }
+            };
+            let state: usize = match &self.instructions[pc] {
+                Instruction::Jump(_) | Instruction::Split(_, _) | Instruction::Save(_) | Instruction::Assert(_) => {
+                    self.loops.state(pc, self.heads.iter().copied())
+                }
+                _ => self.loops.state(pc, core::iter::empty()),
+            };
+            if threads.visited[state] {
+                continue;
+            }
+            threads.visited[state] = true;
+            if self.loops.is_head(pc) {
+                self.heads.push(pc);
+                self.stack.push(Frame::Leave);
+            }
+            if let Some(budget) = self.budget.as_mut() {
+                if *budget == 0 {
+                    self.stack.clear();
+                    return false;
+                }
+                *budget -= 1;
+            }
+            match &self.instructions[pc] {
+                Instruction::Jump(counter) => self.explore(pc, *counter),
+                Instruction::Split(counter1, counter2) => {
+                    self.explore(pc, *counter2);
+                    self.explore(pc, *counter1);
+                }
+                Instruction::Save(slot) => {
+                    self.stack.push(Frame::Restore(*slot, slots[*slot]));
+                    slots[*slot] = Some(pos);
+                    self.stack.push(Frame::Explore(pc + 1));
+                }
+                Instruction::Assert(assertion) => {
+                    if is_assertion_satisfied(*assertion, self.input, pos, self.start) {
+                        self.stack.push(Frame::Explore(pc + 1));
+                    }
+                }
+                _ => {
+                    threads.pcs.push(pc);
+                    threads.slots.extend_from_slice(slots);
+                }
+            }
+        }
+        true
+    }
+
+    /// pc から target に進む分岐を stack に積む  
+    /// 同じ位置で始めた繰り返しの中から、ループの先頭に戻る分岐は積まない
+    fn explore(&mut self, pc: usize, target: usize) {
+        if target > pc || !self.heads.contains(&target) {
+            self.stack.push(Frame::Explore(target));
+        }
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::{is_set_supported, is_supported, search, search_set, Scratch};
+    use crate::compiler::{compile, compile_set, Instruction};
+    use crate::evaluator::{self, search_backtrack, slots_len, EvalOptions, Partial};
+    use crate::parser::{parse, AST};
+    use crate::regex::Regex;
+
+    /// Pike VM とバックトラックで、同じマッチとキャプチャを返すかを確かめる
+    fn assert_same(pattern: &str, text: &str, options: EvalOptions) {
+        let insts: Vec<Instruction> = compile(&parse(pattern).unwrap());
+        let mut expected: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+        let mut scratch: evaluator::Scratch = evaluator::Scratch::default();
+        let matched: bool = search_backtrack(&insts, text, 0, 0, &mut expected, options, true, &mut scratch) == Partial::Matched;
+        let mut actual: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+        let result: Partial = search(&insts, text, 0, 0, &mut actual, options, &mut Scratch::default());
+        assert_eq!(result == Partial::Matched, matched, "{} / {}", pattern, text);
+        if matched {
+            assert_eq!(actual, expected, "{} / {}", pattern, text);
+        }
+    }
+
+    #[test]
+    fn test_same_as_backtrack() {
+        let cases: &[(&str, &str)] = &[
+            ("a(b|bc)c", "xabcc"),
+            ("(a|ab)(c|bcd)", "abcd"),
+            ("(a*)(a*)", "aaa"),
+            ("(a+|b+)*c", "abbac"),
+            ("x(a|ab)*", "xabab"),
+            ("^(a|b)$", "a\nb"),
+            ("(?m)^(b)$", "a\nb"),
+            ("(?<n>α+)β", "ααβ"),
+            ("a?b?", "c"),
+            // 空の文字列にマッチする繰り返しの最後の1回
+            ("(a\n?|)+", "a\n"),
+            ("(aa*||)+", "a"),
+            ("(?:aa|((?:)))+()", "aa"),
+            ("(a|)+b", "aab"),
+            ("((a)|b|)*?c", "abc"),
+        ];
+        for (pattern, text) in cases {
+            assert_same(pattern, text, EvalOptions::default());
+            assert_same(pattern, text, EvalOptions { longest: true, ..EvalOptions::default() });
+            assert_same(pattern, text, EvalOptions { anchored_end: true, ..EvalOptions::default() });
+            assert_same(pattern, text, EvalOptions { anchored: true, ..EvalOptions::default() });
+        }
+    }
+
+    #[test]
+    fn test_empty_iteration() {
+        // 同じ位置でループの先頭に戻る分岐は失敗とし、空の文字列にマッチする繰り返しは最後の1回だけ記録する
+        let cases: &[(&str, &str, [Option<usize>; 2])] = &[
+            ("(a\n?|)+", "a\n", [Some(2), Some(2)]),
+            ("(aa*||)+", "a", [Some(1), Some(1)]),
+            ("(?:aa|((?:)))+()", "aa", [Some(2), Some(2)]),
+            ("(a|)*", "a", [Some(0), Some(1)]),
+        ];
+        for (pattern, text, group) in cases {
+            let insts: Vec<Instruction> = compile(&parse(pattern).unwrap());
+            let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+            let result: Partial = search(&insts, *text, 0, 0, &mut slots, EvalOptions::default(), &mut Scratch::default());
+            assert_eq!(result, Partial::Matched, "{}", pattern);
+            assert_eq!(slots[2..4], *group, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_linear_time() {
+        // バックトラックでは指数的な時間がかかる入力でも、すぐに終わる
+        let insts: Vec<Instruction> = compile(&parse("(a|a)*b").unwrap());
+        let text: &str = &"a".repeat(64);
+        let mut slots: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+        let mut scratch: Scratch = Scratch::default();
+        assert_eq!(search(&insts, text, 0, 0, &mut slots, EvalOptions::default(), &mut scratch), Partial::NotMatched);
+        // 作業領域を使い回しても、同じ結果になる
+        assert_eq!(search(&insts, "aab", 0, 0, &mut slots, EvalOptions::default(), &mut scratch), Partial::Matched);
+        assert_eq!(slots[..2], [Some(0), Some(3)]);
+    }
+
+    #[test]
+    fn test_is_supported() {
+        assert!(is_supported(&compile(&parse("(a|b)*c").unwrap())));
+        assert!(!is_supported(&compile(&parse("(?>a+)b").unwrap())));
+        let set: Vec<Instruction> = compile_set(&[parse("a").unwrap(), parse("b").unwrap()]);
+        assert!(!is_supported(&set));
+        assert!(is_set_supported(&set));
+        assert!(!is_set_supported(&compile_set(&[parse("(a)(?(1)b)").unwrap()])));
+    }
+
+    #[test]
+    fn test_search_set() {
+        let cases: &[(&[&str], &str, &[bool])] = &[
+            (&["ab+", "^b", "c$", "(x|y)z"], "abbc", &[true, false, true, false]),
+            (&["^a", "a$", "\\Ga"], "bab", &[false, false, false]),
+            (&["(?m)^b$", "α+β", ""], "a\nb\nααβ", &[true, true, true]),
+            (&["(a|a)*b", "aaa"], &"a".repeat(64), &[false, true]),
+        ];
+        for (patterns, text, expected) in cases {
+            let asts: Vec<AST> = patterns.iter().map(|p| parse(p).unwrap()).collect();
+            let insts: Vec<Instruction> = compile_set(&asts);
+            assert_eq!(search_set(&insts, *text, patterns.len()), *expected, "{:?} / {}", patterns, text);
+            // 各パターンを別々に評価した場合と、同じ結果になる
+            let each: Vec<bool> = patterns.iter().map(|p| Regex::new(p).unwrap().is_match(text)).collect();
+            assert_eq!(each, *expected);
+        }
+        assert_eq!(search_set(&compile_set(&[]), "abc", 0), Vec::<bool>::new());
+    }
+}
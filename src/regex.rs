@@ -0,0 +1,1249 @@
+//! コンパイル済みの正規表現を表す型
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+
+use crate::aho_corasick::AhoCorasick;
+use crate::builder::RegexBuilder;
+use crate::cache::Cache;
+use crate::captures::{CaptureLocations, CaptureMatches, Captures};
+use crate::compiler::{compile_reverse, disassemble, Instruction};
+use crate::dfa::{Dfa, LazyDfa};
+use crate::error::Error;
+use crate::evaluator::{
+    search, search_partial, search_partial_with, search_slots, search_traced, shortest_search, slots_len, Debugger,
+    EvalOptions, Partial, Step,
+};
+#[cfg(feature = "std")]
+use crate::lines::MatchingLines;
+use crate::input::Input;
+use crate::literal::{self, InnerLiteral};
+use crate::matches::{Match, Matches, OverlappingMatches, Searcher};
+#[cfg(feature = "mmap")]
+use crate::mmap::FileMatches;
+use crate::parser::{parse_with_spans, Flags, Spans, AST};
+use crate::replace::Replacer;
+use crate::shift_or::ShiftOr;
+use crate::split::{Split, SplitN};
+use crate::stream::PushSearcher;
+#[cfg(feature = "std")]
+use crate::stream::StreamSearcher;
+
+/// コンパイル済みの正規表現の型  
+/// パターンと命令列は Arc で共有するため、clone してもコンパイルし直さず、複数のスレッドで使える
+///
+/// # 探索の上限
+///
+/// RegexBuilder::backtrack_limit, depth_limit で上限を設定した場合、戻り値が bool や Option のメソッド (is_match, find, captures など) は、  
+/// 上限に達した探索を **マッチしなかったもの** として扱い、マッチする部分があっても false や None を返す  
+/// 上限に達したことを区別する必要がある場合は、Error::BudgetExceeded を返す try_ で始まるメソッドを使う
+#[derive(Clone)]
+pub struct Regex {
+    pattern: Arc<str>,                    // コンパイルする前のパターン
+    instructions: Arc<[Instruction]>,
+    capture_names: Arc<[Option<String>]>, // 各キャプチャグループの名前 (0番目はマッチ全体)
+    options: EvalOptions,                 // RegexBuilder で指定した探索の設定
+    flags: Flags,                         // パターンをパースしたフラグ (debug_ast でパースし直すのに使う)
+    dfa: Option<Arc<Dfa>>,                // RegexBuilder::dfa で生成した DFA (is_match で使う)
+    reverse_dfa: Option<Arc<Dfa>>,        // パターンを逆順にした命令列の DFA (find でマッチの開始位置を求めるのに使う)
+    lazy_dfa: Option<LazyDfa>,            // RegexBuilder::lazy_dfa で準備した lazy DFA (is_match で使う)
+    literals: Option<Arc<AhoCorasick>>,   // パターンがリテラルの選択だけからなる場合のオートマトン
+    inner: Option<Arc<InnerLiteral>>,     // マッチの途中に必ず現れるリテラル (探索を始める位置の絞り込みに使う)
+    shift_or: Option<Arc<ShiftOr>>,       // 小さなパターンの場合に is_match で使う Shift-Or の判定器
+    anchored_start: bool,                 // パターンのどのマッチも入力の先頭から始まるかどうか
+}
+
+impl Regex {
+    /// パターンをパースし、命令列にコンパイルする  
+    /// パターンが不正な場合はエラーを返す  
+    /// フラグや上限を指定する場合は RegexBuilder を使う
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
+        RegexBuilder::new(pattern).build()
+    }
+
+    pub(crate) fn from_parts(
+        pattern: &str,
+        instructions: Vec<Instruction>,
+        capture_names: Vec<Option<String>>,
+        options: EvalOptions,
+    ) -> Regex {
+        let anchored_start: bool = literal::is_anchored_start(&instructions);
+        Regex {
+            pattern: pattern.into(),
+            instructions: instructions.into(),
+            capture_names: capture_names.into(),
+            options,
+            flags: Flags::default(),
+            dfa: None,
+            reverse_dfa: None,
+            lazy_dfa: None,
+            literals: None,
+            inner: None,
+            shift_or: None,
+            anchored_start,
+        }
+    }
+
+    /// パターンをパースした際のフラグ (RegexBuilder で指定したもの) を記録する
+    pub(crate) fn with_flags(mut self, flags: Flags) -> Regex {
+        self.flags = flags;
+        self
+    }
+
+    /// 命令列を DFA に変換し、is_match で使うようにする  
+    /// パターンを逆順にした命令列も DFA に変換できた場合は、find でマッチの開始位置を求めるのに使う  
+    /// DFA で表せない命令列の場合や、状態の数が state_limit を超えた場合は、変換せずにそのまま返す
+    pub(crate) fn with_dfa(mut self, ast: &AST, state_limit: usize) -> Regex {
+        self.dfa = Dfa::build(&self.instructions, self.options, state_limit).map(Arc::new);
+        if self.dfa.is_some() {
+            // anchored_end の場合、逆順の DFA は入力の末尾からのみ読み始める
+            let options: EvalOptions = EvalOptions { anchored: self.options.anchored_end, ..EvalOptions::default() };
+            self.reverse_dfa = compile_reverse(ast)
+                .and_then(|reverse| Dfa::build(&reverse, options, state_limit))
+                .map(Arc::new);
+        }
+        self
+    }
+
+    /// is_match で lazy DFA を使うようにする (状態のキャッシュは capacity 個まで)  
+    /// DFA で表せない命令列の場合は、そのまま返す
+    pub(crate) fn with_lazy_dfa(mut self, capacity: usize) -> Regex {
+        self.lazy_dfa = LazyDfa::new(self.instructions.clone(), self.options, capacity);
+        self
+    }
+
+    /// パターンがリテラルの選択だけからなる場合に、is_match, find, find_iter で命令列の代わりに使う Aho-Corasick のオートマトンを生成する  
+    /// anchored, anchored_end, longest を指定した場合は、命令列で評価するため生成しない
+    pub(crate) fn with_literals(mut self, literals: &[String]) -> Regex {
+        if !self.options.anchored && !self.options.anchored_end && !self.options.longest {
+            self.literals = Some(Arc::new(AhoCorasick::new(literals)));
+        }
+        self
+    }
+
+    /// パターンの AST からマッチの途中に必ず現れるリテラルを探し、is_match, find で探索を始める位置の絞り込みに使う
+    pub(crate) fn with_inner_literal(mut self, ast: &AST) -> Regex {
+        if self.literals.is_none() && !self.options.anchored && !self.anchored_start {
+            self.inner = InnerLiteral::new(ast).map(Arc::new);
+        }
+        self
+    }
+
+    /// 文字を読む位置が64個以下の小さなパターンの場合に、is_match で使う Shift-Or の判定器を生成する
+    pub(crate) fn with_shift_or(mut self, ast: &AST) -> Regex {
+        if self.literals.is_none() {
+            self.shift_or = ShiftOr::new(ast, self.options).map(Arc::new);
+        }
+        self
+    }
+
+    /// コンパイルする前のパターンを返す
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// コンパイルした命令列を、1行に1命令ずつプログラムカウンタを付けた一覧で返す (デバッグ用)  
+    /// 形式は安定しておらず、バージョンによって変わることがある
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("a(b|c)").unwrap();
+    /// print!("{}", re.debug_program());
+    /// assert!(re.debug_program().starts_with("0 : Char(a)\n"));
+    /// ```
+    pub fn debug_program(&self) -> String {
+        disassemble(&self.instructions)
+    }
+
+    /// パターンを RegexBuilder で指定したフラグでパースし直し、AST を1行に1つのノードの木の形で返す (デバッグ用)  
+    /// 各ノードの後ろには、`Char(a) @ 0..1` のように対応するパターンの範囲 (バイト単位) を書く  
+    /// 命令列にする前に簡約する前の AST を返す。形式は安定しておらず、バージョンによって変わることがある  
+    /// from_bytes などで読み込んだパターンが不正な場合はエラーを返す
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("a(b|c)").unwrap();
+    /// print!("{}", re.debug_ast().unwrap());
+    /// assert!(re.debug_ast().unwrap().starts_with("Seq @ 0..6\n  Char(a) @ 0..1\n  Capture(1) @ 1..6\n    Or @ 2..5\n"));
+    /// ```
+    pub fn debug_ast(&self) -> Result<String, Error> {
+        let (ast, spans): (AST, Spans) = parse_with_spans(&self.pattern, self.flags, usize::MAX)?;
+        Ok(ast.to_tree_with_spans(&spans))
+    }
+
+    /// text の中で最初にパターンにマッチする部分を、バックトラックの評価器で探し、実行した命令の記録とともに返す (デバッグ用)  
+    /// RegexBuilder で DFA などを有効にしていても常にバックトラックで評価し、先頭から1文字ずつ開始位置を進めて試す  
+    /// 命令を実行するたびに記録が1つ増えるため、大きな入力には使わない。カウンタは debug_program の一覧と対応する
+    ///
+    /// ```
+    /// use small_regex::{Regex, StepResult};
+    ///
+    /// let re = Regex::new("a|b").unwrap();
+    /// let (found, steps) = re.trace("xb");
+    /// assert_eq!(found.unwrap().as_str(), "b");
+    /// for step in &steps {
+    ///     println!("{step}");
+    /// }
+    /// assert_eq!(steps.last().unwrap().result, StepResult::Matched);
+    /// ```
+    pub fn trace<'t>(&self, text: &'t str) -> (Option<Match<'t>>, Vec<Step>) {
+        let (found, steps) = search_traced(&self.instructions, text, 0, self.options);
+        (found.map(|(start, end)| Match::new(text, start, end)), steps)
+    }
+
+    /// text の中で最初にパターンにマッチする部分を探すバックトラックの評価器を、1命令ずつ実行するデバッガを返す (可視化・デバッグ用)  
+    /// trace と同様に常にバックトラックで評価し、先頭から1文字ずつ開始位置を進めて試す。カウンタは debug_program の一覧と対応する
+    ///
+    /// ```
+    /// use small_regex::{Debugger, Regex};
+    ///
+    /// let re = Regex::new("a+b").unwrap();
+    /// let mut debugger: Debugger = re.debugger("xaab");
+    /// debugger.set_breakpoint(2); // Char(b)
+    /// assert!(debugger.run());
+    /// assert_eq!((debugger.start(), debugger.index()), (1, 3));
+    /// assert_eq!(debugger.slots(), [None, None]);
+    /// assert!(!debugger.run());
+    /// assert_eq!(debugger.found().unwrap().as_str(), "aab");
+    /// ```
+    pub fn debugger<'a>(&'a self, text: &'a str) -> Debugger<'a> {
+        Debugger::new(&self.instructions, text, 0, self.options)
+    }
+
+    pub(crate) fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// パターンがリテラルの選択だけからなる場合の Aho-Corasick のオートマトン (bytes::Regex の find_iter で使う)
+    pub(crate) fn literals(&self) -> Option<&AhoCorasick> {
+        self.literals.as_deref()
+    }
+
+    /// bytes::Regex の is_match で使う  
+    /// try_is_match_with と同様に、リテラルのオートマトン、DFA、lazy DFA、Shift-Or があればそれで判定する  
+    /// 探索が上限に達した場合は false を返す
+    pub(crate) fn is_match_bytes(&self, haystack: &[u8]) -> bool {
+        if self.literals.is_none() && !self.anchored_start {
+            if let Some(dfa) = &self.dfa {
+                return dfa.is_match_at(haystack, 0);
+            }
+            if let Some(lazy_dfa) = &self.lazy_dfa {
+                return lazy_dfa.is_match_at(haystack, 0);
+            }
+            if let Some(shift_or) = &self.shift_or {
+                return shift_or.is_match_at(haystack, 0);
+            }
+        }
+        self.find_bytes(haystack).is_some()
+    }
+
+    /// bytes::Regex の find で使う  
+    /// リテラルのオートマトンと、評価器の接頭辞や先頭のバイトによる読み飛ばしは使うが、  
+    /// 逆順の DFA と InnerLiteral は &str を前提とするため使わない。探索が上限に達した場合は None を返す
+    pub(crate) fn find_bytes(&self, haystack: &[u8]) -> Option<(usize, usize)> {
+        if let Some(literals) = &self.literals {
+            return literals.find(haystack, 0);
+        }
+        search(&self.instructions, haystack, 0, self.options)
+    }
+
+    pub(crate) fn options(&self) -> EvalOptions {
+        self.options
+    }
+
+    pub(crate) fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    pub(crate) fn names(&self) -> &[Option<String>] {
+        &self.capture_names
+    }
+
+    /// text のいずれかの位置にパターンにマッチする部分があるかを判定する  
+    /// 先頭からのマッチのみを調べる場合は is_prefix_match を、text 全体がマッチするかを調べる場合は is_full_match を使う  
+    /// 探索が上限に達した場合は false を返す (区別する場合は try_is_match を使う)
+    pub fn is_match(&self, text: &str) -> bool {
+        self.is_match_at(text, 0)
+    }
+
+    /// text の start (バイト単位) 以降の位置に、パターンにマッチする部分があるかを判定する  
+    /// start より前の文字も `^` などのアンカーの判定には使う  
+    /// start が文字の境界でない場合はパニックする  
+    /// 探索が上限に達した場合は false を返す (区別する場合は try_is_match_at を使う)
+    pub fn is_match_at(&self, text: &str, start: usize) -> bool {
+        self.try_is_match_with(text, start, &mut Cache::default()).unwrap_or(false)
+    }
+
+    /// is_match と同様に判定するが、探索が RegexBuilder::backtrack_limit などの上限に達した場合は  
+    /// マッチしなかったものとせずに Error::BudgetExceeded を返す
+    ///
+    /// ```
+    /// use small_regex::{Error, RegexBuilder};
+    ///
+    /// let re = RegexBuilder::new("(a|aa)*c").backtrack_limit(1000).build().unwrap();
+    /// assert_eq!(re.try_is_match("aaac"), Ok(true));
+    /// let text = "a".repeat(1000) + "c";
+    /// assert_eq!(re.try_is_match(&text), Err(Error::BudgetExceeded));
+    /// assert!(!re.is_match(&text));
+    /// ```
+    pub fn try_is_match(&self, text: &str) -> Result<bool, Error> {
+        self.try_is_match_with(text, 0, &mut Cache::default())
+    }
+
+    /// is_match_at と同様に判定するが、探索が上限に達した場合は Error::BudgetExceeded を返す  
+    /// start が文字の境界でない場合はパニックする
+    pub fn try_is_match_at(&self, text: &str, start: usize) -> Result<bool, Error> {
+        self.try_is_match_with(text, start, &mut Cache::default())
+    }
+
+    fn try_is_match_with(&self, text: &str, start: usize, cache: &mut Cache) -> Result<bool, Error> {
+        assert_char_boundary(text, start);
+        if let Some(literals) = &self.literals {
+            return Ok(literals.find(text.as_bytes(), start).is_some());
+        }
+        if self.anchored_start {
+            // 入力の先頭から1度だけ評価すれば済むため、入力全体を読む DFA などは使わない
+            return Ok(self.search_from(text, start, self.options, cache)?.is_some());
+        }
+        if let Some(dfa) = &self.dfa {
+            return Ok(dfa.is_match_at(text, start));
+        }
+        if let Some(lazy_dfa) = &self.lazy_dfa {
+            return Ok(lazy_dfa.is_match_at(text, start));
+        }
+        if let Some(shift_or) = &self.shift_or {
+            return Ok(shift_or.is_match_at(text, start));
+        }
+        Ok(self.search_from(text, start, self.options, cache)?.is_some())
+    }
+
+    /// 探索に渡して使い回す、空の作業領域を生成する
+    pub fn create_cache(&self) -> Cache {
+        Cache::new()
+    }
+
+    /// is_match と同様に判定するが、評価器の作業領域は cache のものを使い回す  
+    /// 多くの入力に対して繰り返し判定する場合に、判定ごとのメモリの確保を省ける  
+    /// 探索が上限に達した場合は false を返す
+    pub fn is_match_with_cache(&self, cache: &mut Cache, text: &str) -> bool {
+        self.try_is_match_with(text, 0, cache).unwrap_or(false)
+    }
+
+    /// find_at と同様に探索するが、評価器の作業領域は cache のものを使い回す  
+    /// start が文字の境界でない場合はパニックする。探索が上限に達した場合は None を返す
+    pub fn find_at_with_cache<'t>(&self, cache: &mut Cache, text: &'t str, start: usize) -> Option<Match<'t>> {
+        assert_char_boundary(text, start);
+        let found: Option<(usize, usize)> = self.search_from(text, start, self.options, cache).unwrap_or(None);
+        found.map(|(start, end)| Match::new(text, start, end))
+    }
+
+    /// text の中で最も早く終わるマッチの終了位置を返す  
+    /// マッチが確定した時点で探索を打ち切るため、find で得られるマッチの終了位置より前になることがある  
+    /// マッチする部分が無い場合は None を返す
+    pub fn shortest_match(&self, text: &str) -> Option<usize> {
+        shortest_search(&self.instructions, text, 0, self.options)
+    }
+
+    /// text の中で最初にパターンにマッチする部分を返す  
+    /// マッチする部分が無い場合は None を返す  
+    /// 探索が上限に達した場合も None を返す (区別する場合は try_find を使う)
+    pub fn find<'t>(&self, text: &'t str) -> Option<Match<'t>> {
+        self.find_at(text, 0)
+    }
+
+    /// text の start (バイト単位) 以降で、最初にパターンにマッチする部分を返す  
+    /// 返す Match の位置は text の先頭からの位置で、start より前の文字も `^` などのアンカーの判定には使う  
+    /// start が文字の境界でない場合はパニックする  
+    /// 探索が上限に達した場合は None を返す (区別する場合は try_find_at を使う)
+    pub fn find_at<'t>(&self, text: &'t str, start: usize) -> Option<Match<'t>> {
+        self.try_find_at(text, start).unwrap_or(None)
+    }
+
+    /// find と同様に探索するが、探索が RegexBuilder::backtrack_limit などの上限に達した場合は  
+    /// マッチしなかったものとせずに Error::BudgetExceeded を返す
+    pub fn try_find<'t>(&self, text: &'t str) -> Result<Option<Match<'t>>, Error> {
+        self.try_find_at(text, 0)
+    }
+
+    /// find_at と同様に探索するが、探索が上限に達した場合は Error::BudgetExceeded を返す  
+    /// start が文字の境界でない場合はパニックする
+    pub fn try_find_at<'t>(&self, text: &'t str, start: usize) -> Result<Option<Match<'t>>, Error> {
+        if let Some(literals) = &self.literals {
+            assert_char_boundary(text, start);
+            let found: Option<(usize, usize)> = literals.find(text.as_bytes(), start);
+            return Ok(found.map(|(start, end)| Match::new(text, start, end)));
+        }
+        self.find_with(text, start, self.options)
+    }
+
+    /// find_at と同様だが、start の位置から始まるマッチのみを返す (探索が上限に達した場合は None を返す)
+    pub fn find_anchored_at<'t>(&self, text: &'t str, start: usize) -> Option<Match<'t>> {
+        self.find_with(text, start, EvalOptions { anchored: true, ..self.options }).unwrap_or(None)
+    }
+
+    fn find_with<'t>(&self, text: &'t str, start: usize, options: EvalOptions) -> Result<Option<Match<'t>>, Error> {
+        assert_char_boundary(text, start);
+        let found: Option<(usize, usize)> = self.search_from(text, start, options, &mut Cache::default())?;
+        Ok(found.map(|(start, end)| Match::new(text, start, end)))
+    }
+
+    /// search と同様に text の start 以降で探索するが、マッチの途中に必ず現れるリテラルがある場合は、  
+    /// マッチが始まり得る位置まで読み飛ばしてから評価する  
+    /// 探索が上限に達した場合は Error::BudgetExceeded を返す  
+    /// 評価器の作業領域とスロットは cache のものを使う
+    fn search_from(
+        &self,
+        text: &str,
+        start: usize,
+        options: EvalOptions,
+        cache: &mut Cache,
+    ) -> Result<Option<(usize, usize)>, Error> {
+        let Cache { scratch, slots } = cache;
+        slots.clear();
+        slots.resize(slots_len(&self.instructions), None);
+        let partial: Partial = if let (Some(dfa), Some(reverse_dfa), false) =
+            (&self.dfa, &self.reverse_dfa, options.anchored || self.anchored_start)
+        {
+            // DFA でマッチがあるかを調べ、逆順の DFA で最も前の開始位置を求めてから、その位置から始まるマッチを評価する
+            if !dfa.is_match_at(text, start) {
+                return Ok(None);
+            }
+            let Some(from) = reverse_dfa.leftmost_start(text, start) else {
+                return Ok(None);
+            };
+            let anchored: EvalOptions = EvalOptions { anchored: true, ..options };
+            search_partial_with(&self.instructions, text, from, from, slots, anchored, true, scratch)
+        } else {
+            let from: usize = match &self.inner {
+                Some(inner) if !options.anchored => match inner.candidate(text, start) {
+                    Some(from) => from,
+                    None => return Ok(None),
+                },
+                _ => start,
+            };
+            search_partial_with(&self.instructions, text, start, from, slots, options, true, scratch)
+        };
+        match partial {
+            Partial::Matched => Ok(slots[0].zip(slots[1])),
+            Partial::Aborted => Err(Error::BudgetExceeded),
+            Partial::NotMatched | Partial::NeedInput(_) => Ok(None),
+        }
+    }
+
+    /// text の中でパターンにマッチする部分を、重ならないように先頭から順に返すイテレータを返す
+    pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> Matches<'r, 't> {
+        Matches::new(&self.instructions, self.options, text, self.literals.as_deref())
+    }
+
+    /// text の中でパターンにマッチする部分を、重なるものも含めて開始位置の順に返すイテレータを返す  
+    /// 各マッチの開始位置の1文字後から次の探索を始めるため、同じ位置から始まるマッチは1つだけ返す
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("aba").unwrap();
+    /// let starts: Vec<usize> = re.find_overlapping_iter("ababa").map(|m| m.start()).collect();
+    /// assert_eq!(starts, vec![0, 2]);
+    /// assert_eq!(re.find_iter("ababa").count(), 1);
+    /// ```
+    pub fn find_overlapping_iter<'r, 't>(&'r self, text: &'t str) -> OverlappingMatches<'r, 't> {
+        OverlappingMatches::new(&self.instructions, self.options, text)
+    }
+
+    /// reader から1行ずつ読み込み、パターンにマッチする部分を含む行を (行番号, 行) の組で返すイテレータを返す  
+    /// 行番号は1から数え、返す行には末尾の改行を含めない
+    #[cfg(feature = "std")]
+    pub fn matching_lines<R: std::io::BufRead>(&self, reader: R) -> MatchingLines<'_, R> {
+        MatchingLines::new(self, reader)
+    }
+
+    /// path のファイルをメモリにマップし、パターンにマッチする部分を重ならないように先頭から順に返すイテレータを返す  
+    /// マッチの位置は、ファイルの先頭からのバイト単位の位置で返す  
+    /// ファイルを開けない場合やマップできない場合はエラーを返す  
+    /// 探索している間に、他のプロセスなどがファイルを変更・切り詰めてはならない
+    #[cfg(feature = "mmap")]
+    pub fn find_in_file<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<FileMatches<'_>> {
+        FileMatches::open(self, path.as_ref())
+    }
+
+    /// 入力を少しずつ渡しながら、パターンにマッチする部分を重ならないように先頭から順に探索する PushSearcher を返す  
+    /// 入力を受け取るたびに PushSearcher::feed を、入力の終わりに PushSearcher::finish を呼ぶ
+    pub fn push_searcher(&self) -> PushSearcher<'_> {
+        PushSearcher::new(self)
+    }
+
+    /// reader から少しずつ読み込みながら、パターンにマッチする部分を重ならないように先頭から順に返すイテレータを返す  
+    /// マッチの位置は、ストリームの先頭からのバイト単位の位置で返す
+    #[cfg(feature = "std")]
+    pub fn stream_find_iter<R: std::io::Read>(&self, reader: R) -> StreamSearcher<'_, R> {
+        StreamSearcher::new(self, reader)
+    }
+
+    /// text の中でパターンにマッチする部分の数を返す (find_iter で得られる数と同じ)  
+    /// Match を生成しないため、数だけが必要な場合は find_iter より速い
+    pub fn count_matches(&self, text: &str) -> usize {
+        let mut searcher: Searcher<&str> = Searcher::new(&self.instructions, self.options, text);
+        let mut slots: Vec<Option<usize>> = vec![None; slots_len(&self.instructions)];
+        let mut count: usize = 0;
+        while searcher.next(&mut slots) {
+            count += 1;
+        }
+        count
+    }
+
+    /// text の中で最初にパターンにマッチする部分と、各キャプチャグループがマッチした部分を返す  
+    /// マッチする部分が無い場合は None を返す  
+    /// 探索が上限に達した場合も None を返す (区別する場合は try_captures を使う)
+    pub fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        self.captures_at(text, 0)
+    }
+
+    /// text の start (バイト単位) 以降で、最初にパターンにマッチする部分と、各キャプチャグループがマッチした部分を返す  
+    /// start が文字の境界でない場合はパニックする  
+    /// 探索が上限に達した場合は None を返す (区別する場合は try_captures_at を使う)
+    pub fn captures_at<'t>(&self, text: &'t str, start: usize) -> Option<Captures<'t>> {
+        self.try_captures_at(text, start).unwrap_or(None)
+    }
+
+    /// captures と同様に探索するが、探索が RegexBuilder::backtrack_limit などの上限に達した場合は  
+    /// マッチしなかったものとせずに Error::BudgetExceeded を返す
+    pub fn try_captures<'t>(&self, text: &'t str) -> Result<Option<Captures<'t>>, Error> {
+        self.try_captures_at(text, 0)
+    }
+
+    /// captures_at と同様に探索するが、探索が上限に達した場合は Error::BudgetExceeded を返す  
+    /// start が文字の境界でない場合はパニックする
+    pub fn try_captures_at<'t>(&self, text: &'t str, start: usize) -> Result<Option<Captures<'t>>, Error> {
+        assert_char_boundary(text, start);
+        let mut slots: Vec<Option<usize>> = vec![None; self.capture_names.len() * 2];
+        match search_partial(&self.instructions, text, start, start, &mut slots, self.options, true) {
+            Partial::Matched => Ok(Some(Captures::new(text, slots, Arc::clone(&self.capture_names)))),
+            Partial::Aborted => Err(Error::BudgetExceeded),
+            Partial::NotMatched | Partial::NeedInput(_) => Ok(None),
+        }
+    }
+
+    /// captures_read に渡す、このパターンのグループの数に合わせた CaptureLocations を生成する
+    pub fn capture_locations(&self) -> CaptureLocations {
+        CaptureLocations::new(self.capture_names.len())
+    }
+
+    /// captures と同様に探索し、各キャプチャグループがマッチした位置を locs に記録する  
+    /// Captures を生成しないため、locs を使い回せば探索ごとにグループの結果を確保せずに済む  
+    /// マッチした場合はマッチ全体を返し、マッチする部分が無い場合は None を返す (locs はすべて None になる)
+    pub fn captures_read<'t>(&self, locs: &mut CaptureLocations, text: &'t str) -> Option<Match<'t>> {
+        self.captures_read_at(locs, text, 0)
+    }
+
+    /// captures_read と同様だが、text の start (バイト単位) 以降で探索する  
+    /// start が文字の境界でない場合はパニックする
+    pub fn captures_read_at<'t>(&self, locs: &mut CaptureLocations, text: &'t str, start: usize) -> Option<Match<'t>> {
+        assert_char_boundary(text, start);
+        let slots: &mut [Option<usize>] = locs.slots_mut();
+        if !search_slots(&self.instructions, text, start, slots, self.options) {
+            return None;
+        }
+        Some(Match::new(text, slots[0]?, slots[1]?))
+    }
+
+    /// text の中でパターンにマッチする部分ごとに、キャプチャグループの結果を返すイテレータを返す
+    pub fn captures_iter<'r, 't>(&'r self, text: &'t str) -> CaptureMatches<'r, 't> {
+        CaptureMatches::new(&self.instructions, self.options, text, Arc::clone(&self.capture_names))
+    }
+
+    /// キャプチャグループの数 (マッチ全体を表す0番目のグループを含む)
+    pub fn captures_len(&self) -> usize {
+        self.capture_names.len()
+    }
+
+    /// 各キャプチャグループの名前を、グループの番号の順に返すイテレータを返す  
+    /// マッチ全体を表す0番目のグループと、名前の無いグループは None とする
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("(?<year>(?:1|2)+)-((?:1|2)+)").unwrap();
+    /// let names: Vec<Option<&str>> = re.capture_names().collect();
+    /// assert_eq!(names, vec![None, Some("year"), None]);
+    /// ```
+    pub fn capture_names(&self) -> impl ExactSizeIterator<Item = Option<&str>> + '_ {
+        self.capture_names.iter().map(|name| name.as_deref())
+    }
+
+    /// text の中で最初にパターンにマッチする部分を rep で置換した文字列を返す  
+    /// rep が文字列の場合、`$1` や `${name}` は対応するキャプチャグループがマッチした文字列に展開する  
+    /// rep にはクロージャなど、Replacer を実装した型も渡せる  
+    /// マッチする部分が無い場合は、text をそのまま借用して返す
+    pub fn replace<'t, R: Replacer>(&self, text: &'t str, rep: R) -> Cow<'t, str> {
+        self.replacen(text, 1, rep)
+    }
+
+    /// text の中でパターンにマッチするすべての部分を rep で置換した文字列を返す
+    pub fn replace_all<'t, R: Replacer>(&self, text: &'t str, rep: R) -> Cow<'t, str> {
+        self.replacen(text, 0, rep)
+    }
+
+    /// text の中でパターンにマッチする部分を、先頭から limit 個まで rep で置換した文字列を返す  
+    /// limit が 0 の場合はすべて置換する
+    pub fn replacen<'t, R: Replacer>(&self, text: &'t str, limit: usize, mut rep: R) -> Cow<'t, str> {
+        let mut it = self.captures_iter(text).peekable();
+        if it.peek().is_none() {
+            return Cow::Borrowed(text);
+        }
+
+        let mut result: String = String::with_capacity(text.len());
+        let mut last_end: usize = 0;
+        for (i, caps) in it.enumerate() {
+            if limit > 0 && i >= limit {
+                break;
+            }
+            let m: Match = caps.get(0).unwrap();
+            result.push_str(&text[last_end..m.start()]);
+            rep.replace_append(&caps, &mut result);
+            last_end = m.end();
+        }
+        result.push_str(&text[last_end..]);
+        Cow::Owned(result)
+    }
+
+    /// パターンにマッチする部分を区切りとして text を分割した部分文字列を返すイテレータを返す  
+    /// 先頭・末尾が区切りの場合は、空の部分文字列を含める
+    pub fn split<'r, 't>(&'r self, text: &'t str) -> Split<'r, 't> {
+        Split::new(self.find_iter(text), text)
+    }
+
+    /// split と同様に分割するが、部分文字列の数を最大 limit 個とする  
+    /// 最後の部分文字列には、残りの文字列を区切りごと含める
+    pub fn splitn<'r, 't>(&'r self, text: &'t str, limit: usize) -> SplitN<'r, 't> {
+        SplitN::new(self.split(text), limit)
+    }
+
+    /// text の先頭からパターンにマッチするかを判定する (先頭にアンカーを置いた場合と同じ)  
+    /// マッチした部分より後ろに残りの文字列があってもよい
+    pub fn is_prefix_match(&self, text: &str) -> bool {
+        let options: EvalOptions = EvalOptions { anchored: true, ..self.options };
+        search(&self.instructions, text, 0, options).is_some()
+    }
+
+    /// text 全体がパターンにマッチするかを判定する (先頭と末尾にアンカーを置いた場合と同じ)  
+    /// is_prefix_match と異なり、マッチした部分より後ろに文字列が残る場合はマッチしないものとする  
+    /// 先に見つかったマッチが末尾まで届かない場合は、他の分岐を試す
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("abc").unwrap();
+    /// assert!(re.is_full_match("abc"));
+    /// assert!(!re.is_full_match("abcXYZ"));
+    /// assert!(re.is_prefix_match("abcXYZ"));
+    /// ```
+    pub fn is_full_match(&self, text: &str) -> bool {
+        let options: EvalOptions = EvalOptions { anchored: true, anchored_end: true, ..self.options };
+        search(&self.instructions, text, 0, options).is_some()
+    }
+
+    /// Input を実装した任意の入力 (`str`, `[u8]`, `[char]` など) のいずれかの位置に、パターンにマッチする部分があるかを判定する
+    pub fn is_match_input<I: Input + ?Sized>(&self, input: &I) -> bool {
+        search(&self.instructions, input, 0, self.options).is_some()
+    }
+
+    /// Input を実装した任意の入力の中で、最初にパターンにマッチする部分の範囲を返す  
+    /// 範囲は入力の単位で表す (`[u8]` はバイト単位、`[char]` は文字単位)  
+    /// マッチする部分が無い場合は None を返す
+    ///
+    /// ```
+    /// use small_regex::Regex;
+    ///
+    /// let re = Regex::new("b+").unwrap();
+    /// let chars: Vec<char> = "αbb".chars().collect();
+    /// assert_eq!(re.find_input(&chars[..]), Some(1..3));
+    /// assert_eq!(re.find_input("αbb"), Some(2..4));
+    /// ```
+    pub fn find_input<I: Input + ?Sized>(&self, input: &I) -> Option<Range<usize>> {
+        let (start, end) = search(&self.instructions, input, 0, self.options)?;
+        Some(start..end)
+    }
+}
+
+/// start が text の中の文字の境界でない場合や、text の長さを超える場合はパニックする
+fn assert_char_boundary(text: &str, start: usize) {
+    assert!(text.is_char_boundary(start), "start ({}) is not a char boundary of the text", start);
+}
+
+impl fmt::Display for Regex {
+    /// コンパイルする前のパターンを表示する
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl fmt::Debug for Regex {
+    /// 命令列ではなく、コンパイルする前のパターンを表示する
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Regex").field(&self.pattern).finish()
+    }
+}
+
+impl FromStr for Regex {
+    type Err = Error;
+
+    /// Regex::new と同様に、パターンをパースしてコンパイルする
+    fn from_str(pattern: &str) -> Result<Regex, Error> {
+        Regex::new(pattern)
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+
+    use super::Regex;
+    use crate::builder::RegexBuilder;
+    use crate::cache::Cache;
+    use crate::captures::{CaptureLocations, Captures};
+    use crate::error::{Error, ParseError, ParseErrorKind};
+    use crate::replace::NoExpand;
+
+    #[test]
+    fn test_inner_literal() {
+        let re: Regex = Regex::new("(a|b)+@(a|b)+").unwrap();
+        assert!(re.inner.is_some());
+        assert_eq!(re.find("x@ @ ab@ba!").map(|m| m.range()), Some(5..10));
+        assert_eq!(re.find_at("ab@ba", 1).map(|m| m.range()), Some(1..5));
+        assert!(re.is_match("b@a"));
+        assert!(!re.is_match("ab@ @ba"));
+    }
+
+    #[test]
+    fn test_shift_or() {
+        let re: Regex = Regex::new("a(b|c)*d").unwrap();
+        assert!(re.shift_or.is_none());
+        let re: Regex = Regex::new("a(?:b|c)*d").unwrap();
+        assert!(re.shift_or.is_some());
+        assert!(re.is_match("xabcbd"));
+        assert!(!re.is_match("xabcb"));
+    }
+
+    #[test]
+    fn test_literals() {
+        // リテラルの選択だけのパターンは、命令列の代わりにオートマトンで探索する
+        let re: Regex = Regex::new("ab|abcd|bcd").unwrap();
+        assert!(re.literals.is_some());
+        assert!(re.is_match("xbcd"));
+        assert!(!re.is_match_at("xabc", 2));
+        assert_eq!(re.find("xabcd").map(|m| m.range()), Some(1..3));
+        assert_eq!(re.find_at("xabcd", 2).map(|m| m.range()), Some(2..5));
+        let found: Vec<&str> = re.find_iter("abcdbcdab").map(|m| m.as_str()).collect();
+        assert_eq!(found, vec!["ab", "bcd", "ab"]);
+        // 命令列で評価した場合と同じ結果になる
+        assert_eq!(re.find_iter("abcdbcdab").count(), re.count_matches("abcdbcdab"));
+
+        // メタ文字を含まないパターン (エスケープしたメタ文字を含む) は、部分文字列として探す
+        let re: Regex = Regex::new(r"x\.y\*").unwrap();
+        assert!(re.literals.is_some());
+        assert!(!re.is_match("xay*"));
+        assert_eq!(re.find("xy* x.y*").map(|m| m.range()), Some(4..8));
+        assert_eq!(re.find_iter("x.y*x.y*").count(), 2);
+    }
+
+    #[test]
+    fn test_is_match() {
+        let re: Regex = Regex::new("ab*(de|fg)").unwrap();
+        assert!(re.is_match("abbbfg"));
+        assert!(re.is_match("ade"));
+        assert!(!re.is_match("acde"));
+
+        // 先頭以外の位置でもマッチする
+        assert!(Regex::new("bc").unwrap().is_match("abc"));
+        assert!(!Regex::new("^bc").unwrap().is_match("abc"));
+        assert!(!Regex::new("bd").unwrap().is_match("abc"));
+    }
+
+    #[test]
+    fn test_find() {
+        let re: Regex = Regex::new("b+c").unwrap();
+        let m = re.find("abbcd").unwrap();
+        assert_eq!((m.start(), m.end()), (1, 4));
+        assert_eq!(m.as_str(), "bbc");
+
+        // 位置はバイト単位で返す
+        let m = Regex::new("い+").unwrap().find("あいいう").unwrap();
+        assert_eq!(m.range(), 3..9);
+        assert_eq!(m.as_str(), "いい");
+
+        assert_eq!(re.find("abd"), None);
+    }
+
+    #[test]
+    fn test_shortest_match() {
+        // find は "abbb" にマッチするが、shortest_match は "ab" の時点で打ち切る
+        let re: Regex = Regex::new("ab+").unwrap();
+        assert_eq!(re.shortest_match("xabbb"), Some(3));
+        assert_eq!(re.find("xabbb").unwrap().end(), 5);
+        assert_eq!(re.shortest_match("xyz"), None);
+
+        // 開始位置に関わらず、最も早く終わるマッチの終了位置を返す
+        let re: Regex = Regex::new("abcd|bc").unwrap();
+        assert_eq!(re.shortest_match("abcd"), Some(3));
+
+        // アトミックグループを含む場合は find と同じ終了位置を返す
+        let re: Regex = Regex::new("(?>a+)b").unwrap();
+        assert_eq!(re.shortest_match("aab"), Some(3));
+        assert_eq!(Regex::new("a").unwrap().shortest_match("αa"), Some(3));
+    }
+
+    #[test]
+    fn test_find_at() {
+        let re: Regex = Regex::new("ab").unwrap();
+        let m = re.find_at("abxab", 1).unwrap();
+        assert_eq!((m.start(), m.end()), (3, 5));
+        assert!(re.find_anchored_at("abxab", 1).is_none());
+        assert_eq!(re.find_anchored_at("abxab", 3).unwrap().start(), 3);
+        assert!(!re.is_match_at("abxab", 4));
+
+        // start より前の文字もアンカーの判定に使う
+        let re: Regex = Regex::new("^b").unwrap();
+        assert!(re.find_at("ab", 1).is_none());
+        let re: Regex = Regex::new("\\Gb").unwrap();
+        assert_eq!(re.find_at("ab", 1).unwrap().as_str(), "b");
+
+        // 位置はバイト単位
+        let re: Regex = Regex::new("(b)").unwrap();
+        let caps: Captures = re.captures_at("bαb", 1).unwrap();
+        assert_eq!(caps.get(1).unwrap().start(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_find_at_not_char_boundary() {
+        Regex::new("a").unwrap().find_at("αa", 1);
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let re: Regex = Regex::new("a+").unwrap();
+        let matches: Vec<&str> = re.find_iter("baaxaxx").map(|m| m.as_str()).collect();
+        assert_eq!(matches, vec!["aa", "a"]);
+
+        // 空の文字列へのマッチは、同じ位置で繰り返さずに1文字ずつ進める
+        let re: Regex = Regex::new("a*").unwrap();
+        let ranges: Vec<(usize, usize)> = re.find_iter("baab").map(|m| (m.start(), m.end())).collect();
+        assert_eq!(ranges, vec![(0, 0), (1, 3), (4, 4)]);
+
+        // マルチバイト文字の場合も文字単位で進める
+        let re: Regex = Regex::new("x*").unwrap();
+        let ranges: Vec<(usize, usize)> = re.find_iter("あx").map(|m| (m.start(), m.end())).collect();
+        assert_eq!(ranges, vec![(0, 0), (3, 4)]);
+
+        // \G は直前のマッチの終了位置にのみマッチする
+        let re: Regex = Regex::new("\\Ga").unwrap();
+        assert_eq!(re.find_iter("aabaa").count(), 2);
+    }
+
+    #[test]
+    fn test_captures_read() {
+        let re: Regex = Regex::new("(?<key>(?:α|b)+)=(1|2)?").unwrap();
+        let mut locs: CaptureLocations = re.capture_locations();
+        assert_eq!(locs.len(), 3);
+
+        let m = re.captures_read(&mut locs, " αb=1").unwrap();
+        assert_eq!(m.as_str(), "αb=1");
+        assert_eq!(locs.get(0), Some((1, 6)));
+        assert_eq!(locs.get(1), Some((1, 4)));
+        assert_eq!(locs.get(2), Some((5, 6)));
+
+        // 同じ locs を使い回しても、前回の結果は残らない
+        assert!(re.captures_read(&mut locs, "b=").is_some());
+        assert_eq!(locs.get(2), None);
+        assert!(re.captures_read_at(&mut locs, "b=", 1).is_none());
+        assert_eq!(locs.get(0), None);
+        assert_eq!(locs.get(3), None);
+    }
+
+    #[test]
+    fn test_cache() {
+        // Pike VM で評価するパターンと、バックトラックで評価するパターンで同じ cache を使い回す
+        let pike: Regex = Regex::new("(a|ab)(c|bcd)").unwrap();
+        let backtrack: Regex = Regex::new("(?>a|ab)c").unwrap();
+        let mut cache: Cache = pike.create_cache();
+        for _ in 0..3 {
+            assert_eq!(pike.find_at_with_cache(&mut cache, "xabcd", 0).map(|m| m.range()), Some(1..5));
+            assert_eq!(backtrack.find_at_with_cache(&mut cache, "abc ac", 0).map(|m| m.range()), Some(4..6));
+            assert!(pike.is_match_with_cache(&mut cache, "abcd"));
+            assert!(!backtrack.is_match_with_cache(&mut cache, "abc"));
+            assert_eq!(pike.find_at_with_cache(&mut cache, "xabcd", 2), None);
+        }
+    }
+
+    #[test]
+    fn test_find_overlapping_iter() {
+        let re: Regex = Regex::new("(a|c)(a|c)(a|c)").unwrap();
+        let ranges: Vec<(usize, usize)> = re.find_overlapping_iter("acaxacaca").map(|m| (m.start(), m.end())).collect();
+        assert_eq!(ranges, vec![(0, 3), (4, 7), (5, 8), (6, 9)]);
+
+        // 位置はバイト単位で、空の文字列へのマッチは各位置で1回ずつ返す
+        let re: Regex = Regex::new("α*").unwrap();
+        let ranges: Vec<(usize, usize)> = re.find_overlapping_iter("αα").map(|m| (m.start(), m.end())).collect();
+        assert_eq!(ranges, vec![(0, 4), (2, 4), (4, 4)]);
+        assert_eq!(Regex::new("b").unwrap().find_overlapping_iter("aaa").next(), None);
+    }
+
+    #[test]
+    fn test_count_matches() {
+        let re: Regex = Regex::new("a+").unwrap();
+        assert_eq!(re.count_matches("baaxaxx"), 2);
+        assert_eq!(re.count_matches("xyz"), 0);
+
+        // 空の文字列へのマッチも find_iter と同じように数える
+        let re: Regex = Regex::new("x*").unwrap();
+        for text in ["", "あx", "xxaxb"] {
+            assert_eq!(re.count_matches(text), re.find_iter(text).count());
+        }
+    }
+
+    #[test]
+    fn test_captures() {
+        let re: Regex = Regex::new("(A|B)(x)?((?:1|2)+)").unwrap();
+        assert_eq!(re.captures_len(), 4);
+
+        let caps = re.captures("id: A12, B3").unwrap();
+        assert_eq!(caps.len(), 4);
+        assert_eq!(&caps[0], "A12");
+        assert_eq!(&caps[1], "A");
+        assert_eq!(caps.get(2), None);
+        assert_eq!(caps.get(3).unwrap().range(), 5..7);
+
+        assert!(re.captures("id: a12").is_none());
+    }
+
+    #[test]
+    fn test_captures_iter() {
+        let re: Regex = Regex::new("(a|b)=(1|2)").unwrap();
+        let pairs: Vec<(String, String)> = re
+            .captures_iter("a=1, b=2")
+            .map(|caps| (caps[1].to_string(), caps[2].to_string()))
+            .collect();
+        assert_eq!(pairs, vec![("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())]);
+    }
+
+    #[test]
+    fn test_named_captures() {
+        let re: Regex = Regex::new("(?P<key>a|b)=(?<value>1|2)").unwrap();
+        let caps = re.captures("x b=2").unwrap();
+        assert_eq!(&caps["key"], "b");
+        assert_eq!(caps.name("value").unwrap().start(), 4);
+        assert_eq!(caps.name("unknown"), None);
+    }
+
+    #[test]
+    fn test_capture_names() {
+        // マッチを試みる前に、グループの数と名前を調べられる
+        let re: Regex = Regex::new("(?P<key>a|b)(?:=)((?<value>1|2))?").unwrap();
+        assert_eq!(re.captures_len(), 4);
+        assert_eq!(re.capture_names().len(), re.captures_len());
+        assert_eq!(re.capture_names().collect::<Vec<_>>(), vec![None, Some("key"), None, Some("value")]);
+    }
+
+    #[test]
+    fn test_replace() {
+        let re: Regex = Regex::new("(?P<key>a|b)=(1|2)").unwrap();
+        assert_eq!(re.replace("a=1, b=2", "$2:${key}"), "1:a, b=2");
+        assert_eq!(re.replace_all("a=1, b=2", "$2:${key}"), "1:a, 2:b");
+        assert_eq!(re.replace_all("a=1", "$$$2$3$key"), "$1a");
+        assert_eq!(re.replacen("a=1 a=2 a=1", 2, "x"), "x x a=1");
+
+        // マッチしない場合は借用したまま返す
+        assert!(matches!(re.replace_all("c=3", "x"), Cow::Borrowed("c=3")));
+
+        // 空の文字列へのマッチも置換する
+        assert_eq!(Regex::new("x*").unwrap().replace_all("abc", "-"), "-a-b-c-");
+    }
+
+    #[test]
+    fn test_replace_with_closure() {
+        let re: Regex = Regex::new("(a|b)+").unwrap();
+        let upper = re.replace_all("xaby", |caps: &Captures| caps[0].to_uppercase());
+        assert_eq!(upper, "xABy");
+
+        // 呼び出しの間で状態を持てる
+        let mut count: usize = 0;
+        let numbered = re.replace_all("a-b-ab", |_: &Captures| {
+            count += 1;
+            count.to_string()
+        });
+        assert_eq!(numbered, "1-2-3");
+
+        assert_eq!(re.replace_all("a", NoExpand("$1")), "$1");
+    }
+
+    #[test]
+    fn test_split() {
+        let re: Regex = Regex::new(", *").unwrap();
+        let pieces: Vec<&str> = re.split("a, b,c").collect();
+        assert_eq!(pieces, vec!["a", "b", "c"]);
+
+        // 先頭・末尾の区切り
+        let pieces: Vec<&str> = re.split(",a,").collect();
+        assert_eq!(pieces, vec!["", "a", ""]);
+
+        // 区切りが無い場合と、空の文字列
+        assert_eq!(re.split("abc").collect::<Vec<&str>>(), vec!["abc"]);
+        assert_eq!(re.split("").collect::<Vec<&str>>(), vec![""]);
+
+        // 空の文字列にマッチする区切り
+        let re: Regex = Regex::new("x*").unwrap();
+        assert_eq!(re.split("axb").collect::<Vec<&str>>(), vec!["", "a", "b", ""]);
+    }
+
+    #[test]
+    fn test_splitn() {
+        let re: Regex = Regex::new(",").unwrap();
+        assert_eq!(re.splitn("a,b,c,d", 2).collect::<Vec<&str>>(), vec!["a", "b,c,d"]);
+        assert_eq!(re.splitn("a,b", 5).collect::<Vec<&str>>(), vec!["a", "b"]);
+        assert_eq!(re.splitn("a,b", 1).collect::<Vec<&str>>(), vec!["a,b"]);
+        assert_eq!(re.splitn("a,b", 0).count(), 0);
+    }
+
+    #[test]
+    fn test_is_prefix_match() {
+        let re: Regex = Regex::new("bc").unwrap();
+        assert!(re.is_prefix_match("bcd"));
+        assert!(!re.is_prefix_match("abc"));
+    }
+
+    #[test]
+    fn test_find_input() {
+        let re: Regex = Regex::new("b+$").unwrap();
+        let chars: Vec<char> = "αbb".chars().collect();
+        assert_eq!(re.find_input(&chars[..]), Some(1..3));
+        assert_eq!(re.find_input("αbb"), Some(2..4));
+        // 不正な UTF-8 のバイトは、1バイトの文字として読む
+        assert_eq!(re.find_input(&b"\xffbb"[..]), Some(1..3));
+        assert!(!re.is_match_input(&b"\xffbb\xff"[..]));
+    }
+
+    #[test]
+    fn test_is_full_match() {
+        let re: Regex = Regex::new("abc").unwrap();
+        assert!(re.is_full_match("abc"));
+        assert!(!re.is_full_match("abcXYZ"));
+        assert!(!re.is_full_match("XYZabc"));
+
+        // 先に試す分岐が末尾まで届かない場合は、後の分岐を試す
+        let re: Regex = Regex::new("a|ab").unwrap();
+        assert!(re.is_full_match("ab"));
+        let re: Regex = Regex::new("(a|b)*?").unwrap();
+        assert!(re.is_full_match("abba"));
+
+        // アトミックグループの中では末尾まで届かなくても確定する
+        let re: Regex = Regex::new("(?>a|ab)").unwrap();
+        assert!(!re.is_full_match("ab"));
+    }
+
+    /// pattern を、既定の評価器、DFA、遅延 DFA のそれぞれで構築した Regex について f を呼ぶ
+    fn each_engine(pattern: &str, mut f: impl FnMut(&Regex)) {
+        for builder in [RegexBuilder::new(pattern).clone(), RegexBuilder::new(pattern).dfa(true).clone(), RegexBuilder::new(pattern).lazy_dfa(true).clone()] {
+            f(&builder.build().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_short_text() {
+        // パターンより短い入力や空の入力では、入力の末尾で分岐が失敗するだけでパニックしない
+        let patterns: &[&str] = &["abc", "(a|b)c", "a(?>bc)", "(a)?(?(1)bc|xyz)", "abc$", "(?i)abc", "a.c", "abb+", "\\p{Greek}"];
+        for pattern in patterns {
+            // \p{..} は unicode フィーチャーが無効の場合はパースできない
+            if !cfg!(feature = "unicode") && pattern.contains("\\p") {
+                continue;
+            }
+            each_engine(pattern, |re| {
+                for text in ["", "a", "ab"] {
+                    assert!(!re.is_match(text), "{} / {:?}", pattern, text);
+                    assert!(!re.is_full_match(text));
+                    assert!(!re.is_prefix_match(text));
+                    assert_eq!(re.find(text), None);
+                    assert!(re.captures(text).is_none());
+                    assert_eq!(re.shortest_match(text), None);
+                    assert_eq!(re.find_iter(text).count(), 0);
+                    assert_eq!(re.find_at(text, text.len()), None);
+                }
+            });
+        }
+        let re: Regex = Regex::new("a?").unwrap();
+        assert_eq!(re.find("").map(|m| m.range()), Some(0..0));
+    }
+
+    #[test]
+    fn test_empty_branch() {
+        // 空の分岐は空文字列にマッチする
+        each_engine("x(a|)y", |re| {
+            assert_eq!(re.find_iter("xy xay xby").map(|m| m.as_str()).collect::<Vec<&str>>(), vec!["xy", "xay"]);
+        });
+        let re: Regex = Regex::new("a|").unwrap();
+        assert_eq!(re.find("ba").map(|m| m.range()), Some(0..0));
+        assert_eq!(re.find_iter("ab").map(|m| m.range()).collect::<Vec<_>>(), vec![0..1, 2..2]);
+
+        // 先に空の分岐を試す
+        let re: Regex = Regex::new("(|b)c").unwrap();
+        let caps: Captures = re.captures("bc").unwrap();
+        assert_eq!(caps.get(0).unwrap().as_str(), "bc");
+        assert_eq!(caps.get(1).unwrap().as_str(), "b");
+        assert_eq!(Regex::new("(|b)").unwrap().find("b").map(|m| m.range()), Some(0..0));
+    }
+
+    #[test]
+    fn test_empty_pattern() {
+        // 空のパターンは、すべての文字の境界で空文字列にマッチする
+        each_engine("", |re| {
+            assert!(re.is_match(""));
+            assert!(re.is_match("abc"));
+            assert!(re.is_full_match(""));
+            assert!(!re.is_full_match("a"));
+            assert_eq!(re.find("abc").map(|m| m.range()), Some(0..0));
+            assert_eq!(re.find_at("abc", 2).map(|m| m.range()), Some(2..2));
+            assert_eq!(re.find_iter("aé").map(|m| m.range()).collect::<Vec<_>>(), vec![0..0, 1..1, 3..3]);
+            assert_eq!(re.replace_all("ab", "-"), "-a-b-");
+            assert_eq!(re.captures_len(), 1);
+        });
+        let re: Regex = Regex::from_bytes(&Regex::new("").unwrap().to_bytes()).unwrap();
+        assert_eq!(re.find("x").map(|m| m.range()), Some(0..0));
+    }
+
+    #[test]
+    fn test_utf8_offsets() {
+        // マッチの位置は、text の文字の境界になるバイト単位の位置
+        let re: Regex = Regex::new("é+").unwrap();
+        assert_eq!(re.find("aééb").map(|m| m.range()), Some(1..5));
+        let re: Regex = Regex::new("x*").unwrap();
+        assert_eq!(re.find_iter("a😀é").map(|m| m.range()).collect::<Vec<_>>(), vec![0..0, 1..1, 5..5, 7..7]);
+
+        // 結合文字 (U+0301) は、直前の文字とは別の1文字として読む
+        let re: Regex = Regex::new(".\u{301}").unwrap();
+        assert_eq!(re.find("ae\u{301}b").map(|m| m.as_str()), Some("e\u{301}"));
+        let re: Regex = Regex::new("e").unwrap();
+        assert_eq!(re.find("e\u{301}").map(|m| m.range()), Some(0..1));
+
+        let patterns: &[&str] = &["é", ".", "..", "(?i)k", "a|é", "^.", "é$", "x*", "\\u{301}", "😀+", "(?i)Σ", "é.*é", "\\p{Greek}+"];
+        let texts: &[&str] = &["", "é", "e\u{301}", "aébé😀x", "KkK\u{212a}", "ΣσςéÉ", "ae\u{301}\u{301}b", "éaé bé"];
+        for pattern in patterns {
+            // \p{..} は unicode フィーチャーが無効の場合はパースできない
+            if !cfg!(feature = "unicode") && pattern.contains("\\p") {
+                continue;
+            }
+            each_engine(pattern, |re| {
+                for text in texts {
+                    for start in (0..=text.len()).filter(|i| text.is_char_boundary(*i)) {
+                        if let Some(m) = re.find_at(text, start) {
+                            assert!(text.is_char_boundary(m.start()) && text.is_char_boundary(m.end()), "{} / {:?}", pattern, text);
+                        }
+                    }
+                    for m in re.find_iter(text) {
+                        assert_eq!(m.as_str(), &text[m.range()]);
+                    }
+                    if let Some(end) = re.shortest_match(text) {
+                        assert!(text.is_char_boundary(end));
+                    }
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_debug_ast() {
+        // RegexBuilder で指定したフラグでパースし直す
+        let re: Regex = RegexBuilder::new("ab").case_insensitive(true).build().unwrap();
+        assert_eq!(re.debug_ast().unwrap(), "Seq @ 0..2\n  CharNoCase(a) @ 0..1\n  CharNoCase(b) @ 1..2\n");
+        let re: Regex = RegexBuilder::new("\\d").strict_escapes(false).build().unwrap();
+        assert_eq!(re.debug_ast().unwrap(), "Seq @ 0..2\n  Char(d) @ 0..2\n");
+        assert_eq!(Regex::new("").unwrap().debug_ast().unwrap(), "Empty @ 0..0\n");
+    }
+
+    #[test]
+    fn test_anchored_start() {
+        let re: Regex = Regex::new(r"^(.+)@(.+)\.com$").unwrap();
+        assert_eq!(re.captures("user@example.com").unwrap().get(2).unwrap().as_str(), "example");
+        assert!(!re.is_match("user@example.org"));
+        assert!(re.find_at("user@example.com", 1).is_none());
+        assert_eq!(re.find_iter("a@b.com").count(), 1);
+
+        // 先頭から1度だけ評価するため、開始位置ごとに命令を実行して上限に達することはない
+        let re: Regex = RegexBuilder::new("^(?:a|b)*c").backtrack_limit(1_000).build().unwrap();
+        let text: String = "x".to_string() + &"a".repeat(10_000) + "c";
+        assert_eq!(re.try_is_match(&text), Ok(false));
+        assert_eq!(re.try_find(&text), Ok(None));
+        let re: Regex = RegexBuilder::new("(?:a|b)*c").backtrack_limit(1_000).build().unwrap();
+        assert_eq!(re.try_find(&text), Err(Error::BudgetExceeded));
+    }
+
+    #[test]
+    fn test_new_error() {
+        assert_eq!(
+            Regex::new("a(b").unwrap_err(),
+            Error::Parse(ParseError::new(ParseErrorKind::UnbalancedParen, 1))
+        );
+        assert_eq!(
+            Regex::new("a(b").unwrap_err().to_string(),
+            "parse error: unbalanced parenthesis at 1"
+        );
+
+        // 理由と位置を取り出して、式の不正な箇所を示せる
+        let Err(Error::Parse(e)) = Regex::new("(a|b)\\q") else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(e.kind, ParseErrorKind::InvalidEscape('q'));
+        assert_eq!(e.position, 5);
+        assert_eq!(e.to_string(), "invalid escape '\\q' at 5");
+    }
+
+    #[test]
+    fn test_as_str() {
+        let re: Regex = Regex::new("a(b|c)*").unwrap();
+        assert_eq!(re.as_str(), "a(b|c)*");
+        assert_eq!(re.to_string(), "a(b|c)*");
+        assert_eq!(format!("{re:?}"), "Regex(\"a(b|c)*\")");
+    }
+
+    #[test]
+    fn test_from_str() {
+        let re: Regex = "a(b|c)*".parse().unwrap();
+        assert!(re.is_match("abcb"));
+        assert_eq!(
+            "a(b".parse::<Regex>().unwrap_err(),
+            Error::Parse(ParseError::new(ParseErrorKind::UnbalancedParen, 1))
+        );
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Regex>();
+        assert_send_sync::<crate::bytes::Regex>();
+    }
+
+    #[test]
+    fn test_clone_across_threads() {
+        let re: Regex = Regex::new("a(b|c)+").unwrap();
+        let handles: Vec<std::thread::JoinHandle<usize>> = ["abc", "xacbab", "x"]
+            .into_iter()
+            .map(|text| {
+                let re: Regex = re.clone();
+                std::thread::spawn(move || re.find_iter(text).count())
+            })
+            .collect();
+        let counts: Vec<usize> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(counts, vec![1, 2, 0]);
+
+        // clone したものは同じ命令列を共有する
+        assert!(core::ptr::eq(re.instructions(), re.clone().instructions()));
+    }
+}
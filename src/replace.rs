@@ -0,0 +1,62 @@
+//! マッチした部分を置換するための型・関数
+
+use alloc::string::String;
+
+use crate::captures::Captures;
+
+/// マッチした部分を置換する文字列を生成するトレイト  
+/// `Regex::replace` などに渡す置換の方法を表す
+///
+/// ```
+/// use small_regex::{Captures, Regex};
+///
+/// let re = Regex::new("(1|2)+").unwrap();
+/// let doubled = re.replace_all("a12b2", |caps: &Captures| {
+///     (caps[0].parse::<u32>().unwrap() * 2).to_string()
+/// });
+/// assert_eq!(doubled, "a24b4");
+/// ```
+pub trait Replacer {
+    /// caps に対応する置換後の文字列を dst に追加する
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String);
+}
+
+/// `$1`, `${name}` などを展開するテンプレートとして置換する
+impl Replacer for &str {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+}
+
+impl Replacer for String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+}
+
+impl Replacer for &String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+}
+
+/// クロージャが返す文字列で置換する
+impl<F, T> Replacer for F
+where
+    F: FnMut(&Captures<'_>) -> T,
+    T: AsRef<str>,
+{
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str((*self)(caps).as_ref());
+    }
+}
+
+/// `$` を展開せず、文字列をそのまま使って置換する型
+#[derive(Debug, Clone)]
+pub struct NoExpand<'t>(pub &'t str);
+
+impl Replacer for NoExpand<'_> {
+    fn replace_append(&mut self, _caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str(self.0);
+    }
+}
@@ -0,0 +1,124 @@
+//! コンパイル済みの Regex を serde でシリアライズ・デシリアライズするための実装  
+//! `serde` フィーチャが有効な場合のみ組み込む。デシリアライズではパターンをパースし直さず、命令列をそのまま使う  
+//! Unicode プロパティは名前だけを書き出し、読み込む際に範囲表を引き直す (大文字・小文字を区別しないものは `(?i)Lu` のように書く)
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::compiler::{is_valid_program, Instruction};
+use crate::evaluator::EvalOptions;
+use crate::parser::Flags;
+use crate::regex::Regex;
+use crate::unicode::Property;
+
+impl Serialize for Property {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.key())
+    }
+}
+
+impl<'de> Deserialize<'de> for Property {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Property, D::Error> {
+        let name: String = String::deserialize(deserializer)?;
+        Property::from_key(&name).ok_or_else(|| D::Error::custom(format_args!("unknown Unicode property '{name}'")))
+    }
+}
+
+/// シリアライズする際の Regex の中身
+#[derive(Serialize)]
+struct RegexRef<'a> {
+    pattern: &'a str,
+    instructions: &'a [Instruction],
+    capture_names: &'a [Option<String>],
+    options: EvalOptions,
+    flags: Flags,
+}
+
+/// デシリアライズする際の Regex の中身
+#[derive(Deserialize)]
+struct RegexData {
+    pattern: String,
+    instructions: Vec<Instruction>,
+    capture_names: Vec<Option<String>>,
+    options: EvalOptions,
+    #[serde(default)]
+    flags: Flags, // フラグを持たない以前の形式は、既定のフラグとして読み込む
+}
+
+impl Serialize for Regex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RegexRef {
+            pattern: self.as_str(),
+            instructions: self.instructions(),
+            capture_names: self.names(),
+            options: self.options(),
+            flags: self.flags(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Regex {
+    /// 命令列の飛び先が範囲外の場合など、評価できない命令列はエラーとする
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+        let data: RegexData = RegexData::deserialize(deserializer)?;
+        if !is_valid_program(&data.instructions, data.capture_names.len()) {
+            return Err(D::Error::custom("invalid instruction sequence"));
+        }
+        Ok(Regex::from_parts(&data.pattern, data.instructions, data.capture_names, data.options).with_flags(data.flags))
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::RegexBuilder;
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_round_trip() {
+        let re: Regex = RegexBuilder::new("(?<x>a+)(?i)b").anchored(true).build().unwrap();
+        let json: String = serde_json::to_string(&re).unwrap();
+        let re: Regex = serde_json::from_str(&json).unwrap();
+        assert_eq!(re.as_str(), "(?<x>a+)(?i)b");
+        assert_eq!(&re.captures("aaB").unwrap()["x"], "aa");
+        assert!(!re.is_match("xaab"));
+
+        // パースのフラグ (フラグを持たない形式は既定のフラグとする)
+        let re: Regex = RegexBuilder::new("a b").ignore_whitespace(true).build().unwrap();
+        let json: String = serde_json::to_string(&re).unwrap();
+        assert_eq!(serde_json::from_str::<Regex>(&json).unwrap().flags(), re.flags());
+        let json: &str = r#"{"pattern":"a","instructions":[{"Char":"a"},"Match"],"capture_names":[null],"options":{"anchored":false,"backtrack_limit":null}}"#;
+        assert_eq!(serde_json::from_str::<Regex>(json).unwrap().flags(), Default::default());
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_property() {
+        let re: Regex = Regex::new("\\p{Greek}+").unwrap();
+        let json: String = serde_json::to_string(&re).unwrap();
+        assert!(json.contains("\"Greek\""));
+        let re: Regex = serde_json::from_str(&json).unwrap();
+        assert_eq!(re.find("abαβ").unwrap().as_str(), "αβ");
+
+        let json: String = json.replace("\"Greek\"", "\"Unknown\"");
+        assert!(serde_json::from_str::<Regex>(&json).is_err());
+
+        let json: String = serde_json::to_string(&Regex::new("(?i)\\p{Lu}+").unwrap()).unwrap();
+        assert!(json.contains("\"(?i)Lu\""));
+        let re: Regex = serde_json::from_str(&json).unwrap();
+        assert_eq!(re.find("1aB2").unwrap().as_str(), "aB");
+    }
+
+    #[test]
+    fn test_invalid_program() {
+        let json: &str = r#"{"pattern":"a","instructions":[{"Jump":5},"Match"],"capture_names":[null],"options":{"anchored":false,"backtrack_limit":null}}"#;
+        assert!(serde_json::from_str::<Regex>(json).is_err());
+        let json: &str = r#"{"pattern":"a","instructions":[{"Char":"a"}],"capture_names":[null],"options":{"anchored":false,"backtrack_limit":null}}"#;
+        assert!(serde_json::from_str::<Regex>(json).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! 複数のパターンを1つの命令列にまとめ、1度の走査でマッチしたパターンを調べる型
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::aho_corasick::{literals, AhoCorasick};
+use crate::builder::DEFAULT_NEST_LIMIT;
+use crate::compiler::{compile_set, Instruction};
+use crate::error::Error;
+use crate::evaluator::search_set;
+use crate::optimizer::optimize;
+use crate::parser::{parse_with_flags, Flags, AST};
+use crate::simplify::simplify;
+
+/// 複数のパターンをまとめてコンパイルした型  
+/// 各パターンを分岐とする1つの命令列を持ち、text を1度走査するだけでマッチしたパターンをすべて調べる  
+/// アトミックグループや条件分岐を含むパターンがある場合は、開始位置ごとにバックトラックで評価するため遅くなる
+///
+/// ```
+/// use small_regex::RegexSet;
+///
+/// let set = RegexSet::new(["^ERROR", "timeout", "^WARN"]).unwrap();
+/// let matches = set.matches("ERROR: connection timeout");
+/// assert_eq!(matches.iter().collect::<Vec<usize>>(), vec![0, 1]);
+/// ```
+#[derive(Debug)]
+pub struct RegexSet {
+    instructions: Vec<Instruction>,
+    patterns: Vec<String>,
+    literals: Option<(AhoCorasick, Vec<usize>)>, // すべてのパターンがリテラルの選択だけからなる場合のオートマトンと、各リテラルのパターンの番号
+}
+
+impl RegexSet {
+    /// 各パターンをパースし、1つの命令列にコンパイルする  
+    /// いずれかのパターンが不正な場合はエラーを返す
+    pub fn new<I, S>(patterns: I) -> Result<RegexSet, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let patterns: Vec<String> = patterns.into_iter().map(|p| p.as_ref().to_string()).collect();
+        let asts: Vec<AST> = patterns
+            .iter()
+            .map(|p| parse_with_flags(p, Flags::default(), DEFAULT_NEST_LIMIT).map(simplify))
+            .collect::<Result<_, _>>()?;
+        Ok(RegexSet { instructions: optimize(compile_set(&asts)), literals: set_literals(&asts), patterns })
+    }
+
+    /// text のいずれかの位置に、いずれかのパターンにマッチする部分があるかを判定する
+    pub fn is_match(&self, text: &str) -> bool {
+        self.matches(text).matched_any()
+    }
+
+    /// text のいずれかの位置でマッチしたパターンを調べる
+    pub fn matches(&self, text: &str) -> SetMatches {
+        if let Some((ac, owners)) = &self.literals {
+            let mut found: Vec<bool> = vec![false; owners.len()];
+            ac.matches(text.as_bytes(), &mut found);
+            let mut matched: Vec<bool> = vec![false; self.patterns.len()];
+            for (i, _) in found.iter().enumerate().filter(|(_, found)| **found) {
+                matched[owners[i]] = true;
+            }
+            return SetMatches { matched };
+        }
+        SetMatches { matched: search_set(&self.instructions, text, self.patterns.len()) }
+    }
+
+    /// パターンの数
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// パターンが1つも無いかどうか
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// RegexSet の生成に使ったパターン
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+}
+
+/// すべてのパターンがリテラルの選択だけからなる場合に、全パターンのリテラルを探索するオートマトンを生成する  
+/// 各リテラルがどのパターンのものかを、リテラルの順に返す
+fn set_literals(asts: &[AST]) -> Option<(AhoCorasick, Vec<usize>)> {
+    if asts.is_empty() {
+        return None;
+    }
+    let mut all: Vec<String> = Vec::new();
+    let mut owners: Vec<usize> = Vec::new();
+    for (i, ast) in asts.iter().enumerate() {
+        let literals: Vec<String> = literals(ast)?;
+        owners.extend(core::iter::repeat_n(i, literals.len()));
+        all.extend(literals);
+    }
+    Some((AhoCorasick::new(&all), owners))
+}
+
+/// RegexSet でマッチしたパターンの集合を表す型  
+/// `RegexSet::matches` で生成する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetMatches {
+    matched: Vec<bool>,
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl SetMatches {
+    /// いずれかのパターンがマッチしたかどうか
+    pub fn matched_any(&self) -> bool {
+        self.matched.iter().any(|m| *m)
+    }
+
+    /// i 番目のパターンがマッチしたかどうか
+    pub fn matched(&self, i: usize) -> bool {
+        self.matched.get(i).copied().unwrap_or(false)
+    }
+
+    /// RegexSet のパターンの数 (マッチしなかったパターンを含む)
+    pub fn len(&self) -> usize {
+        self.matched.len()
+    }
+
+    /// マッチしたパターンの番号を昇順に返すイテレータを返す
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.matched.iter().enumerate().filter(|(_, m)| **m).map(|(i, _)| i)
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use super::RegexSet;
+    use crate::error::{Error, ParseError, ParseErrorKind};
+
+    #[test]
+    fn test_matches() {
+        let set: RegexSet = RegexSet::new(["ab+", "^b", "c$", "(x|y)z"]).unwrap();
+        let matches = set.matches("abbc");
+        assert!(matches.matched_any());
+        assert_eq!(matches.iter().collect::<Vec<usize>>(), vec![0, 2]);
+        assert!(matches.matched(2));
+        assert!(!matches.matched(3));
+        assert_eq!(matches.len(), 4);
+
+        assert!(set.is_match("yz"));
+        assert!(!set.is_match("zz"));
+    }
+
+    #[test]
+    fn test_groups_do_not_interfere() {
+        // 各パターンのグループの番号は重なるが、条件分岐は自分のパターンのグループだけを参照する
+        let set: RegexSet = RegexSet::new(["(a)", "(b)?(?(1)c|d)"]).unwrap();
+        assert_eq!(set.matches("ad").iter().collect::<Vec<usize>>(), vec![0, 1]);
+        assert_eq!(set.matches("ac").iter().collect::<Vec<usize>>(), vec![0]);
+    }
+
+    #[test]
+    fn test_literals() {
+        // リテラルだけのパターンは、命令列の代わりにオートマトンで探索する
+        let set: RegexSet = RegexSet::new(["error", "warn|fatal", "timeout"]).unwrap();
+        assert!(set.literals.is_some());
+        assert_eq!(set.matches("fatal: timeout").iter().collect::<Vec<usize>>(), vec![1, 2]);
+        assert!(!set.is_match("info"));
+    }
+
+    #[test]
+    fn test_empty_set() {
+        let set: RegexSet = RegexSet::new(Vec::<&str>::new()).unwrap();
+        assert!(set.is_empty());
+        assert!(!set.is_match("abc"));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert_eq!(
+            RegexSet::new(["a", "b("]).unwrap_err(),
+            Error::Parse(ParseError::new(ParseErrorKind::UnbalancedParen, 1))
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! 文字を読む位置が64個以下の小さなパターンを、ビット並列 (Shift-Or) で判定するための型  
+//! パターンの各文字の位置をビットに対応させ (Glushkov のオートマトン)、到達しうる位置の集合を u64 のビット演算で1文字ずつ進める
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::compiler::Instruction;
+use crate::evaluator::{is_char_matched, EvalOptions};
+use crate::input::{symbols, Input};
+use crate::parser::AST;
+
+/// 扱える位置の数の上限
+const MAX_POSITIONS: usize = 64;
+
+/// Shift-Or でマッチするかどうかを判定する型
+#[derive(Debug)]
+pub struct ShiftOr {
+    positions: Vec<Instruction>, // 各位置で文字を読む命令
+    ascii: Vec<u64>,             // ASCII の各文字にマッチする位置の集合
+    follow: Vec<[u64; 256]>,     // 位置の集合の i 番目のバイトから、次に到達しうる位置の集合を引く表
+    first: u64,                  // マッチの最初の文字を読む位置の集合
+    last: u64,                   // マッチの最後の文字を読む位置の集合
+    nullable: bool,              // 空の文字列にマッチするかどうか
+    anchored: bool,
+    anchored_end: bool,
+}
+
+impl ShiftOr {
+    /// パターンの AST から ShiftOr を生成する  
+    /// 文字を読む位置が64個を超える場合や、キャプチャグループ、アンカー、アトミックグループ、条件分岐を含む場合は None を返す
+    pub fn new(ast: &AST, options: EvalOptions) -> Option<ShiftOr> {
+        let mut builder: Builder = Builder { positions: Vec::new(), follow: Vec::new() };
+        let (first, last, nullable) = builder.build(ast)?;
+
+        let ascii: Vec<u64> = (0..128u8).map(|c| mask_of(&builder.positions, c as char)).collect();
+        let mut follow: Vec<[u64; 256]> = vec![[0; 256]; builder.positions.len().div_ceil(8)];
+        for (i, table) in follow.iter_mut().enumerate() {
+            for (byte, set) in table.iter_mut().enumerate() {
+                for bit in 0..8 {
+                    if byte & (1 << bit) != 0 {
+                        *set |= builder.follow.get(i * 8 + bit).copied().unwrap_or(0);
+                    }
+                }
+            }
+        }
+        Some(ShiftOr {
+            positions: builder.positions,
+            ascii,
+            follow,
+            first,
+            last,
+            nullable,
+            anchored: options.anchored,
+            anchored_end: options.anchored_end,
+        })
+    }
+
+    /// text の start (バイト単位) 以降に、マッチする部分があるかを判定する
+    pub fn is_match_at<I: Input + ?Sized>(&self, text: &I, start: usize) -> bool {
+        // 空の文字列へのマッチは、anchored_end の場合は入力の末尾でのみ認める
+        if self.nullable && (!self.anchored_end || !self.anchored || start == text.len()) {
+            return true;
+        }
+        let mut state: u64 = 0; // 直前の文字を読んだ位置の集合
+        let mut restart: u64 = self.first;
+        for c in symbols(text, start) {
+            let mut reach: u64 = restart;
+            for (i, table) in self.follow.iter().enumerate() {
+                reach |= table[(state >> (i * 8)) as usize & 0xFF];
+            }
+            state = reach & self.mask(c);
+            if state & self.last != 0 && !self.anchored_end {
+                return true;
+            }
+            if self.anchored {
+                if state == 0 {
+                    return false;
+                }
+                restart = 0;
+            }
+        }
+        state & self.last != 0
+    }
+
+    /// 文字 c にマッチする位置の集合
+    fn mask(&self, c: char) -> u64 {
+        match self.ascii.get(c as usize) {
+            Some(mask) => *mask,
+            None => mask_of(&self.positions, c),
+        }
+    }
+}
+
+/// positions のうち、文字 c にマッチする位置の集合を返す
+fn mask_of(positions: &[Instruction], c: char) -> u64 {
+    positions
+        .iter()
+        .enumerate()
+        .filter(|(_, inst)| is_char_matched(inst, &c))
+        .fold(0, |mask, (i, _)| mask | (1 << i))
+}
+
+/// Glushkov のオートマトンを生成するための型
+struct Builder {
+    positions: Vec<Instruction>,
+    follow: Vec<u64>, // 各位置の次に到達しうる位置の集合
+}
+
+impl Builder {
+    /// ast の (最初の位置の集合, 最後の位置の集合, 空の文字列にマッチするか) を返す
+    fn build(&mut self, ast: &AST) -> Option<(u64, u64, bool)> {
+        let inst: Instruction = match ast {
+            AST::Char(c) => Instruction::Char(*c),
+            AST::CharNoCase(c) => Instruction::CharNoCase(*c),
+            AST::AnyChar(dot_all) => Instruction::AnyChar(*dot_all),
+            AST::Property(property, negated) => Instruction::Property(*property, *negated),
+            AST::Seq(asts) => {
+                let (mut first, mut last, mut nullable): (u64, u64, bool) = (0, 0, true);
+                for ast in asts {
+                    let (f, l, n) = self.build(ast)?;
+                    self.link(last, f);
+                    if nullable {
+                        first |= f;
+                    }
+                    last = if n { last | l } else { l };
+                    nullable &= n;
+                }
+                return Some((first, last, nullable));
+            }
+            AST::Or(left, right) => {
+                let (f1, l1, n1) = self.build(left)?;
+                let (f2, l2, n2) = self.build(right)?;
+                return Some((f1 | f2, l1 | l2, n1 || n2));
+            }
+            AST::Star(ast) => {
+                let (first, last, _) = self.build(ast)?;
+                self.link(last, first);
+                return Some((first, last, true));
+            }
+            AST::Plus(ast) => {
+                let (first, last, nullable) = self.build(ast)?;
+                self.link(last, first);
+                return Some((first, last, nullable));
+            }
+            AST::Question(ast) => {
+                let (first, last, _) = self.build(ast)?;
+                return Some((first, last, true));
+            }
+            AST::Empty => return Some((0, 0, true)),
+            AST::Capture(_, _, _) | AST::Atomic(_) | AST::Assert(_) | AST::Conditional(_, _, _) => return None,
+        };
+        if self.positions.len() >= MAX_POSITIONS {
+            return None;
+        }
+        let bit: u64 = 1 << self.positions.len();
+        self.positions.push(inst);
+        self.follow.push(0);
+        Some((bit, bit, false))
+    }
+
+    /// from の各位置の次に、to の位置に到達しうるようにする
+    fn link(&mut self, from: u64, to: u64) {
+        for (i, follow) in self.follow.iter_mut().enumerate() {
+            if from & (1 << i) != 0 {
+                *follow |= to;
+            }
+        }
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use super::ShiftOr;
+    use crate::compiler::compile;
+    use crate::evaluator::{search, EvalOptions};
+    use crate::parser::parse;
+
+    /// Shift-Or とバックトラックで、マッチするかどうかの判定が一致するかを確かめる
+    fn assert_same(pattern: &str, texts: &[&str], options: EvalOptions) {
+        let ast = parse(pattern).unwrap();
+        let insts = compile(&ast);
+        let shift_or: ShiftOr = ShiftOr::new(&ast, options).unwrap();
+        for text in texts {
+            for start in (0..=text.len()).filter(|i| text.is_char_boundary(*i)) {
+                let expected: bool = search(&insts, *text, start, options).is_some();
+                assert_eq!(shift_or.is_match_at(*text, start), expected, "{} / {} / {}", pattern, text, start);
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_match() {
+        let texts: &[&str] = &["", "a", "abc", "xabcab", "ba\nc", "ÅβΒ", "aaaab", "abab"];
+        let patterns: &[&str] = &["abc", "a(?:b|c)*", "(?i)åβ", "a.c", "(?s)a.c", "a+b", "(?:ab)+", "b?", "x*y*"];
+        for pattern in patterns {
+            assert_same(pattern, texts, EvalOptions::default());
+            assert_same(pattern, texts, EvalOptions { anchored: true, ..EvalOptions::default() });
+            assert_same(pattern, texts, EvalOptions { anchored_end: true, ..EvalOptions::default() });
+        }
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(ShiftOr::new(&parse("(a)b").unwrap(), EvalOptions::default()).is_none());
+        assert!(ShiftOr::new(&parse("^ab").unwrap(), EvalOptions::default()).is_none());
+        assert!(ShiftOr::new(&parse(&"a".repeat(65)).unwrap(), EvalOptions::default()).is_none());
+        assert!(ShiftOr::new(&parse(&"a".repeat(64)).unwrap(), EvalOptions::default()).is_some());
+    }
+}
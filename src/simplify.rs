@@ -0,0 +1,163 @@
+//! コード生成の前に AST を簡約する関数  
+//! マッチとキャプチャの結果を変えずに AST を小さくし、命令列を短くする。リテラルの抽出にも整理された AST を渡せる
+//!
+//! - ネストした Seq を平坦にし、要素が1つの Seq (`(?:a)` など) はその要素にする  
+//! - 繰り返しの繰り返し (`(?:a*)*`, `(?:a+)?` など) は1つの繰り返しにする  
+//! - 選択の中の同じ分岐 (`a|a`) は、最初の分岐だけを残す  
+//! - 連結の中の空の分岐 (Empty) を取り除き、空の分岐の繰り返しは空の分岐にする
+//!
+//! キャプチャグループは、マッチした位置を記録するため取り除かない
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::parser::{fold_or, parse_qualifier, AST};
+
+/// AST を簡約する関数
+pub fn simplify(ast: AST) -> AST {
+    match ast {
+        AST::Seq(asts) => {
+            let mut flattened: Vec<AST> = Vec::new();
+            for ast in asts {
+                match simplify(ast) {
+                    AST::Seq(inner) => flattened.extend(inner),
+                    AST::Empty => {}
+                    ast => flattened.push(ast),
+                }
+            }
+            match flattened.len() {
+                0 => AST::Empty,
+                1 => flattened.pop().unwrap(),
+                _ => AST::Seq(flattened),
+            }
+        }
+        AST::Or(left, right) => {
+            let mut branches: Vec<AST> = Vec::new();
+            for branch in [*left, *right] {
+                collect_branches(simplify(branch), &mut branches);
+            }
+            fold_or(branches)
+        }
+        AST::Star(ast) => repeat('*', simplify(*ast)),
+        AST::Plus(ast) => repeat('+', simplify(*ast)),
+        AST::Question(ast) => repeat('?', simplify(*ast)),
+        AST::Atomic(ast) => AST::Atomic(Box::new(simplify(*ast))),
+        AST::Capture(index, name, ast) => AST::Capture(index, name, Box::new(simplify(*ast))),
+        AST::Conditional(group, then_ast, else_ast) => {
+            AST::Conditional(group, Box::new(simplify(*then_ast)), Box::new(simplify(*else_ast)))
+        }
+        ast => ast,
+    }
+}
+
+/// 選択の分岐を優先度の順に branches に追加する  
+/// 既に同じ分岐がある場合は、後の分岐は先の分岐が失敗した場合にしか試されず、同じく失敗するため追加しない
+fn collect_branches(ast: AST, branches: &mut Vec<AST>) {
+    match ast {
+        AST::Or(left, right) => {
+            collect_branches(*left, branches);
+            collect_branches(*right, branches);
+        }
+        ast => {
+            if !branches.contains(&ast) {
+                branches.push(ast);
+            }
+        }
+    }
+}
+
+/// 量指定子 c (`*`, `+`, `?` のいずれか) で、簡約した ast を繰り返す AST を生成する  
+/// ast も繰り返しの場合は、2つの繰り返しをまとめた1つの繰り返しにする  
+/// キャプチャグループを含む場合は、最後の繰り返しで記録する位置が変わり得るためまとめない
+fn repeat(c: char, ast: AST) -> AST {
+    if has_capture(&ast) {
+        return parse_qualifier(c, ast);
+    }
+    match (c, ast) {
+        (_, AST::Empty) => AST::Empty,
+        ('+', AST::Plus(inner)) => AST::Plus(inner),
+        ('?', AST::Question(inner)) => AST::Question(inner),
+        (_, AST::Star(inner) | AST::Plus(inner) | AST::Question(inner)) => AST::Star(inner),
+        (c, ast) => parse_qualifier(c, ast),
+    }
+}
+
+/// AST がキャプチャグループを含むかどうか
+fn has_capture(ast: &AST) -> bool {
+    match ast {
+        AST::Capture(_, _, _) => true,
+        AST::Plus(ast) | AST::Star(ast) | AST::Question(ast) | AST::Atomic(ast) => has_capture(ast),
+        AST::Or(left, right) | AST::Conditional(_, left, right) => has_capture(left) || has_capture(right),
+        AST::Seq(asts) => asts.iter().any(has_capture),
+        _ => false,
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::simplify;
+    use crate::compiler::{compile, Instruction};
+    use crate::evaluator::{search_backtrack, slots_len, EvalOptions, Partial, Scratch};
+    use crate::parser::{parse, AST};
+
+    #[test]
+    fn test_flatten() {
+        let expect: AST = AST::Seq(vec![AST::Char('a'), AST::Char('b'), AST::Char('c'), AST::Char('d')]);
+        assert_eq!(simplify(parse("a(?:b(?:c))d").unwrap()), expect);
+        assert_eq!(simplify(parse("(?:a)").unwrap()), AST::Char('a'));
+        let expect: AST = AST::Capture(1, None, Box::new(AST::Char('a')));
+        assert_eq!(simplify(parse("((?:a))").unwrap()), expect);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let star: AST = AST::Star(Box::new(AST::Char('a')));
+        assert_eq!(simplify(parse("(?:a*)*").unwrap()), star);
+        assert_eq!(simplify(parse("(?:a+)?").unwrap()), star);
+        assert_eq!(simplify(parse("(?:a?)+").unwrap()), star);
+        assert_eq!(simplify(parse("(?:a+)+").unwrap()), AST::Plus(Box::new(AST::Char('a'))));
+        assert_eq!(simplify(parse("(?:a?)?").unwrap()), AST::Question(Box::new(AST::Char('a'))));
+        // キャプチャグループを含む繰り返しはまとめない
+        let ast: AST = simplify(parse("(a*)*").unwrap());
+        assert!(matches!(ast, AST::Star(inner) if matches!(*inner, AST::Capture(_, _, _))));
+    }
+
+    #[test]
+    fn test_duplicate_branches() {
+        let expect: AST = AST::Or(Box::new(AST::Char('a')), Box::new(AST::Char('b')));
+        assert_eq!(simplify(parse("a|b|a").unwrap()), expect);
+        assert_eq!(simplify(parse("a|(?:a)").unwrap()), AST::Char('a'));
+    }
+
+    #[test]
+    fn test_same_as_unsimplified() {
+        let cases: &[(&str, &str)] = &[
+            ("(?:(?:ab|a)+)+b", "aabab"),
+            ("(?:(?:ab|a)*)*c", "ababac"),
+            ("(?:a|ab|a)(c|bcd)", "abcd"),
+            ("((?:a?)?)b", "ab"),
+            ("(?:x(?:y|z)*)+$", "xyxz"),
+        ];
+        for (pattern, text) in cases {
+            let insts: Vec<Instruction> = compile(&parse(pattern).unwrap());
+            let simplified: Vec<Instruction> = compile(&simplify(parse(pattern).unwrap()));
+            assert!(simplified.len() <= insts.len(), "{}", pattern);
+            for index in (0..=text.len()).filter(|i| text.is_char_boundary(*i)) {
+                let mut expected: Vec<Option<usize>> = vec![None; slots_len(&insts)];
+                let mut actual: Vec<Option<usize>> = vec![None; slots_len(&simplified)];
+                let options: EvalOptions = EvalOptions::default();
+                let mut scratch: Scratch = Scratch::default();
+                let matched: Partial = search_backtrack(&insts, *text, index, index, &mut expected, options, true, &mut scratch);
+                let result: Partial = search_backtrack(&simplified, *text, index, index, &mut actual, options, true, &mut scratch);
+                assert_eq!(result, matched, "{} / {} / {}", pattern, text, index);
+                assert_eq!(actual, expected, "{} / {} / {}", pattern, text, index);
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! パターンにマッチする部分を区切りとして文字列を分割するイテレータ
+
+use crate::matches::Matches;
+
+/// パターンにマッチする部分で text を分割した部分文字列を順に返すイテレータ  
+/// `Regex::split` で生成する
+#[derive(Debug)]
+pub struct Split<'r, 't> {
+    finder: Matches<'r, 't>,
+    text: &'t str,
+    last: usize, // 次の部分文字列の開始位置
+    done: bool,  // 末尾の部分文字列を返し終えたかどうか
+}
+
+impl<'r, 't> Split<'r, 't> {
+    pub(crate) fn new(finder: Matches<'r, 't>, text: &'t str) -> Split<'r, 't> {
+        Split { finder, text, last: 0, done: false }
+    }
+}
+
+impl<'r, 't> Iterator for Split<'r, 't> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<&'t str> {
+        if self.done {
+            return None;
+        }
+        match self.finder.next() {
+            Some(m) => {
+                let piece: &'t str = &self.text[self.last..m.start()];
+                self.last = m.end();
+                Some(piece)
+            }
+            None => {
+                // 最後の区切りより後ろの部分 (空の場合も含む)
+                self.done = true;
+                Some(&self.text[self.last..])
+            }
+        }
+    }
+}
+
+/// 最大 limit 個の部分文字列に分割するイテレータ  
+/// 最後の部分文字列には、残りの文字列をすべて含める。`Regex::splitn` で生成する
+#[derive(Debug)]
+pub struct SplitN<'r, 't> {
+    splits: Split<'r, 't>,
+    limit: usize, // 残りの部分文字列の数
+}
+
+impl<'r, 't> SplitN<'r, 't> {
+    pub(crate) fn new(splits: Split<'r, 't>, limit: usize) -> SplitN<'r, 't> {
+        SplitN { splits, limit }
+    }
+}
+
+impl<'r, 't> Iterator for SplitN<'r, 't> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<&'t str> {
+        if self.limit == 0 {
+            return None;
+        }
+        self.limit -= 1;
+        if self.limit > 0 {
+            return self.splits.next();
+        }
+
+        // 最後の部分文字列は、区切りを探さずに残りをすべて返す
+        if self.splits.done {
+            return None;
+        }
+        self.splits.done = true;
+        Some(&self.splits.text[self.splits.last..])
+    }
+}
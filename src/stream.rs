@@ -0,0 +1,427 @@
+//! 入力を少しずつ受け取りながら探索するための型  
+//! マッチが確定しない間は、入力を受け取るたびに確定していない位置から探索し直すため、保持する入力が長いほど1回の探索が遅くなる  
+//! 保持する入力の長さは buffer_limit で制限し、超えた場合は Error::LimitExceeded を返して探索を終える
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::io::{self, ErrorKind, Read};
+
+use crate::error::{Error, LimitKind};
+use crate::evaluator::{slots_len, Partial};
+use crate::matches::Searcher;
+use crate::regex::Regex;
+
+/// 1回の read で読み込むバイト数
+#[cfg(feature = "std")]
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// マッチが確定しないまま保持する入力のバイト数の、既定の上限
+const DEFAULT_BUFFER_LIMIT: usize = 1024 * 1024;
+
+/// ストリームの中でマッチした部分の位置を表す型  
+/// 位置はすべてストリームの先頭からのバイト単位の位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamMatch {
+    start: usize,
+    end: usize,
+}
+
+impl StreamMatch {
+    pub(crate) fn new(start: usize, end: usize) -> StreamMatch {
+        StreamMatch { start, end }
+    }
+
+    /// マッチした部分の開始位置
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// マッチした部分の終了位置 (この位置の文字はマッチした部分に含まない)
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// マッチした部分の範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+/// 入力を feed で少しずつ渡しながら、マッチする部分を重ならないように先頭から順に探索する型  
+/// `Regex::push_searcher` で生成する  
+/// 渡された入力のうち、まだマッチが確定していない部分だけを保持するため、呼び出し側で入力をためておく必要は無い  
+/// 探索が上限に達した場合や、保持する入力が buffer_limit を超えた場合は、エラーを返してそれ以降はマッチを返さない
+///
+/// ```
+/// use small_regex::Regex;
+///
+/// let re = Regex::new("ab+").unwrap();
+/// let mut searcher = re.push_searcher();
+/// // "ab" の後に b が続くかどうかは、続きを受け取るまで確定しない
+/// assert_eq!(searcher.feed("xxab").count(), 0);
+/// assert_eq!(searcher.feed("b a").map(|m| m.unwrap().range()).collect::<Vec<_>>(), vec![2..5]);
+/// assert_eq!(searcher.feed("b").count(), 0);
+/// assert_eq!(searcher.finish().map(|m| m.unwrap().range()).collect::<Vec<_>>(), vec![6..8]);
+/// ```
+#[derive(Debug)]
+pub struct PushSearcher<'r> {
+    searcher: Searcher<'r, String>,
+    slots: Vec<Option<usize>>,
+    buffer_limit: usize, // マッチが確定しないまま保持する入力のバイト数の上限
+    finished: bool,      // finish を呼んだかどうか
+    failed: bool,        // エラーを返したかどうか
+}
+
+impl<'r> PushSearcher<'r> {
+    pub(crate) fn new(regex: &'r Regex) -> PushSearcher<'r> {
+        PushSearcher {
+            searcher: Searcher::streaming(regex.instructions(), regex.options()),
+            slots: vec![None; slots_len(regex.instructions())],
+            buffer_limit: DEFAULT_BUFFER_LIMIT,
+            finished: false,
+            failed: false,
+        }
+    }
+
+    /// マッチが確定しないまま保持する入力のバイト数の上限を設定する (デフォルトは 1 MiB)
+    pub fn buffer_limit(&mut self, limit: usize) -> &mut Self {
+        self.buffer_limit = limit;
+        self
+    }
+
+    /// 入力の続き chunk を渡し、新たに確定したマッチを返すイテレータを返す  
+    /// 返すマッチの位置は、最初に渡した入力の先頭からのバイト単位の位置  
+    /// エラーを返した後は chunk を保持せず、何も返さない。finish の後に呼んだ場合はパニックする
+    pub fn feed(&mut self, chunk: &str) -> PushMatches<'_, 'r> {
+        assert!(!self.finished, "feed called after finish");
+        if !self.failed {
+            self.searcher.push(chunk);
+        }
+        PushMatches { searcher: self }
+    }
+
+    /// 入力の終わりに達したことを伝え、残りのマッチを返すイテレータを返す
+    pub fn finish(&mut self) -> PushMatches<'_, 'r> {
+        self.finished = true;
+        self.searcher.finish();
+        PushMatches { searcher: self }
+    }
+}
+
+/// PushSearcher に渡した入力の中で、マッチが確定した部分を順に返すイテレータ  
+/// `PushSearcher::feed`, `PushSearcher::finish` で生成する  
+/// 途中で破棄した場合、残りのマッチは次の feed, finish で返す
+#[derive(Debug)]
+pub struct PushMatches<'s, 'r> {
+    searcher: &'s mut PushSearcher<'r>,
+}
+
+impl Iterator for PushMatches<'_, '_> {
+    type Item = Result<StreamMatch, Error>;
+
+    fn next(&mut self) -> Option<Result<StreamMatch, Error>> {
+        let searcher: &mut PushSearcher = self.searcher;
+        if searcher.failed {
+            return None;
+        }
+        match poll(&mut searcher.searcher, &mut searcher.slots, searcher.buffer_limit) {
+            Ok(Partial::Matched) => Some(Ok(StreamMatch::new(searcher.slots[0]?, searcher.slots[1]?))),
+            Ok(_) => None,
+            Err(e) => {
+                searcher.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// searcher で次のマッチを探索する  
+/// 探索が上限に達した場合は Error::BudgetExceeded を、続きの入力を待つ間に保持する入力が limit を超えた場合は  
+/// Error::LimitExceeded を返す
+fn poll(searcher: &mut Searcher<String>, slots: &mut [Option<usize>], limit: usize) -> Result<Partial, Error> {
+    match searcher.poll(slots) {
+        Partial::Aborted => Err(Error::BudgetExceeded),
+        Partial::NeedInput(_) if searcher.buffered() > limit => {
+            Err(Error::LimitExceeded { kind: LimitKind::StreamBuffer, limit })
+        }
+        partial => Ok(partial),
+    }
+}
+
+/// reader から読み込みながら、マッチする部分を重ならないように先頭から順に返すイテレータ  
+/// `Regex::stream_find_iter` で生成する  
+/// 読み込んだ入力のうち、まだマッチが確定していない部分だけを保持するため、入力全体を読み込む必要は無い  
+/// 入力が UTF-8 として不正な場合や、読み込みに失敗した場合はエラーを返して終了する  
+/// 探索が上限に達した場合や、保持する入力が buffer_limit を超えた場合も、Error を包んだ io::Error (ErrorKind::Other) を返して終了する
+///
+/// ```
+/// use small_regex::Regex;
+///
+/// let re = Regex::new("ab+").unwrap();
+/// let reader: &[u8] = b"xxabb ab";
+/// let ranges: Vec<(usize, usize)> = re
+///     .stream_find_iter(reader)
+///     .map(|m| m.map(|m| (m.start(), m.end())))
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(ranges, vec![(2, 5), (6, 8)]);
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StreamSearcher<'r, R> {
+    reader: R,
+    searcher: Searcher<'r, String>,
+    slots: Vec<Option<usize>>,
+    pending: Vec<u8>,    // 読み込んだが、文字の途中で終わっているため Searcher に渡していないバイト列
+    invalid: bool,       // pending の先頭が UTF-8 として不正かどうか
+    done: bool,          // 入力の終わりに達したか、エラーを返したかどうか
+    buffer_limit: usize, // マッチが確定しないまま保持する入力のバイト数の上限
+}
+
+#[cfg(feature = "std")]
+impl<'r, R: Read> StreamSearcher<'r, R> {
+    pub(crate) fn new(regex: &'r Regex, reader: R) -> StreamSearcher<'r, R> {
+        StreamSearcher {
+            reader,
+            searcher: Searcher::streaming(regex.instructions(), regex.options()),
+            slots: vec![None; slots_len(regex.instructions())],
+            pending: Vec::new(),
+            invalid: false,
+            done: false,
+            buffer_limit: DEFAULT_BUFFER_LIMIT,
+        }
+    }
+
+    /// マッチが確定しないまま保持する入力のバイト数の上限を設定する (デフォルトは 1 MiB)
+    pub fn buffer_limit(&mut self, limit: usize) -> &mut Self {
+        self.buffer_limit = limit;
+        self
+    }
+
+    /// reader から次の入力を読み込み、文字の境界までを Searcher に渡す  
+    /// 入力の終わりに達した場合は Searcher に終わりを伝える  
+    /// 不正な UTF-8 を読み込んだ場合は、その手前までを Searcher に渡し、次に呼ばれた時にエラーを返す
+    fn fill(&mut self) -> io::Result<()> {
+        if self.invalid {
+            return Err(io::Error::new(ErrorKind::InvalidData, "stream did not contain valid UTF-8"));
+        }
+        let mut chunk: Vec<u8> = vec![0; CHUNK_SIZE];
+        let n: usize = loop {
+            match self.reader.read(&mut chunk) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        if n == 0 {
+            if !self.pending.is_empty() {
+                return Err(io::Error::new(ErrorKind::InvalidData, "stream did not contain valid UTF-8"));
+            }
+            self.searcher.finish();
+            return Ok(());
+        }
+
+        self.pending.extend_from_slice(&chunk[..n]);
+        let valid: usize = match core::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            // 末尾が文字の途中で終わっている場合は、続きを読み込むまで残しておく
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => {
+                self.invalid = true;
+                e.valid_up_to()
+            }
+        };
+        let text: &str = core::str::from_utf8(&self.pending[..valid]).unwrap();
+        self.searcher.push(text);
+        self.pending.drain(..valid);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'r, R: Read> Iterator for StreamSearcher<'r, R> {
+    type Item = io::Result<StreamMatch>;
+
+    fn next(&mut self) -> Option<io::Result<StreamMatch>> {
+        while !self.done {
+            match poll(&mut self.searcher, &mut self.slots, self.buffer_limit) {
+                Ok(Partial::Matched) => {
+                    return Some(Ok(StreamMatch::new(self.slots[0]?, self.slots[1]?)));
+                }
+                Ok(Partial::NeedInput(_)) => {
+                    if let Err(e) = self.fill() {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+                Ok(_) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(io::Error::other(e)));
+                }
+            }
+        }
+        None
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use std::io::{self, Read};
+
+    use super::StreamMatch;
+    use crate::builder::RegexBuilder;
+    use crate::error::{Error, LimitKind};
+    use crate::regex::Regex;
+
+    /// 1回の read で最大 size バイトずつ返す reader
+    #[cfg(feature = "std")]
+    struct Chunked<'a> {
+        data: &'a [u8],
+        size: usize,
+    }
+
+    #[cfg(feature = "std")]
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n: usize = self.size.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn stream_ranges(re: &Regex, text: &str, size: usize) -> Vec<(usize, usize)> {
+        let reader: Chunked = Chunked { data: text.as_bytes(), size };
+        re.stream_find_iter(reader).map(|m| m.map(|m: StreamMatch| (m.start(), m.end())).unwrap()).collect()
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_same_as_find_iter() {
+        let cases: [(&str, &str); 7] = [
+            ("ab+", "xabbbyabab"),
+            ("a*", "baab"),
+            ("(?m)^b|c$", "b\nab\nbc\n"),
+            ("\\Ga", "aabaa"),
+            ("い+|x$", "あいいうx"),
+            ("(a|ab)(c|bcd)", "abcdabc"),
+            ("\\Z", "ab\n"),
+        ];
+        for (pattern, text) in cases {
+            let re: Regex = Regex::new(pattern).unwrap();
+            let expected: Vec<(usize, usize)> = re.find_iter(text).map(|m| (m.start(), m.end())).collect();
+            // 文字の途中で区切られる場合も含め、読み込む単位によらず find_iter と同じ結果になる
+            for size in 1..=text.len() {
+                assert_eq!(stream_ranges(&re, text, size), expected, "{pattern} {text} {size}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_push_searcher() {
+        let re: Regex = Regex::new("(?m)^(a|b)+$").unwrap();
+        let text: &str = "ab\nxa\nba\nα\nb";
+        let expected: Vec<(usize, usize)> = re.find_iter(text).map(|m| (m.start(), m.end())).collect();
+        for size in 1..=4 {
+            let mut searcher = re.push_searcher();
+            let mut ranges: Vec<(usize, usize)> = Vec::new();
+            let mut rest: &str = text;
+            while !rest.is_empty() {
+                let mut end: usize = size.min(rest.len());
+                while !rest.is_char_boundary(end) {
+                    end += 1;
+                }
+                ranges.extend(searcher.feed(&rest[..end]).map(|m| m.unwrap()).map(|m| (m.start(), m.end())));
+                rest = &rest[end..];
+            }
+            ranges.extend(searcher.finish().map(|m| m.unwrap()).map(|m| (m.start(), m.end())));
+            assert_eq!(ranges, expected, "{size}");
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_feed_after_finish() {
+        let re: Regex = Regex::new("a").unwrap();
+        let mut searcher = re.push_searcher();
+        searcher.finish();
+        searcher.feed("a");
+    }
+
+    #[test]
+    fn test_push_buffer_limit() {
+        // "a" から始まるマッチは "b" を受け取るまで確定しないため、その後の入力を保持し続ける
+        let re: Regex = Regex::new("(?s)a.*b").unwrap();
+        let mut searcher = re.push_searcher();
+        searcher.buffer_limit(100);
+        assert_eq!(searcher.feed("xa").count(), 0);
+        for _ in 0..9 {
+            assert_eq!(searcher.feed(&"x".repeat(10)).count(), 0);
+        }
+        let error: Error = Error::LimitExceeded { kind: LimitKind::StreamBuffer, limit: 100 };
+        assert_eq!(searcher.feed(&"x".repeat(10)).collect::<Vec<_>>(), vec![Err(error)]);
+        // エラーを返した後は、何も返さない
+        assert_eq!(searcher.feed("b").count(), 0);
+        assert_eq!(searcher.finish().count(), 0);
+
+        // 確定したマッチの前の入力は保持しない
+        let re: Regex = Regex::new("ab").unwrap();
+        let mut searcher = re.push_searcher();
+        searcher.buffer_limit(4);
+        for _ in 0..100 {
+            assert_eq!(searcher.feed("xxabxx").map(|m| m.unwrap().range().len()).collect::<Vec<_>>(), vec![2]);
+        }
+    }
+
+    #[test]
+    fn test_push_budget_exceeded() {
+        let re: Regex = RegexBuilder::new("(a|aa)*c").backtrack_limit(1000).build().unwrap();
+        let mut searcher = re.push_searcher();
+        assert_eq!(searcher.feed("aac").map(|m| m.unwrap().range()).collect::<Vec<_>>(), vec![0..3]);
+        let results: Vec<Result<StreamMatch, Error>> = searcher.feed(&("a".repeat(2000) + "c")).collect();
+        assert_eq!(results, vec![Err(Error::BudgetExceeded)]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_stream_errors() {
+        let re: Regex = RegexBuilder::new("(a|aa)*c").backtrack_limit(1000).build().unwrap();
+        let text: String = "a".repeat(2000) + "c";
+        let error: io::Error = re.stream_find_iter(text.as_bytes()).last().unwrap().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Other);
+        assert_eq!(error.into_inner().unwrap().downcast_ref::<Error>(), Some(&Error::BudgetExceeded));
+
+        let re: Regex = Regex::new("(?s)a.*b").unwrap();
+        let text: String = "a".to_string() + &"x".repeat(100_000) + "b";
+        let mut searcher = re.stream_find_iter(Chunked { data: text.as_bytes(), size: 1000 });
+        searcher.buffer_limit(10_000);
+        let results: Vec<io::Result<StreamMatch>> = searcher.collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].as_ref().is_err());
+        assert_eq!(stream_ranges(&re, &text, 1000), vec![(0, text.len())]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_invalid_utf8() {
+        let re: Regex = Regex::new("a").unwrap();
+        // 不正なバイトの手前までのマッチを返してから、エラーを返す
+        let reader: &[u8] = b"a\xffa";
+        let results: Vec<io::Result<StreamMatch>> = re.stream_find_iter(reader).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().range(), 0..1);
+        assert_eq!(results[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // 文字の途中で入力が終わっている
+        let reader: &[u8] = b"a\xe3\x81";
+        assert!(re.stream_find_iter(reader).last().unwrap().is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! 文字以外の記号 (トークン、イベント、列挙型の値など) の列 (`&[T]`) を対象にマッチする正規表現の型  
+//! パターンの中の各文字を記号に変換した命令列で探索する。位置はすべて記号単位
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::compiler::Instruction;
+use crate::error::Error;
+use crate::evaluator::{search, slots_len, EvalOptions};
+use crate::input::Symbol;
+use crate::matches::Searcher;
+use crate::regex;
+
+/// 記号の列を対象にマッチするコンパイル済みの正規表現の型  
+/// パターンの中の文字は、Char の命令に限って記号に変換する  
+/// `.` は任意の記号にマッチし、`(?i)` の文字や `\p{..}` は Symbol::as_char で文字として読める記号にのみマッチする
+///
+/// ```
+/// use small_regex::tokens::Regex;
+/// use small_regex::Symbol;
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum Token {
+///     Open,
+///     Close,
+///     Word,
+/// }
+///
+/// impl Symbol for Token {}
+///
+/// let re = Regex::new("ow+c", |c| match c {
+///     'o' => Token::Open,
+///     'c' => Token::Close,
+///     _ => Token::Word,
+/// })
+/// .unwrap();
+/// let tokens = [Token::Word, Token::Open, Token::Word, Token::Word, Token::Close];
+/// assert_eq!(re.find(&tokens), Some(1..5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Regex<T> {
+    pattern: String,
+    instructions: Vec<Instruction<T>>,
+    options: EvalOptions,
+}
+
+impl<T: Symbol> Regex<T> {
+    /// パターンをパースし、パターンの中の各文字を symbol で記号に変換した命令列にコンパイルする  
+    /// パターンが不正な場合はエラーを返す
+    pub fn new<F: FnMut(char) -> T>(pattern: &str, symbol: F) -> Result<Regex<T>, Error> {
+        Ok(Regex::from_regex(&regex::Regex::new(pattern)?, symbol))
+    }
+
+    /// RegexBuilder などで生成した Regex の命令列の各文字を、symbol で記号に変換する  
+    /// 探索の設定 (anchored, backtrack_limit など) は regex のものを引き継ぐ
+    pub fn from_regex<F: FnMut(char) -> T>(regex: &regex::Regex, symbol: F) -> Regex<T> {
+        let mut symbol: F = symbol;
+        let instructions: Vec<Instruction<T>> =
+            regex.instructions().iter().map(|inst| inst.clone().map_symbol(&mut symbol)).collect();
+        Regex { pattern: regex.as_str().into(), instructions, options: regex.options() }
+    }
+
+    /// コンパイルする前のパターンを返す
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// haystack のいずれかの位置にパターンにマッチする部分があるかを判定する
+    pub fn is_match(&self, haystack: &[T]) -> bool {
+        self.find(haystack).is_some()
+    }
+
+    /// haystack の中で最初にパターンにマッチする部分の範囲を返す  
+    /// マッチする部分が無い場合は None を返す
+    pub fn find(&self, haystack: &[T]) -> Option<Range<usize>> {
+        self.find_at(haystack, 0)
+    }
+
+    /// haystack の start 以降で、最初にパターンにマッチする部分の範囲を返す  
+    /// start より前の記号も `^` などのアンカーの判定には使う
+    pub fn find_at(&self, haystack: &[T], start: usize) -> Option<Range<usize>> {
+        let (start, end) = search(&self.instructions, haystack, start, self.options)?;
+        Some(start..end)
+    }
+
+    /// haystack の中でパターンにマッチする部分の範囲を、重ならないように先頭から順に返すイテレータを返す
+    pub fn find_iter<'r, 'h>(&'r self, haystack: &'h [T]) -> Matches<'r, 'h, T> {
+        Matches {
+            searcher: Searcher::new(&self.instructions, self.options, haystack),
+            slots: vec![None; slots_len(&self.instructions)],
+        }
+    }
+}
+
+/// haystack の中でマッチする部分の範囲を、重ならないように先頭から順に返すイテレータ  
+/// `tokens::Regex::find_iter` で生成する
+#[derive(Debug)]
+pub struct Matches<'r, 'h, T> {
+    searcher: Searcher<'r, &'h [T], T>,
+    slots: Vec<Option<usize>>,
+}
+
+impl<T: Symbol> Iterator for Matches<'_, '_, T> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        if !self.searcher.next(&mut self.slots) {
+            return None;
+        }
+        Some(self.slots[0]?..self.slots[1]?)
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::ops::Range;
+
+    use super::Regex;
+    use crate::input::Symbol;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Event {
+        Start,
+        Stop,
+        Tick(u8),
+    }
+
+    impl Symbol for Event {}
+
+    /// 's' を Start、'e' を Stop、数字をその値の Tick に変換する
+    fn event(c: char) -> Event {
+        match c {
+            's' => Event::Start,
+            'e' => Event::Stop,
+            _ => Event::Tick(c.to_digit(10).unwrap() as u8),
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        let re: Regex<Event> = Regex::new("s(1|2)*e", event).unwrap();
+        let events: [Event; 5] = [Event::Tick(1), Event::Start, Event::Tick(2), Event::Tick(1), Event::Stop];
+        assert_eq!(re.find(&events), Some(1..5));
+        assert!(!re.is_match(&[Event::Start, Event::Tick(3), Event::Stop]));
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let re: Regex<Event> = Regex::new("s.e", event).unwrap();
+        let events: [Event; 7] =
+            [Event::Start, Event::Tick(3), Event::Stop, Event::Stop, Event::Start, Event::Tick(1), Event::Stop];
+        let found: Vec<Range<usize>> = re.find_iter(&events).collect();
+        assert_eq!(found, vec![0..3, 4..7]);
+    }
+
+    #[test]
+    fn test_non_char_symbols() {
+        // (?i) の文字は記号に変換せず、文字として読めない記号にはマッチしない
+        let re: Regex<Event> = Regex::new("s(?i)x", event).unwrap();
+        assert!(!re.is_match(&[Event::Start, Event::Stop]));
+
+        // アンカーは記号の列の先頭・末尾で判定する
+        let re: Regex<Event> = Regex::new("^s.*e$", event).unwrap();
+        assert!(re.is_match(&[Event::Start, Event::Tick(1), Event::Stop]));
+        assert!(!re.is_match(&[Event::Tick(1), Event::Start, Event::Stop]));
+    }
+}
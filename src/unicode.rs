@@ -0,0 +1,206 @@
+//! `\p{...}` / `\P{...}` で指定する Unicode プロパティの型・関数  
+//! 範囲表 (unicode_tables.rs) は `unicode` フィーチャが有効な場合のみ組み込む
+
+use alloc::format;
+use alloc::string::String;
+
+#[cfg(feature = "unicode")]
+use crate::unicode_tables::{CASE_INSENSITIVE, CATEGORIES, SCRIPTS};
+
+/// シリアライズする際に、大文字・小文字を区別しないプロパティの名前の前に付ける文字列
+const CASE_INSENSITIVE_PREFIX: &str = "(?i)";
+
+/// Unicode プロパティの型  
+/// 名前と、プロパティに含まれるコードポイントの範囲表を持つ  
+/// `(?i)` の中で指定したプロパティは、大文字・小文字で同一視する文字を加えた範囲表を持つ (`(?i)\p{Lu}` は `a` にもマッチする)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Property {
+    pub name: &'static str,
+    ranges: &'static [(char, char)],
+    case_insensitive: bool, // 大文字・小文字を区別しない範囲表かどうか
+}
+
+impl Property {
+    /// 名前と範囲表から Property を生成する (regex! マクロの展開先で使う)
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, ranges: &'static [(char, char)]) -> Property {
+        Property { name, ranges, case_insensitive: false }
+    }
+
+    /// 名前と、大文字・小文字で同一視する文字を加えた範囲表から Property を生成する (regex! マクロの展開先で使う)
+    #[doc(hidden)]
+    pub const fn new_case_insensitive(name: &'static str, ranges: &'static [(char, char)]) -> Property {
+        Property { name, ranges, case_insensitive: true }
+    }
+
+    /// プロパティに含まれるコードポイントの範囲表
+    #[doc(hidden)]
+    pub fn ranges(&self) -> &'static [(char, char)] {
+        self.ranges
+    }
+
+    /// 一般カテゴリ名 (`Lu`, `L` など) またはスクリプト名 (`Greek` など) から Property を取得する  
+    /// 該当するプロパティが無い場合は None を返す
+    #[cfg(feature = "unicode")]
+    pub fn lookup(name: &str) -> Option<Property> {
+        CATEGORIES
+            .iter()
+            .chain(SCRIPTS.iter())
+            .find(|(n, _)| *n == name)
+            .map(|(name, ranges)| Property { name, ranges, case_insensitive: false })
+    }
+
+    /// 大文字・小文字を区別しないプロパティを返す (`(?i)\p{..}`)  
+    /// 範囲表は、含まれる文字と simple_fold で同じ文字に畳み込まれる文字をすべて加えたものにする
+    pub fn case_insensitive(self) -> Property {
+        #[cfg(feature = "unicode")]
+        let ranges: &'static [(char, char)] =
+            CASE_INSENSITIVE.iter().find(|(n, _)| *n == self.name).map_or(self.ranges, |(_, ranges)| *ranges);
+        #[cfg(not(feature = "unicode"))]
+        let ranges: &'static [(char, char)] = self.ranges;
+        Property { ranges, case_insensitive: true, ..self }
+    }
+
+    /// 大文字・小文字を区別しないプロパティかどうか
+    pub fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    /// シリアライズする際の名前 (大文字・小文字を区別しない場合は `(?i)Lu` のように前に付ける)
+    pub(crate) fn key(&self) -> String {
+        if self.case_insensitive {
+            format!("{CASE_INSENSITIVE_PREFIX}{}", self.name)
+        } else {
+            String::from(self.name)
+        }
+    }
+
+    /// key で得た名前から Property を取得する
+    pub(crate) fn from_key(key: &str) -> Option<Property> {
+        match key.strip_prefix(CASE_INSENSITIVE_PREFIX) {
+            Some(name) => Property::lookup(name).map(Property::case_insensitive),
+            None => Property::lookup(key),
+        }
+    }
+
+    /// パターンでの書き方 (`\p{Lu}`, `(?i)\P{Lu}` など)
+    pub(crate) fn escape(&self, negated: bool) -> String {
+        let prefix: &str = if self.case_insensitive { CASE_INSENSITIVE_PREFIX } else { "" };
+        let escape: char = if negated { 'P' } else { 'p' };
+        format!("{prefix}\\{escape}{{{}}}", self.name)
+    }
+
+    /// `unicode` フィーチャが無効な場合は、どのプロパティも利用できない
+    #[cfg(not(feature = "unicode"))]
+    pub fn lookup(_name: &str) -> Option<Property> {
+        None
+    }
+
+    /// 文字がプロパティに含まれるかを二分探索で判定する
+    pub fn contains(&self, c: char) -> bool {
+        self.ranges
+            .binary_search_by(|&(start, end)| {
+                if end < c {
+                    core::cmp::Ordering::Less
+                } else if start > c {
+                    core::cmp::Ordering::Greater
+                } else {
+                    core::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+}
+
+/// 大文字・小文字を区別せずに比較するため、文字を小文字に畳み込む  
+/// 大文字にしてから小文字にするため、'Σ', 'σ', 'ς' や 'S', 's', 'ſ' のように、大文字・小文字で同一視する文字はすべて同じ文字になる  
+/// 大文字・小文字が複数の文字になる場合 ('İ' の小文字など) は、その変換を使わない  
+/// Unicode の case folding と同じく、トルコ語の 'ı' は 'i', 'I' と同一視しない
+pub fn simple_fold(c: char) -> char {
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    if c == 'ı' {
+        return c;
+    }
+    let upper: char = single(c.to_uppercase()).unwrap_or(c);
+    single(upper.to_lowercase()).or_else(|| single(c.to_lowercase())).unwrap_or(c)
+}
+
+/// 1文字だけからなる場合に、その文字を返す
+pub(crate) fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use super::simple_fold;
+    #[cfg(feature = "unicode")]
+    use super::Property;
+
+    #[test]
+    fn test_simple_fold() {
+        let orbits: &[&[char]] = &[
+            &['a', 'A'],
+            &['k', 'K', '\u{212a}'],
+            &['s', 'S', 'ſ'],
+            &['σ', 'Σ', 'ς'],
+            &['μ', 'Μ', 'µ'],
+            &['ι', 'Ι', '\u{345}', '\u{1fbe}'],
+            &['θ', 'Θ', 'ϑ', 'ϴ'],
+            &['ß', 'ẞ'],
+            &['ǆ', 'Ǆ', 'ǅ'],
+            &['å', 'Å', '\u{212b}'],
+        ];
+        for orbit in orbits {
+            for c in orbit.iter() {
+                assert_eq!(simple_fold(*c), orbit[0], "{:?}", c);
+            }
+        }
+        assert_eq!(simple_fold('ı'), 'ı');
+        assert_eq!(simple_fold('İ'), 'İ');
+        assert_eq!(simple_fold('1'), '1');
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_case_insensitive() {
+        let lu: Property = Property::lookup("Lu").unwrap().case_insensitive();
+        assert!(lu.contains('A'));
+        assert!(lu.contains('a'));
+        assert!(lu.contains('ς'));
+        assert!(!lu.contains('1'));
+        assert!(lu.is_case_insensitive());
+        assert_eq!(lu.key(), "(?i)Lu");
+        assert_eq!(Property::from_key("(?i)Lu"), Some(lu));
+        assert_eq!(Property::from_key("Lu"), Property::lookup("Lu"));
+
+        // 'µ' (MICRO SIGN) は Common、'μ' は Greek に含まれる
+        let common: Property = Property::lookup("Common").unwrap();
+        assert!(!common.contains('μ'));
+        assert!(common.case_insensitive().contains('μ'));
+        // 範囲表が変わらないプロパティ
+        let latin: Property = Property::lookup("Latin").unwrap();
+        assert_eq!(latin.case_insensitive().ranges(), latin.ranges());
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_lookup() {
+        let lu: Property = Property::lookup("Lu").unwrap();
+        assert!(lu.contains('A'));
+        assert!(lu.contains('Ω'));
+        assert!(!lu.contains('a'));
+
+        let greek: Property = Property::lookup("Greek").unwrap();
+        assert!(greek.contains('α'));
+        assert!(!greek.contains('a'));
+
+        assert_eq!(Property::lookup("Unknown"), None);
+    }
+}
@@ -0,0 +1,263 @@
+//! AST をパターンの文字列に戻すための実装 (parse の逆)  
+//! 生成するパターンは、すべてのフラグが無効の状態から読んで元の AST と同じ意味になるよう、必要な位置に `(?i)` などを書く  
+//! エスケープはフラグが無効の状態で特別な意味を持つ文字と、制御文字だけにする
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::error::Error;
+use crate::parser::{literal, parse_with_flags, Assertion, Flags, AST};
+use crate::regex::Regex;
+use crate::simplify::simplify;
+
+/// フラグが無効の状態で特別な意味を持つ文字 (`\` でエスケープする)
+const SPECIAL_CHARACTERS: &[char] = &['\\', '(', ')', '|', '+', '*', '?', '^', '$', '.'];
+
+impl Regex {
+    /// パターンを RegexBuilder で指定したフラグでパースし直し、簡約した AST を正規化したパターンの文字列にする  
+    /// 同じ意味のパターン (`(?:a+)+` と `a+` など) は同じ文字列になりやすく、パターンの重複を取り除くのに使える  
+    /// 指定したフラグは `(?i)` などとしてパターンに書くため、返すパターンはフラグを指定せずに Regex::new に渡せる
+    ///
+    /// ```
+    /// use small_regex::{Regex, RegexBuilder};
+    ///
+    /// assert_eq!(Regex::new("(?:a+)+(?:b|b)").unwrap().to_pattern_string().unwrap(), "a+b");
+    /// let re = RegexBuilder::new("x.").dot_matches_new_line(true).build().unwrap();
+    /// assert_eq!(re.to_pattern_string().unwrap(), "x(?s).");
+    /// ```
+    pub fn to_pattern_string(&self) -> Result<String, Error> {
+        let ast: AST = simplify(parse_with_flags(self.as_str(), self.flags(), usize::MAX)?);
+        Ok(ast.to_pattern_string())
+    }
+}
+
+impl AST {
+    /// AST をパターンの文字列にする  
+    /// parse で生成した AST は、返したパターンをパースし直すと元の AST と等しくなる
+    pub fn to_pattern_string(&self) -> String {
+        let mut writer: Writer = Writer { pattern: String::new(), flags: Flags::default() };
+        writer.write(self);
+        writer.pattern
+    }
+}
+
+/// 生成中のパターンと、パターンの末尾の位置で有効なフラグ
+struct Writer {
+    pattern: String,
+    flags: Flags,
+}
+
+impl Writer {
+    fn write(&mut self, ast: &AST) {
+        match ast {
+            AST::Char(c) => {
+                // 大文字・小文字のある文字は、(?i) が有効な位置では CharNoCase になる
+                if self.flags.case_insensitive && literal(*c, self.flags) != AST::Char(*c) {
+                    self.set_flag('i', false);
+                }
+                self.write_char(*c);
+            }
+            AST::CharNoCase(c) => {
+                self.set_flag('i', true);
+                self.write_char(*c);
+            }
+            AST::AnyChar(dot_all) => {
+                self.set_flag('s', *dot_all);
+                self.pattern.push('.');
+            }
+            AST::Property(property, negated) => {
+                self.set_flag('i', property.is_case_insensitive());
+                let escape: char = if *negated { 'P' } else { 'p' };
+                self.pattern.push_str(&format!("\\{}{{{}}}", escape, property.name));
+            }
+            AST::Plus(ast) => self.write_repeat(ast, '+'),
+            AST::Star(ast) => self.write_repeat(ast, '*'),
+            AST::Question(ast) => self.write_repeat(ast, '?'),
+            AST::Or(left, right) => {
+                self.write(left);
+                self.pattern.push('|');
+                self.write(right);
+            }
+            AST::Seq(asts) => {
+                for ast in asts {
+                    // 入れ子の連結はグループの中で変えたフラグを戻すため、グループのまま書く
+                    match ast {
+                        AST::Or(_, _) | AST::Seq(_) | AST::Empty => self.write_group("(?:", ast),
+                        ast => self.write(ast),
+                    }
+                }
+            }
+            AST::Atomic(ast) => self.write_group("(?>", ast),
+            AST::Assert(assertion) => self.write_assertion(*assertion),
+            AST::Capture(_, None, ast) => self.write_group("(", ast),
+            AST::Capture(_, Some(name), ast) => self.write_group(&format!("(?<{name}>"), ast),
+            AST::Conditional(group, then_ast, else_ast) => {
+                let outer: Flags = self.flags;
+                self.pattern.push_str(&format!("(?({group})"));
+                self.write_branch(then_ast);
+                if **else_ast != AST::Empty {
+                    self.pattern.push('|');
+                    self.write_branch(else_ast);
+                }
+                self.pattern.push(')');
+                self.flags = outer;
+            }
+            AST::Empty => {}
+        }
+    }
+
+    /// 文字を書く (特別な意味を持つ文字と制御文字はエスケープする)
+    fn write_char(&mut self, c: char) {
+        match c {
+            '\n' => self.pattern.push_str("\\n"),
+            '\t' => self.pattern.push_str("\\t"),
+            '\r' => self.pattern.push_str("\\r"),
+            '\0' => self.pattern.push_str("\\0"),
+            c if c.is_control() => self.pattern.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => {
+                if SPECIAL_CHARACTERS.contains(&c) {
+                    self.pattern.push('\\');
+                }
+                self.pattern.push(c);
+            }
+        }
+    }
+
+    /// 繰り返しを書く  
+    /// 連結や選択、繰り返しの繰り返し (`a++` は強欲な量指定子になる) は、非キャプチャグループで囲む
+    fn write_repeat(&mut self, ast: &AST, qualifier: char) {
+        match ast {
+            AST::Seq(_) | AST::Or(_, _) | AST::Empty | AST::Plus(_) | AST::Star(_) | AST::Question(_) => {
+                self.write_group("(?:", ast)
+            }
+            ast => self.write(ast),
+        }
+        self.pattern.push(qualifier);
+    }
+
+    /// 条件分岐の then または else を書く (選択は `|` が分岐の区切りと区別できないため、グループで囲む)
+    fn write_branch(&mut self, ast: &AST) {
+        match ast {
+            AST::Or(_, _) => self.write_group("(?:", ast),
+            ast => self.write(ast),
+        }
+    }
+
+    /// open から `)` までのグループを書く (グループの中で変えたフラグは、`)` の後では元に戻る)
+    fn write_group(&mut self, open: &str, ast: &AST) {
+        let outer: Flags = self.flags;
+        self.pattern.push_str(open);
+        self.write(ast);
+        self.pattern.push(')');
+        self.flags = outer;
+    }
+
+    fn write_assertion(&mut self, assertion: Assertion) {
+        let text: &str = match assertion {
+            Assertion::StartText if self.flags.multi_line => "\\A",
+            Assertion::StartText => "^",
+            Assertion::EndText if self.flags.multi_line => "\\z",
+            Assertion::EndText => "$",
+            Assertion::StartLine => {
+                self.set_flag('m', true);
+                "^"
+            }
+            Assertion::EndLine => {
+                self.set_flag('m', true);
+                "$"
+            }
+            Assertion::EndTextNewline => "\\Z",
+            Assertion::StartSearch => "\\G",
+        };
+        self.pattern.push_str(text);
+    }
+
+    /// フラグが enable の状態でなければ、`(?i)`, `(?-i)` などを書いて切り替える
+    fn set_flag(&mut self, flag: char, enable: bool) {
+        let current: &mut bool = match flag {
+            'i' => &mut self.flags.case_insensitive,
+            'm' => &mut self.flags.multi_line,
+            _ => &mut self.flags.dot_all,
+        };
+        if *current != enable {
+            *current = enable;
+            self.pattern.push_str(if enable { "(?" } else { "(?-" });
+            self.pattern.push(flag);
+            self.pattern.push(')');
+        }
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use crate::builder::RegexBuilder;
+    use crate::parser::{parse, AST};
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_round_trip() {
+        let patterns: &[&str] = &[
+            "abc",
+            "a|b|c|",
+            "(a|b)*c+d?",
+            "(?:ab)+(?:a|b)?",
+            "(?<name>x)(?(1)y|z)(?(1)w)",
+            "(?>a|ab)c",
+            "\\(\\)\\|\\+\\*\\?\\^\\$\\.\\\\ #{}",
+            "\\n\\t\\r\\0\\x01é",
+            "^a$\\A\\z\\Z\\G",
+            "(?m)^a$(?-m)^b$",
+            "(?i)ab1(?-i)cd",
+            "(?is)a.(?-s).",
+            "(?:(?i)a)b",
+            "a|(?i)b|c",
+            "(?:a*)+(?:)*",
+            "((a)|b)",
+        ];
+        for pattern in patterns {
+            let ast: AST = parse(pattern).unwrap();
+            let rendered: String = ast.to_pattern_string();
+            assert_eq!(parse(&rendered), Ok(ast), "{} / {}", pattern, rendered);
+        }
+    }
+
+    #[test]
+    fn test_to_pattern_string() {
+        let render = |pattern: &str| -> String { parse(pattern).unwrap().to_pattern_string() };
+        assert_eq!(render("\\Qa.b\\E\\x20#"), "a\\.b #");
+        assert_eq!(render("(?i)ab1(?-i)cd"), "(?i)ab1(?-i)cd");
+        assert_eq!(render("(?i)1(?-i)2"), "12");
+        assert_eq!(render("(?m)^(?-m)^"), "(?m)^\\A");
+        assert_eq!(render("(?P<x>a)"), "(?<x>a)");
+        assert_eq!(render("(?:a|b)c|d"), "(?:a|b)c|d");
+        assert_eq!(render("(?:a+)+"), "(?:a+)+");
+        assert_eq!(render("(?:(?i)a)b"), "(?:(?i)a)b");
+        assert_eq!(render("\\u{7f}"), "\\u{7f}");
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_property() {
+        let ast: AST = parse("\\pL\\P{Greek}+").unwrap();
+        assert_eq!(ast.to_pattern_string(), "\\p{L}\\P{Greek}+");
+        let ast: AST = parse("(?i)\\p{Lu}a\\pL(?-i)\\pL").unwrap();
+        assert_eq!(ast.to_pattern_string(), "(?i)\\p{Lu}a\\p{L}(?-i)\\p{L}");
+        assert_eq!(parse(&ast.to_pattern_string()), Ok(ast));
+    }
+
+    #[test]
+    fn test_regex_to_pattern_string() {
+        let normalize = |pattern: &str| -> String { Regex::new(pattern).unwrap().to_pattern_string().unwrap() };
+        assert_eq!(normalize("(?:a+)+"), "a+");
+        assert_eq!(normalize("(?:a)(?:b|b|c)"), "a(?:b|c)");
+        assert_eq!(normalize("(?i)a"), normalize("(?i)A"));
+        let re: Regex = RegexBuilder::new("ab").case_insensitive(true).multi_line(true).build().unwrap();
+        assert_eq!(re.to_pattern_string().unwrap(), "(?i)ab");
+        let re: Regex = RegexBuilder::new("^a").multi_line(true).build().unwrap();
+        assert_eq!(re.to_pattern_string().unwrap(), "(?m)^a");
+    }
+}
@@ -0,0 +1,99 @@
+//! AST をたどるためのトレイト  
+//! Visit と VisitMut は、すべてのノードで呼ばれる visit, visit_mut を持ち、既定では walk, walk_mut で子のノードをたどる  
+//! 特定の種類のノードだけを扱う処理は、visit でそのノードを処理してから walk を呼べば、他のノードの再帰を書かずに済む
+
+use crate::parser::AST;
+
+/// AST を読み取りながらたどるトレイト
+pub(crate) trait Visit {
+    /// ノードを訪れる (既定では、子のノードを順に訪れる)
+    fn visit(&mut self, ast: &AST) {
+        walk(self, ast);
+    }
+}
+
+/// AST を書き換えながらたどるトレイト
+pub(crate) trait VisitMut {
+    /// ノードを訪れる (既定では、子のノードを順に訪れる)
+    fn visit_mut(&mut self, ast: &mut AST) {
+        walk_mut(self, ast);
+    }
+}
+
+/// ast の子のノードを、パターンに現れる順に訪れる
+pub(crate) fn walk<V: Visit + ?Sized>(visitor: &mut V, ast: &AST) {
+    match ast {
+        AST::Plus(ast) | AST::Star(ast) | AST::Question(ast) | AST::Atomic(ast) | AST::Capture(_, _, ast) => {
+            visitor.visit(ast)
+        }
+        AST::Or(left, right) | AST::Conditional(_, left, right) => {
+            visitor.visit(left);
+            visitor.visit(right);
+        }
+        AST::Seq(asts) => asts.iter().for_each(|ast| visitor.visit(ast)),
+        AST::Char(_) | AST::CharNoCase(_) | AST::AnyChar(_) | AST::Property(_, _) | AST::Assert(_) | AST::Empty => {}
+    }
+}
+
+/// ast の子のノードを、パターンに現れる順に書き換えながら訪れる
+pub(crate) fn walk_mut<V: VisitMut + ?Sized>(visitor: &mut V, ast: &mut AST) {
+    match ast {
+        AST::Plus(ast) | AST::Star(ast) | AST::Question(ast) | AST::Atomic(ast) | AST::Capture(_, _, ast) => {
+            visitor.visit_mut(ast)
+        }
+        AST::Or(left, right) | AST::Conditional(_, left, right) => {
+            visitor.visit_mut(left);
+            visitor.visit_mut(right);
+        }
+        AST::Seq(asts) => asts.iter_mut().for_each(|ast| visitor.visit_mut(ast)),
+        AST::Char(_) | AST::CharNoCase(_) | AST::AnyChar(_) | AST::Property(_, _) | AST::Assert(_) | AST::Empty => {}
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{walk, walk_mut, Visit, VisitMut};
+    use crate::parser::{parse, AST};
+
+    /// 文字を順に集める
+    struct Chars(Vec<char>);
+
+    impl Visit for Chars {
+        fn visit(&mut self, ast: &AST) {
+            if let AST::Char(c) = ast {
+                self.0.push(*c);
+            }
+            walk(self, ast);
+        }
+    }
+
+    /// 文字を大文字にする
+    struct Upper;
+
+    impl VisitMut for Upper {
+        fn visit_mut(&mut self, ast: &mut AST) {
+            if let AST::Char(c) = ast {
+                *c = c.to_ascii_uppercase();
+            }
+            walk_mut(self, ast);
+        }
+    }
+
+    #[test]
+    fn test_visit() {
+        let mut chars: Chars = Chars(Vec::new());
+        chars.visit(&parse("a(b|c)*(?>d)?(?(1)e|f)").unwrap());
+        assert_eq!(chars.0, ['a', 'b', 'c', 'd', 'e', 'f']);
+    }
+
+    #[test]
+    fn test_visit_mut() {
+        let mut ast: AST = parse("a(b|.)+").unwrap();
+        Upper.visit_mut(&mut ast);
+        assert_eq!(ast, parse("A(B|.)+").unwrap());
+    }
+}
@@ -0,0 +1,82 @@
+//! wasm-bindgen で JavaScript から正規表現を使うための型・関数  
+//! `wasm` フィーチャが有効な場合のみ組み込む。JavaScript の文字列に合わせ、位置は UTF-16 のコード単位で返す
+//!
+//! ```text
+//! const re = compile("b+");
+//! re.isMatch("abb");   // true
+//! re.findAll("abbxb"); // Uint32Array [1, 3, 4, 5] (開始位置・終了位置の組)
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::*;
+
+use crate::regex::Regex;
+
+/// JavaScript から使うコンパイル済みの正規表現の型
+#[wasm_bindgen(js_name = Regex)]
+#[derive(Debug)]
+pub struct WasmRegex {
+    regex: Regex,
+}
+
+/// パターンをコンパイルする  
+/// パターンが不正な場合は、エラーのメッセージを持つ Error を投げる
+#[wasm_bindgen]
+pub fn compile(pattern: &str) -> Result<WasmRegex, JsError> {
+    match Regex::new(pattern) {
+        Ok(regex) => Ok(WasmRegex { regex }),
+        Err(e) => Err(JsError::new(&e.to_string())),
+    }
+}
+
+#[wasm_bindgen(js_class = Regex)]
+impl WasmRegex {
+    /// text のいずれかの位置にパターンにマッチする部分があるかを判定する
+    #[wasm_bindgen(js_name = isMatch)]
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    /// text の中でパターンにマッチする部分の開始位置・終了位置を、重ならないように先頭から順に並べて返す  
+    /// 位置は UTF-16 のコード単位
+    #[wasm_bindgen(js_name = findAll)]
+    pub fn find_all(&self, text: &str) -> Vec<u32> {
+        let mut offsets: Vec<u32> = Vec::new();
+        let mut byte: usize = 0; // 直前に変換したバイト単位の位置
+        let mut utf16: u32 = 0;  // byte に対応する UTF-16 の位置
+        for m in self.regex.find_iter(text) {
+            for end in [m.start(), m.end()] {
+                utf16 += text[byte..end].encode_utf16().count() as u32;
+                byte = end;
+                offsets.push(utf16);
+            }
+        }
+        offsets
+    }
+
+    /// コンパイルする前のパターンを返す
+    #[wasm_bindgen(getter)]
+    pub fn pattern(&self) -> String {
+        self.regex.as_str().to_string()
+    }
+}
+
+// ----- テストコード -----
+
+#[cfg(test)]
+mod tests {
+    use super::WasmRegex;
+    use crate::regex::Regex;
+
+    #[test]
+    fn test_find_all() {
+        let re: WasmRegex = WasmRegex { regex: Regex::new("b+").unwrap() };
+        assert!(re.is_match("abb"));
+        assert_eq!(re.find_all("abbxb"), vec![1, 3, 4, 5]);
+
+        // 位置は UTF-16 のコード単位 ('😀' は2単位)
+        assert_eq!(re.find_all("😀bαb"), vec![2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,3 @@
+//! small-regex の JavaScript 向けの型・関数 (small_regex::wasm) を、WebAssembly のモジュールとしてビルドするクレート
+
+pub use small_regex::wasm::*;